@@ -0,0 +1,173 @@
+//! Compact binary container for serialized databases
+//!
+//! Layout (all integers little-endian):
+//!
+//! | bytes          | content                                          |
+//! |----------------|--------------------------------------------------|
+//! | 4              | magic `IDBV`                                     |
+//! | 4              | format version (`u32`)                           |
+//! | 4              | length of the JSON section (`u32`)               |
+//! | n              | JSON state with every vector left empty          |
+//! | 8              | number of `f32` values in the vector block (`u64`) |
+//! | 4 × count      | vector block: each node's vector in id order     |
+//! | 8              | FNV-1a checksum of every preceding byte (`u64`)  |
+//!
+//! Version 1 containers, written before the checksum was added, end at the
+//! vector block and still decode.
+//!
+//! Keeping the graph and metadata as JSON means new index fields round-trip
+//! without touching this layout, while the float vectors, which dominate the
+//! size of a JSON dump, are stored as raw bytes.
+
+use crate::checksum::fnv1a64;
+
+pub const MAGIC: &[u8; 4] = b"IDBV";
+pub const FORMAT_VERSION: u32 = 2;
+
+/// The last version without a trailing checksum
+const UNCHECKED_VERSION: u32 = 1;
+
+/// Assemble a binary container from the JSON section and the vector block
+pub fn encode(json: &[u8], vectors: &[f32]) -> Result<Vec<u8>, String> {
+    let json_len = u32::try_from(json.len()).map_err(|_| "JSON section too large".to_string())?;
+    let mut out = Vec::with_capacity(28 + json.len() + vectors.len() * 4);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&json_len.to_le_bytes());
+    out.extend_from_slice(json);
+    out.extend_from_slice(&(vectors.len() as u64).to_le_bytes());
+    for v in vectors {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    let checksum = fnv1a64(&out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    Ok(out)
+}
+
+/// Check a container's trailing checksum against its contents
+///
+/// Passes containers this can't vouch for either way (version 1, bad magic,
+/// too short) so `decode` can report what's actually wrong with them.
+pub fn verify_checksum(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() < 20 || &bytes[..4] != MAGIC {
+        return Ok(());
+    }
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    if version != FORMAT_VERSION {
+        return Ok(());
+    }
+    let (body, stored) = bytes.split_at(bytes.len() - 8);
+    let mut expected = [0u8; 8];
+    expected.copy_from_slice(stored);
+    if fnv1a64(body) != u64::from_le_bytes(expected) {
+        return Err("Checksum mismatch: the binary database was modified or corrupted after it was saved".to_string());
+    }
+    Ok(())
+}
+
+/// Split a binary container back into its JSON section and vector block
+pub fn decode(bytes: &[u8]) -> Result<(&[u8], Vec<f32>), String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err("Not an idbvec binary database (bad magic)".to_string());
+    }
+    let version = u32::from_le_bytes(reader.array()?);
+    match version {
+        FORMAT_VERSION => {
+            let end = bytes.len().checked_sub(8).ok_or("Truncated binary database")?;
+            reader.bytes = &bytes[..end];
+        }
+        UNCHECKED_VERSION => {}
+        _ => return Err(format!("Unsupported binary format version: {}", version)),
+    }
+    let json_len = u32::from_le_bytes(reader.array()?) as usize;
+    let json = reader.take(json_len)?;
+    let count = u64::from_le_bytes(reader.array()?);
+    let count = usize::try_from(count).map_err(|_| "Vector block too large".to_string())?;
+    let block = reader.take(count.checked_mul(4).ok_or("Vector block too large")?)?;
+    if reader.pos != reader.bytes.len() {
+        return Err("Trailing bytes after vector block".to_string());
+    }
+    let vectors = block
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    Ok((json, vectors))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| "Truncated binary database".to_string())?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let json = br#"{"version":1}"#;
+        let vectors = vec![1.0, -2.5, f32::MIN_POSITIVE, 1e30];
+        let bytes = encode(json, &vectors).unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(bytes.len(), 4 + 4 + 4 + json.len() + 8 + 16 + 8);
+        verify_checksum(&bytes).unwrap();
+        let (json_out, vectors_out) = decode(&bytes).unwrap();
+        assert_eq!(json_out, json);
+        assert_eq!(vectors_out, vectors);
+    }
+
+    #[test]
+    fn checksum_catches_flipped_bytes_and_version_1_still_decodes() {
+        let bytes = encode(br#"{"version":1}"#, &[1.0, 2.0]).unwrap();
+        for i in [12, bytes.len() - 9, bytes.len() - 1] {
+            let mut flipped = bytes.clone();
+            flipped[i] ^= 0x01;
+            assert!(verify_checksum(&flipped).unwrap_err().contains("Checksum mismatch"));
+        }
+
+        let mut v1 = bytes[..bytes.len() - 8].to_vec();
+        v1[4..8].copy_from_slice(&UNCHECKED_VERSION.to_le_bytes());
+        verify_checksum(&v1).unwrap();
+        assert_eq!(decode(&v1).unwrap().1, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn rejects_bad_magic_version_and_truncation() {
+        let bytes = encode(b"{}", &[1.0, 2.0]).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(decode(&bad_magic).unwrap_err().contains("magic"));
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        assert!(decode(&bad_version).unwrap_err().contains("version"));
+
+        assert!(decode(&bytes[..bytes.len() - 1]).unwrap_err().contains("Truncated"));
+        assert!(decode(&[]).is_err());
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(decode(&trailing).unwrap_err().contains("Trailing"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Content checksums for serialized databases
+//!
+//! JSON snapshots carry a 64-bit FNV-1a hash of their body as a leading
+//! `"checksum"` field:
+//!
+//! ```text
+//! {"checksum":"<16 hex digits>","version":1,...}
+//! ```
+//!
+//! The hash covers the exact bytes of the snapshot without that field, so
+//! any change to the index or metadata after `serialize`, including
+//! reformatting, fails verification.
+
+/// How every sealed snapshot starts
+pub const PREFIX: &str = "{\"checksum\":\"";
+const HEX_LEN: usize = 16;
+
+/// 64-bit FNV-1a hash of `bytes`
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// Prefix the JSON object `body` with a `"checksum"` field covering it
+pub fn seal(body: &str) -> String {
+    debug_assert!(body.starts_with('{'), "only JSON objects can be sealed");
+    let rest = &body[1..];
+    let separator = if rest.starts_with('}') { "" } else { "," };
+    format!("{}{:016x}\"{}{}", PREFIX, fnv1a64(body.as_bytes()), separator, rest)
+}
+
+/// Check a snapshot written by `seal` against its checksum
+pub fn verify(json: &str) -> Result<(), String> {
+    let mismatch = || "Checksum mismatch: the snapshot was modified or corrupted after it was saved".to_string();
+    let sealed = json.strip_prefix(PREFIX).ok_or_else(mismatch)?;
+    let (hex, rest) = sealed.split_at_checked(HEX_LEN).ok_or_else(mismatch)?;
+    let expected = u64::from_str_radix(hex, 16).map_err(|_| mismatch())?;
+    let rest = rest.strip_prefix('"').ok_or_else(mismatch)?;
+    let rest = rest.strip_prefix(',').unwrap_or(rest);
+    let mut body = String::with_capacity(rest.len() + 1);
+    body.push('{');
+    body.push_str(rest);
+    if fnv1a64(body.as_bytes()) != expected {
+        return Err(mismatch());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a64_matches_reference_values() {
+        assert_eq!(fnv1a64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a64(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn sealed_json_verifies_and_still_parses() {
+        for body in [r#"{"version":1,"metadata":{}}"#, "{}"] {
+            let sealed = seal(body);
+            verify(&sealed).unwrap();
+            let value: serde_json::Value = serde_json::from_str(&sealed).unwrap();
+            assert_eq!(value["checksum"].as_str().unwrap().len(), HEX_LEN);
+        }
+    }
+
+    #[test]
+    fn any_change_fails_verification() {
+        let sealed = seal(r#"{"version":1,"vector":[0.5,1.5]}"#);
+        assert!(verify(&sealed.replace("0.5", "0.6")).unwrap_err().contains("Checksum mismatch"));
+        assert!(verify(&sealed.replace(",", ", ")).is_err());
+        assert!(verify(r#"{"version":1}"#).is_err());
+    }
+}
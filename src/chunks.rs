@@ -0,0 +1,372 @@
+//! Chunked JSON snapshots, for stores that cap the size of a single value
+//!
+//! A snapshot is split into a manifest and numbered record chunks:
+//!
+//! ```text
+//! {"manifest":{"chunks":2,"records":3},"state":{...}}
+//! {"chunk":0,"records":[{"id":"a","node":{...},"metadata":{...}}, ...]}
+//! {"chunk":1,"records":[...]}
+//! ```
+//!
+//! `state` is the `serialize` snapshot with its node, metadata and original
+//! vector maps emptied, along with the index's per-id timestamps and
+//! dropped-vector entries (see `INDEX_FIELDS`); each record carries one id's
+//! entries from those. Every chunk is a complete JSON document, and records
+//! are never split across chunks. Each piece, manifest included, is sealed
+//! with its own checksum (see `checksum`).
+//!
+//! The manifest keeps everything that isn't per record, such as the index
+//! settings and the random projection's shape and seed, so it isn't held to
+//! `chunk_bytes`.
+
+use serde_json::{json, Map, Value};
+
+use crate::checksum;
+
+/// A per-id collection under `hnsw_index` that travels inside records
+struct IndexField {
+    /// Key under `hnsw_index`
+    key: &'static str,
+    /// Key of an id's entry in its record
+    record_key: &'static str,
+    /// Stored as an array of ids rather than an object keyed by id
+    set: bool,
+}
+
+const INDEX_FIELDS: [IndexField; 3] = [
+    IndexField { key: "timestamps", record_key: "timestamp", set: false },
+    IndexField { key: "dropped_vectors", record_key: "dropped", set: true },
+    IndexField { key: "dropped_hashes", record_key: "dropped_hash", set: false },
+];
+
+/// Split a `serialize` snapshot into a manifest followed by record chunks of
+/// at most `chunk_bytes` bytes each
+///
+/// A record larger than `chunk_bytes` gets a chunk to itself.
+pub fn split(mut state: Value, chunk_bytes: usize) -> Result<Vec<String>, String> {
+    let nodes = take_object(&mut state["hnsw_index"]["nodes"])?;
+    let mut metadata = take_object(&mut state["metadata"])?;
+    let mut originals = match state.get_mut("originals") {
+        Some(originals) => take_object(originals)?,
+        None => Map::new(),
+    };
+    let mut index_entries = INDEX_FIELDS
+        .iter()
+        .map(|field| take_field(&mut state["hnsw_index"], field))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let record_count = nodes.len();
+    let mut chunks = Vec::new();
+    let mut records: Vec<String> = Vec::new();
+    let mut size = 0;
+    for (id, node) in nodes {
+        let mut record = json!({ "id": id, "node": node });
+        if let Some(meta) = metadata.remove(&id) {
+            record["metadata"] = meta;
+        }
+        if let Some(original) = originals.remove(&id) {
+            record["original"] = original;
+        }
+        for (field, entries) in INDEX_FIELDS.iter().zip(&mut index_entries) {
+            if let Some(entry) = entries.remove(&id) {
+                record[field.record_key] = entry;
+            }
+        }
+        let record = record.to_string();
+        let overhead = chunk_overhead(chunks.len());
+        if !records.is_empty() && overhead + size + records.len() + record.len() > chunk_bytes {
+            chunks.push(chunk_json(chunks.len(), &records));
+            records.clear();
+            size = 0;
+        }
+        size += record.len();
+        records.push(record);
+    }
+    if !records.is_empty() {
+        chunks.push(chunk_json(chunks.len(), &records));
+    }
+
+    // Entries for ids without a node (none in a consistent database) stay in
+    // the manifest so nothing is lost
+    state["metadata"] = Value::Object(metadata);
+    if !originals.is_empty() {
+        state["originals"] = Value::Object(originals);
+    }
+    for (field, entries) in INDEX_FIELDS.iter().zip(index_entries) {
+        restore_field(&mut state["hnsw_index"], field, entries);
+    }
+    let manifest = json!({
+        "manifest": { "chunks": chunks.len(), "records": record_count },
+        "state": state,
+    });
+
+    let mut out = Vec::with_capacity(chunks.len() + 1);
+    out.push(checksum::seal(&manifest.to_string()));
+    out.extend(chunks.iter().map(|chunk| checksum::seal(chunk)));
+    Ok(out)
+}
+
+/// Check every piece against its checksum
+///
+/// Pieces from before chunks were sealed carry none and pass, but once one
+/// piece is sealed all of them must be, so a checksum lost from a single
+/// piece is caught too.
+pub fn verify(pieces: &[String]) -> Result<(), String> {
+    let sealed = pieces.iter().filter(|piece| piece.starts_with(checksum::PREFIX)).count();
+    if sealed == 0 {
+        return Ok(());
+    }
+    if sealed < pieces.len() {
+        return Err(format!(
+            "Checksum mismatch: {} of {} pieces have no checksum",
+            pieces.len() - sealed,
+            pieces.len()
+        ));
+    }
+    pieces.iter().try_for_each(|piece| checksum::verify(piece))
+}
+
+/// Reassemble the snapshot `split` produced, from its pieces in any order
+pub fn join(pieces: &[String]) -> Result<String, String> {
+    let mut manifest = None;
+    let mut pending = Vec::new();
+    for piece in pieces {
+        let value: Value = serde_json::from_str(piece).map_err(|e| format!("Invalid chunk: {}", e))?;
+        if value.get("manifest").is_some() {
+            if manifest.replace(value).is_some() {
+                return Err("More than one manifest".to_string());
+            }
+        } else {
+            pending.push(value);
+        }
+    }
+
+    let mut manifest = manifest.ok_or("Missing manifest")?;
+    let expected_chunks = manifest["manifest"]["chunks"].as_u64().ok_or("Manifest has no chunk count")? as usize;
+    let expected_records = manifest["manifest"]["records"].as_u64().ok_or("Manifest has no record count")? as usize;
+    // Checked before sizing anything by these counts, so a corrupt one can't
+    // request a huge allocation
+    if expected_chunks > pending.len() {
+        return Err(format!(
+            "Missing chunks: the manifest lists {}, but {} were given",
+            expected_chunks,
+            pending.len()
+        ));
+    }
+
+    let mut chunks: Vec<Option<Vec<Value>>> = vec![None; expected_chunks];
+    for mut value in pending {
+        let index = value["chunk"].as_u64().ok_or("Chunk is missing its index")?;
+        let slot = usize::try_from(index)
+            .ok()
+            .and_then(|index| chunks.get_mut(index))
+            .ok_or_else(|| format!("Chunk {} is beyond the manifest's {} chunks", index, expected_chunks))?;
+        let Value::Array(records) = value["records"].take() else {
+            return Err(format!("Chunk {} has no records array", index));
+        };
+        if slot.replace(records).is_some() {
+            return Err(format!("Chunk {} appears twice", index));
+        }
+    }
+
+    let mut state = manifest["state"].take();
+    let mut nodes = take_object(&mut state["hnsw_index"]["nodes"])?;
+    let mut metadata = take_object(&mut state["metadata"])?;
+    let mut originals = match state.get_mut("originals") {
+        Some(originals) => take_object(originals)?,
+        None => Map::new(),
+    };
+    let mut index_entries = INDEX_FIELDS
+        .iter()
+        .map(|field| take_field(&mut state["hnsw_index"], field))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut record_count = 0;
+    for (index, records) in chunks.into_iter().enumerate() {
+        let records = records.ok_or_else(|| format!("Missing chunk {} of {}", index, expected_chunks))?;
+        for mut record in records {
+            let id = record["id"].as_str().ok_or_else(|| format!("Chunk {} has a record without an id", index))?;
+            let id = id.to_string();
+            nodes.insert(id.clone(), record["node"].take());
+            if let Some(meta) = record.get_mut("metadata") {
+                metadata.insert(id.clone(), meta.take());
+            }
+            if let Some(original) = record.get_mut("original") {
+                originals.insert(id.clone(), original.take());
+            }
+            for (field, entries) in INDEX_FIELDS.iter().zip(&mut index_entries) {
+                if let Some(entry) = record.get_mut(field.record_key) {
+                    entries.insert(id.clone(), entry.take());
+                }
+            }
+            record_count += 1;
+        }
+    }
+    if record_count != expected_records {
+        return Err(format!(
+            "Chunks hold {} records, but the manifest lists {}",
+            record_count, expected_records
+        ));
+    }
+
+    state["hnsw_index"]["nodes"] = Value::Object(nodes);
+    state["metadata"] = Value::Object(metadata);
+    if !originals.is_empty() {
+        state["originals"] = Value::Object(originals);
+    }
+    for (field, entries) in INDEX_FIELDS.iter().zip(index_entries) {
+        restore_field(&mut state["hnsw_index"], field, entries);
+    }
+    Ok(state.to_string())
+}
+
+/// Move `field` out of `index` as a map from id to entry (`true` for a set)
+fn take_field(index: &mut Value, field: &IndexField) -> Result<Map<String, Value>, String> {
+    let invalid = || format!("Snapshot has an invalid '{}'", field.key);
+    match index.as_object_mut().and_then(|index| index.remove(field.key)) {
+        None => Ok(Map::new()),
+        Some(Value::Object(map)) if !field.set => Ok(map),
+        Some(Value::Array(ids)) if field.set => ids
+            .into_iter()
+            .map(|id| match id {
+                Value::String(id) => Ok((id, Value::Bool(true))),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Put entries from `take_field` back under `index`, leaving the key out
+/// when there are none, as `serialize` does
+fn restore_field(index: &mut Value, field: &IndexField, entries: Map<String, Value>) {
+    if entries.is_empty() {
+        return;
+    }
+    index[field.key] = if field.set {
+        Value::Array(entries.into_iter().map(|(id, _)| Value::String(id)).collect())
+    } else {
+        Value::Object(entries)
+    };
+}
+
+/// Move an object out of `value`, leaving an empty one behind
+fn take_object(value: &mut Value) -> Result<Map<String, Value>, String> {
+    match value.take() {
+        Value::Object(map) => {
+            *value = Value::Object(Map::new());
+            Ok(map)
+        }
+        _ => Err("Snapshot is missing an expected object".to_string()),
+    }
+}
+
+/// Bytes a sealed chunk adds around its records, besides the commas between
+/// them
+fn chunk_overhead(index: usize) -> usize {
+    checksum::seal(&chunk_json(index, &[])).len()
+}
+
+fn chunk_json(index: usize, records: &[String]) -> String {
+    format!("{{\"chunk\":{},\"records\":[{}]}}", index, records.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(ids: &[&str]) -> Value {
+        let nodes: Map<String, Value> =
+            ids.iter().map(|id| (id.to_string(), json!({ "vector": [1.0, 2.0], "layers": [[]] }))).collect();
+        let metadata: Map<String, Value> = ids.iter().map(|id| (id.to_string(), json!({ "tag": id }))).collect();
+        json!({ "version": 1, "hnsw_index": { "dimensions": 2, "nodes": nodes }, "metadata": metadata })
+    }
+
+    #[test]
+    fn roundtrip_in_any_order() {
+        let state = snapshot(&["a", "b", "c", "d", "e"]);
+        let mut pieces = split(state.clone(), 120).unwrap();
+        assert!(pieces.len() > 2, "{:?}", pieces);
+        pieces.reverse();
+        let joined: Value = serde_json::from_str(&join(&pieces).unwrap()).unwrap();
+        assert_eq!(joined, state);
+    }
+
+    #[test]
+    fn chunks_respect_the_byte_limit_on_record_boundaries() {
+        let pieces = split(snapshot(&["a", "b", "c", "d", "e", "f"]), 150).unwrap();
+        for chunk in &pieces[1..] {
+            let value: Value = serde_json::from_str(chunk).unwrap();
+            let records = value["records"].as_array().unwrap();
+            assert!(chunk.len() <= 150 || records.len() == 1, "{}", chunk);
+        }
+        // A limit below one record still makes progress, one record each
+        assert_eq!(split(snapshot(&["a", "b", "c"]), 1).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn join_rejects_missing_or_repeated_pieces() {
+        let pieces = split(snapshot(&["a", "b", "c", "d"]), 100).unwrap();
+        assert!(join(&pieces[1..]).unwrap_err().contains("manifest"));
+        assert!(join(&pieces[..pieces.len() - 1]).unwrap_err().contains("Missing chunk"));
+        let mut repeated = pieces.clone();
+        repeated.push(pieces[1].clone());
+        assert!(join(&repeated).unwrap_err().contains("twice"));
+    }
+
+    #[test]
+    fn per_id_index_fields_travel_with_their_records() {
+        let mut state = snapshot(&["a", "b", "c", "d"]);
+        state["hnsw_index"]["timestamps"] = json!({ "a": 10, "c": 30 });
+        state["hnsw_index"]["dropped_vectors"] = json!(["b"]);
+        state["hnsw_index"]["dropped_hashes"] = json!({ "b": 7 });
+        let pieces = split(state.clone(), 100).unwrap();
+
+        let manifest: Value = serde_json::from_str(&pieces[0]).unwrap();
+        for field in &INDEX_FIELDS {
+            assert!(manifest["state"]["hnsw_index"].get(field.key).is_none());
+        }
+        assert!(pieces.iter().any(|piece| piece.contains("\"timestamp\":30")));
+        assert_eq!(serde_json::from_str::<Value>(&join(&pieces).unwrap()).unwrap(), state);
+    }
+
+    #[test]
+    fn join_rejects_chunk_indexes_beyond_the_manifest() {
+        let pieces = split(snapshot(&["a", "b"]), 1000).unwrap();
+        assert_eq!(pieces.len(), 2);
+        let mut corrupt = pieces.clone();
+        corrupt[1] = r#"{"chunk":4000000000,"records":[]}"#.to_string();
+        assert!(join(&corrupt).unwrap_err().contains("beyond"));
+
+        let mut manifest: Value = serde_json::from_str(&pieces[0]).unwrap();
+        manifest["manifest"]["chunks"] = json!(4_000_000_000u64);
+        let inflated = vec![manifest.to_string(), pieces[1].clone()];
+        assert!(join(&inflated).unwrap_err().contains("Missing chunks"));
+    }
+
+    #[test]
+    fn every_piece_is_sealed() {
+        let pieces = split(snapshot(&["a", "b", "c", "d"]), 100).unwrap();
+        verify(&pieces).unwrap();
+
+        let mut edited = pieces.clone();
+        edited[2] = edited[2].replace("1.0", "1.5");
+        assert!(verify(&edited).unwrap_err().contains("Checksum mismatch"));
+
+        // A piece whose checksum field was damaged no longer counts as sealed
+        let mut stripped = pieces.clone();
+        stripped[0] = stripped[0].replacen("checksum", "checksun", 1);
+        assert!(verify(&stripped).unwrap_err().contains("1 of"));
+
+        // Unsealed pieces, as written before chunks had checksums, still pass
+        let unsealed: Vec<String> = pieces
+            .iter()
+            .map(|piece| {
+                let mut value: Value = serde_json::from_str(piece).unwrap();
+                value.as_object_mut().unwrap().remove("checksum");
+                value.to_string()
+            })
+            .collect();
+        verify(&unsealed).unwrap();
+        assert_eq!(join(&unsealed).unwrap(), join(&pieces).unwrap());
+    }
+}
@@ -1,5 +1,10 @@
 //! Distance and similarity metrics for vectors
-//! Optimized for performance with potential SIMD support
+//! Optimized for performance with optional SIMD support
+//!
+//! With the `simd` feature on a wasm32 target built with `+simd128`,
+//! `dot_product`, `euclidean_distance_squared`, and `magnitude` use v128
+//! kernels whenever the length is a multiple of the 4-lane width; otherwise
+//! (and on every other target) they use the scalar loops in `scalar`.
 
 /// Compute cosine similarity between two vectors
 /// Returns value in range [-1, 1], where 1 means identical direction
@@ -23,43 +28,65 @@ pub fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
     1.0 - cosine_similarity(a, b)
 }
 
+/// Cosine distance from unit-length `unit` to `b`, given `b`'s squared norm,
+/// without computing the magnitude of `unit`
+///
+/// Matches `cosine_distance` when `unit` really is unit length, including the
+/// distance of 1 to a zero vector.
+#[inline]
+pub fn cosine_distance_from_unit(unit: &[f32], b: &[f32], b_norm_sq: f32) -> f32 {
+    if b_norm_sq == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot_product(unit, b) / b_norm_sq.sqrt()
+}
+
+/// Compute normalized angular distance `acos(cosine_similarity) / PI`, in [0, 1]
+///
+/// Unlike cosine distance this is a true metric (it satisfies the triangle
+/// inequality). The similarity is clamped to [-1, 1] first, since rounding
+/// can push it just past ±1 and make `acos` return NaN.
+#[inline]
+pub fn angular_distance(a: &[f32], b: &[f32]) -> f32 {
+    cosine_similarity(a, b).clamp(-1.0, 1.0).acos() / std::f32::consts::PI
+}
+
 /// Compute Euclidean (L2) distance between two vectors
 #[inline]
 pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
-    a.iter()
-        .zip(b.iter())
-        .map(|(x, y)| {
-            let diff = x - y;
-            diff * diff
-        })
-        .sum::<f32>()
-        .sqrt()
+    euclidean_distance_squared(a, b).sqrt()
 }
 
 /// Compute squared Euclidean distance (avoids sqrt for performance)
 #[inline]
 pub fn euclidean_distance_squared(a: &[f32], b: &[f32]) -> f32 {
-    a.iter()
-        .zip(b.iter())
-        .map(|(x, y)| {
-            let diff = x - y;
-            diff * diff
-        })
-        .sum()
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    if simd::applies(a, b) {
+        return simd::euclidean_distance_squared(a, b);
+    }
+    scalar::euclidean_distance_squared(a, b)
+}
+
+/// Compute squared Euclidean distance from precomputed squared norms using
+/// `|a-b|^2 = |a|^2 + |b|^2 - 2<a,b>`, so only one dot product is needed.
+/// Clamped at 0 since cancellation can go slightly negative for near-equal vectors.
+#[inline]
+pub fn euclidean_distance_squared_with_norms(a: &[f32], a_norm_sq: f32, b: &[f32], b_norm_sq: f32) -> f32 {
+    (a_norm_sq + b_norm_sq - 2.0 * dot_product(a, b)).max(0.0)
 }
 
 /// Compute dot product of two vectors
 #[inline]
 pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
-    a.iter()
-        .zip(b.iter())
-        .map(|(x, y)| x * y)
-        .sum()
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    if simd::applies(a, b) {
+        return simd::dot_product(a, b);
+    }
+    scalar::dot_product(a, b)
 }
 
 /// Compute Manhattan (L1) distance
 #[inline]
-#[allow(dead_code)]
 pub fn manhattan_distance(a: &[f32], b: &[f32]) -> f32 {
     a.iter()
         .zip(b.iter())
@@ -67,17 +94,69 @@ pub fn manhattan_distance(a: &[f32], b: &[f32]) -> f32 {
         .sum()
 }
 
+/// Compute Hamming distance between two bit vectors: the number of positions
+/// where exactly one side is set
+///
+/// Each `f32` encodes one bit: any nonzero value is set, 0.0 (or -0.0) is
+/// clear. Binary embeddings are expected one bit per dimension, e.g. the
+/// 0.0/1.0 output of a SimHash or a sign-quantized model.
+#[inline]
+pub fn hamming_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .filter(|(x, y)| (**x != 0.0) != (**y != 0.0))
+        .count() as f32
+}
+
+/// Compute Jaccard distance `1 - |A ∩ B| / |A ∪ B|` between two sets
+///
+/// Each vector encodes a set by membership: a position with a nonzero value
+/// is in the set, 0.0 (or -0.0) is not, so weights are ignored. Suits tag or
+/// bag-of-words vectors. Two empty sets (both vectors all zero) are treated
+/// as identical and return 0.0 rather than dividing by zero.
+#[inline]
+pub fn jaccard_distance(a: &[f32], b: &[f32]) -> f32 {
+    let (mut intersection, mut union) = (0usize, 0usize);
+    for (x, y) in a.iter().zip(b.iter()) {
+        let (in_a, in_b) = (*x != 0.0, *y != 0.0);
+        intersection += (in_a && in_b) as usize;
+        union += (in_a || in_b) as usize;
+    }
+    if union == 0 {
+        return 0.0;
+    }
+    1.0 - intersection as f32 / union as f32
+}
+
+/// Compute Minkowski distance `(Σ|x_i - y_i|^p)^(1/p)`
+///
+/// `p = 1` is Manhattan and `p = 2` Euclidean distance; as `p` grows the
+/// result approaches the Chebyshev distance (largest coordinate difference).
+/// It is a metric for `p >= 1`. A `p` between 0 and 1 is accepted but
+/// breaks the triangle inequality (the distance to a diagonal point exceeds
+/// the sum of the two axis steps), so graph search may lose recall.
+#[inline]
+pub fn minkowski_distance(a: &[f32], b: &[f32], p: f32) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y).abs().powf(p))
+        .sum::<f32>()
+        .powf(1.0 / p)
+}
+
 /// Compute vector magnitude (L2 norm)
 #[inline]
 pub fn magnitude(v: &[f32]) -> f32 {
-    v.iter()
-        .map(|x| x * x)
-        .sum::<f32>()
-        .sqrt()
+    dot_product(v, v).sqrt()
+}
+
+/// Compute squared magnitude (squared L2 norm)
+#[inline]
+pub fn squared_norm(v: &[f32]) -> f32 {
+    dot_product(v, v)
 }
 
 /// Normalize a vector to unit length (in-place)
-#[allow(dead_code)]
 pub fn normalize(v: &mut [f32]) {
     let mag = magnitude(v);
     if mag > 0.0 {
@@ -95,6 +174,77 @@ pub fn normalized(v: &[f32]) -> Vec<f32> {
     result
 }
 
+/// Portable scalar kernels, used whenever the SIMD path doesn't apply
+mod scalar {
+    #[inline]
+    pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| x * y)
+            .sum()
+    }
+
+    #[inline]
+    pub fn euclidean_distance_squared(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| {
+                let diff = x - y;
+                diff * diff
+            })
+            .sum()
+    }
+}
+
+/// WebAssembly SIMD (v128) kernels processing 4 f32 lanes at a time
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod simd {
+    use core::arch::wasm32::*;
+
+    const LANES: usize = 4;
+
+    /// The SIMD kernels only handle equal-length inputs that fill whole lanes
+    #[inline]
+    pub fn applies(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.len() % LANES == 0
+    }
+
+    #[inline]
+    fn load(chunk: &[f32]) -> v128 {
+        debug_assert_eq!(chunk.len(), LANES);
+        // SAFETY: chunk holds exactly LANES contiguous f32s (16 bytes), and
+        // v128_load has no alignment requirement
+        unsafe { v128_load(chunk.as_ptr() as *const v128) }
+    }
+
+    #[inline]
+    fn horizontal_sum(v: v128) -> f32 {
+        f32x4_extract_lane::<0>(v)
+            + f32x4_extract_lane::<1>(v)
+            + f32x4_extract_lane::<2>(v)
+            + f32x4_extract_lane::<3>(v)
+    }
+
+    #[inline]
+    pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = f32x4_splat(0.0);
+        for (ca, cb) in a.chunks_exact(LANES).zip(b.chunks_exact(LANES)) {
+            acc = f32x4_add(acc, f32x4_mul(load(ca), load(cb)));
+        }
+        horizontal_sum(acc)
+    }
+
+    #[inline]
+    pub fn euclidean_distance_squared(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = f32x4_splat(0.0);
+        for (ca, cb) in a.chunks_exact(LANES).zip(b.chunks_exact(LANES)) {
+            let diff = f32x4_sub(load(ca), load(cb));
+            acc = f32x4_add(acc, f32x4_mul(diff, diff));
+        }
+        horizontal_sum(acc)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -222,6 +372,65 @@ mod tests {
         assert!((euclidean_distance_squared(&a, &a) - 0.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_euclidean_distance_squared_with_norms_matches_direct() {
+        // Simple LCG so the vectors are varied but reproducible
+        let mut state: u32 = 12345;
+        let mut next = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0
+        };
+        for dims in [1, 3, 32, 128, 768] {
+            for _ in 0..20 {
+                let a: Vec<f32> = (0..dims).map(|_| next()).collect();
+                let b: Vec<f32> = (0..dims).map(|_| next()).collect();
+                let direct = euclidean_distance_squared(&a, &b);
+                let cached =
+                    euclidean_distance_squared_with_norms(&a, squared_norm(&a), &b, squared_norm(&b));
+                assert!(
+                    (direct - cached).abs() <= 1e-4 * direct.max(1.0),
+                    "dims {}: {} vs {}",
+                    dims,
+                    direct,
+                    cached
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_euclidean_distance_squared_with_norms_identical_not_negative() {
+        let a = vec![0.1, 0.2, 0.3, 1e3];
+        let n = squared_norm(&a);
+        assert!(euclidean_distance_squared_with_norms(&a, n, &a, n) >= 0.0);
+    }
+
+    // ── angular_distance ───────────────────────────────────────────
+
+    #[test]
+    fn test_angular_distance_identical_is_zero() {
+        let a = vec![0.3, 0.4, 0.5];
+        assert!(angular_distance(&a, &a).abs() < 1e-3);
+        // Self-similarity can round just past 1.0; clamping keeps it finite
+        for v in [vec![0.1, 0.7, 0.2], vec![1.0 / 3.0; 7], vec![0.123, 4.56, 7.89, 0.001]] {
+            assert!(angular_distance(&v, &v).is_finite());
+        }
+    }
+
+    #[test]
+    fn test_angular_distance_orthogonal_is_half() {
+        let a = vec![1.0, 0.0];
+        let b = vec![0.0, 2.0];
+        assert!((angular_distance(&a, &b) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_angular_distance_opposite_is_one() {
+        let a = vec![1.0, 2.0, 3.0];
+        let b = vec![-2.0, -4.0, -6.0];
+        assert!((angular_distance(&a, &b) - 1.0).abs() < 1e-3);
+    }
+
     // ── manhattan_distance ─────────────────────────────────────────
 
     #[test]
@@ -245,6 +454,88 @@ mod tests {
         assert!((manhattan_distance(&a, &a) - 0.0).abs() < 1e-6);
     }
 
+    // ── hamming_distance ───────────────────────────────────────────
+
+    #[test]
+    fn test_hamming_distance_all_equal() {
+        let a = [1.0, 0.0, 1.0, 1.0];
+        assert_eq!(hamming_distance(&a, &a), 0.0);
+        // Any nonzero value is a set bit
+        assert_eq!(hamming_distance(&a, &[0.5, -0.0, -3.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_hamming_distance_all_different() {
+        let a = [1.0, 0.0, 1.0, 0.0];
+        let b = [0.0, 1.0, 0.0, 1.0];
+        assert_eq!(hamming_distance(&a, &b), 4.0);
+    }
+
+    #[test]
+    fn test_hamming_distance_partial_overlap() {
+        let a = [1.0, 1.0, 0.0, 0.0, 1.0];
+        let b = [1.0, 0.0, 0.0, 1.0, 1.0];
+        assert_eq!(hamming_distance(&a, &b), 2.0);
+    }
+
+    // ── jaccard_distance ───────────────────────────────────────────
+
+    #[test]
+    fn test_jaccard_distance_identical() {
+        let a = [1.0, 0.0, 2.0, 0.0];
+        assert_eq!(jaccard_distance(&a, &a), 0.0);
+        // Only membership counts, not the weights
+        assert_eq!(jaccard_distance(&a, &[0.1, 0.0, -7.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_jaccard_distance_disjoint() {
+        let a = [1.0, 1.0, 0.0, 0.0];
+        let b = [0.0, 0.0, 1.0, 1.0];
+        assert_eq!(jaccard_distance(&a, &b), 1.0);
+    }
+
+    #[test]
+    fn test_jaccard_distance_partial_overlap() {
+        // A = {0, 1, 4}, B = {0, 3, 4}: |A ∩ B| = 2, |A ∪ B| = 4
+        let a = [1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        let b = [1.0, 0.0, 0.0, 1.0, 1.0, 0.0];
+        assert!((jaccard_distance(&a, &b) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_jaccard_distance_both_empty() {
+        assert_eq!(jaccard_distance(&[0.0, 0.0, 0.0], &[0.0, -0.0, 0.0]), 0.0);
+        assert_eq!(jaccard_distance(&[0.0, 0.0], &[0.0, 1.0]), 1.0);
+    }
+
+    // ── minkowski_distance ─────────────────────────────────────────
+
+    #[test]
+    fn test_minkowski_matches_manhattan_and_euclidean() {
+        let a = [1.0, -2.0, 3.5, 0.0];
+        let b = [4.0, 2.0, -1.0, 0.5];
+        assert!((minkowski_distance(&a, &b, 1.0) - manhattan_distance(&a, &b)).abs() < 1e-5);
+        assert!((minkowski_distance(&a, &b, 2.0) - euclidean_distance(&a, &b)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_minkowski_large_p_approaches_chebyshev() {
+        let a = [0.0, 0.0, 0.0];
+        let b = [1.0, 3.0, 2.0];
+        assert!((minkowski_distance(&a, &b, 50.0) - 3.0).abs() < 1e-3);
+        assert_eq!(minkowski_distance(&a, &a, 3.0), 0.0);
+    }
+
+    #[test]
+    fn test_minkowski_below_one_breaks_the_triangle_inequality() {
+        let (a, b, c) = ([0.0, 0.0], [1.0, 0.0], [1.0, 1.0]);
+        let direct = minkowski_distance(&a, &c, 0.5);
+        let via_b = minkowski_distance(&a, &b, 0.5) + minkowski_distance(&b, &c, 0.5);
+        assert!((direct - 4.0).abs() < 1e-5);
+        assert!(direct > via_b);
+    }
+
     // ── magnitude ──────────────────────────────────────────────────
 
     #[test]
@@ -332,4 +623,46 @@ mod tests {
         let mag = magnitude(&a);
         assert!((dot_product(&a, &a) - mag * mag).abs() < 1e-4);
     }
+
+    // ── SIMD kernels ───────────────────────────────────────────────
+
+    /// Reproducible values in [-1, 1)
+    fn lcg_vector(dims: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..dims)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dispatch_matches_scalar_for_odd_lengths() {
+        // Lengths that aren't a multiple of the lane width always take the scalar path
+        for dims in [1, 3, 767, 1535] {
+            let a = lcg_vector(dims, 1);
+            let b = lcg_vector(dims, 2);
+            assert_eq!(dot_product(&a, &b), scalar::dot_product(&a, &b));
+            assert_eq!(
+                euclidean_distance_squared(&a, &b),
+                scalar::euclidean_distance_squared(&a, &b)
+            );
+        }
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+    #[wasm_bindgen_test::wasm_bindgen_test]
+    fn test_simd_matches_scalar_high_dimensional() {
+        for (dims, seed) in [(4, 1), (128, 2), (768, 3), (1536, 4)] {
+            let a = lcg_vector(dims, seed);
+            let b = lcg_vector(dims, seed + 100);
+            assert!((simd::dot_product(&a, &b) - scalar::dot_product(&a, &b)).abs() < 1e-4);
+            assert!(
+                (simd::euclidean_distance_squared(&a, &b) - scalar::euclidean_distance_squared(&a, &b)).abs()
+                    < 1e-4
+            );
+            assert!((magnitude(&a) - scalar::dot_product(&a, &a).sqrt()).abs() < 1e-4);
+        }
+    }
 }
@@ -0,0 +1,94 @@
+//! Coded errors for JavaScript callers
+//!
+//! A `DbError` reaches JavaScript as an `Error` whose `message` is the
+//! human-readable text and whose `code` is one of the `ErrorCode` strings,
+//! so callers can branch on `err.code` instead of matching messages.
+
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+/// Machine-readable category of a `DbError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// A vector or query has the wrong number of components
+    DimensionMismatch,
+    /// A vector or metadata value contains NaN or Infinity
+    NonFinite,
+    /// A referenced id isn't stored
+    NotFound,
+    /// A snapshot or other serialized input couldn't be read (or, rarely,
+    /// written)
+    ParseError,
+    /// A snapshot's format version is newer than this build reads
+    UnsupportedVersion,
+    /// The database was loaded with `load_readonly`
+    ReadOnly,
+    /// Vectors were discarded by `optimize_storage("drop-vectors")`
+    VectorsDropped,
+    /// Any other rejected argument
+    InvalidArgument,
+    /// A snapshot's checksum doesn't match its contents
+    ChecksumMismatch,
+}
+
+impl ErrorCode {
+    /// The `code` string JavaScript sees
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::DimensionMismatch => "DIMENSION_MISMATCH",
+            ErrorCode::NonFinite => "NON_FINITE",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::ParseError => "PARSE_ERROR",
+            ErrorCode::UnsupportedVersion => "UNSUPPORTED_VERSION",
+            ErrorCode::ReadOnly => "READ_ONLY",
+            ErrorCode::VectorsDropped => "VECTORS_DROPPED",
+            ErrorCode::InvalidArgument => "INVALID_ARGUMENT",
+            ErrorCode::ChecksumMismatch => "CHECKSUM_MISMATCH",
+        }
+    }
+}
+
+/// An error with a code and a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl DbError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        DbError { code, message: message.into() }
+    }
+
+    /// `message` as a snapshot that couldn't be read
+    pub fn parse(message: impl fmt::Display) -> Self {
+        DbError::new(ErrorCode::ParseError, message.to_string())
+    }
+
+    /// `message` as a rejected argument
+    pub fn invalid(message: impl Into<String>) -> Self {
+        DbError::new(ErrorCode::InvalidArgument, message)
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Keeps just the message, for helpers that report plain strings
+impl From<DbError> for String {
+    fn from(error: DbError) -> Self {
+        error.message
+    }
+}
+
+impl From<DbError> for JsValue {
+    fn from(error: DbError) -> Self {
+        let js_error = js_sys::Error::new(&error.message);
+        // Setting a property on a fresh Error object can't fail
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &error.code.as_str().into());
+        js_error.into()
+    }
+}
@@ -0,0 +1,195 @@
+//! Metadata filters for restricting search and export to matching records
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::metadata::{Metadata, MetadataValue};
+
+/// Bounds on a numeric metadata field; every bound given must hold
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NumericRange {
+    pub gte: Option<f64>,
+    pub gt: Option<f64>,
+    pub lte: Option<f64>,
+    pub lt: Option<f64>,
+    pub eq: Option<f64>,
+}
+
+impl NumericRange {
+    fn bounds(&self) -> [Option<f64>; 5] {
+        [self.gte, self.gt, self.lte, self.lt, self.eq]
+    }
+
+    /// Whether `x` satisfies every bound
+    pub fn contains(&self, x: f64) -> bool {
+        self.gte.is_none_or(|b| x >= b)
+            && self.gt.is_none_or(|b| x > b)
+            && self.lte.is_none_or(|b| x <= b)
+            && self.lt.is_none_or(|b| x < b)
+            && self.eq.is_none_or(|b| x == b)
+    }
+}
+
+/// What one metadata field must satisfy
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Condition {
+    /// The field holds this exact value, of the same type
+    Equals(MetadataValue),
+    /// The field holds a number within the range
+    Range(NumericRange),
+}
+
+/// Filter over metadata: every `key -> condition` pair must hold for a
+/// record to match
+///
+/// An equality condition requires the same value with the same type. A range
+/// condition requires a numeric value in range; records missing the field or
+/// holding a string or boolean there are excluded.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataFilter {
+    conditions: HashMap<String, Condition>,
+}
+
+impl MetadataFilter {
+    /// Filter from arbitrary conditions; each range needs at least one
+    /// bound, and every bound must be finite
+    pub fn from_conditions(conditions: HashMap<String, Condition>) -> Result<Self, String> {
+        for (key, condition) in &conditions {
+            if let Condition::Range(range) = condition {
+                let bounds = range.bounds();
+                if bounds.iter().all(Option::is_none) {
+                    return Err(format!(
+                        "Range on '{}' needs at least one of gte, gt, lte, lt, eq",
+                        key
+                    ));
+                }
+                if bounds.iter().flatten().any(|b| !b.is_finite()) {
+                    return Err(format!("Range on '{}' has a NaN or infinite bound", key));
+                }
+            }
+        }
+        Ok(MetadataFilter { conditions })
+    }
+
+    /// Whether the filter has no conditions (and so matches every record)
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Whether a record with the given metadata passes the filter.
+    /// An empty filter matches everything, including records without metadata.
+    pub fn matches(&self, metadata: Option<&Metadata>) -> bool {
+        if self.conditions.is_empty() {
+            return true;
+        }
+        match metadata {
+            Some(meta) => self.conditions.iter().all(|(key, condition)| match condition {
+                Condition::Equals(value) => meta.get(key) == Some(value),
+                Condition::Range(range) => meta
+                    .get(key)
+                    .and_then(MetadataValue::as_f64)
+                    .is_some_and(|x| range.contains(x)),
+            }),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Filter requiring each pair of `required` to be present
+    fn equals(required: Metadata) -> MetadataFilter {
+        let conditions = required.into_iter().map(|(k, v)| (k, Condition::Equals(v))).collect();
+        MetadataFilter::from_conditions(conditions).unwrap()
+    }
+
+    fn meta(pairs: &[(&str, &str)]) -> Metadata {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), (*v).into()))
+            .collect()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = MetadataFilter::default();
+        assert!(filter.matches(None));
+        assert!(filter.matches(Some(&meta(&[("tenant", "acme")]))));
+    }
+
+    #[test]
+    fn all_pairs_must_match() {
+        let filter = equals(meta(&[("tenant", "acme"), ("lang", "en")]));
+        assert!(filter.matches(Some(&meta(&[("tenant", "acme"), ("lang", "en"), ("x", "y")]))));
+        assert!(!filter.matches(Some(&meta(&[("tenant", "acme")]))));
+        assert!(!filter.matches(Some(&meta(&[("tenant", "other"), ("lang", "en")]))));
+        assert!(!filter.matches(None));
+    }
+
+    #[test]
+    fn values_must_match_in_type() {
+        let stored: Metadata = [("year".to_string(), 2024.0.into()), ("draft".to_string(), true.into())]
+            .into_iter()
+            .collect();
+        let year = |value: MetadataValue| {
+            equals([("year".to_string(), value)].into_iter().collect())
+        };
+        assert!(year(2024.0.into()).matches(Some(&stored)));
+        assert!(!year("2024".into()).matches(Some(&stored)));
+        let draft = equals([("draft".to_string(), true.into())].into_iter().collect());
+        assert!(draft.matches(Some(&stored)));
+    }
+
+    #[test]
+    fn ranges_match_numbers_within_every_bound() {
+        let range = |json: &str| -> MetadataFilter {
+            let conditions: HashMap<String, Condition> = serde_json::from_str(json).unwrap();
+            MetadataFilter::from_conditions(conditions).unwrap()
+        };
+        let price = |x: f64| -> Metadata { [("price".to_string(), x.into())].into_iter().collect() };
+
+        let window = range(r#"{"price": {"gte": 10, "lt": 50}}"#);
+        assert!(window.matches(Some(&price(10.0))));
+        assert!(window.matches(Some(&price(49.5))));
+        assert!(!window.matches(Some(&price(50.0))));
+        assert!(!window.matches(Some(&price(9.99))));
+
+        let open = range(r#"{"price": {"gt": 10, "lte": 50}}"#);
+        assert!(!open.matches(Some(&price(10.0))));
+        assert!(open.matches(Some(&price(50.0))));
+        assert!(range(r#"{"price": {"eq": 20}}"#).matches(Some(&price(20.0))));
+
+        // Missing or non-numeric fields are excluded
+        assert!(!window.matches(Some(&meta(&[("price", "20")]))));
+        assert!(!window.matches(Some(&meta(&[("other", "x")]))));
+        assert!(!window.matches(None));
+
+        // Ranges combine with equality conditions
+        let mixed = range(r#"{"price": {"lte": 30}, "tenant": "acme"}"#);
+        let mut record = price(20.0);
+        record.insert("tenant".to_string(), "acme".into());
+        assert!(mixed.matches(Some(&record)));
+        record.insert("tenant".to_string(), "other".into());
+        assert!(!mixed.matches(Some(&record)));
+    }
+
+    #[test]
+    fn ranges_need_a_finite_bound() {
+        let parse = |json: &str| {
+            let conditions: HashMap<String, Condition> = serde_json::from_str(json).unwrap();
+            MetadataFilter::from_conditions(conditions)
+        };
+        assert!(parse(r#"{"price": {}}"#).unwrap_err().contains("at least one"));
+        assert!(serde_json::from_str::<HashMap<String, Condition>>(r#"{"price": {"between": 1}}"#).is_err());
+        let mut conditions = HashMap::new();
+        conditions.insert(
+            "price".to_string(),
+            Condition::Range(NumericRange { lt: Some(f64::NAN), ..Default::default() }),
+        );
+        assert!(MetadataFilter::from_conditions(conditions).is_err());
+    }
+}
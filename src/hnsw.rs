@@ -6,21 +6,316 @@
 //! - Each node connects to M nearest neighbors per layer
 //! - Search starts at the top layer and descends to layer 0
 
+use crate::checksum;
 use crate::distance;
+use crate::kmeans;
+use crate::quantize::{Quantization, QuantizedVector};
+use crate::rng;
 use serde::{Deserialize, Serialize};
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::cmp::Ordering;
 
+/// Default multiplier applied to k when automatic ef scaling is enabled
+pub const DEFAULT_AUTO_EF_FACTOR: usize = 2;
+
+/// Highest layer a node can be assigned unless `set_max_layer_cap` says otherwise
+pub const DEFAULT_MAX_LAYER_CAP: usize = 16;
+
+/// Smallest `m` an index accepts: the layer multiplier `1 / ln(m)` is
+/// infinite at `m = 1`, which would put nodes on arbitrarily high layers
+pub const MIN_M: usize = 2;
+
+/// Number of vectors inspected when an automatic metric is resolved
+pub const AUTO_METRIC_SAMPLE: usize = 64;
+
+/// A vector counts as unit-normalized when its length is within this of 1
+const AUTO_METRIC_NORM_TOLERANCE: f32 = 1e-3;
+
 /// Distance metric used for nearest-neighbor search
 #[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
 pub enum DistanceMetric {
     Euclidean,
     Cosine,
     DotProduct,
+    Manhattan,
+    /// `acos(cosine similarity) / PI`: a true metric in [0, 1]
+    Angular,
+    /// `(Σ|x_i - y_i|^p)^(1/p)`; `p = 1` is Manhattan, `p = 2` Euclidean
+    Minkowski { p: f32 },
+    /// Number of differing bits, with each dimension one bit (nonzero = set)
+    Hamming,
+    /// `1 - |A ∩ B| / |A ∪ B|` over the sets of nonzero dimensions
+    Jaccard,
+}
+
+impl DistanceMetric {
+    /// Lowercase name as accepted by `VectorDB::new`
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceMetric::Euclidean => "euclidean",
+            DistanceMetric::Cosine => "cosine",
+            DistanceMetric::DotProduct => "dotproduct",
+            DistanceMetric::Manhattan => "manhattan",
+            DistanceMetric::Angular => "angular",
+            DistanceMetric::Minkowski { .. } => "minkowski",
+            DistanceMetric::Hamming => "hamming",
+            DistanceMetric::Jaccard => "jaccard",
+        }
+    }
+
+    /// The "higher is better" score behind a reported distance, for metrics
+    /// whose distance is a negated similarity
+    ///
+    /// Dot product distances are `-dot(a, b)`, so they sort ascending like
+    /// every other metric but can be negative; this recovers the raw dot
+    /// product. Other metrics are true distances and return None.
+    pub fn similarity(&self, distance: f32) -> Option<f32> {
+        match self {
+            DistanceMetric::DotProduct => Some(-distance),
+            _ => None,
+        }
+    }
+}
+
+/// Shape of an index, see `HNSWIndex::stats`
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct IndexStats {
+    pub node_count: usize,
+    pub max_layer: usize,
+    pub dimensions: usize,
+    pub metric: String,
+    pub m: usize,
+    pub ef_construction: usize,
+    /// Mean number of layer-0 connections per node
+    pub avg_layer0_connections: f32,
+    /// Number of nodes present on each layer, from layer 0 up
+    pub nodes_per_layer: Vec<usize>,
+    /// `k` multiplier from `set_auto_ef_factor`, if automatic ef is enabled
+    pub auto_ef_factor: Option<usize>,
+}
+
+/// Aggregates behind `HNSWIndex::stats`, kept current on every insert and delete
+#[derive(Clone, Debug, Default, PartialEq)]
+struct RunningStats {
+    /// Nodes present on each layer
+    nodes_per_layer: Vec<usize>,
+    /// Total directed layer-0 links
+    layer0_edges: usize,
+}
+
+impl RunningStats {
+    /// Count a node present on layers `0..layers` (negative `sign` removes it)
+    fn add_node(&mut self, layers: usize, sign: isize) {
+        if self.nodes_per_layer.len() < layers {
+            self.nodes_per_layer.resize(layers, 0);
+        }
+        for count in self.nodes_per_layer.iter_mut().take(layers) {
+            *count = count.saturating_add_signed(sign);
+        }
+    }
+
+    fn adjust_edges(&mut self, added: usize, removed: usize) {
+        self.layer0_edges = (self.layer0_edges + added).saturating_sub(removed);
+    }
+}
+
+/// Distribution of k-th nearest neighbor distances over a sample of nodes.
+///
+/// A wide spread (high coefficient of variation) indicates the dataset mixes
+/// dense and sparse regions, which a single global `m` serves poorly.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DensityProfile {
+    pub k: usize,
+    /// Number of sampled nodes that had at least k neighbors
+    pub samples: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub std_dev: f32,
+    /// std_dev / mean (0 when mean is 0)
+    pub coefficient_of_variation: f32,
+    /// Per-sample k-th neighbor distances, ascending
+    pub distances: Vec<f32>,
+}
+
+/// Observed layer distribution compared against the geometric expectation for `ml`.
+///
+/// Each node's top layer should follow P(layer >= l) = exp(-l / ml). Large
+/// deviations point at a broken RNG or a misconfigured `ml`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LayerHealth {
+    pub healthy: bool,
+    pub node_count: usize,
+    pub ml: f32,
+    /// Number of nodes whose top layer is exactly `i`
+    pub observed: Vec<usize>,
+    /// Expected number of nodes whose top layer is exactly `i`
+    pub expected: Vec<f32>,
+    /// Human-readable description of each detected deviation
+    pub anomalies: Vec<String>,
+}
+
+/// One layer of the graph as node and edge lists, for visualization
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct LayerAdjacency {
+    pub layer: usize,
+    /// Ids of the nodes present on the layer, sorted
+    pub nodes: Vec<String>,
+    /// Directed links as `[from, to]`, sorted; a mutual link appears once
+    /// in each direction
+    pub edges: Vec<(String, String)>,
+}
+
+/// A pair of records, one from each index, that are each other's nearest neighbor
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct MutualPair {
+    /// Id in the index the search was run from
+    pub a: String,
+    /// Id in the other index
+    pub b: String,
+    pub distance: f32,
+}
+
+/// How often a node was routed through during sample searches, see
+/// `HNSWIndex::navigation_importance`
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct NodeImportance {
+    pub id: String,
+    /// Fraction of sample searches that expanded this node (0.0–1.0)
+    pub score: f32,
+}
+
+/// Usage of the query seed cache, see `HNSWIndex::enable_seed_cache`
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SeedCacheStats {
+    pub cell_size: f32,
+    /// Cached query cells
+    pub entries: usize,
+    /// Searches that skipped the upper-layer descent
+    pub hits: u64,
+    /// Searches that ran the full descent
+    pub misses: u64,
+}
+
+/// Maximum number of query cells kept before the seed cache is reset
+const SEED_CACHE_MAX_ENTRIES: usize = 4096;
+
+/// Maps quantized query cells to the layer-0 entry points the upper-layer
+/// descent found for an earlier query in the same cell
+#[derive(Clone, Debug)]
+struct SeedCache {
+    cell_size: f32,
+    entries: HashMap<Vec<i32>, Vec<String>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl SeedCache {
+    fn cell(&self, query: &[f32]) -> Vec<i32> {
+        query
+            .iter()
+            .map(|x| (x / self.cell_size).floor() as i32)
+            .collect()
+    }
+}
+
+/// Minimum node count before per-layer fractions are compared statistically
+const LAYER_HEALTH_MIN_NODES: usize = 100;
+
+/// `search` retries with `ef` widened by this factor when the graph returns
+/// fewer than k results although the index holds at least k nodes
+const SEARCH_RETRY_EF_FACTOR: usize = 4;
+/// Upper bound on the widened `ef` of a retried search
+const SEARCH_RETRY_MAX_EF: usize = 2048;
+/// A search still short after the retry falls back to an exact scan when
+/// the index has at most this many nodes; larger indexes return what the
+/// graph reached
+const EXACT_FALLBACK_MAX_NODES: usize = 10_000;
+
+/// `search` scans every node instead of walking the graph while the index
+/// holds fewer nodes than this; off until `set_exact_threshold` opts in
+pub const DEFAULT_EXACT_THRESHOLD: usize = 0;
+
+/// Largest `ef` that `tune_ef` tries before giving up on the target recall
+pub const TUNE_EF_MAX: usize = 4096;
+
+/// Below this dimensionality the direct subtraction loop is as cheap as the
+/// cached-norm identity, so Euclidean distances use the direct path
+const NORM_IDENTITY_MIN_DIMS: usize = 32;
+
+/// Compare a top-layer histogram against the geometric distribution for `ml`
+fn assess_layers(observed: Vec<usize>, ml: f32) -> LayerHealth {
+    let n: usize = observed.iter().sum();
+    let mut anomalies = Vec::new();
+
+    if !ml.is_finite() || ml <= 0.0 {
+        anomalies.push(format!("ml = {} is degenerate; every node lands on layer 0", ml));
+        return LayerHealth {
+            healthy: false,
+            node_count: n,
+            ml,
+            expected: vec![n as f32],
+            observed,
+            anomalies,
+        };
+    }
+
+    // Tallest layer we'd expect to see, with some slack for randomness
+    let expected_top = (ml * (n.max(1) as f32).ln()).ceil() as usize;
+    let observed_top = observed.len().saturating_sub(1);
+    if n > 1 && observed_top > expected_top + 3 {
+        anomalies.push(format!(
+            "max layer {} is far above the expected ~{} for {} nodes",
+            observed_top, expected_top, n
+        ));
+    }
+
+    let layers = observed.len().max(expected_top + 1);
+    let expected: Vec<f32> = (0..layers)
+        .map(|l| n as f32 * ((-(l as f32) / ml).exp() - (-((l + 1) as f32) / ml).exp()))
+        .collect();
+
+    if n >= LAYER_HEALTH_MIN_NODES {
+        // Compare the share of nodes reaching each layer (P(top >= l))
+        let mut at_or_above = n;
+        for (l, &count) in observed.iter().enumerate() {
+            let want = (-(l as f32) / ml).exp();
+            let got = at_or_above as f32 / n as f32;
+            let tolerance = 4.0 * (want * (1.0 - want) / n as f32).sqrt() + 0.02;
+            if (got - want).abs() > tolerance {
+                anomalies.push(format!(
+                    "{:.3} of nodes reach layer {}, expected {:.3}",
+                    got, l, want
+                ));
+            }
+            at_or_above -= count;
+        }
+        // A missing layer 1 means every node stayed on layer 0
+        if observed.len() == 1 {
+            let want = (-1.0 / ml).exp();
+            if want > 4.0 * (want * (1.0 - want) / n as f32).sqrt() + 0.02 {
+                anomalies.push(format!("no nodes reach layer 1, expected {:.3}", want));
+            }
+        }
+    }
+
+    LayerHealth {
+        healthy: anomalies.is_empty(),
+        node_count: n,
+        ml,
+        observed,
+        expected,
+        anomalies,
+    }
 }
 
 /// Max-heap element: pop() returns the element with the LARGEST distance.
 /// Used for the result set (`nearest`) to evict the farthest neighbor.
+/// Equal distances are ordered by id, so among ties the largest id is
+/// evicted first and results come out in a stable, id-sorted order.
 #[derive(Clone)]
 struct MaxDistElement {
     id: String,
@@ -29,7 +324,7 @@ struct MaxDistElement {
 
 impl PartialEq for MaxDistElement {
     fn eq(&self, other: &Self) -> bool {
-        self.distance == other.distance
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -37,7 +332,10 @@ impl Eq for MaxDistElement {}
 
 impl Ord for MaxDistElement {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.distance.partial_cmp(&other.distance).unwrap_or(Ordering::Equal)
+        self.distance
+            .partial_cmp(&other.distance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.id.cmp(&other.id))
     }
 }
 
@@ -48,7 +346,8 @@ impl PartialOrd for MaxDistElement {
 }
 
 /// Min-heap element: pop() returns the element with the SMALLEST distance.
-/// Used for the candidate queue to explore closest nodes first.
+/// Used for the candidate queue to explore closest nodes first; equal
+/// distances pop in ascending id order.
 #[derive(Clone)]
 struct MinDistElement {
     id: String,
@@ -57,7 +356,7 @@ struct MinDistElement {
 
 impl PartialEq for MinDistElement {
     fn eq(&self, other: &Self) -> bool {
-        self.distance == other.distance
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -65,7 +364,11 @@ impl Eq for MinDistElement {}
 
 impl Ord for MinDistElement {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.distance.partial_cmp(&self.distance).unwrap_or(Ordering::Equal)
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.id.cmp(&self.id))
     }
 }
 
@@ -79,22 +382,84 @@ impl PartialOrd for MinDistElement {
 #[derive(Clone, Serialize, Deserialize)]
 struct HNSWNode {
     id: String,
+    /// Full-precision vector; empty when the node is stored quantized
     vector: Vec<f32>,
+    /// Quantized vector, set instead of `vector` in an int8 or f16 index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quantized: Option<QuantizedVector>,
     /// Connections per layer: layer_idx -> set of neighbor IDs
+    #[serde(serialize_with = "serialize_sorted_connections")]
     connections: Vec<HashSet<String>>,
+    /// Per-node override of the index-wide `m`, for hub vectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_m: Option<usize>,
+    /// Cached squared L2 norm of `vector`; recomputed on load, never persisted
+    #[serde(skip)]
+    norm_sq: f32,
+}
+
+impl HNSWNode {
+    /// Length of the stored vector, without decoding it
+    fn dimensions(&self) -> usize {
+        match &self.quantized {
+            Some(q) => q.dimensions(),
+            None => self.vector.len(),
+        }
+    }
+
+    /// The node's vector, reconstructed from its codes when quantized
+    fn vector(&self) -> Cow<'_, [f32]> {
+        match &self.quantized {
+            Some(q) => Cow::Owned(q.decode()),
+            None => Cow::Borrowed(&self.vector),
+        }
+    }
+}
+
+/// Connection limits of an index: `m` above layer 0 and `m0` at layer 0
+#[derive(Clone, Copy)]
+struct LayerCaps {
+    m: usize,
+    m0: usize,
+}
+
+impl LayerCaps {
+    /// Connection limit at `layer` for a node with its own `max_m`, or the
+    /// index-wide limits when it has none (a node's own `m` allows twice as
+    /// many links at layer 0)
+    fn at(&self, max_m: Option<usize>, layer: usize) -> usize {
+        match (max_m, layer) {
+            (Some(m), 0) => m * 2,
+            (Some(m), _) => m,
+            (None, 0) => self.m0,
+            (None, _) => self.m,
+        }
+    }
 }
 
 /// HNSW Index
+///
+/// (De)serialized through the derived `remote = "Self"` functions so that
+/// `Deserialize` can seed the runtime-only fields afterwards.
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct HNSWIndex {
     pub dimensions: usize,
     /// M: max number of connections per layer
     m: usize,
+    /// M0: max number of connections at layer 0; None (snapshots from before
+    /// it was configurable) means `2 * m`
+    #[serde(default)]
+    m0: Option<usize>,
     /// ef_construction: size of dynamic candidate list during construction
     ef_construction: usize,
     /// Distance metric used for search
     pub metric: DistanceMetric,
     /// All nodes in the index
+    #[serde(
+        serialize_with = "serialize_sorted_nodes",
+        deserialize_with = "deserialize_nodes_with_norms"
+    )]
     nodes: HashMap<String, HNSWNode>,
     /// Entry point (top-level node)
     entry_point: Option<String>,
@@ -102,6 +467,155 @@ pub struct HNSWIndex {
     max_layer: usize,
     /// Layer assignment multiplier
     ml: f32,
+    /// When set, search explores at least `k * factor` candidates at layer 0
+    #[serde(default)]
+    auto_ef_factor: Option<usize>,
+    /// `ef` for `search_with_default_ef`; None means `ef_construction`
+    #[serde(default)]
+    default_ef: Option<usize>,
+    /// Highest layer `random_layer` may assign
+    #[serde(default = "default_max_layer_cap")]
+    max_layer_cap: usize,
+    /// `search` answers with an exact scan below this many nodes
+    #[serde(default = "default_exact_threshold")]
+    exact_threshold: usize,
+    /// Select diverse neighbors (HNSW paper heuristic) instead of the plain nearest m
+    #[serde(default = "default_true")]
+    neighbor_heuristic: bool,
+    /// Backfill heuristic selections with discarded candidates up to m
+    #[serde(default)]
+    keep_pruned_connections: bool,
+    /// Reject zero-magnitude vectors at insert time under the cosine and
+    /// angular metrics, where they have no direction
+    #[serde(default)]
+    reject_zero_vectors: bool,
+    /// When set, replace a deleted entry point with `recompute_entry_point`
+    /// over a sample of this many nodes instead of any top-layer node
+    #[serde(default)]
+    medoid_entry_sample: Option<usize>,
+    /// Relink a deleted node's former neighbors to each other, see `repair_links`
+    #[serde(default)]
+    repair_on_delete: bool,
+    /// Stored vectors are unit length, so cosine distances skip their
+    /// magnitude; see `set_normalized`
+    #[serde(default)]
+    normalized: bool,
+    /// Ids whose vectors `drop_vectors` discarded and that `restore_vector`
+    /// hasn't refilled yet
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    dropped_vectors: HashSet<String>,
+    /// Hash of each dropped vector as it was stored, so `restore_vector` can
+    /// refuse a different one
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    dropped_hashes: HashMap<String, u64>,
+    /// Caller-supplied insertion times (seconds) of records given one, see
+    /// `set_timestamp`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    timestamps: HashMap<String, u64>,
+    /// xorshift64* state when seeded; persisted so a restored index continues
+    /// the same sequence
+    #[serde(default)]
+    rng_state: Option<u64>,
+    /// Compute Euclidean distances from cached norms and a single dot product
+    #[serde(default = "default_true")]
+    cached_norms: bool,
+    /// Optional descent cache for hot query regions; runtime-only, so a
+    /// restored index starts with it disabled
+    #[serde(skip)]
+    seed_cache: Option<RefCell<SeedCache>>,
+    /// Storage precision for vectors inserted into this index
+    #[serde(default)]
+    quantization: Quantization,
+    /// Metric is still to be chosen from the first inserted vectors, see
+    /// `resolve_auto_metric`
+    #[serde(default)]
+    auto_metric: bool,
+    /// Incrementally maintained `stats` aggregates, seeded by a scan when
+    /// deserializing and updated on every mutation
+    #[serde(skip)]
+    running_stats: Option<RunningStats>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Serialize for HNSWIndex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HNSWIndex::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HNSWIndex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut index = HNSWIndex::deserialize(deserializer)?;
+        index.running_stats = Some(index.scan_stats());
+        Ok(index)
+    }
+}
+
+fn default_max_layer_cap() -> usize {
+    DEFAULT_MAX_LAYER_CAP
+}
+
+fn default_exact_threshold() -> usize {
+    DEFAULT_EXACT_THRESHOLD
+}
+
+/// FNV-1a of a vector's f32 bit patterns
+fn vector_hash(vector: &[f32]) -> u64 {
+    let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+    checksum::fnv1a64(&bytes)
+}
+
+/// Scramble a user seed (splitmix64) into a non-zero xorshift state
+fn seed_rng(seed: u64) -> u64 {
+    let mut state = seed;
+    let z = rng::splitmix64(&mut state);
+    if z == 0 {
+        0x9E37_79B9_7F4A_7C15
+    } else {
+        z
+    }
+}
+
+/// Advance an xorshift64* state and return its next 32 random bits
+fn xorshift_next(state: &mut u64) -> u32 {
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+}
+
+/// Serialize a node map in id order so identical indexes produce identical output
+fn serialize_sorted_nodes<S: serde::Serializer>(
+    nodes: &HashMap<String, HNSWNode>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeMap<&String, &HNSWNode> = nodes.iter().collect();
+    sorted.serialize(serializer)
+}
+
+/// Deserialize a node map, filling in the cached norms that aren't persisted
+fn deserialize_nodes_with_norms<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, HNSWNode>, D::Error> {
+    let mut nodes = HashMap::<String, HNSWNode>::deserialize(deserializer)?;
+    for node in nodes.values_mut() {
+        node.norm_sq = distance::squared_norm(&node.vector());
+    }
+    Ok(nodes)
+}
+
+/// Serialize per-layer neighbor sets as sorted lists
+fn serialize_sorted_connections<S: serde::Serializer>(
+    connections: &[HashSet<String>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: Vec<BTreeSet<&String>> = connections.iter().map(|c| c.iter().collect()).collect();
+    sorted.serialize(serializer)
 }
 
 impl HNSWIndex {
@@ -112,656 +626,4729 @@ impl HNSWIndex {
     /// * `m` - Max connections per layer (typically 16-32)
     /// * `ef_construction` - Dynamic list size during construction (typically 200)
     /// * `metric` - Distance metric to use
-    pub fn new(dimensions: usize, m: usize, ef_construction: usize, metric: DistanceMetric) -> Self {
-        HNSWIndex {
+    /// * `seed` - Seed for deterministic layer assignment (None uses OS randomness)
+    ///
+    /// # Panics
+    /// If `m` is below `MIN_M`; use `try_new` to get an error instead.
+    pub fn new(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: DistanceMetric,
+        seed: Option<u64>,
+    ) -> Self {
+        match Self::try_new(dimensions, m, ef_construction, metric, seed) {
+            Ok(index) => index,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// `new` with room reserved for `capacity` nodes, so a load of known size
+    /// doesn't grow (and rehash) the node map along the way
+    pub fn with_capacity(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: DistanceMetric,
+        seed: Option<u64>,
+        capacity: usize,
+    ) -> Self {
+        let mut index = Self::new(dimensions, m, ef_construction, metric, seed);
+        index.reserve(capacity);
+        index
+    }
+
+    /// Reserve room for at least `additional` more nodes
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// `new` that returns an error for `m` below `MIN_M` instead of panicking
+    pub fn try_new(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: DistanceMetric,
+        seed: Option<u64>,
+    ) -> Result<Self, String> {
+        if m < MIN_M {
+            return Err(format!("m must be at least {}, got {}", MIN_M, m));
+        }
+        Ok(HNSWIndex {
             dimensions,
             m,
+            m0: Some(m * 2),
             ef_construction,
             metric,
             nodes: HashMap::new(),
             entry_point: None,
             max_layer: 0,
             ml: 1.0 / (m as f32).ln(),
+            auto_ef_factor: None,
+            default_ef: None,
+            max_layer_cap: DEFAULT_MAX_LAYER_CAP,
+            exact_threshold: DEFAULT_EXACT_THRESHOLD,
+            neighbor_heuristic: true,
+            keep_pruned_connections: false,
+            reject_zero_vectors: false,
+            medoid_entry_sample: None,
+            repair_on_delete: false,
+            normalized: false,
+            dropped_vectors: HashSet::new(),
+            dropped_hashes: HashMap::new(),
+            timestamps: HashMap::new(),
+            rng_state: seed.map(seed_rng),
+            cached_norms: true,
+            seed_cache: None,
+            quantization: Quantization::None,
+            auto_metric: false,
+            running_stats: Some(RunningStats::default()),
+        })
+    }
+
+    /// Choose how vectors are stored; only allowed while the index is empty
+    ///
+    /// `Quantization::Int8` keeps one byte per component (plus a per-vector
+    /// scale and offset) instead of four. Distances are computed on the
+    /// reconstructed vectors, so rankings can change for neighbors whose
+    /// distances differ by less than the quantization error, costing a
+    /// little recall, and each distance evaluation pays for the decode.
+    ///
+    /// `Quantization::F16` keeps two bytes per component. Its rounding error
+    /// (about 0.05% per component) is far below int8's, so recall stays
+    /// close to full precision.
+    pub fn set_quantization(&mut self, quantization: Quantization) -> Result<(), String> {
+        if !self.nodes.is_empty() && quantization != self.quantization {
+            return Err("Quantization can only be changed on an empty index".to_string());
         }
+        self.quantization = quantization;
+        Ok(())
     }
 
-    /// Check if a vector with the given ID exists
-    pub fn contains(&self, id: &str) -> bool {
-        self.nodes.contains_key(id)
+    /// How vectors are stored in this index
+    pub fn quantization(&self) -> Quantization {
+        self.quantization
     }
 
-    /// Get the vector data for a given ID
-    pub fn get_vector(&self, id: &str) -> Option<&Vec<f32>> {
-        self.nodes.get(id).map(|node| &node.vector)
+    /// Set the layer-0 connection limit (M0); only allowed while the index is
+    /// empty
+    ///
+    /// Defaults to `2 * m`, the usual choice in the HNSW paper. Layer 0 holds
+    /// every node, so its degree dominates both recall and graph memory; a
+    /// larger M0 trades memory for recall without adding links on the upper
+    /// layers. Nodes inserted with their own `m` (`insert_with_m`) keep a
+    /// layer-0 limit of twice that `m`.
+    pub fn set_m0(&mut self, m0: usize) -> Result<(), String> {
+        if m0 < MIN_M {
+            return Err(format!("m0 must be at least {}, got {}", MIN_M, m0));
+        }
+        if !self.nodes.is_empty() && m0 != self.m0() {
+            return Err("m0 can only be changed on an empty index".to_string());
+        }
+        self.m0 = Some(m0);
+        Ok(())
     }
 
-    /// Get all vector IDs
-    pub fn all_ids(&self) -> Vec<String> {
-        self.nodes.keys().cloned().collect()
+    /// Set the highest layer a node can be assigned; only allowed while the
+    /// index is empty
+    ///
+    /// The default of 16 is far above what `ml` reaches for realistic sizes.
+    /// A lower cap trims hierarchy that small datasets don't need; a higher
+    /// one only matters for very large datasets or a large `ml` (small m).
+    pub fn set_max_layer_cap(&mut self, cap: usize) -> Result<(), String> {
+        if !self.nodes.is_empty() && cap != self.max_layer_cap {
+            return Err("The max layer cap can only be changed on an empty index".to_string());
+        }
+        self.max_layer_cap = cap;
+        Ok(())
     }
 
-    /// Get the number of nodes in the index
-    pub fn node_count(&self) -> usize {
-        self.nodes.len()
+    /// Highest layer a node can be assigned
+    pub fn max_layer_cap(&self) -> usize {
+        self.max_layer_cap
     }
 
-    /// Insert a vector into the index
-    pub fn insert(&mut self, id: String, vector: Vec<f32>) {
-        if vector.len() != self.dimensions {
-            return;
+    /// Defer the choice of metric until the first vectors are inserted
+    ///
+    /// Only allowed while the index is empty. See `resolve_auto_metric` for
+    /// how the metric is picked.
+    pub fn set_auto_metric(&mut self, enabled: bool) -> Result<(), String> {
+        if !self.nodes.is_empty() {
+            return Err("Automatic metric selection can only be enabled on an empty index".to_string());
         }
+        self.auto_metric = enabled;
+        Ok(())
+    }
 
-        // Determine layer for new node (exponential decay)
-        let layer = self.random_layer();
-
-        // Create new node
-        let mut node = HNSWNode {
-            id: id.clone(),
-            vector: vector.clone(),
-            connections: vec![HashSet::new(); layer + 1],
-        };
+    /// Whether the metric is still waiting to be chosen automatically
+    pub fn auto_metric_pending(&self) -> bool {
+        self.auto_metric
+    }
 
-        // If this is the first node, make it the entry point
-        if self.entry_point.is_none() {
-            self.entry_point = Some(id.clone());
-            self.max_layer = layer;
-            self.nodes.insert(id, node);
+    /// Pick the metric of an automatic index from a sample of its first vectors
+    ///
+    /// If the first `AUTO_METRIC_SAMPLE` vectors all have unit length (within
+    /// 1e-3), dot product is chosen: on unit vectors it ranks exactly like
+    /// cosine and Euclidean distance but needs a single dot product per
+    /// comparison. Otherwise Euclidean is chosen. The decision is final, so
+    /// later non-normalized inserts into a dot-product index rank by raw dot
+    /// product. Does nothing once the metric has been chosen or when the
+    /// sample is empty.
+    pub fn resolve_auto_metric(&mut self, sample: &[&[f32]]) {
+        if !self.auto_metric || sample.is_empty() {
             return;
         }
+        let normalized = sample.iter().take(AUTO_METRIC_SAMPLE).all(|v| {
+            (distance::squared_norm(v).sqrt() - 1.0).abs() <= AUTO_METRIC_NORM_TOLERANCE
+        });
+        self.metric = if normalized {
+            DistanceMetric::DotProduct
+        } else {
+            DistanceMetric::Euclidean
+        };
+        self.auto_metric = false;
+    }
 
-        // Find nearest neighbors at each layer
-        let entry = self.entry_point.clone().unwrap();
-        let mut curr_nearest = vec![entry.clone()];
+    /// Cache upper-layer descent results per query cell
+    ///
+    /// Queries are bucketed by flooring each component to a multiple of
+    /// `cell_size`; a later query in an already-seen cell starts its layer-0
+    /// search from the entry points found for the earlier one, skipping the
+    /// top-down descent. Coarser cells hit more often but start further from
+    /// the target. The cache is cleared on every insert or delete.
+    pub fn enable_seed_cache(&mut self, cell_size: f32) {
+        self.seed_cache = Some(RefCell::new(SeedCache {
+            cell_size,
+            entries: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }));
+    }
 
-        // Search from top to target layer
-        for lc in (layer + 1..=self.max_layer).rev() {
-            let results = self.search_layer(&vector, curr_nearest, 1, lc);
-            curr_nearest = results.into_iter().map(|(id, _)| id).collect();
+    /// Turn off the seed cache and drop its entries
+    pub fn disable_seed_cache(&mut self) {
+        self.seed_cache = None;
+    }
+
+    /// Seed cache usage, or None when the cache is disabled
+    pub fn seed_cache_stats(&self) -> Option<SeedCacheStats> {
+        self.seed_cache.as_ref().map(|cache| {
+            let cache = cache.borrow();
+            SeedCacheStats {
+                cell_size: cache.cell_size,
+                entries: cache.entries.len(),
+                hits: cache.hits,
+                misses: cache.misses,
+            }
+        })
+    }
+
+    /// Drop cached descents after the graph changes
+    fn invalidate_seed_cache(&mut self) {
+        if let Some(cache) = self.seed_cache.as_mut() {
+            cache.get_mut().entries.clear();
         }
+    }
 
-        // Insert and connect at layers 0..=layer
-        for lc in (0..=layer).rev() {
-            let candidates = self.search_layer(&vector, curr_nearest.clone(), self.ef_construction, lc);
-            let candidate_ids: Vec<String> = candidates.into_iter().map(|(id, _)| id).collect();
+    /// Toggle the cached-norm identity for Euclidean distances (on by default;
+    /// only used at or above `NORM_IDENTITY_MIN_DIMS` dimensions)
+    pub fn set_cached_norms(&mut self, enabled: bool) {
+        self.cached_norms = enabled;
+    }
 
-            // Select M neighbors
-            let m = if lc == 0 { self.m * 2 } else { self.m };
-            let neighbors = self.select_neighbors(&vector, candidate_ids, m);
+    /// Toggle the diversity heuristic used to pick neighbors at insert time
+    pub fn set_neighbor_heuristic(&mut self, enabled: bool) {
+        self.neighbor_heuristic = enabled;
+    }
 
-            // Add bidirectional connections
-            let max_conn = if lc == 0 { self.m * 2 } else { self.m };
-            let mut to_prune = Vec::new();
+    /// When the heuristic selects fewer than m neighbors, fill the remaining
+    /// slots with the nearest discarded candidates
+    pub fn set_keep_pruned_connections(&mut self, enabled: bool) {
+        self.keep_pruned_connections = enabled;
+    }
 
-            for neighbor_id in &neighbors {
-                node.connections[lc].insert(neighbor_id.clone());
+    /// Reject zero vectors in `validate_vector` under cosine/angular (off by default)
+    pub fn set_reject_zero_vectors(&mut self, enabled: bool) {
+        self.reject_zero_vectors = enabled;
+    }
 
-                if let Some(neighbor) = self.nodes.get_mut(neighbor_id) {
-                    // Only add bidirectional connection if neighbor exists at this layer
-                    if lc < neighbor.connections.len() {
-                        neighbor.connections[lc].insert(id.clone());
+    /// Whether zero vectors are rejected under cosine/angular
+    pub fn reject_zero_vectors(&self) -> bool {
+        self.reject_zero_vectors
+    }
 
-                        // Check if pruning needed
-                        if neighbor.connections[lc].len() > max_conn {
-                            to_prune.push(neighbor_id.clone());
-                        }
-                    }
-                }
-            }
+    /// Relink a deleted node's former neighbors to each other on `remove`
+    /// (off by default, since each delete then pays for a few distance evaluations
+    /// per pair of former neighbors)
+    pub fn set_repair_on_delete(&mut self, enabled: bool) {
+        self.repair_on_delete = enabled;
+    }
 
-            // Prune connections in separate pass
-            for neighbor_id in to_prune {
-                let pruned = self.prune_connections(&neighbor_id, lc, max_conn);
-                if let Some(neighbor) = self.nodes.get_mut(&neighbor_id) {
-                    neighbor.connections[lc] = pruned;
-                }
-            }
+    /// Whether `remove` relinks a deleted node's former neighbors
+    pub fn repair_on_delete(&self) -> bool {
+        self.repair_on_delete
+    }
 
-            curr_nearest = neighbors.into_iter().collect();
+    /// Store every vector at unit length (cosine only), so each distance
+    /// divides by the query's magnitude alone
+    ///
+    /// Enabling normalizes the vectors already stored, and later inserts are
+    /// normalized as they arrive, so `get_vector` returns unit vectors.
+    /// Cosine distances are unchanged up to rounding. The flag is saved with
+    /// the index, and `reindex_with_metric` clears it for other metrics.
+    pub fn set_normalized(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && self.metric != DistanceMetric::Cosine {
+            return Err(format!("Normalized storage needs the cosine metric, not {}", self.metric.name()));
         }
-
-        // Update entry point if new node is at a higher layer
-        if layer > self.max_layer {
-            self.max_layer = layer;
-            self.entry_point = Some(id.clone());
+        if enabled && !self.normalized {
+            let quantization = self.quantization;
+            for node in self.nodes.values_mut() {
+                let mut vector = node.vector().into_owned();
+                distance::normalize(&mut vector);
+                let quantized = QuantizedVector::encode(quantization, &vector);
+                node.norm_sq = match &quantized {
+                    Some(q) => distance::squared_norm(&q.decode()),
+                    None => distance::squared_norm(&vector),
+                };
+                node.vector = if quantized.is_some() { Vec::new() } else { vector };
+                node.quantized = quantized;
+            }
+            self.invalidate_seed_cache();
         }
-
-        self.nodes.insert(id, node);
+        self.normalized = enabled;
+        Ok(())
     }
 
-    /// Search for k nearest neighbors
-    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(String, f32)> {
-        if self.entry_point.is_none() {
-            return vec![];
-        }
+    /// Whether stored vectors are kept at unit length
+    pub fn normalized(&self) -> bool {
+        self.normalized
+    }
 
-        let entry = self.entry_point.clone().unwrap();
-        let mut curr_nearest = vec![entry];
+    /// Choose a deleted entry point's replacement with `recompute_entry_point`
+    /// over `sample_size` nodes (Some), or take any top-layer node (None, the default)
+    pub fn set_medoid_entry_point(&mut self, sample_size: Option<usize>) {
+        self.medoid_entry_sample = sample_size;
+    }
 
-        // Search from top to layer 1
-        for lc in (1..=self.max_layer).rev() {
-            let results = self.search_layer(query, curr_nearest, 1, lc);
-            curr_nearest = results.into_iter().map(|(id, _)| id).collect();
+    /// Discard every stored vector, full-precision or quantized, keeping the
+    /// ids and graph links; returns the approximate bytes released
+    ///
+    /// Dropped ids have no vector for `get_vector`, and distances to them are
+    /// meaningless, so searches and other distance-based operations must not
+    /// run until `restore_vector` has refilled every one (`vectors_dropped`).
+    /// `rebuild`, `reconnect`, `reprune` and `reindex_with_metric` fail until
+    /// then.
+    pub fn drop_vectors(&mut self) -> usize {
+        let mut freed = 0;
+        for node in self.nodes.values_mut() {
+            if !self.dropped_vectors.contains(&node.id) {
+                self.dropped_hashes.insert(node.id.clone(), vector_hash(&node.vector()));
+            }
+            freed += node.vector.len() * std::mem::size_of::<f32>();
+            freed += node.quantized.take().map_or(0, |q| q.byte_size());
+            node.vector = Vec::new();
+            node.norm_sq = 0.0;
+            self.dropped_vectors.insert(node.id.clone());
         }
+        self.invalidate_seed_cache();
+        freed
+    }
 
-        // Search at layer 0
-        let candidates = self.search_layer(query, curr_nearest, ef.max(k), 0);
+    /// Whether any vector discarded by `drop_vectors` is still missing
+    pub fn vectors_dropped(&self) -> bool {
+        !self.dropped_vectors.is_empty()
+    }
 
-        // Return top k with final distances
-        candidates
-            .into_iter()
-            .take(k)
-            .map(|(id, dist)| {
-                // For Euclidean, internal computations use squared distance;
-                // convert to actual Euclidean distance for the final result
-                let final_dist = match self.metric {
-                    DistanceMetric::Euclidean => dist.sqrt(),
-                    _ => dist,
-                };
-                (id, final_dist)
-            })
-            .collect()
+    /// Whether `id`'s vector was dropped and not yet restored
+    pub fn is_dropped(&self, id: &str) -> bool {
+        self.dropped_vectors.contains(id)
     }
 
-    /// Delete a vector by ID
-    pub fn delete(&mut self, id: &str) -> bool {
-        if !self.nodes.contains_key(id) {
-            return false;
+    /// Give a node its dropped vector back in place, keeping its graph
+    /// links; Ok(false) if `id` isn't waiting for one
+    ///
+    /// The links were chosen for the dropped vector, so only that vector is
+    /// accepted; a different one fails, see `matches_dropped`.
+    pub fn restore_vector(&mut self, id: &str, vector: Vec<f32>) -> Result<bool, String> {
+        if !self.dropped_vectors.contains(id) || !self.nodes.contains_key(id) {
+            return Ok(false);
+        }
+        if !self.matches_dropped(id, &vector) {
+            return Err(format!(
+                "'{}' differs from the vector that was dropped; its graph links only fit the original",
+                id
+            ));
         }
+        self.dropped_vectors.remove(id);
+        self.dropped_hashes.remove(id);
+        let vector = self.stored_form(vector);
+        let Some(node) = self.nodes.get_mut(id) else {
+            return Ok(false);
+        };
+        let quantized = QuantizedVector::encode(self.quantization, &vector);
+        node.norm_sq = match &quantized {
+            Some(q) => distance::squared_norm(&q.decode()),
+            None => distance::squared_norm(&vector),
+        };
+        node.vector = if quantized.is_some() { Vec::new() } else { vector };
+        node.quantized = quantized;
+        Ok(true)
+    }
 
-        // Remove all connections to this node
-        let node = self.nodes.get(id).unwrap().clone();
-        for (layer, neighbors) in node.connections.iter().enumerate() {
-            for neighbor_id in neighbors {
-                if let Some(neighbor) = self.nodes.get_mut(neighbor_id) {
-                    if layer < neighbor.connections.len() {
-                        neighbor.connections[layer].remove(id);
-                    }
-                }
-            }
+    /// Whether `vector` is the one `drop_vectors` discarded for `id`, as far
+    /// as storage can tell (after normalization and quantization); true when
+    /// no hash was recorded, e.g. for snapshots from before hashes were kept
+    pub fn matches_dropped(&self, id: &str, vector: &[f32]) -> bool {
+        let Some(&expected) = self.dropped_hashes.get(id) else {
+            return true;
+        };
+        let stored = self.stored_form(vector.to_vec());
+        match QuantizedVector::encode(self.quantization, &stored) {
+            Some(q) => vector_hash(&q.decode()) == expected,
+            None => vector_hash(&stored) == expected,
         }
+    }
 
-        // Remove the node
-        self.nodes.remove(id);
+    /// `vector` as `restore_vector` stores it, before quantization
+    fn stored_form(&self, mut vector: Vec<f32>) -> Vec<f32> {
+        if self.normalized {
+            distance::normalize(&mut vector);
+        }
+        vector
+    }
 
-        // Update entry point if needed
-        if self.entry_point.as_ref() == Some(&id.to_string()) {
-            // Pick the node with the most layers as new entry point
-            self.entry_point = self
-                .nodes
-                .values()
-                .max_by_key(|n| n.connections.len())
-                .map(|n| n.id.clone());
-            self.max_layer = self
-                .nodes
-                .values()
-                .map(|n| n.connections.len().saturating_sub(1))
-                .max()
-                .unwrap_or(0);
+    /// Fail while `drop_vectors` has left any vector missing
+    fn ensure_vectors(&self, operation: &str) -> Result<(), String> {
+        if self.vectors_dropped() {
+            return Err(format!("Cannot {} while vectors are dropped; restore every vector first", operation));
         }
+        Ok(())
+    }
 
-        true
+    /// True when the metric only compares direction, so a zero vector is
+    /// equally far from everything
+    fn is_directional(&self) -> bool {
+        matches!(self.metric, DistanceMetric::Cosine | DistanceMetric::Angular)
     }
 
-    /// Search within a specific layer
-    fn search_layer(
-        &self,
-        query: &[f32],
-        entry_points: Vec<String>,
-        ef: usize,
-        layer: usize,
-    ) -> Vec<(String, f32)> {
-        let mut visited = HashSet::new();
-        let mut candidates: BinaryHeap<MinDistElement> = BinaryHeap::new();
-        let mut nearest: BinaryHeap<MaxDistElement> = BinaryHeap::new();
+    /// Enable (Some(factor)) or disable (None) automatic ef scaling with k
+    pub fn set_auto_ef_factor(&mut self, factor: Option<usize>) {
+        self.auto_ef_factor = factor;
+    }
 
-        for ep in entry_points {
-            let dist = self.distance_to(&ep, query);
-            candidates.push(MinDistElement {
-                id: ep.clone(),
-                distance: dist,
-            });
-            nearest.push(MaxDistElement {
-                id: ep.clone(),
-                distance: dist,
+    /// Get the automatic ef scaling factor, if enabled
+    pub fn auto_ef_factor(&self) -> Option<usize> {
+        self.auto_ef_factor
+    }
+
+    /// Set the `ef` used by `search_with_default_ef`; must be at least 1
+    pub fn set_ef(&mut self, ef: usize) -> Result<(), String> {
+        if ef == 0 {
+            return Err("ef must be at least 1".to_string());
+        }
+        self.default_ef = Some(ef);
+        Ok(())
+    }
+
+    /// The `ef` used by `search_with_default_ef`: the value from `set_ef`, or
+    /// `ef_construction` if none was set
+    pub fn default_ef(&self) -> usize {
+        self.default_ef.unwrap_or(self.ef_construction)
+    }
+
+    /// The `ef` from `set_ef`, or None if it was never set
+    pub fn ef_setting(&self) -> Option<usize> {
+        self.default_ef
+    }
+
+    /// The layer-0 candidate list size a search with these parameters will use
+    pub fn effective_ef(&self, k: usize, ef: usize) -> usize {
+        let scaled = self
+            .auto_ef_factor
+            .map(|factor| k.saturating_mul(factor))
+            .unwrap_or(0);
+        ef.max(k).max(scaled)
+    }
+
+    /// Check if a vector with the given ID exists
+    pub fn contains(&self, id: &str) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// Get the vector data for a given ID (approximate in a quantized index)
+    pub fn get_vector(&self, id: &str) -> Option<Cow<'_, [f32]>> {
+        if self.dropped_vectors.contains(id) {
+            return None;
+        }
+        self.nodes.get(id).map(|node| node.vector())
+    }
+
+    /// Max connections per layer above layer 0
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    /// Max connections at layer 0, see `set_m0`
+    pub fn m0(&self) -> usize {
+        self.m0.unwrap_or(self.m * 2)
+    }
+
+    /// Node every search starts its descent from; None when empty
+    pub fn entry_point(&self) -> Option<&str> {
+        self.entry_point.as_deref()
+    }
+
+    /// Highest layer in the graph, the entry point's top layer
+    pub fn max_layer(&self) -> usize {
+        self.max_layer
+    }
+
+    /// Candidate list size used while building the graph
+    pub fn ef_construction(&self) -> usize {
+        self.ef_construction
+    }
+
+    /// Record when `id` was inserted (seconds, on the caller's clock); false
+    /// if the id is unknown. Deleting or replacing the record forgets it.
+    pub fn set_timestamp(&mut self, id: &str, timestamp: u64) -> bool {
+        if !self.nodes.contains_key(id) {
+            return false;
+        }
+        self.timestamps.insert(id.to_string(), timestamp);
+        true
+    }
+
+    /// Insertion time of `id`, if it was given one
+    pub fn timestamp(&self, id: &str) -> Option<u64> {
+        self.timestamps.get(id).copied()
+    }
+
+    /// Get all vector IDs
+    pub fn all_ids(&self) -> Vec<String> {
+        self.nodes.keys().cloned().collect()
+    }
+
+    /// Ids ranked `offset..offset + limit` in sorted order, plus the total count
+    ///
+    /// Node storage has no stable order, so the keys are sorted on every call
+    /// (O(n log n)); only the requested window is cloned. Pages stay
+    /// consistent as long as the index isn't modified between calls.
+    pub fn ids_page(&self, offset: usize, limit: usize) -> (Vec<String>, usize) {
+        let mut ids: Vec<&String> = self.nodes.keys().collect();
+        ids.sort_unstable();
+        let page = ids.into_iter().skip(offset).take(limit).cloned().collect();
+        (page, self.nodes.len())
+    }
+
+    /// All (id, vector) pairs sorted by id, for deterministic bulk processing
+    pub fn vectors_by_id(&self) -> Vec<(&str, Cow<'_, [f32]>)> {
+        let mut out: Vec<(&str, Cow<'_, [f32]>)> = self
+            .nodes
+            .values()
+            .filter(|n| !self.dropped_vectors.contains(&n.id))
+            .map(|n| (n.id.as_str(), n.vector()))
+            .collect();
+        out.sort_by(|a, b| a.0.cmp(b.0));
+        out
+    }
+
+    /// Exact ranking of `ids` by distance to `query`, nearest first, keeping
+    /// at most k (ids not in the index are skipped, and a repeated id is
+    /// ranked once)
+    pub fn rank_ids(&self, query: &[f32], ids: &[&str], k: usize) -> Vec<(String, f32)> {
+        let mut seen = HashSet::new();
+        let mut ranked: Vec<(String, f32)> = ids
+            .iter()
+            .filter(|id| seen.insert(**id))
+            .filter_map(|id| self.nodes.get(*id))
+            .map(|node| (node.id.clone(), self.compute_distance(&node.vector(), query)))
+            .collect();
+        ranked.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        ranked.truncate(k);
+        ranked
+            .into_iter()
+            .map(|(id, dist)| (id, self.final_distance(dist)))
+            .collect()
+    }
+
+    /// Copy of the index with every full-precision vector emptied, plus those
+    /// vectors concatenated in id order, for formats that store vectors as raw
+    /// floats (quantized nodes keep their codes in the graph)
+    pub(crate) fn split_vectors(&self) -> (HNSWIndex, Vec<f32>) {
+        let mut graph = self.clone();
+        let mut ids: Vec<&String> = graph
+            .nodes
+            .values()
+            .filter(|n| n.quantized.is_none() && !self.dropped_vectors.contains(&n.id))
+            .map(|n| &n.id)
+            .collect();
+        ids.sort();
+        let mut block = Vec::with_capacity(self.nodes.len() * self.dimensions);
+        for id in ids {
+            block.extend_from_slice(&self.nodes[id].vector);
+        }
+        for node in graph.nodes.values_mut() {
+            node.vector = Vec::new();
+        }
+        (graph, block)
+    }
+
+    /// Refill vectors emptied by `split_vectors` from a block in id order
+    pub(crate) fn restore_vectors(&mut self, block: &[f32]) -> Result<(), String> {
+        let mut ids: Vec<String> = self
+            .nodes
+            .values()
+            .filter(|n| n.quantized.is_none() && !self.dropped_vectors.contains(&n.id))
+            .map(|n| n.id.clone())
+            .collect();
+        let expected = ids.len() * self.dimensions;
+        if block.len() != expected {
+            return Err(format!(
+                "Vector block has {} values, expected {} ({} nodes x {} dimensions)",
+                block.len(),
+                expected,
+                ids.len(),
+                self.dimensions
+            ));
+        }
+        ids.sort();
+        for (id, chunk) in ids.iter().zip(block.chunks_exact(self.dimensions.max(1))) {
+            let node = self.nodes.get_mut(id).unwrap();
+            node.vector = chunk.to_vec();
+            node.norm_sq = distance::squared_norm(&node.vector);
+        }
+        Ok(())
+    }
+
+    /// Check that every stored vector has the declared `dimensions`
+    ///
+    /// Distance functions zip their inputs, so a wrong-length vector would
+    /// silently truncate instead of failing. Run this on any index that was
+    /// deserialized from untrusted or possibly corrupt data. When every vector
+    /// shares one other length, the error blames the declared `dimensions`.
+    pub fn check_dimensions(&self) -> Result<(), String> {
+        if self.dimensions == 0 && !self.nodes.is_empty() {
+            return Err("Index declares 0 dimensions but stores vectors".to_string());
+        }
+        let mut bad: Vec<&HNSWNode> = self
+            .nodes
+            .values()
+            .filter(|n| n.dimensions() != self.dimensions && !self.dropped_vectors.contains(&n.id))
+            .collect();
+        bad.sort_by(|a, b| a.id.cmp(&b.id));
+
+        // Every vector agreeing on another length points at a corrupt
+        // `dimensions` field rather than corrupt vectors
+        if let Some(first) = bad.first() {
+            let len = first.dimensions();
+            if bad.len() == self.nodes.len() - self.dropped_vectors.len() && bad.iter().all(|n| n.dimensions() == len) {
+                return Err(format!(
+                    "Declared dimensions ({}) do not match the stored vectors, which all have {}",
+                    self.dimensions, len
+                ));
+            }
+        }
+        match bad.first() {
+            Some(node) => Err(format!(
+                "Vector '{}' has {} dimensions but the index declares {} ({} mismatched)",
+                node.id,
+                node.dimensions(),
+                self.dimensions,
+                bad.len()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Walk the graph and report integrity problems, one message each
+    ///
+    /// Reports an entry point that is missing (or absent from a non-empty
+    /// index), neighbor ids not present in the index, and nodes without
+    /// layer-0 connections in an index of two or more nodes. Links are not
+    /// required to be reciprocal: a neighbor over its cap prunes its side of a
+    /// link while the other side keeps it, and per-node `m` relies on that.
+    /// `validate_reciprocal` reports those one-way links too.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        self.find_problems(false)
+    }
+
+    /// `validate`, also reporting every link whose target doesn't link back
+    /// on that layer
+    ///
+    /// Insertion and pruning leave one-way links in a healthy graph wherever
+    /// a neighbor over its cap dropped its side, so this is for graphs
+    /// expected to be symmetric, or for measuring how far one is from it.
+    pub fn validate_reciprocal(&self) -> Result<(), Vec<String>> {
+        self.find_problems(true)
+    }
+
+    fn find_problems(&self, reciprocal: bool) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        match &self.entry_point {
+            Some(entry) if !self.nodes.contains_key(entry) => {
+                problems.push(format!("Entry point '{}' is not in the index", entry));
+            }
+            None if !self.nodes.is_empty() => {
+                problems.push("Index has nodes but no entry point".to_string());
+            }
+            _ => {}
+        }
+
+        let mut nodes: Vec<&HNSWNode> = self.nodes.values().collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        for node in nodes {
+            for (layer, neighbors) in node.connections.iter().enumerate() {
+                let mut dangling: Vec<&String> =
+                    neighbors.iter().filter(|id| !self.nodes.contains_key(*id)).collect();
+                dangling.sort();
+                for id in dangling {
+                    problems.push(format!(
+                        "Node '{}' links to missing node '{}' at layer {}",
+                        node.id, id, layer
+                    ));
+                }
+                if reciprocal {
+                    let mut one_way: Vec<&String> = neighbors
+                        .iter()
+                        .filter(|id| {
+                            self.nodes
+                                .get(*id)
+                                .is_some_and(|n| n.connections.get(layer).is_none_or(|c| !c.contains(&node.id)))
+                        })
+                        .collect();
+                    one_way.sort();
+                    for id in one_way {
+                        problems.push(format!(
+                            "Node '{}' links to '{}' at layer {}, but not the other way",
+                            node.id, id, layer
+                        ));
+                    }
+                }
+            }
+            if self.nodes.len() > 1 && node.connections.first().is_none_or(|c| c.is_empty()) {
+                problems.push(format!("Node '{}' has no layer-0 connections", node.id));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Get the number of nodes in the index
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check a vector's dimensionality and that every value is finite
+    pub fn validate_vector(&self, vector: &[f32]) -> Result<(), String> {
+        if vector.len() != self.dimensions {
+            return Err(format!(
+                "Vector dimension mismatch: expected {}, got {}",
+                self.dimensions,
+                vector.len()
+            ));
+        }
+        if vector.iter().any(|x| !x.is_finite()) {
+            return Err("Vector contains NaN or Infinity values".to_string());
+        }
+        if self.quantization == Quantization::F16 && vector.iter().any(|x| x.abs() > half::f16::MAX.to_f32()) {
+            return Err("Vector has values beyond the f16 range (±65504)".to_string());
+        }
+        if self.reject_zero_vectors && self.is_directional() && vector.iter().all(|&x| x == 0.0) {
+            return Err(format!("Zero vector has no direction under the {} metric", self.metric.name()));
+        }
+        Ok(())
+    }
+
+    /// Check a search query: it must match the index dimensions and, under
+    /// cosine/angular, must not be a zero vector (which would rank every node
+    /// at the same distance)
+    pub fn validate_query(&self, query: &[f32]) -> Result<(), String> {
+        if query.len() != self.dimensions {
+            return Err(format!(
+                "Query dimension mismatch: expected {}, got {}",
+                self.dimensions,
+                query.len()
+            ));
+        }
+        if self.is_directional() && query.iter().all(|&x| x == 0.0) {
+            return Err(format!("Zero query has no direction under the {} metric", self.metric.name()));
+        }
+        Ok(())
+    }
+
+    /// Validated insert for native callers: rejects vectors with the wrong
+    /// dimensionality or non-finite values, and replaces any existing node
+    /// with the same id (`insert` itself assumes a new, valid vector)
+    pub fn try_insert(&mut self, id: String, vector: Vec<f32>) -> Result<(), String> {
+        self.validate_vector(&vector)?;
+        if self.contains(&id) {
+            self.delete(&id);
+        }
+        self.insert(id, vector);
+        Ok(())
+    }
+
+    /// Insert a whole dataset in a random order
+    ///
+    /// Inserting data in its natural order (often sorted or clustered) builds
+    /// the graph from early nodes that never saw the later ones; a shuffled
+    /// order spreads each region's nodes over the whole build and gives
+    /// better connectivity. The order comes from the index RNG, so a seeded
+    /// index builds the same graph every time. Vectors must already be valid
+    /// (see `validate_vector`); when an id repeats, the last occurrence wins.
+    pub fn bulk_insert(&mut self, records: Vec<(String, Vec<f32>)>) {
+        let last: HashMap<&str, usize> = records.iter().enumerate().map(|(i, (id, _))| (id.as_str(), i)).collect();
+        let keep: Vec<bool> = records.iter().enumerate().map(|(i, (id, _))| last[id.as_str()] == i).collect();
+        let mut records: Vec<(String, Vec<f32>)> =
+            records.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(record, _)| record).collect();
+        for i in (1..records.len()).rev() {
+            let j = self.next_random_u32() as usize % (i + 1);
+            records.swap(i, j);
+        }
+        for (id, vector) in records {
+            if self.contains(&id) {
+                self.delete(&id);
+            }
+            self.insert(id, vector);
+        }
+    }
+
+    /// Insert a vector into the index
+    pub fn insert(&mut self, id: String, vector: Vec<f32>) {
+        self.insert_with_m(id, vector, None);
+    }
+
+    /// Insert a vector whose connection limits use `m` instead of the index-wide
+    /// value, letting "hub" vectors link more widely without raising memory for
+    /// every node
+    pub fn insert_with_m(&mut self, id: String, vector: Vec<f32>, m: Option<usize>) {
+        self.insert_node(id, vector, m, self.ef_construction);
+    }
+
+    /// Insert a vector searching `ef_construction` candidates for its
+    /// neighbors instead of the index-wide value, e.g. a low value during a
+    /// bulk load and a higher one for records added later
+    pub fn insert_with_ef(&mut self, id: String, vector: Vec<f32>, ef_construction: usize) {
+        self.insert_node(id, vector, None, ef_construction.max(1));
+    }
+
+    fn insert_node(&mut self, id: String, mut vector: Vec<f32>, m: Option<usize>, ef_construction: usize) {
+        if vector.len() != self.dimensions {
+            return;
+        }
+        if self.normalized {
+            distance::normalize(&mut vector);
+        }
+        self.resolve_auto_metric(&[&vector]);
+        self.invalidate_seed_cache();
+
+        // Determine layer for new node (exponential decay)
+        let layer = self.random_layer();
+
+        // Create new node, storing only the codes when quantizing
+        let quantized = QuantizedVector::encode(self.quantization, &vector);
+        let norm_sq = match &quantized {
+            Some(q) => distance::squared_norm(&q.decode()),
+            None => distance::squared_norm(&vector),
+        };
+        let node = HNSWNode {
+            id: id.clone(),
+            vector: if quantized.is_some() { Vec::new() } else { vector.clone() },
+            quantized,
+            connections: vec![HashSet::new(); layer + 1],
+            max_m: m.filter(|&m| m != self.m),
+            norm_sq,
+        };
+
+        if let Some(stats) = self.running_stats.as_mut() {
+            stats.add_node(layer + 1, 1);
+        }
+
+        // If this is the first node, make it the entry point
+        if self.entry_point.is_none() {
+            self.entry_point = Some(id.clone());
+            self.max_layer = layer;
+            self.nodes.insert(id, node);
+            return;
+        }
+
+        // Add the node up front so pruning can measure distances to it;
+        // it stays unreachable until neighbors link back at each layer
+        self.nodes.insert(id.clone(), node);
+        let entry = self.entry_point.clone().unwrap();
+        self.connect(&id, &vector, layer, entry, m, ef_construction);
+
+        // Update entry point if new node is at a higher layer
+        if layer > self.max_layer {
+            self.max_layer = layer;
+            self.entry_point = Some(id);
+        }
+    }
+
+    /// Select and link neighbors for the stored node `id` on layers
+    /// `0..=layer`, descending from `entry` through the layers above
+    ///
+    /// Replaces the node's own neighbor sets, adds the reverse links, and
+    /// prunes neighbors that end up over their cap.
+    fn connect(
+        &mut self,
+        id: &str,
+        vector: &[f32],
+        layer: usize,
+        entry: String,
+        m: Option<usize>,
+        ef_construction: usize,
+    ) {
+        let mut curr_nearest = vec![entry];
+
+        // Search from top to target layer
+        for lc in (layer + 1..=self.max_layer).rev() {
+            let results = self.search_layer(vector, curr_nearest, 1, lc);
+            curr_nearest = results.into_iter().map(|(id, _)| id).collect();
+        }
+
+        // Connect at layers 0..=layer
+        let caps = self.layer_caps();
+        let m = m.filter(|&m| m != caps.m);
+        for lc in (0..=layer).rev() {
+            let mut candidates = self.search_layer(vector, curr_nearest.clone(), ef_construction, lc);
+            candidates.retain(|(c, _)| {
+                c != id && self.nodes.get(c).is_some_and(|n| lc < n.connections.len())
             });
-            visited.insert(ep);
+
+            // Select M neighbors
+            let max_conn = caps.at(m, lc);
+            let neighbors = self.select_neighbors(candidates, max_conn);
+
+            // Add bidirectional connections
+            let mut to_prune = Vec::new();
+            let (mut added, mut removed) = (neighbors.len(), 0);
+
+            if let Some(node) = self.nodes.get_mut(id) {
+                removed += std::mem::replace(&mut node.connections[lc], neighbors.clone()).len();
+            }
+
+            for neighbor_id in &neighbors {
+                if let Some(neighbor) = self.nodes.get_mut(neighbor_id) {
+                    if neighbor.connections[lc].insert(id.to_string()) {
+                        added += 1;
+                    }
+
+                    // Check if pruning needed
+                    let neighbor_cap = caps.at(neighbor.max_m, lc);
+                    if neighbor.connections[lc].len() > neighbor_cap {
+                        to_prune.push((neighbor_id.clone(), neighbor_cap));
+                    }
+                }
+            }
+
+            // Prune connections in separate pass
+            for (neighbor_id, neighbor_cap) in to_prune {
+                let pruned = self.prune_connections(&neighbor_id, lc, neighbor_cap);
+                added += pruned.len();
+                if let Some(neighbor) = self.nodes.get_mut(&neighbor_id) {
+                    removed += std::mem::replace(&mut neighbor.connections[lc], pruned).len();
+                }
+            }
+            if lc == 0 {
+                if let Some(stats) = self.running_stats.as_mut() {
+                    stats.adjust_edges(added, removed);
+                }
+            }
+
+            if !neighbors.is_empty() {
+                curr_nearest = neighbors.into_iter().collect();
+            }
+        }
+    }
+
+    /// Re-run neighbor selection and linking for just the given nodes
+    ///
+    /// A cheaper, targeted alternative to `rebuild` for nodes that lost most
+    /// of their links (e.g. after deletions around them). Each node keeps its
+    /// layers and the links other nodes hold to it; its own neighbor sets are
+    /// recomputed as if it were being inserted. Unknown ids are skipped.
+    /// Returns the number of nodes reconnected; fails while vectors are
+    /// dropped.
+    pub fn reconnect(&mut self, ids: &[&str]) -> Result<usize, String> {
+        self.ensure_vectors("reconnect")?;
+        let mut reconnected = 0;
+        for &id in ids {
+            let Some(node) = self.nodes.get(id) else { continue };
+            let vector = node.vector().into_owned();
+            let layer = node.connections.len().saturating_sub(1);
+            let m = node.max_m;
+
+            // Descend from the entry point, or from the tallest other node
+            // when this node is the entry point and may have no links to follow
+            let entry = match &self.entry_point {
+                Some(ep) if ep != id => Some(ep.clone()),
+                _ => self
+                    .nodes
+                    .values()
+                    .filter(|n| n.id != id)
+                    .max_by(|a, b| {
+                        a.connections.len().cmp(&b.connections.len()).then_with(|| b.id.cmp(&a.id))
+                    })
+                    .map(|n| n.id.clone()),
+            };
+            self.invalidate_seed_cache();
+            if let Some(entry) = entry {
+                self.connect(id, &vector, layer, entry, m, self.ef_construction);
+            }
+            reconnected += 1;
+        }
+        Ok(reconnected)
+    }
+
+    /// Re-select every node's neighbors on every layer with `select_neighbors`
+    ///
+    /// Pruning at insert time keeps the nearest `max_conn` links by raw
+    /// distance, which can pack a node's links into its own cluster. This pass
+    /// re-runs neighbor selection (the diversity heuristic, unless disabled)
+    /// over each node's current links, without the searches a `rebuild` needs.
+    /// Links to missing nodes are dropped. All selections are made against
+    /// the graph as it was before the pass; afterwards each kept link is made
+    /// reciprocal wherever the other end still has room under its cap, so
+    /// pruning doesn't leave one-way links behind. That back-link pass can
+    /// add links as well, so a node may end up with more links than before,
+    /// though never more than its cap. Fails while vectors are dropped.
+    pub fn reprune(&mut self) -> Result<(), String> {
+        self.ensure_vectors("reprune")?;
+        let mut ids = self.all_ids();
+        ids.sort();
+        let caps = self.layer_caps();
+
+        let mut selections: HashMap<String, Vec<HashSet<String>>> = HashMap::new();
+        for id in &ids {
+            let node = &self.nodes[id];
+            let layers = node
+                .connections
+                .iter()
+                .enumerate()
+                .map(|(layer, links)| {
+                    let mut candidates: Vec<(String, f32)> = links
+                        .iter()
+                        .filter(|n| self.nodes.get(*n).is_some_and(|n| layer < n.connections.len()))
+                        .map(|n| (n.clone(), self.distance_between(id, n)))
+                        .collect();
+                    candidates.sort_by(|a, b| {
+                        a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(&b.0))
+                    });
+                    self.select_neighbors(candidates, caps.at(node.max_m, layer))
+                })
+                .collect();
+            selections.insert(id.clone(), layers);
+        }
+
+        for id in &ids {
+            for (layer, selected) in selections[id].clone().into_iter().enumerate() {
+                for neighbor_id in selected {
+                    let cap = caps.at(self.nodes[&neighbor_id].max_m, layer);
+                    let back = &mut selections.get_mut(&neighbor_id).unwrap()[layer];
+                    if back.len() < cap {
+                        back.insert(id.clone());
+                    }
+                }
+            }
+        }
+
+        for (id, layers) in selections {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.connections = layers;
+            }
+        }
+        self.invalidate_seed_cache();
+        self.running_stats = Some(self.scan_stats());
+        Ok(())
+    }
+
+    /// Search for k nearest neighbors
+    ///
+    /// A damaged graph (e.g. after heavy deletion) can strand nodes where
+    /// layer-0 traversal never reaches them, so the search returns fewer than
+    /// k results although the index holds k or more. In that case the search
+    /// is retried with `ef` widened `SEARCH_RETRY_EF_FACTOR` times (at most
+    /// `SEARCH_RETRY_MAX_EF`), and if still short on an index of at most
+    /// `EXACT_FALLBACK_MAX_NODES` nodes, answered by an exact scan.
+    ///
+    /// An index with fewer nodes than its exact threshold (see
+    /// `set_exact_threshold`) skips the graph and scans every node.
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(String, f32)> {
+        if self.nodes.len() < self.exact_threshold {
+            return self.search_exact(query, k);
+        }
+        let results = self.search_filtered(query, k, ef, |_| true);
+        let wanted = k.min(self.nodes.len());
+        if results.len() >= wanted {
+            return results;
+        }
+
+        let effective = self.effective_ef(k, ef);
+        let widened = (effective * SEARCH_RETRY_EF_FACTOR).min(SEARCH_RETRY_MAX_EF);
+        let results = if widened > effective {
+            self.search_filtered(query, k, widened, |_| true)
+        } else {
+            results
+        };
+        if results.len() >= wanted || self.nodes.len() > EXACT_FALLBACK_MAX_NODES {
+            return results;
+        }
+        self.search_exact(query, k)
+    }
+
+    /// Exact k nearest neighbors by scanning every node, nearest first
+    ///
+    /// Costs one distance per node. Ties are ordered by id, as in `search`.
+    pub fn search_exact(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        self.exact_knn(query, k)
+            .into_iter()
+            .map(|(id, dist)| (id, self.final_distance(dist)))
+            .collect()
+    }
+
+    /// Scan every node in `search` while the index has fewer than `threshold`
+    /// nodes (0 always walks the graph)
+    ///
+    /// On small indexes a scan is exact and often faster than the graph walk.
+    pub fn set_exact_threshold(&mut self, threshold: usize) {
+        self.exact_threshold = threshold;
+    }
+
+    /// Node count below which `search` scans instead of walking the graph
+    pub fn exact_threshold(&self) -> usize {
+        self.exact_threshold
+    }
+
+    /// `search` with the stored `default_ef`
+    pub fn search_with_default_ef(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        self.search(query, k, self.default_ef())
+    }
+
+    /// Search for the k nearest neighbors whose id passes `filter`
+    ///
+    /// The filter is applied during layer-0 traversal rather than to the final
+    /// k: non-matching nodes are still expanded so the search can route through
+    /// them, but only matching nodes enter the result set. Until `ef` matches
+    /// have been found every reachable neighbor is explored, so a selective
+    /// filter effectively over-fetches (visiting far more nodes than an
+    /// unfiltered search) in exchange for still returning k results when k
+    /// matching nodes exist.
+    pub fn search_filtered<F: Fn(&str) -> bool>(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        filter: F,
+    ) -> Vec<(String, f32)> {
+        if self.entry_point.is_none() {
+            return vec![];
+        }
+
+        let entry_points = self.descend(query);
+        let candidates = self.search_layer_filtered(query, entry_points, self.effective_ef(k, ef), 0, &filter);
+
+        // Return top k with final distances
+        candidates
+            .into_iter()
+            .take(k)
+            .map(|(id, dist)| (id, self.final_distance(dist)))
+            .collect()
+    }
+
+    /// `search` re-ranked by distance blended with recency
+    ///
+    /// The `max(k, ef)` nearest candidates are weighted by
+    /// `w = 0.5^(age / half_life_secs)`, where age is `now` minus the record's
+    /// timestamp, and ranked by `d * (2 - w)` for a non-negative distance `d`
+    /// or `d / (2 - w)` for a negative one (dot product). Older records rank
+    /// lower either way, and records of the same age keep their distance
+    /// order: a record one half-life old counts as 1.5x as far as a new one,
+    /// or 1.5x less similar. Records without a timestamp get `w = 0`;
+    /// timestamps after `now` count as new. The returned distances are the
+    /// unweighted ones.
+    pub fn search_recent(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        half_life_secs: f64,
+        now: u64,
+    ) -> Vec<(String, f32)> {
+        let candidates = self.search(query, k.max(ef), ef);
+        let mut scored: Vec<(String, f32, f32)> = candidates
+            .into_iter()
+            .map(|(id, dist)| {
+                let weight = self.timestamps.get(&id).map_or(0.0, |&t| {
+                    0.5f64.powf(now.saturating_sub(t) as f64 / half_life_secs) as f32
+                });
+                let score = if dist >= 0.0 { dist * (2.0 - weight) } else { dist / (2.0 - weight) };
+                (id, dist, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            a.2.partial_cmp(&b.2)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        scored.truncate(k);
+        scored.into_iter().map(|(id, dist, _)| (id, dist)).collect()
+    }
+
+    /// Results ranked `offset..offset + limit`, for paging through a result list
+    ///
+    /// Searches for the top `offset + limit` and returns the requested window,
+    /// ordering equal distances by id so the ranking is stable. Deeper pages
+    /// need more exploration: the layer-0 candidate list is at least
+    /// `offset + limit`. Pages fetched with the same `ef` (at least the deepest
+    /// `offset + limit`) come from the same ranking, so they never overlap or
+    /// repeat.
+    pub fn search_page(&self, query: &[f32], offset: usize, limit: usize, ef: usize) -> Vec<(String, f32)> {
+        let mut results = self.search(query, offset.saturating_add(limit), ef);
+        results.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        results.into_iter().skip(offset).take(limit).collect()
+    }
+
+    /// Every node within `radius` of the query (in final, metric-appropriate
+    /// units), nearest first
+    ///
+    /// Layer-0 traversal keeps expanding while candidates lie inside the radius,
+    /// so the result is not capped at `ef`; beyond the radius the usual
+    /// `ef`-bounded stopping rule applies, which lets the search cross small
+    /// gaps between in-radius regions. Euclidean radii are squared once up
+    /// front and compared against internal squared distances.
+    pub fn search_radius(&self, query: &[f32], radius: f32, ef: usize) -> Vec<(String, f32)> {
+        if self.entry_point.is_none() || radius.is_nan() {
+            return vec![];
+        }
+        let limit = match self.metric {
+            DistanceMetric::Euclidean if radius < 0.0 => return vec![],
+            DistanceMetric::Euclidean => radius * radius,
+            _ => radius,
+        };
+
+        let ef = ef.max(1);
+        let query_norm_sq = self.query_norm_sq(query);
+        let mut visited = HashSet::new();
+        let mut candidates: BinaryHeap<MinDistElement> = BinaryHeap::new();
+        let mut nearest: BinaryHeap<MaxDistElement> = BinaryHeap::new();
+        let mut within = Vec::new();
+        let mut decoded = Vec::with_capacity(self.dimensions);
+
+        for ep in self.descend(query) {
+            let dist = self.distance_to_with_norm(&ep, query, query_norm_sq, &mut decoded);
+            if dist <= limit {
+                within.push((ep.clone(), dist));
+            }
+            candidates.push(MinDistElement { id: ep.clone(), distance: dist });
+            nearest.push(MaxDistElement { id: ep.clone(), distance: dist });
+            visited.insert(ep);
+        }
+
+        while let Some(curr) = candidates.pop() {
+            let furthest = nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+            if curr.distance > limit && nearest.len() >= ef && curr.distance > furthest {
+                break;
+            }
+
+            let Some(node) = self.nodes.get(&curr.id) else { continue };
+            let Some(neighbors) = node.connections.first() else { continue };
+            for neighbor_id in neighbors {
+                if !visited.insert(neighbor_id.clone()) {
+                    continue;
+                }
+                let dist = self.distance_to_with_norm(neighbor_id, query, query_norm_sq, &mut decoded);
+                if dist <= limit {
+                    within.push((neighbor_id.clone(), dist));
+                }
+                let furthest = nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+                if dist <= limit || dist < furthest || nearest.len() < ef {
+                    candidates.push(MinDistElement { id: neighbor_id.clone(), distance: dist });
+                    nearest.push(MaxDistElement { id: neighbor_id.clone(), distance: dist });
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+
+        within.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        within
+            .into_iter()
+            .map(|(id, dist)| (id, self.final_distance(dist)))
+            .collect()
+    }
+
+    /// Greedy descent from the entry point through layers above 0,
+    /// returning the entry points for the layer-0 search
+    fn descend(&self, query: &[f32]) -> Vec<String> {
+        let cell = self.seed_cache.as_ref().map(|cache| {
+            let mut cache = cache.borrow_mut();
+            let cell = cache.cell(query);
+            if let Some(seeds) = cache.entries.get(&cell).cloned() {
+                cache.hits += 1;
+                return Err(seeds);
+            }
+            cache.misses += 1;
+            Ok(cell)
+        });
+        let cell = match cell {
+            Some(Err(seeds)) => return seeds,
+            Some(Ok(cell)) => Some(cell),
+            None => None,
+        };
+
+        let mut curr_nearest: Vec<String> = self.entry_point.iter().cloned().collect();
+        for lc in (1..=self.max_layer).rev() {
+            let results = self.search_layer(query, curr_nearest, 1, lc);
+            curr_nearest = results.into_iter().map(|(id, _)| id).collect();
+        }
+
+        if let (Some(cache), Some(cell)) = (self.seed_cache.as_ref(), cell) {
+            let mut cache = cache.borrow_mut();
+            if cache.entries.len() >= SEED_CACHE_MAX_ENTRIES {
+                cache.entries.clear();
+            }
+            cache.entries.insert(cell, curr_nearest.clone());
+        }
+        curr_nearest
+    }
+
+    /// Distance between two stored records in the units `search` reports,
+    /// or None if either id is missing
+    pub fn pair_distance(&self, id_a: &str, id_b: &str) -> Option<f32> {
+        if !self.nodes.contains_key(id_a) || !self.nodes.contains_key(id_b) {
+            return None;
+        }
+        Some(self.final_distance(self.distance_between(id_a, id_b)))
+    }
+
+    /// Convert an internal distance to the units reported to callers
+    pub fn final_distance(&self, dist: f32) -> f32 {
+        // For Euclidean, internal computations use squared distance;
+        // convert to actual Euclidean distance for the final result
+        match self.metric {
+            DistanceMetric::Euclidean => dist.sqrt(),
+            _ => dist,
+        }
+    }
+
+    /// The stored vector nearest to the mean of all stored vectors, with its
+    /// distance to that mean, or None when the index is empty
+    ///
+    /// Both the centroid and the nearest-vector scan are exact, so this costs
+    /// two passes over every vector.
+    pub fn medoid(&self) -> Option<(String, f32)> {
+        let records = self.vectors_by_id();
+        let vectors: Vec<&[f32]> = records.iter().map(|(_, v)| v.as_ref()).collect();
+        let center = kmeans::centroid(&vectors);
+        self.exact_knn(&center, 1)
+            .into_iter()
+            .next()
+            .map(|(id, dist)| (id, self.final_distance(dist)))
+    }
+
+    /// Move the entry point to the most central node on the top layer
+    ///
+    /// Candidates are the nodes reaching the highest layer, so the descent
+    /// keeps its full height; the one with the smallest total distance to a
+    /// random sample of `sample_size` nodes wins (ties go to the smaller id),
+    /// so searches start near the middle of the data rather than wherever
+    /// the tallest node happens to sit. Costs candidates × sample distance
+    /// computations. Returns the new entry point, or None when empty.
+    pub fn recompute_entry_point(&mut self, sample_size: usize) -> Option<String> {
+        let Some(top) = self.nodes.values().map(|n| n.connections.len()).max() else {
+            self.entry_point = None;
+            self.max_layer = 0;
+            return None;
+        };
+        let mut candidates: Vec<String> = self
+            .nodes
+            .values()
+            .filter(|n| n.connections.len() == top)
+            .map(|n| n.id.clone())
+            .collect();
+        candidates.sort();
+
+        let mut ids: Vec<String> = self.all_ids();
+        ids.sort();
+        let sample_count = sample_size.clamp(1, ids.len());
+        for i in 0..sample_count {
+            let j = i + self.next_random_u32() as usize % (ids.len() - i);
+            ids.swap(i, j);
+        }
+        let sample: Vec<Vec<f32>> = ids[..sample_count]
+            .iter()
+            .map(|id| self.nodes[id].vector().into_owned())
+            .collect();
+
+        let mut best: Option<(String, f32)> = None;
+        for id in candidates {
+            let vector = self.nodes[&id].vector();
+            let total: f32 = sample.iter().map(|v| self.compute_distance(&vector, v)).sum();
+            if best.as_ref().is_none_or(|(_, b)| total < *b) {
+                best = Some((id, total));
+            }
+        }
+        self.entry_point = best.map(|(id, _)| id);
+        self.max_layer = top - 1;
+        self.invalidate_seed_cache();
+        self.entry_point.clone()
+    }
+
+    /// Up to `n` ids chosen uniformly at random (all of them when `n` is at
+    /// least the node count), by reservoir sampling over the ids in sorted
+    /// order so a given `seed` always picks the same ids from the same index.
+    /// Without a seed the pick differs on every call. Uses its own generator,
+    /// leaving the index's seeded sequence untouched.
+    pub fn sample_ids(&self, n: usize, seed: Option<u64>) -> Vec<String> {
+        let seed = seed.unwrap_or_else(|| {
+            let mut buf = [0u8; 8];
+            getrandom::getrandom(&mut buf).unwrap_or_default();
+            u64::from_le_bytes(buf)
+        });
+        let mut state = seed_rng(seed);
+
+        let mut ids: Vec<&String> = self.nodes.keys().collect();
+        ids.sort_unstable();
+        let mut reservoir: Vec<&String> = Vec::with_capacity(n.min(ids.len()));
+        for (i, id) in ids.into_iter().enumerate() {
+            if i < n {
+                reservoir.push(id);
+            } else {
+                let j = xorshift_next(&mut state) as usize % (i + 1);
+                if j < n {
+                    reservoir[j] = id;
+                }
+            }
+        }
+        reservoir.into_iter().cloned().collect()
+    }
+
+    /// Exact k nearest neighbors by scanning every node, nearest first, with
+    /// internal (not final) distances
+    fn exact_knn(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        let mut all: Vec<(String, f32)> = self
+            .nodes
+            .values()
+            .map(|node| (node.id.clone(), self.compute_distance(&node.vector(), query)))
+            .collect();
+        all.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        all.truncate(k);
+        all
+    }
+
+    /// Average fraction of the true top-k (by brute-force scan) that search
+    /// returns for each query
+    ///
+    /// Each query contributes |found ∩ truth| / |truth|, where the truth set
+    /// holds min(k, node_count) ids. Returns 1.0 for no queries or an empty index.
+    pub fn estimate_recall(&self, queries: &[Vec<f32>], k: usize, ef: usize) -> f32 {
+        if queries.is_empty() || self.nodes.is_empty() || k == 0 {
+            return 1.0;
+        }
+        let total: f32 = queries
+            .iter()
+            .map(|q| {
+                let truth: HashSet<String> = self.exact_knn(q, k).into_iter().map(|(id, _)| id).collect();
+                let found = self
+                    .search(q, k, ef)
+                    .into_iter()
+                    .filter(|(id, _)| truth.contains(id))
+                    .count();
+                found as f32 / truth.len() as f32
+            })
+            .sum();
+        total / queries.len() as f32
+    }
+
+    /// Smallest `ef` in the sweep k, 2k, 4k, ... whose `estimate_recall` over
+    /// `queries` reaches `target_recall`
+    ///
+    /// The sweep stops at the first ef meeting the target. It is capped at
+    /// `TUNE_EF_MAX`, and also stops once ef covers every node, since a wider
+    /// list can't find more; the last ef tried is returned when the target
+    /// is never met. The exact top-k of each query is computed once.
+    pub fn tune_ef(&self, queries: &[Vec<f32>], k: usize, target_recall: f32) -> usize {
+        let k = k.max(1);
+        let truths: Vec<HashSet<String>> = queries
+            .iter()
+            .map(|q| self.exact_knn(q, k).into_iter().map(|(id, _)| id).collect())
+            .collect();
+        let mut ef = k;
+        loop {
+            let total: f32 = queries
+                .iter()
+                .zip(&truths)
+                .filter(|(_, truth)| !truth.is_empty())
+                .map(|(q, truth)| {
+                    let found = self.search(q, k, ef).into_iter().filter(|(id, _)| truth.contains(id)).count();
+                    found as f32 / truth.len() as f32
+                })
+                .sum();
+            let recall = if self.nodes.is_empty() || queries.is_empty() { 1.0 } else { total / queries.len() as f32 };
+            if recall >= target_recall || ef >= TUNE_EF_MAX || ef >= self.nodes.len() {
+                return ef;
+            }
+            ef = (ef * 2).min(TUNE_EF_MAX);
+        }
+    }
+
+    /// Leave-one-out recall: for `samples` randomly chosen nodes, remove the
+    /// node, search with its vector, and score the result against the exact
+    /// top-k of the remaining nodes
+    ///
+    /// Runs on a scratch copy (the node is reinserted there after each probe),
+    /// so this index is left untouched. Sampling uses the copy's seeded RNG, so
+    /// a seeded index gives the same answer every call. Returns 1.0 when there
+    /// is nothing to measure.
+    pub fn loo_recall(&self, k: usize, ef: usize, samples: usize) -> f32 {
+        if k == 0 || samples == 0 || self.nodes.len() < 2 {
+            return 1.0;
+        }
+        let mut scratch = self.clone();
+        let mut ids: Vec<String> = self.nodes.keys().cloned().collect();
+        ids.sort();
+
+        // Partial Fisher-Yates shuffle picks the sample
+        let sample_count = samples.min(ids.len());
+        for i in 0..sample_count {
+            let j = i + scratch.next_random_u32() as usize % (ids.len() - i);
+            ids.swap(i, j);
+        }
+
+        let mut total = 0.0;
+        for id in &ids[..sample_count] {
+            let max_m = scratch.nodes[id].max_m;
+            let Some(vector) = scratch.remove(id) else { continue };
+            let truth: HashSet<String> = scratch.exact_knn(&vector, k).into_iter().map(|(id, _)| id).collect();
+            let found = scratch
+                .search(&vector, k, ef)
+                .into_iter()
+                .filter(|(id, _)| truth.contains(id))
+                .count();
+            total += found as f32 / truth.len() as f32;
+            scratch.insert_with_m(id.clone(), vector, max_m);
+        }
+        total / sample_count as f32
+    }
+
+    /// Average top-k id overlap between this index and `other` over `queries`
+    ///
+    /// Each query contributes |A ∩ B| / max(|A|, |B|) where A and B are the two
+    /// result id sets; a query where both return nothing counts as full overlap.
+    pub fn result_overlap(&self, other: &HNSWIndex, queries: &[Vec<f32>], k: usize, ef: usize) -> f32 {
+        if queries.is_empty() {
+            return 1.0;
+        }
+        let total: f32 = queries
+            .iter()
+            .map(|q| {
+                let ours: HashSet<String> = self.search(q, k, ef).into_iter().map(|(id, _)| id).collect();
+                let theirs: HashSet<String> = other.search(q, k, ef).into_iter().map(|(id, _)| id).collect();
+                let denom = ours.len().max(theirs.len());
+                if denom == 0 {
+                    1.0
+                } else {
+                    ours.intersection(&theirs).count() as f32 / denom as f32
+                }
+            })
+            .sum();
+        total / queries.len() as f32
+    }
+
+    /// Approximate mutual nearest neighbors between this index and `other`
+    ///
+    /// For every node `a` here, finds its nearest neighbor `b` in `other`, and
+    /// keeps the pair when `a` is also `b`'s nearest neighbor here. Both
+    /// lookups are HNSW searches with the given ef, so pairs may be missed
+    /// when either search misses the true nearest. Distances use this index's
+    /// metric; pairs are returned in id order of `a`.
+    pub fn mutual_nearest_neighbors(&self, other: &HNSWIndex, ef: usize) -> Vec<MutualPair> {
+        let mut pairs = Vec::new();
+        for (a, vector) in self.vectors_by_id() {
+            let Some((b, distance)) = other.search(&vector, 1, ef).into_iter().next() else {
+                continue;
+            };
+            let Some(b_vector) = other.get_vector(&b) else {
+                continue;
+            };
+            let back = self.search(&b_vector, 1, ef);
+            if back.first().map(|(id, _)| id.as_str()) == Some(a) {
+                pairs.push(MutualPair {
+                    a: a.to_string(),
+                    b,
+                    distance,
+                });
+            }
+        }
+        pairs
+    }
+
+    /// Approximate navigational importance (a sampled betweenness) of every node
+    ///
+    /// Runs up to `samples` searches, using stored vectors spread evenly over
+    /// the ids in sorted order as queries, and scores each node by the fraction
+    /// of those searches that expanded it on any layer. Nodes that many
+    /// traversals route through, such as the entry point and upper-layer hubs,
+    /// score highest and are the most disruptive to delete. Sorted by score,
+    /// highest first, with ties broken by id.
+    pub fn navigation_importance(&self, samples: usize, ef: usize) -> Vec<NodeImportance> {
+        let mut ids = self.all_ids();
+        ids.sort();
+
+        let mut counts: HashMap<&str, usize> = ids.iter().map(|id| (id.as_str(), 0)).collect();
+        let sample_count = samples.min(ids.len());
+        for i in 0..sample_count {
+            let query = self.nodes[&ids[i * ids.len() / sample_count]].vector();
+            let mut expanded = HashSet::new();
+            let mut curr_nearest: Vec<String> = self.entry_point.iter().cloned().collect();
+            for lc in (1..=self.max_layer).rev() {
+                curr_nearest = self
+                    .search_layer_traced(&query, curr_nearest, 1, lc, &|_: &str| true, Some(&mut expanded))
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect();
+            }
+            self.search_layer_traced(&query, curr_nearest, ef.max(1), 0, &|_: &str| true, Some(&mut expanded));
+            for id in &expanded {
+                if let Some(count) = counts.get_mut(id.as_str()) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let mut scores: Vec<NodeImportance> = counts
+            .into_iter()
+            .map(|(id, count)| NodeImportance {
+                id: id.to_string(),
+                score: if sample_count > 0 { count as f32 / sample_count as f32 } else { 0.0 },
+            })
+            .collect();
+        scores.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        scores
+    }
+
+    /// Profile local density by each sampled node's distance to its k-th nearest neighbor
+    ///
+    /// Samples are spread evenly over the ids in sorted order so the profile is
+    /// deterministic for a given index.
+    pub fn density_profile(&self, k: usize, ef: usize, samples: usize) -> DensityProfile {
+        let mut ids = self.all_ids();
+        ids.sort();
+
+        let sample_count = samples.min(ids.len());
+        let mut distances = Vec::with_capacity(sample_count);
+        if k > 0 {
+            for i in 0..sample_count {
+                let id = &ids[i * ids.len() / sample_count];
+                let vector = self.nodes[id].vector();
+                // Ask for one extra neighbor since the node finds itself
+                let kth = self
+                    .search(&vector, k + 1, ef)
+                    .into_iter()
+                    .filter(|(other, _)| other != id)
+                    .nth(k - 1);
+                if let Some((_, dist)) = kth {
+                    distances.push(dist);
+                }
+            }
+        }
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let n = distances.len();
+        if n == 0 {
+            return DensityProfile {
+                k,
+                samples: 0,
+                min: 0.0,
+                max: 0.0,
+                mean: 0.0,
+                median: 0.0,
+                std_dev: 0.0,
+                coefficient_of_variation: 0.0,
+                distances,
+            };
+        }
+
+        let mean = distances.iter().sum::<f32>() / n as f32;
+        let variance = distances.iter().map(|d| (d - mean) * (d - mean)).sum::<f32>() / n as f32;
+        let std_dev = variance.sqrt();
+        DensityProfile {
+            k,
+            samples: n,
+            min: distances[0],
+            max: distances[n - 1],
+            mean,
+            median: distances[n / 2],
+            std_dev,
+            coefficient_of_variation: if mean > 0.0 { std_dev / mean } else { 0.0 },
+            distances,
+        }
+    }
+
+    /// Zero-pad every stored vector up to `new_dimensions` and adopt it as the
+    /// index dimensionality.
+    ///
+    /// Appending zeros leaves every Euclidean, cosine and dot-product distance
+    /// between existing vectors unchanged, so the graph stays valid and new
+    /// full-width vectors can be inserted alongside the padded ones.
+    pub fn pad_dimensions(&mut self, new_dimensions: usize) -> Result<(), String> {
+        if new_dimensions < self.dimensions {
+            return Err(format!(
+                "Cannot shrink dimensions from {} to {}; only padding to a larger size is supported",
+                self.dimensions, new_dimensions
+            ));
+        }
+        for node in self.nodes.values_mut() {
+            match &mut node.quantized {
+                Some(q) => {
+                    let mut vector = q.decode();
+                    vector.resize(new_dimensions, 0.0);
+                    *q = QuantizedVector::encode(self.quantization, &vector)
+                        .expect("a quantized node belongs to a quantized index");
+                    node.norm_sq = distance::squared_norm(&q.decode());
+                }
+                None => node.vector.resize(new_dimensions, 0.0),
+            }
+        }
+        self.dimensions = new_dimensions;
+        self.invalidate_seed_cache();
+        Ok(())
+    }
+
+    /// Number of nodes present on each layer (a node whose top layer is L
+    /// appears on layers 0..=L), from layer 0 up
+    pub fn layer_node_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.max_layer + 1];
+        for node in self.nodes.values() {
+            for count in counts.iter_mut().take(node.connections.len()) {
+                *count += 1;
+            }
+        }
+        if self.nodes.is_empty() {
+            counts.clear();
+        }
+        counts
+    }
+
+    /// Size, configuration and layer shape of the index
+    ///
+    /// Served from running aggregates in O(layers)
+    pub fn stats(&self) -> IndexStats {
+        let scanned;
+        let running = match &self.running_stats {
+            Some(running) => running,
+            None => {
+                scanned = self.scan_stats();
+                &scanned
+            }
+        };
+
+        let mut nodes_per_layer = running.nodes_per_layer.clone();
+        if self.nodes.is_empty() {
+            nodes_per_layer.clear();
+        } else {
+            nodes_per_layer.resize(self.max_layer + 1, 0);
+        }
+        IndexStats {
+            node_count: self.nodes.len(),
+            max_layer: self.max_layer,
+            dimensions: self.dimensions,
+            metric: self.metric.name().to_string(),
+            m: self.m,
+            ef_construction: self.ef_construction,
+            avg_layer0_connections: if self.nodes.is_empty() {
+                0.0
+            } else {
+                running.layer0_edges as f32 / self.nodes.len() as f32
+            },
+            nodes_per_layer,
+            auto_ef_factor: self.auto_ef_factor,
+        }
+    }
+
+    /// Recompute the `stats` aggregates by scanning every node
+    fn scan_stats(&self) -> RunningStats {
+        let mut stats = RunningStats::default();
+        for node in self.nodes.values() {
+            stats.add_node(node.connections.len(), 1);
+            stats.layer0_edges += node.connections.first().map_or(0, |c| c.len());
+        }
+        stats
+    }
+
+    /// Number of nodes whose top layer is exactly `i`, for i in 0..=max_layer
+    pub fn top_layer_histogram(&self) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for node in self.nodes.values() {
+            let top = node.connections.len().saturating_sub(1);
+            if counts.len() <= top {
+                counts.resize(top + 1, 0);
+            }
+            counts[top] += 1;
+        }
+        counts
+    }
+
+    /// The nodes and links of `layer` (empty above the top layer)
+    pub fn to_adjacency(&self, layer: usize) -> LayerAdjacency {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for node in self.nodes.values() {
+            let Some(links) = node.connections.get(layer) else {
+                continue;
+            };
+            nodes.push(node.id.clone());
+            edges.extend(links.iter().map(|to| (node.id.clone(), to.clone())));
+        }
+        nodes.sort();
+        edges.sort();
+        LayerAdjacency { layer, nodes, edges }
+    }
+
+    /// `to_adjacency` as a Graphviz DOT digraph, e.g. for `dot -Tsvg`
+    pub fn to_dot(&self, layer: usize) -> String {
+        let adjacency = self.to_adjacency(layer);
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = format!("digraph layer{} {{\n", layer);
+        for id in &adjacency.nodes {
+            dot.push_str(&format!("  {};\n", quote(id)));
+        }
+        for (from, to) in &adjacency.edges {
+            dot.push_str(&format!("  {} -> {};\n", quote(from), quote(to)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Check the observed layer assignment against the distribution `ml` implies
+    pub fn layer_health(&self) -> LayerHealth {
+        assess_layers(self.top_layer_histogram(), self.ml)
+    }
+
+    /// Rebuild the graph from scratch by re-inserting every stored vector
+    ///
+    /// Deletion only unlinks a node from its neighbors, so heavy churn leaves
+    /// neighborhoods that lost their bridges poorly connected. Rebuilding keeps
+    /// every id, vector, per-node `m` and setting, and reinserts in id order,
+    /// continuing the seeded layer sequence when the index is seeded.
+    ///
+    /// Fails while vectors are dropped, since there is nothing to reinsert.
+    pub fn rebuild(&mut self) -> Result<(), String> {
+        self.ensure_vectors("rebuild")?;
+        let mut records: Vec<(String, Vec<f32>, Option<usize>)> = self
+            .nodes
+            .drain()
+            .map(|(id, node)| {
+                let vector = node.vector().into_owned();
+                (id, vector, node.max_m)
+            })
+            .collect();
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.entry_point = None;
+        self.max_layer = 0;
+        self.invalidate_seed_cache();
+        self.running_stats = Some(RunningStats::default());
+        for (id, vector, m) in records {
+            self.insert_with_m(id, vector, m);
+        }
+        Ok(())
+    }
+
+    /// Switch to `metric` and rebuild the graph under it, since links chosen
+    /// under one metric don't make a navigable graph for another. Also ends
+    /// any pending automatic metric selection. Fails, leaving the index
+    /// unchanged, while vectors are dropped.
+    pub fn reindex_with_metric(&mut self, metric: DistanceMetric) -> Result<(), String> {
+        self.ensure_vectors("reindex")?;
+        self.metric = metric;
+        if metric != DistanceMetric::Cosine {
+            self.normalized = false;
+        }
+        self.auto_metric = false;
+        self.rebuild()
+    }
+
+    /// Remove every node, keeping the configuration (dimensions, metric, m,
+    /// ef settings, quantization) and the RNG state
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.dropped_vectors.clear();
+        self.dropped_hashes.clear();
+        self.timestamps.clear();
+        self.entry_point = None;
+        self.max_layer = 0;
+        self.invalidate_seed_cache();
+        self.running_stats = Some(RunningStats::default());
+    }
+
+    /// Delete a vector by ID
+    pub fn delete(&mut self, id: &str) -> bool {
+        self.remove(id).is_some()
+    }
+
+    /// Delete a node by ID, returning its vector if it was present
+    pub fn remove(&mut self, id: &str) -> Option<Vec<f32>> {
+        // Remove the node
+        let node = self.nodes.remove(id)?;
+        self.dropped_vectors.remove(id);
+        self.dropped_hashes.remove(id);
+        self.timestamps.remove(id);
+        self.invalidate_seed_cache();
+
+        // Remove all connections to this node, including one-way links from
+        // nodes it had pruned from its own lists
+        let mut removed = node.connections.first().map_or(0, |c| c.len());
+        let layers = node.connections.len();
+        for other in self.nodes.values_mut() {
+            for (layer, links) in other.connections.iter_mut().take(layers).enumerate() {
+                if links.remove(id) && layer == 0 {
+                    removed += 1;
+                }
+            }
+        }
+        let added = if self.repair_on_delete && self.dropped_vectors.is_empty() {
+            self.repair_links(&node.connections)
+        } else {
+            0
+        };
+        if let Some(stats) = self.running_stats.as_mut() {
+            stats.add_node(node.connections.len(), -1);
+            stats.adjust_edges(added, removed);
+        }
+
+        // Update entry point if needed
+        if self.entry_point.as_ref() == Some(&id.to_string()) {
+            if let Some(sample_size) = self.medoid_entry_sample {
+                self.recompute_entry_point(sample_size);
+            } else {
+                // Pick the node with the most layers as new entry point
+                self.entry_point = self
+                    .nodes
+                    .values()
+                    .max_by_key(|n| n.connections.len())
+                    .map(|n| n.id.clone());
+                self.max_layer = self
+                    .nodes
+                    .values()
+                    .map(|n| n.connections.len().saturating_sub(1))
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+
+        Some(match node.quantized {
+            Some(q) => q.decode(),
+            None => node.vector,
+        })
+    }
+
+    /// Link the former neighbors of a deleted node to each other
+    ///
+    /// On each layer, every former neighbor takes the nearest of the others
+    /// it isn't linked to yet, as long as both ends stay within their caps.
+    /// Links are added in both directions. Returns the number of directed
+    /// layer-0 links added.
+    fn repair_links(&mut self, former: &[HashSet<String>]) -> usize {
+        let caps = self.layer_caps();
+        let mut added = 0;
+        for (layer, neighbors) in former.iter().enumerate() {
+            let mut orphans: Vec<&String> = neighbors
+                .iter()
+                .filter(|id| self.nodes.get(*id).is_some_and(|n| layer < n.connections.len()))
+                .collect();
+            orphans.sort();
+
+            for &orphan in &orphans {
+                let mut candidates: Vec<(&String, f32)> = orphans
+                    .iter()
+                    .filter(|&&other| {
+                        other != orphan && !self.nodes[orphan].connections[layer].contains(other)
+                    })
+                    .map(|&other| (other, self.distance_between(orphan, other)))
+                    .collect();
+                candidates.sort_by(|a, b| {
+                    a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(b.0))
+                });
+
+                for (other, _) in candidates {
+                    let cap = |n: &HNSWNode| caps.at(n.max_m, layer);
+                    let node = &self.nodes[orphan];
+                    if node.connections[layer].len() >= cap(node) {
+                        break;
+                    }
+                    let peer = &self.nodes[other];
+                    if peer.connections[layer].len() >= cap(peer) {
+                        continue;
+                    }
+                    for (from, to) in [(orphan, other), (other, orphan)] {
+                        let linked = self
+                            .nodes
+                            .get_mut(from)
+                            .is_some_and(|n| n.connections[layer].insert(to.clone()));
+                        if linked && layer == 0 {
+                            added += 1;
+                        }
+                    }
+                }
+            }
+        }
+        added
+    }
+
+    /// The index-wide connection limits, copied out so they can be read
+    /// while nodes are borrowed
+    fn layer_caps(&self) -> LayerCaps {
+        LayerCaps { m: self.m, m0: self.m0() }
+    }
+
+    /// Search within a specific layer
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: Vec<String>,
+        ef: usize,
+        layer: usize,
+    ) -> Vec<(String, f32)> {
+        self.search_layer_filtered(query, entry_points, ef, layer, &|_: &str| true)
+    }
+
+    /// Search within a specific layer, admitting only nodes that pass `filter`
+    /// into the result set (every visited node is still used for routing)
+    fn search_layer_filtered<F: Fn(&str) -> bool>(
+        &self,
+        query: &[f32],
+        entry_points: Vec<String>,
+        ef: usize,
+        layer: usize,
+        filter: &F,
+    ) -> Vec<(String, f32)> {
+        self.search_layer_traced(query, entry_points, ef, layer, filter, None)
+    }
+
+    /// `search_layer_filtered`, additionally recording into `expanded` the id
+    /// of every node whose neighbor list was scanned
+    fn search_layer_traced<F: Fn(&str) -> bool>(
+        &self,
+        query: &[f32],
+        entry_points: Vec<String>,
+        ef: usize,
+        layer: usize,
+        filter: &F,
+        mut expanded: Option<&mut HashSet<String>>,
+    ) -> Vec<(String, f32)> {
+        let mut visited = HashSet::new();
+        let mut candidates: BinaryHeap<MinDistElement> = BinaryHeap::new();
+        let mut nearest: BinaryHeap<MaxDistElement> = BinaryHeap::new();
+        let query_norm_sq = self.query_norm_sq(query);
+        // Quantized nodes decode here, reused across the whole layer search
+        let mut decoded = Vec::with_capacity(self.dimensions);
+
+        for ep in entry_points {
+            let dist = self.distance_to_with_norm(&ep, query, query_norm_sq, &mut decoded);
+            candidates.push(MinDistElement {
+                id: ep.clone(),
+                distance: dist,
+            });
+            if filter(&ep) {
+                nearest.push(MaxDistElement {
+                    id: ep.clone(),
+                    distance: dist,
+                });
+            }
+            visited.insert(ep);
+        }
+
+        while let Some(curr) = candidates.pop() {
+            // curr is the closest unexplored candidate
+            let furthest_dist = nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+
+            if nearest.len() >= ef && curr.distance > furthest_dist {
+                break;
+            }
+
+            if let Some(expanded) = expanded.as_deref_mut() {
+                expanded.insert(curr.id.clone());
+            }
+            if let Some(node) = self.nodes.get(&curr.id) {
+                if layer < node.connections.len() {
+                    for neighbor_id in &node.connections[layer] {
+                        if visited.insert(neighbor_id.clone()) {
+                            let dist = self.distance_to_with_norm(
+                                neighbor_id,
+                                query,
+                                query_norm_sq,
+                                &mut decoded,
+                            );
+                            let furthest =
+                                nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+
+                            if dist < furthest || nearest.len() < ef {
+                                candidates.push(MinDistElement {
+                                    id: neighbor_id.clone(),
+                                    distance: dist,
+                                });
+                                if filter(neighbor_id) {
+                                    nearest.push(MaxDistElement {
+                                        id: neighbor_id.clone(),
+                                        distance: dist,
+                                    });
+
+                                    if nearest.len() > ef {
+                                        nearest.pop(); // removes the farthest element
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // into_sorted_vec() returns ascending order = nearest first
+        nearest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|h| (h.id, h.distance))
+            .collect()
+    }
+
+    /// Select up to m neighbors from candidates sorted nearest-first
+    ///
+    /// With the heuristic enabled (SELECT-NEIGHBORS-HEURISTIC from the HNSW
+    /// paper) a candidate is admitted only if no neighbor already selected is
+    /// strictly closer to it than the query is. This spreads links across directions
+    /// instead of packing them into the nearest cluster, which keeps the graph
+    /// navigable between clusters.
+    fn select_neighbors(&self, candidates: Vec<(String, f32)>, m: usize) -> HashSet<String> {
+        if !self.neighbor_heuristic {
+            return candidates.into_iter().take(m).map(|(id, _)| id).collect();
+        }
+
+        let mut selected: Vec<String> = Vec::with_capacity(m);
+        let mut discarded = Vec::new();
+        for (id, dist) in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let diverse = selected
+                .iter()
+                .all(|chosen| dist <= self.distance_between(&id, chosen));
+            if diverse {
+                selected.push(id);
+            } else {
+                discarded.push(id);
+            }
+        }
+
+        if self.keep_pruned_connections {
+            let missing = m.saturating_sub(selected.len());
+            selected.extend(discarded.into_iter().take(missing));
+        }
+
+        selected.into_iter().collect()
+    }
+
+    /// Prune connections for a node
+    fn prune_connections(&self, node_id: &str, layer: usize, max_conn: usize) -> HashSet<String> {
+        if let Some(node) = self.nodes.get(node_id) {
+            let mut neighbors: Vec<_> = node.connections[layer]
+                .iter()
+                .map(|id| {
+                    let dist = self.distance_between(node_id, id);
+                    (id.clone(), dist)
+                })
+                .collect();
+
+            neighbors.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            neighbors.into_iter().take(max_conn).map(|(id, _)| id).collect()
+        } else {
+            HashSet::new()
+        }
+    }
+
+    /// Calculate distance using the configured metric, from a stored vector
+    /// `a` to `b`
+    fn compute_distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert_eq!(a.len(), b.len(), "distance between vectors of different lengths");
+        match self.metric {
+            DistanceMetric::Euclidean => distance::euclidean_distance_squared(a, b),
+            DistanceMetric::Cosine if self.normalized => {
+                distance::cosine_distance_from_unit(a, b, distance::squared_norm(b))
+            }
+            DistanceMetric::Cosine => distance::cosine_distance(a, b),
+            DistanceMetric::DotProduct => {
+                // For dot product, negate so that higher dot product = smaller "distance".
+                // Huge components can overflow the sum to inf - inf = NaN, which
+                // has no order; rank such pairs last instead.
+                let dot = distance::dot_product(a, b);
+                if dot.is_nan() {
+                    f32::INFINITY
+                } else {
+                    -dot
+                }
+            }
+            DistanceMetric::Manhattan => distance::manhattan_distance(a, b),
+            DistanceMetric::Angular => distance::angular_distance(a, b),
+            DistanceMetric::Minkowski { p } => distance::minkowski_distance(a, b, p),
+            DistanceMetric::Hamming => distance::hamming_distance(a, b),
+            DistanceMetric::Jaccard => distance::jaccard_distance(a, b),
+        }
+    }
+
+    /// Whether Euclidean distances go through the cached-norm identity
+    fn uses_norm_identity(&self) -> bool {
+        self.cached_norms
+            && self.metric == DistanceMetric::Euclidean
+            && self.dimensions >= NORM_IDENTITY_MIN_DIMS
+    }
+
+    /// Whether cosine distances divide by the query's magnitude alone
+    fn uses_unit_cosine(&self) -> bool {
+        self.normalized && self.metric == DistanceMetric::Cosine
+    }
+
+    /// Squared norm of a query, computed once per layer search when the
+    /// cached-norm identity or normalized storage is in use (0 otherwise,
+    /// where it's unused)
+    fn query_norm_sq(&self, query: &[f32]) -> f32 {
+        if self.uses_norm_identity() || self.uses_unit_cosine() {
+            distance::squared_norm(query)
+        } else {
+            0.0
+        }
+    }
+
+    /// Calculate distance from a node to a query vector
+    ///
+    /// A quantized node is decoded into `decoded`, a buffer the caller keeps
+    /// for the whole search so it doesn't allocate per distance.
+    fn node_distance(&self, node: &HNSWNode, query: &[f32], query_norm_sq: f32, decoded: &mut Vec<f32>) -> f32 {
+        match &node.quantized {
+            Some(q) => {
+                q.decode_into(decoded);
+                self.stored_distance(decoded, node.norm_sq, query, query_norm_sq)
+            }
+            None => self.stored_distance(&node.vector, node.norm_sq, query, query_norm_sq),
+        }
+    }
+
+    /// `node_distance` for a stored vector already in f32 form
+    fn stored_distance(&self, vector: &[f32], norm_sq: f32, query: &[f32], query_norm_sq: f32) -> f32 {
+        debug_assert_eq!(vector.len(), query.len(), "distance between vectors of different lengths");
+        if self.uses_norm_identity() {
+            distance::euclidean_distance_squared_with_norms(vector, norm_sq, query, query_norm_sq)
+        } else if self.uses_unit_cosine() {
+            distance::cosine_distance_from_unit(vector, query, query_norm_sq)
+        } else {
+            self.compute_distance(vector, query)
+        }
+    }
+
+    /// Calculate distance to a query vector
+    #[allow(dead_code)]
+    fn distance_to(&self, id: &str, query: &[f32]) -> f32 {
+        self.distance_to_with_norm(id, query, self.query_norm_sq(query), &mut Vec::new())
+    }
+
+    /// Calculate distance to a query vector whose squared norm is precomputed,
+    /// decoding a quantized node into `decoded`
+    fn distance_to_with_norm(&self, id: &str, query: &[f32], query_norm_sq: f32, decoded: &mut Vec<f32>) -> f32 {
+        self.nodes
+            .get(id)
+            .map(|node| self.node_distance(node, query, query_norm_sq, decoded))
+            .unwrap_or(f32::INFINITY)
+    }
+
+    /// Calculate distance between two nodes
+    fn distance_between(&self, id1: &str, id2: &str) -> f32 {
+        match (self.nodes.get(id1), self.nodes.get(id2)) {
+            (Some(n1), Some(n2)) => self.node_distance(n1, &n2.vector(), n2.norm_sq, &mut Vec::new()),
+            _ => f32::INFINITY,
+        }
+    }
+
+    /// Next 32 random bits from the seeded generator, or getrandom when unseeded
+    fn next_random_u32(&mut self) -> u32 {
+        match self.rng_state.as_mut() {
+            Some(state) => xorshift_next(state),
+            None => {
+                let mut buf = [0u8; 4];
+                getrandom::getrandom(&mut buf).unwrap_or_default();
+                u32::from_le_bytes(buf)
+            }
+        }
+    }
+
+    /// Random layer assignment (exponential decay)
+    fn random_layer(&mut self) -> usize {
+        // Uniform in [0, 1); flip to (0, 1] to avoid ln(0) = -inf
+        let uniform = self.next_random_u32() as f64 / (u32::MAX as f64 + 1.0);
+        let layer = (-(1.0 - uniform).ln() * self.ml as f64) as usize;
+        layer.min(self.max_layer_cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hnsw::DistanceMetric;
+    use crate::quantize::Quantization;
+    use crate::vector::random_vector_seeded;
+
+    /// Helper: create a deterministic vector from a seed
+    fn make_vec(dims: usize, seed: u64) -> Vec<f32> {
+        random_vector_seeded(dims, seed)
+    }
+
+    /// Helper: exact top-k ids by linear scan
+    fn brute_force_knn(idx: &HNSWIndex, query: &[f32], k: usize) -> Vec<String> {
+        let mut dists: Vec<(String, f32)> = idx
+            .nodes
+            .values()
+            .map(|n| (n.id.clone(), idx.compute_distance(&n.vector, query)))
+            .collect();
+        dists.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        dists.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    // ── Construction & basics ──────────────────────────────────────
+
+    #[test]
+    fn new_creates_empty_index() {
+        let idx = HNSWIndex::new(128, 16, 200, DistanceMetric::Euclidean, None);
+        assert_eq!(idx.dimensions, 128);
+        assert_eq!(idx.m, 16);
+        assert_eq!(idx.ef_construction, 200);
+        assert_eq!(idx.metric, DistanceMetric::Euclidean);
+        assert!(idx.entry_point.is_none());
+        assert_eq!(idx.nodes.len(), 0);
+    }
+
+    #[test]
+    fn first_insert_sets_entry_point() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        assert_eq!(idx.entry_point, Some("a".into()));
+        assert_eq!(idx.nodes.len(), 1);
+    }
+
+    #[test]
+    fn size_tracking_after_insertions() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        for i in 0..10 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64));
+        }
+        assert_eq!(idx.nodes.len(), 10);
+    }
+
+    // ── Insert & search correctness ────────────────────────────────
+
+    #[test]
+    fn insert_one_search_finds_it() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let v = vec![1.0, 0.0, 0.0];
+        idx.insert("a".into(), v.clone());
+        let results = idx.search(&v, 1, 50);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "a");
+        assert!(results[0].1 < 1e-6); // distance ~0
+    }
+
+    #[test]
+    fn insert_two_search_returns_correct_order() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let close = vec![1.0, 0.0, 0.0];
+        let far = vec![10.0, 10.0, 10.0];
+        idx.insert("close".into(), close.clone());
+        idx.insert("far".into(), far);
+
+        let results = idx.search(&close, 2, 50);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "close");
+        assert!(results[0].1 < results[1].1);
+    }
+
+    #[test]
+    fn search_returns_k_sorted_by_distance() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        for i in 0..15 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 7 + 42));
+        }
+        let query = make_vec(3, 999);
+        let results = idx.search(&query, 5, 50);
+        assert_eq!(results.len(), 5);
+        // Verify sorted by distance
+        for w in results.windows(2) {
+            assert!(w[0].1 <= w[1].1);
+        }
+    }
+
+    #[test]
+    fn search_k_greater_than_size_returns_all() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        let results = idx.search(&[0.5, 0.5, 0.0], 100, 200);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn search_empty_index_returns_empty() {
+        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let results = idx.search(&[1.0, 0.0, 0.0], 5, 50);
+        assert!(results.is_empty());
+    }
+
+    // ── Nearest-neighbor quality ───────────────────────────────────
+
+    #[test]
+    fn search_finds_true_nearest_neighbor() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let target = vec![5.0, 5.0, 5.0];
+        let nearest = vec![5.1, 5.1, 5.1]; // very close to target
+        let far1 = vec![100.0, 0.0, 0.0];
+        let far2 = vec![0.0, 100.0, 0.0];
+
+        idx.insert("nearest".into(), nearest);
+        idx.insert("far1".into(), far1);
+        idx.insert("far2".into(), far2);
+
+        let results = idx.search(&target, 1, 50);
+        assert_eq!(results[0].0, "nearest");
+    }
+
+    #[test]
+    fn cluster_search_finds_cluster_before_outlier() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        // Cluster around origin
+        idx.insert("c0".into(), vec![0.1, 0.1, 0.1]);
+        idx.insert("c1".into(), vec![0.2, 0.0, 0.1]);
+        idx.insert("c2".into(), vec![0.0, 0.2, 0.1]);
+        // Outlier
+        idx.insert("outlier".into(), vec![50.0, 50.0, 50.0]);
+
+        let results = idx.search(&[0.0, 0.0, 0.0], 4, 50);
+        // All cluster members should come before outlier
+        let outlier_pos = results.iter().position(|(id, _)| id == "outlier").unwrap();
+        assert_eq!(outlier_pos, 3); // outlier is last
+    }
+
+    // ── Dimension validation ───────────────────────────────────────
+
+    #[test]
+    fn insert_wrong_dimension_is_ignored() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("good".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("bad".into(), vec![1.0, 0.0]); // wrong dimensions
+        assert_eq!(idx.nodes.len(), 1);
+        assert!(!idx.nodes.contains_key("bad"));
+    }
+
+    // ── Delete ─────────────────────────────────────────────────────
+
+    #[test]
+    fn delete_existing_returns_true() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        assert!(idx.delete("a"));
+        assert_eq!(idx.nodes.len(), 0);
+    }
+
+    #[test]
+    fn delete_nonexistent_returns_false() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        assert!(!idx.delete("nope"));
+    }
+
+    #[test]
+    fn remove_returns_vector_and_unlinks_node() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        assert_eq!(idx.remove("a"), Some(vec![1.0, 0.0, 0.0]));
+        assert_eq!(idx.remove("a"), None);
+        assert!(idx.nodes["b"].connections.iter().all(|c| !c.contains("a")));
+    }
+
+    #[test]
+    fn delete_entry_point_search_still_works() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
+
+        let entry = idx.entry_point.clone().unwrap();
+        idx.delete(&entry);
+
+        // Search still works with remaining nodes
+        let results = idx.search(&[0.5, 0.5, 0.0], 2, 50);
+        assert!(!results.is_empty());
+        // Deleted entry should not appear
+        for (id, _) in &results {
+            assert_ne!(id, &entry);
+        }
+    }
+
+    #[test]
+    fn delete_all_vectors_empties_index() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        idx.delete("a");
+        idx.delete("b");
+        assert_eq!(idx.nodes.len(), 0);
+        let results = idx.search(&[1.0, 0.0, 0.0], 5, 50);
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn insert_delete_reinsert_same_id() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.delete("a");
+        idx.insert("a".into(), vec![0.0, 1.0, 0.0]);
+        assert_eq!(idx.nodes.len(), 1);
+        let results = idx.search(&[0.0, 1.0, 0.0], 1, 50);
+        assert_eq!(results[0].0, "a");
+    }
+
+    // ── Serialization round-trip ───────────────────────────────────
+
+    #[test]
+    fn serialize_deserialize_preserves_search_results() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
+
+        let query = vec![0.9, 0.1, 0.0];
+        let results_before = idx.search(&query, 3, 50);
+
+        let json = serde_json::to_string(&idx).unwrap();
+        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
+        let results_after = idx2.search(&query, 3, 50);
+
+        assert_eq!(results_before.len(), results_after.len());
+        for (a, b) in results_before.iter().zip(results_after.iter()) {
+            assert_eq!(a.0, b.0);
+            assert!((a.1 - b.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn serialize_empty_index() {
+        let idx = HNSWIndex::new(128, 16, 200, DistanceMetric::Euclidean, None);
+        let json = serde_json::to_string(&idx).unwrap();
+        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert!(idx2.entry_point.is_none());
+        assert_eq!(idx2.nodes.len(), 0);
+        assert_eq!(idx2.dimensions, 128);
+    }
+
+    #[test]
+    fn serialize_after_deletions() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
+        idx.delete("b");
+
+        let json = serde_json::to_string(&idx).unwrap();
+        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(idx2.nodes.len(), 2);
+        assert!(!idx2.nodes.contains_key("b"));
+        // Search still works
+        let results = idx2.search(&[1.0, 0.0, 0.0], 2, 50);
+        assert_eq!(results.len(), 2);
+    }
+
+    // ── Connection integrity ───────────────────────────────────────
+
+    #[test]
+    fn connections_are_bidirectional_within_shared_layers() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        for i in 0..10 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 13 + 1));
+        }
+
+        // Connections should be bidirectional when both nodes exist on the same layer.
+        // A high-layer node may connect to a low-layer node unidirectionally
+        // (the low-layer node doesn't have connections at that layer).
+        for (id, node) in &idx.nodes {
+            for (layer, neighbors) in node.connections.iter().enumerate() {
+                for neighbor_id in neighbors {
+                    let neighbor = idx.nodes.get(neighbor_id).unwrap();
+                    if layer < neighbor.connections.len() {
+                        assert!(
+                            neighbor.connections[layer].contains(id),
+                            "Missing reverse connection: {} -> {} at layer {}",
+                            neighbor_id, id, layer
+                        );
+                    }
+                    // If neighbor doesn't have this layer, unidirectional is expected
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_dangling_references_after_delete() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        for i in 0..10 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 7 + 3));
+        }
+        idx.delete("v5");
+
+        for node in idx.nodes.values() {
+            for neighbors in &node.connections {
+                assert!(!neighbors.contains("v5"), "Dangling reference to deleted node v5");
+            }
+        }
+    }
+
+    // ── Edge cases ─────────────────────────────────────────────────
+
+    #[test]
+    fn large_ef_does_not_panic() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        let results = idx.search(&[1.0, 0.0, 0.0], 1, 10000);
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn k_zero_returns_empty() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        let results = idx.search(&[1.0, 0.0, 0.0], 0, 50);
+        assert!(results.is_empty());
+    }
+
+    // ── Distance metric tests ──────────────────────────────────────
+
+    #[test]
+    fn cosine_metric_returns_correct_order() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Cosine, None);
+        // Same direction as query (cosine distance ~ 0)
+        idx.insert("same_dir".into(), vec![2.0, 0.0, 0.0]);
+        // Orthogonal (cosine distance ~ 1)
+        idx.insert("ortho".into(), vec![0.0, 1.0, 0.0]);
+        // Opposite (cosine distance ~ 2)
+        idx.insert("opposite".into(), vec![-1.0, 0.0, 0.0]);
+
+        let results = idx.search(&[1.0, 0.0, 0.0], 3, 50);
+        assert_eq!(results[0].0, "same_dir");
+        assert_eq!(results[2].0, "opposite");
+    }
+
+    #[test]
+    fn manhattan_metric_returns_correct_order() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Manhattan, None);
+        let close = vec![1.0, 0.0, 0.0];
+        idx.insert("close".into(), close.clone());
+        idx.insert("mid".into(), vec![2.0, 1.0, 0.0]);
+        idx.insert("far".into(), vec![10.0, 10.0, 10.0]);
+
+        let results = idx.search(&close, 3, 50);
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["close", "mid", "far"]);
+        // L1 distances are reported unchanged (no sqrt conversion)
+        assert!(results[0].1 < 1e-6);
+        assert!((results[1].1 - 2.0).abs() < 1e-6);
+        assert!((results[2].1 - 29.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn angular_metric_orders_by_angle_and_reports_normalized_distance() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Angular, None);
+        idx.insert("same".into(), vec![5.0, 0.0]);
+        idx.insert("right_angle".into(), vec![0.0, 1.0]);
+        idx.insert("opposite".into(), vec![-1.0, 0.0]);
+        idx.insert("diagonal".into(), vec![1.0, 1.0]);
+
+        let results = idx.search(&[1.0, 0.0], 4, 50);
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["same", "diagonal", "right_angle", "opposite"]);
+        assert!(results[0].1.abs() < 1e-3);
+        assert!((results[1].1 - 0.25).abs() < 1e-3);
+        assert!((results[2].1 - 0.5).abs() < 1e-3);
+        assert!((results[3].1 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn minkowski_matches_dedicated_metrics_and_round_trips() {
+        let data: Vec<Vec<f32>> = (0..60).map(|i| make_vec(4, i * 13 + 1)).collect();
+        let build = |metric| {
+            let mut idx = HNSWIndex::new(4, 16, 200, metric, Some(8));
+            for (i, v) in data.iter().enumerate() {
+                idx.insert(format!("v{}", i), v.clone());
+            }
+            idx
+        };
+        let query = make_vec(4, 999);
+        for (p, dedicated) in [(1.0, DistanceMetric::Manhattan), (2.0, DistanceMetric::Euclidean)] {
+            let mink = build(DistanceMetric::Minkowski { p }).search(&query, 5, 100);
+            let reference = build(dedicated).search(&query, 5, 100);
+            for (a, b) in mink.iter().zip(&reference) {
+                assert_eq!(a.0, b.0);
+                assert!((a.1 - b.1).abs() < 1e-4, "p={}: {} vs {}", p, a.1, b.1);
+            }
+        }
+
+        let idx = build(DistanceMetric::Minkowski { p: 3.0 });
+        let json = serde_json::to_string(&idx).unwrap();
+        assert!(json.contains(r#"{"Minkowski":{"p":3.0}}"#));
+        let back: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.metric, DistanceMetric::Minkowski { p: 3.0 });
+        assert_eq!(back.search(&query, 5, 100), idx.search(&query, 5, 100));
+    }
+
+    #[test]
+    fn hamming_metric_counts_differing_bits() {
+        let mut idx = HNSWIndex::new(6, 16, 200, DistanceMetric::Hamming, None);
+        idx.insert("same".into(), vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        idx.insert("one_off".into(), vec![1.0, 0.0, 1.0, 0.0, 1.0, 1.0]);
+        idx.insert("inverse".into(), vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+
+        let results = idx.search(&[1.0, 0.0, 1.0, 0.0, 1.0, 0.0], 3, 50);
+        let got: Vec<(&str, f32)> = results.iter().map(|(id, d)| (id.as_str(), *d)).collect();
+        assert_eq!(got, vec![("same", 0.0), ("one_off", 1.0), ("inverse", 6.0)]);
+    }
+
+    #[test]
+    fn jaccard_metric_ranks_by_tag_overlap() {
+        let mut idx = HNSWIndex::new(5, 16, 200, DistanceMetric::Jaccard, None);
+        idx.insert("same".into(), vec![1.0, 1.0, 0.0, 0.0, 0.0]);
+        idx.insert("overlap".into(), vec![1.0, 0.0, 1.0, 0.0, 0.0]);
+        idx.insert("disjoint".into(), vec![0.0, 0.0, 0.0, 1.0, 1.0]);
+
+        let results = idx.search(&[2.0, 3.0, 0.0, 0.0, 0.0], 3, 50);
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["same", "overlap", "disjoint"]);
+        assert!((results[1].1 - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(results[2].1, 1.0);
+    }
+
+    #[test]
+    fn manhattan_differs_from_euclidean_ordering() {
+        // L1 prefers the axis-aligned point, L2 the diagonal one
+        let axis = vec![3.0, 0.0];
+        let diagonal = vec![2.0, 2.0];
+        let mut l1 = HNSWIndex::new(2, 16, 200, DistanceMetric::Manhattan, None);
+        let mut l2 = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        for idx in [&mut l1, &mut l2] {
+            idx.insert("axis".into(), axis.clone());
+            idx.insert("diagonal".into(), diagonal.clone());
+        }
+        assert_eq!(l1.search(&[0.0, 0.0], 1, 50)[0].0, "axis");
+        assert_eq!(l2.search(&[0.0, 0.0], 1, 50)[0].0, "diagonal");
+    }
+
+    #[test]
+    fn manhattan_search_matches_brute_force() {
+        let mut idx = HNSWIndex::new(8, 16, 200, DistanceMetric::Manhattan, Some(4));
+        for i in 0..200 {
+            idx.insert(format!("v{}", i), make_vec(8, i as u64 * 13 + 1));
+        }
+        let query = make_vec(8, 777);
+        let truth = brute_force_knn(&idx, &query, 10);
+        let results: Vec<String> = idx.search(&query, 10, 100).into_iter().map(|(id, _)| id).collect();
+        let hits = results.iter().filter(|id| truth.contains(id)).count();
+        assert!(hits >= 9, "only {} of 10 true neighbors found", hits);
+    }
+
+    // ── Density profile ────────────────────────────────────────────
+
+    #[test]
+    fn density_profile_mixed_density_has_wider_spread_than_uniform() {
+        // Uniform: random points spread over the unit square
+        let mut uniform = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, Some(1));
+        for i in 0..100 {
+            uniform.insert(format!("u{}", i), make_vec(2, i as u64 * 31 + 7));
+        }
+
+        // Mixed: a tight cluster plus a widely spread sparse region
+        let mut mixed = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, Some(1));
+        for i in 0..50 {
+            let v: Vec<f32> = make_vec(2, i as u64 * 31 + 7).iter().map(|x| x * 0.01).collect();
+            mixed.insert(format!("d{}", i), v);
+        }
+        for i in 50..100 {
+            let v: Vec<f32> = make_vec(2, i as u64 * 31 + 7).iter().map(|x| 10.0 + x * 100.0).collect();
+            mixed.insert(format!("s{}", i), v);
+        }
+
+        let u = uniform.density_profile(5, 100, 50);
+        let m = mixed.density_profile(5, 100, 50);
+        assert_eq!(u.samples, 50);
+        assert_eq!(m.samples, 50);
+        assert!(u.coefficient_of_variation < 0.6, "uniform cv = {}", u.coefficient_of_variation);
+        assert!(m.coefficient_of_variation > 0.9, "mixed cv = {}", m.coefficient_of_variation);
+        assert!(m.max / m.min > u.max / u.min * 10.0);
+    }
+
+    #[test]
+    fn density_profile_empty_index() {
+        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let p = idx.density_profile(5, 50, 10);
+        assert_eq!(p.samples, 0);
+        assert!(p.distances.is_empty());
+    }
+
+    // ── Automatic ef scaling ───────────────────────────────────────
+
+    #[test]
+    fn effective_ef_defaults_to_max_of_ef_and_k() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        assert_eq!(idx.effective_ef(10, 50), 50);
+        assert_eq!(idx.effective_ef(100, 50), 100);
+
+        idx.set_auto_ef_factor(Some(2));
+        assert_eq!(idx.effective_ef(10, 50), 50);
+        assert_eq!(idx.effective_ef(100, 50), 200);
+
+        idx.set_auto_ef_factor(None);
+        assert_eq!(idx.effective_ef(100, 50), 100);
+    }
+
+    #[test]
+    fn stats_report_the_auto_ef_factor() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, Some(5));
+        assert_eq!(idx.stats().auto_ef_factor, None);
+
+        idx.set_auto_ef_factor(Some(3));
+        assert_eq!(idx.stats().auto_ef_factor, Some(3));
+    }
+
+    #[test]
+    fn auto_ef_improves_recall_for_large_k() {
+        let mut idx = HNSWIndex::new(16, 4, 16, DistanceMetric::Euclidean, Some(42));
+        for i in 0..600 {
+            idx.insert(format!("v{}", i), make_vec(16, i as u64 * 7919 + 1));
+        }
+        let queries: Vec<Vec<f32>> = (0..10).map(|i| make_vec(16, i as u64 * 104729 + 5)).collect();
+
+        let raw = idx.estimate_recall(&queries, 50, 10);
+        idx.set_auto_ef_factor(Some(4));
+        let scaled = idx.estimate_recall(&queries, 50, 10);
+        assert!(scaled > raw, "scaled recall {} should beat raw {}", scaled, raw);
+    }
+
+    #[test]
+    fn auto_ef_factor_survives_serialization() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.set_auto_ef_factor(Some(3));
+        let json = serde_json::to_string(&idx).unwrap();
+        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(idx2.auto_ef_factor(), Some(3));
+    }
+
+    // ── Layer health ───────────────────────────────────────────────
+
+    #[test]
+    fn layer_health_accepts_geometric_distribution() {
+        // m = 16 → ml = 1/ln(16): each layer holds ~1/16 of the one below
+        let ml = 1.0 / (16.0f32).ln();
+        let health = assess_layers(vec![9375, 586, 37, 2], ml);
+        assert!(health.healthy, "{:?}", health.anomalies);
+        assert_eq!(health.node_count, 10000);
+        assert!((health.expected[0] - 9375.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn layer_health_flags_flat_distribution() {
+        let ml = 1.0 / (16.0f32).ln();
+        let health = assess_layers(vec![10000], ml);
+        assert!(!health.healthy);
+    }
+
+    #[test]
+    fn layer_health_flags_tall_towers() {
+        let ml = 1.0 / (16.0f32).ln();
+        let mut observed = vec![9375, 586, 37, 1];
+        observed.resize(17, 0);
+        observed[16] = 1;
+        let health = assess_layers(observed, ml);
+        assert!(!health.healthy);
+        assert!(health.anomalies.iter().any(|a| a.contains("max layer 16")));
+    }
+
+    #[test]
+    fn layer_health_flags_broken_ml() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.ml = 0.0;
+        for i in 0..50 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64));
+        }
+        assert_eq!(idx.top_layer_histogram(), vec![50]);
+        let health = idx.layer_health();
+        assert!(!health.healthy);
+        assert!(!health.anomalies.is_empty());
+    }
+
+    #[test]
+    fn layer_health_empty_index_is_healthy() {
+        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let health = idx.layer_health();
+        assert!(health.healthy);
+        assert_eq!(health.node_count, 0);
+    }
+
+    #[test]
+    fn adjacency_export_lists_every_link_on_the_layer() {
+        let mut idx = HNSWIndex::new(2, 4, 50, DistanceMetric::Euclidean, Some(17));
+        for i in 0..40 {
+            idx.insert(format!("n{}", i), vec![(i % 7) as f32, (i / 7) as f32]);
+        }
+        for layer in 0..=idx.max_layer + 1 {
+            let adjacency = idx.to_adjacency(layer);
+            let expected: usize = idx.nodes.values().filter_map(|n| n.connections.get(layer)).map(|c| c.len()).sum();
+            assert_eq!(adjacency.edges.len(), expected, "layer {}", layer);
+            assert_eq!(adjacency.nodes.len(), idx.layer_node_counts().get(layer).copied().unwrap_or(0));
+        }
+        assert!(idx.to_adjacency(idx.max_layer + 1).nodes.is_empty());
+
+        let dot = idx.to_dot(0);
+        assert!(dot.starts_with("digraph layer0 {\n"));
+        assert_eq!(dot.matches(" -> ").count(), idx.to_adjacency(0).edges.len());
+
+        let mut quoted = HNSWIndex::new(1, 4, 50, DistanceMetric::Euclidean, Some(1));
+        quoted.insert("say \"hi\"".into(), vec![0.0]);
+        assert_eq!(quoted.to_dot(0), "digraph layer0 {\n  \"say \\\"hi\\\"\";\n}\n");
+    }
+
+    // ── Dimension padding ──────────────────────────────────────────
+
+    #[test]
+    fn pad_dimensions_mixes_old_and_new_vectors() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("old_a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("old_b".into(), vec![0.0, 1.0, 0.0]);
+
+        idx.pad_dimensions(5).unwrap();
+        assert_eq!(idx.dimensions, 5);
+        assert_eq!(idx.get_vector("old_a").unwrap().as_ref(), &[1.0, 0.0, 0.0, 0.0, 0.0][..]);
+
+        idx.insert("new".into(), vec![0.0, 0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(idx.node_count(), 3);
+
+        let results = idx.search(&[0.0, 0.0, 0.0, 1.0, 0.9], 3, 50);
+        assert_eq!(results[0].0, "new");
+        let results = idx.search(&[0.9, 0.0, 0.0, 0.0, 0.0], 3, 50);
+        assert_eq!(results[0].0, "old_a");
+        assert!((results[0].1 - 0.1).abs() < 1e-5);
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn pad_dimensions_rejects_shrinking() {
+        let mut idx = HNSWIndex::new(5, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0; 5]);
+        assert!(idx.pad_dimensions(3).is_err());
+        assert_eq!(idx.dimensions, 5);
+        assert_eq!(idx.get_vector("a").unwrap().len(), 5);
+    }
+
+    // ── Random layer assignment ────────────────────────────────────
+
+    #[test]
+    fn random_layer_follows_geometric_decay() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        let mut counts = Vec::new();
+        for _ in 0..20000 {
+            let layer = idx.random_layer();
+            if counts.len() <= layer {
+                counts.resize(layer + 1, 0);
+            }
+            counts[layer] += 1;
+        }
+        // With m = 16 roughly 15/16 of nodes stay on layer 0
+        let layer0 = counts[0] as f32 / 20000.0;
+        assert!((layer0 - 0.9375).abs() < 0.01, "layer 0 share = {}", layer0);
+        let health = assess_layers(counts, idx.ml);
+        assert!(health.healthy, "{:?}", health.anomalies);
+    }
+
+    #[test]
+    fn inserted_layer_histogram_matches_ml() {
+        let mut idx = HNSWIndex::new(3, 4, 16, DistanceMetric::Euclidean, Some(3));
+        for i in 0..3000 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 7 + 11));
+        }
+        let histogram = idx.top_layer_histogram();
+        // m = 4: each layer should hold about a quarter of the nodes below it
+        let layer0 = histogram[0] as f32 / 3000.0;
+        assert!((layer0 - 0.75).abs() < 0.05, "layer 0 share = {}", layer0);
+        assert!(idx.max_layer < 12, "max layer = {}", idx.max_layer);
+        let health = idx.layer_health();
+        assert!(health.healthy, "{:?}", health.anomalies);
+    }
+
+    // ── Neighbor selection heuristic ───────────────────────────────
+
+    /// Helper: 1000 vectors in ten tight, well-separated clusters
+    fn clustered_dataset() -> Vec<Vec<f32>> {
+        (0..1000)
+            .map(|i| {
+                let center = make_vec(8, (i % 10) as u64 * 1_000_003 + 17);
+                let jitter = make_vec(8, i as u64 * 7919 + 3);
+                center
+                    .iter()
+                    .zip(jitter.iter())
+                    .map(|(c, j)| c * 20.0 + j * 0.5)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn build(data: &[Vec<f32>], heuristic: bool) -> HNSWIndex {
+        let mut idx = HNSWIndex::new(8, 6, 64, DistanceMetric::Euclidean, Some(42));
+        idx.set_neighbor_heuristic(heuristic);
+        for (i, v) in data.iter().enumerate() {
+            idx.insert(format!("v{}", i), v.clone());
+        }
+        idx
+    }
+
+    #[test]
+    fn heuristic_recall_beats_naive_selection() {
+        let data = clustered_dataset();
+        let queries: Vec<Vec<f32>> = (0..20).map(|i| data[i * 37 + 5].clone()).collect();
+
+        let naive = build(&data, false).estimate_recall(&queries, 10, 20);
+        let heuristic = build(&data, true).estimate_recall(&queries, 10, 20);
+
+        // Naive selection packs links inside each cluster and strands the rest
+        assert!(heuristic > naive + 0.1, "heuristic {} vs naive {}", heuristic, naive);
+        assert!(heuristic > 0.85, "heuristic recall {}", heuristic);
+    }
+
+    #[test]
+    fn heuristic_skips_redundant_candidates() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0]);
+        idx.insert("a2".into(), vec![1.1, 0.0]);
+        idx.insert("b".into(), vec![-1.0, 0.0]);
+
+        // From the origin, a2 sits behind a, so it's redundant; b is another direction
+        let query = [0.0, 0.0];
+        let candidates: Vec<(String, f32)> = ["a", "b", "a2"]
+            .iter()
+            .map(|id| (id.to_string(), idx.distance_to(id, &query)))
+            .collect();
+
+        let selected = idx.select_neighbors(candidates.clone(), 3);
+        assert_eq!(selected, ["a", "b"].iter().map(|s| s.to_string()).collect());
+
+        idx.set_keep_pruned_connections(true);
+        assert_eq!(idx.select_neighbors(candidates, 3).len(), 3);
+    }
+
+    // ── Seeded construction ────────────────────────────────────────
+
+    #[test]
+    fn same_seed_builds_identical_index() {
+        let build = || {
+            let mut idx = HNSWIndex::new(4, 8, 64, DistanceMetric::Euclidean, Some(42));
+            for i in 0..200 {
+                idx.insert(format!("v{}", i), make_vec(4, i as u64 * 31 + 9));
+            }
+            serde_json::to_string(&idx).unwrap()
+        };
+        assert_eq!(build(), build());
+    }
+
+    #[test]
+    fn with_capacity_builds_the_same_index_as_new() {
+        let fill = |mut idx: HNSWIndex| {
+            for i in 0..300 {
+                idx.insert(format!("v{}", i), make_vec(4, i as u64 * 31 + 9));
+            }
+            idx
+        };
+        let plain = fill(HNSWIndex::new(4, 8, 64, DistanceMetric::Euclidean, Some(42)));
+        let reserved = fill(HNSWIndex::with_capacity(4, 8, 64, DistanceMetric::Euclidean, Some(42), 300));
+        assert!(reserved.nodes.capacity() >= 300);
+        assert_eq!(serde_json::to_string(&reserved).unwrap(), serde_json::to_string(&plain).unwrap());
+        let query = make_vec(4, 5);
+        assert_eq!(reserved.search(&query, 10, 50), plain.search(&query, 10, 50));
+
+        // Reserving up front means no growth during the load
+        let mut idx = HNSWIndex::with_capacity(2, 8, 64, DistanceMetric::Euclidean, Some(1), 1000);
+        let capacity = idx.nodes.capacity();
+        for i in 0..1000 {
+            idx.insert(format!("v{}", i), vec![i as f32, 0.0]);
+        }
+        assert_eq!(idx.nodes.capacity(), capacity);
+    }
+
+    #[test]
+    fn different_seeds_assign_different_layers() {
+        let layers = |seed| {
+            let mut idx = HNSWIndex::new(3, 4, 16, DistanceMetric::Euclidean, Some(seed));
+            (0..64).map(|_| idx.random_layer()).collect::<Vec<_>>()
+        };
+        assert_eq!(layers(1), layers(1));
+        assert_ne!(layers(1), layers(2));
+    }
+
+    #[test]
+    fn restored_index_continues_seeded_sequence() {
+        let mut original = HNSWIndex::new(3, 4, 16, DistanceMetric::Euclidean, Some(7));
+        for _ in 0..10 {
+            original.random_layer();
+        }
+        let json = serde_json::to_string(&original).unwrap();
+        let mut restored: HNSWIndex = serde_json::from_str(&json).unwrap();
+
+        let expected: Vec<usize> = (0..32).map(|_| original.random_layer()).collect();
+        let actual: Vec<usize> = (0..32).map(|_| restored.random_layer()).collect();
+        assert_eq!(expected, actual);
+    }
+
+    // ── Cross-index comparison ─────────────────────────────────────
+
+    #[test]
+    fn result_overlap_same_config_is_near_perfect() {
+        let build = |metric, seed| {
+            let mut idx = HNSWIndex::new(8, 16, 200, metric, Some(seed));
+            for i in 0..300 {
+                idx.insert(format!("v{}", i), make_vec(8, i as u64 * 131 + 7));
+            }
+            idx
+        };
+        let queries: Vec<Vec<f32>> = (0..20).map(|i| make_vec(8, i as u64 * 977 + 3)).collect();
+
+        let a = build(DistanceMetric::Euclidean, 1);
+        let rebuilt = build(DistanceMetric::Euclidean, 2);
+        let dot = build(DistanceMetric::DotProduct, 1);
+
+        let same = a.result_overlap(&rebuilt, &queries, 10, 100);
+        let different = a.result_overlap(&dot, &queries, 10, 100);
+        assert!(same > 0.95, "same-config overlap {}", same);
+        assert!(different < same - 0.2, "metric change overlap {} vs {}", different, same);
+        assert!((a.result_overlap(&a, &queries, 10, 100) - 1.0).abs() < 1e-6);
+    }
+
+    // ── Per-node m override ────────────────────────────────────────
+
+    #[test]
+    fn high_m_node_gets_more_layer0_connections() {
+        let mut idx = HNSWIndex::new(4, 4, 64, DistanceMetric::Euclidean, Some(5));
+        idx.set_keep_pruned_connections(true);
+        idx.insert_with_m("hub".into(), vec![0.5, 0.5, 0.5, 0.5], Some(32));
+        for i in 0..300 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 * 17 + 1));
+        }
+
+        let hub_degree = idx.nodes["hub"].connections[0].len();
+        let max_default = idx
+            .nodes
+            .values()
+            .filter(|n| n.id != "hub")
+            .map(|n| n.connections[0].len())
+            .max()
+            .unwrap();
+        assert!(max_default <= 8);
+        assert!(hub_degree > max_default, "hub {} vs default max {}", hub_degree, max_default);
+    }
+
+    #[test]
+    fn per_node_m_survives_serialization() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert_with_m("hub".into(), vec![1.0, 0.0, 0.0], Some(48));
+        idx.insert_with_m("same".into(), vec![0.0, 1.0, 0.0], Some(16));
+        idx.insert("plain".into(), vec![0.0, 0.0, 1.0]);
+
+        let json = serde_json::to_string(&idx).unwrap();
+        // Only caps that differ from the global m are written out
+        assert_eq!(json.matches("max_m").count(), 1);
+        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(idx2.nodes["hub"].max_m, Some(48));
+        assert_eq!(idx2.nodes["same"].max_m, None);
+        assert_eq!(idx2.nodes["plain"].max_m, None);
+    }
+
+    // ── Per-insert ef_construction ─────────────────────────────────
+
+    #[test]
+    fn higher_per_insert_ef_builds_an_equally_connected_graph() {
+        let dims = 8;
+        let build = |ef: Option<usize>| {
+            let mut idx = HNSWIndex::new(dims, 8, 4, DistanceMetric::Euclidean, Some(13));
+            for i in 0..1000 {
+                let (id, v) = (format!("v{}", i), make_vec(dims, i as u64 * 31 + 3));
+                match ef {
+                    Some(ef) => idx.insert_with_ef(id, v, ef),
+                    None => idx.insert(id, v),
+                }
+            }
+            idx
+        };
+        let (default, refined) = (build(None), build(Some(200)));
+        assert_eq!(refined.ef_construction(), 4);
+        assert_eq!(refined.node_count(), 1000);
+
+        let (default_degree, refined_degree) =
+            (default.stats().avg_layer0_connections, refined.stats().avg_layer0_connections);
+        assert!(refined_degree >= default_degree, "{} vs {}", refined_degree, default_degree);
+
+        let queries: Vec<Vec<f32>> = (0..50).map(|i| make_vec(dims, 90_000 + i)).collect();
+        let default_recall = default.estimate_recall(&queries, 10, 20);
+        let refined_recall = refined.estimate_recall(&queries, 10, 20);
+        assert!(
+            refined_recall >= default_recall,
+            "ef 200 {} vs ef 4 {}",
+            refined_recall,
+            default_recall
+        );
+    }
+
+    // ── Filtered search ────────────────────────────────────────────
+
+    #[test]
+    fn filtered_search_returns_k_matching_neighbors() {
+        let mut idx = HNSWIndex::new(4, 8, 100, DistanceMetric::Euclidean, Some(9));
+        for i in 0..500 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 * 61 + 5));
+        }
+        // Only 1 in 25 nodes matches
+        let allowed: HashSet<String> = (0..500).filter(|i| i % 25 == 0).map(|i| format!("v{}", i)).collect();
+        let query = make_vec(4, 424242);
+
+        let results = idx.search_filtered(&query, 5, 10, |id| allowed.contains(id));
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|(id, _)| allowed.contains(id)));
+
+        // Matches the exact top-5 among the allowed subset
+        let mut truth: Vec<(String, f32)> = allowed
+            .iter()
+            .map(|id| (id.clone(), idx.final_distance(idx.distance_to(id, &query))))
+            .collect();
+        truth.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let truth_ids: Vec<String> = truth.into_iter().take(5).map(|(id, _)| id).collect();
+        let result_ids: Vec<String> = results.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(result_ids, truth_ids);
+    }
+
+    #[test]
+    fn filtered_search_with_no_matches_is_empty() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        assert!(idx.search_filtered(&[1.0, 0.0, 0.0], 2, 50, |_| false).is_empty());
+        let only_b = idx.search_filtered(&[1.0, 0.0, 0.0], 2, 50, |id| id == "b");
+        assert_eq!(only_b.len(), 1);
+        assert_eq!(only_b[0].0, "b");
+    }
+
+    // ── Cached norms ───────────────────────────────────────────────
+
+    #[test]
+    fn cached_norm_distances_match_direct_euclidean() {
+        let mut idx = HNSWIndex::new(64, 8, 50, DistanceMetric::Euclidean, Some(2));
+        for i in 0..50 {
+            idx.insert(format!("v{}", i), make_vec(64, i as u64 * 3 + 1));
+        }
+        assert!(idx.uses_norm_identity());
+        let query = make_vec(64, 4242);
+        for i in 0..50 {
+            let id = format!("v{}", i);
+            let node = &idx.nodes[&id];
+            let direct = distance::euclidean_distance_squared(&node.vector, &query);
+            assert!((idx.distance_to(&id, &query) - direct).abs() < 1e-4);
+
+            let other = &idx.nodes["v0"];
+            let direct = distance::euclidean_distance_squared(&node.vector, &other.vector);
+            assert!((idx.distance_between(&id, "v0") - direct).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cached_norms_are_restored_after_deserialize() {
+        let mut idx = HNSWIndex::new(64, 8, 50, DistanceMetric::Euclidean, Some(2));
+        for i in 0..20 {
+            idx.insert(format!("v{}", i), make_vec(64, i as u64 + 100));
+        }
+        let json = serde_json::to_string(&idx).unwrap();
+        assert!(!json.contains("norm_sq"));
+        let restored: HNSWIndex = serde_json::from_str(&json).unwrap();
+        for node in restored.nodes.values() {
+            assert!((node.norm_sq - distance::squared_norm(&node.vector)).abs() < 1e-6);
+        }
+
+        let query = make_vec(64, 9);
+        assert_eq!(idx.search(&query, 5, 50), restored.search(&query, 5, 50));
+    }
+
+    #[test]
+    fn cached_norms_toggle_keeps_results() {
+        let mut idx = HNSWIndex::new(64, 8, 50, DistanceMetric::Euclidean, Some(6));
+        for i in 0..100 {
+            idx.insert(format!("v{}", i), make_vec(64, i as u64 * 11));
+        }
+        let query = make_vec(64, 31337);
+        let with_norms: Vec<String> = idx.search(&query, 5, 100).into_iter().map(|(id, _)| id).collect();
+        idx.set_cached_norms(false);
+        assert!(!idx.uses_norm_identity());
+        let direct: Vec<String> = idx.search(&query, 5, 100).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(with_norms, direct);
+    }
+
+    // ── Exact ranking of ids ───────────────────────────────────────
+
+    #[test]
+    fn rank_ids_orders_subset_and_skips_unknown() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![0.0, 0.0]);
+        idx.insert("b".into(), vec![3.0, 4.0]);
+        idx.insert("c".into(), vec![1.0, 0.0]);
+        idx.insert("d".into(), vec![0.5, 0.0]);
+
+        let ranked = idx.rank_ids(&[0.0, 0.0], &["b", "c", "missing", "a"], 2);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, "a");
+        assert_eq!(ranked[1].0, "c");
+        assert!((ranked[1].1 - 1.0).abs() < 1e-6);
+
+        let all = idx.rank_ids(&[0.0, 0.0], &["b", "c"], 10);
+        assert!((all[1].1 - 5.0).abs() < 1e-6);
+
+        let repeated = idx.rank_ids(&[0.0, 0.0], &["c", "a", "c", "a", "b"], 3);
+        let ids: Vec<&str> = repeated.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["a", "c", "b"]);
+    }
+
+    // ── Mutual nearest neighbors ───────────────────────────────────
+
+    #[test]
+    fn mutual_nn_finds_shared_record_across_indexes() {
+        let mut left = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        let mut right = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        left.insert("l_dup".into(), vec![5.0, 5.0]);
+        left.insert("l_a".into(), vec![0.0, 0.0]);
+        left.insert("l_b".into(), vec![0.5, 0.0]);
+        right.insert("r_dup".into(), vec![5.01, 5.0]);
+        right.insert("r_far".into(), vec![-20.0, 0.0]);
+
+        let pairs = left.mutual_nearest_neighbors(&right, 50);
+        let dup = pairs.iter().find(|p| p.a == "l_dup").expect("duplicate pair missing");
+        assert_eq!(dup.b, "r_dup");
+        assert!((dup.distance - 0.01).abs() < 1e-4);
+
+        // l_a's nearest on the right is r_dup, whose nearest on the left is l_dup
+        assert!(pairs.iter().all(|p| p.a != "l_a" && p.a != "l_b"));
+
+        // Mutual pairs are symmetric
+        let reverse = right.mutual_nearest_neighbors(&left, 50);
+        assert!(reverse.iter().any(|p| p.a == "r_dup" && p.b == "l_dup"));
+    }
+
+    #[test]
+    fn mutual_nn_against_empty_index_is_empty() {
+        let mut left = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        left.insert("a".into(), vec![1.0, 1.0]);
+        let right = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        assert!(left.mutual_nearest_neighbors(&right, 50).is_empty());
+    }
+
+    // ── Recall estimate ────────────────────────────────────────────
+
+    #[test]
+    fn estimate_recall_is_perfect_on_tiny_index() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, Some(1));
+        for i in 0..8 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64));
+        }
+        let queries: Vec<Vec<f32>> = (0..5).map(|i| make_vec(3, 100 + i)).collect();
+        // ef covers the whole index, so HNSW and brute force must agree
+        assert_eq!(idx.estimate_recall(&queries, 3, 50), 1.0);
+        // k larger than the index compares against all 8 nodes
+        assert_eq!(idx.estimate_recall(&queries, 20, 50), 1.0);
+    }
+
+    #[test]
+    fn estimate_recall_edge_cases() {
+        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        assert_eq!(idx.estimate_recall(&[vec![1.0, 0.0, 0.0]], 5, 50), 1.0);
+        assert_eq!(idx.estimate_recall(&[], 5, 50), 1.0);
+    }
+
+    // ── Paged search ───────────────────────────────────────────────
+
+    #[test]
+    fn search_pages_are_disjoint_and_match_full_search() {
+        let mut idx = HNSWIndex::new(4, 16, 200, DistanceMetric::Euclidean, Some(8));
+        for i in 0..300 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 * 17 + 3));
+        }
+        let query = make_vec(4, 9999);
+        let ef = 100;
+
+        let first = idx.search_page(&query, 0, 10, ef);
+        let second = idx.search_page(&query, 10, 10, ef);
+        assert_eq!(first.len(), 10);
+        assert_eq!(second.len(), 10);
+        assert!(first.iter().all(|(id, _)| second.iter().all(|(other, _)| other != id)));
+        assert!(first.last().unwrap().1 <= second[0].1);
+
+        let full = idx.search(&query, 20, ef);
+        let paged: Vec<(String, f32)> = first.into_iter().chain(second).collect();
+        assert_eq!(paged, full);
+    }
+
+    #[test]
+    fn search_page_past_end_is_empty() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![0.0, 0.0]);
+        idx.insert("b".into(), vec![1.0, 0.0]);
+        assert_eq!(idx.search_page(&[0.0, 0.0], 1, 5, 50).len(), 1);
+        assert!(idx.search_page(&[0.0, 0.0], 2, 5, 50).is_empty());
+        // An unbounded limit doesn't overflow the window end
+        assert_eq!(idx.search_page(&[0.0, 0.0], 1, usize::MAX, 50).len(), 1);
+    }
+
+    // ── Vector block split / restore ───────────────────────────────
+
+    #[test]
+    fn split_and_restore_vectors_roundtrip() {
+        let mut idx = HNSWIndex::new(4, 8, 50, DistanceMetric::Euclidean, Some(3));
+        for i in 0..30 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 1));
         }
+        let (mut graph, block) = idx.split_vectors();
+        assert_eq!(block.len(), 30 * 4);
+        assert!(graph.nodes.values().all(|n| n.vector.is_empty()));
+        assert_eq!(&block[..4], idx.nodes["v0"].vector.as_slice());
 
-        while let Some(curr) = candidates.pop() {
-            // curr is the closest unexplored candidate
-            let furthest_dist = nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+        assert!(graph.restore_vectors(&block[1..]).is_err());
+        graph.restore_vectors(&block).unwrap();
+        let query = make_vec(4, 77);
+        assert_eq!(graph.search(&query, 5, 50), idx.search(&query, 5, 50));
+        assert_eq!(serde_json::to_string(&graph).unwrap(), serde_json::to_string(&idx).unwrap());
+    }
 
-            if curr.distance > furthest_dist {
-                break;
-            }
+    #[test]
+    fn dropped_vectors_keep_the_graph_and_restore_in_place() {
+        let mut idx = HNSWIndex::new(4, 8, 50, DistanceMetric::Euclidean, Some(3));
+        for i in 0..30 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 1));
+        }
+        let links_before = serde_json::to_value(&idx).unwrap()["nodes"]["v7"]["connections"].clone();
+        let query = make_vec(4, 77);
+        let expected = idx.search(&query, 5, 50);
 
-            if let Some(node) = self.nodes.get(&curr.id) {
-                if layer < node.connections.len() {
-                    for neighbor_id in &node.connections[layer] {
-                        if visited.insert(neighbor_id.clone()) {
-                            let dist = self.distance_to(neighbor_id, query);
-                            let furthest =
-                                nearest.peek().map(|h| h.distance).unwrap_or(f32::INFINITY);
+        assert_eq!(idx.drop_vectors(), 30 * 4 * 4);
+        assert!(idx.vectors_dropped());
+        assert!(idx.get_vector("v7").is_none());
+        assert!(idx.vectors_by_id().is_empty());
+        assert_eq!(idx.node_count(), 30);
+        idx.check_dimensions().unwrap();
 
-                            if dist < furthest || nearest.len() < ef {
-                                candidates.push(MinDistElement {
-                                    id: neighbor_id.clone(),
-                                    distance: dist,
-                                });
-                                nearest.push(MaxDistElement {
-                                    id: neighbor_id.clone(),
-                                    distance: dist,
-                                });
+        // The dropped state survives both snapshot formats
+        let json: HNSWIndex = serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        json.check_dimensions().unwrap();
+        assert!(!json.matches_dropped("v7", &make_vec(4, 99)));
+        assert!(json.matches_dropped("v7", &make_vec(4, 8)));
+        let (mut graph, block) = idx.split_vectors();
+        assert!(block.is_empty());
+        graph.restore_vectors(&block).unwrap();
+        assert!(graph.is_dropped("v7"));
 
-                                if nearest.len() > ef {
-                                    nearest.pop(); // removes the farthest element
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        // Graph work needs the vectors, and only the dropped ones fit the graph
+        assert!(idx.rebuild().is_err());
+        assert!(idx.reprune().is_err());
+        assert!(idx.reconnect(&["v7"]).is_err());
+        assert!(idx.reindex_with_metric(DistanceMetric::Manhattan).is_err());
+        assert_eq!((idx.metric, idx.node_count()), (DistanceMetric::Euclidean, 30));
+        assert!(!idx.matches_dropped("v7", &make_vec(4, 99)));
+        assert!(idx.restore_vector("v7", make_vec(4, 99)).is_err());
+        assert!(idx.is_dropped("v7"));
+
+        assert_eq!(idx.restore_vector("missing", vec![0.0; 4]), Ok(false));
+        for i in 0..30 {
+            assert_eq!(idx.restore_vector(&format!("v{}", i), make_vec(4, i as u64 + 1)), Ok(true));
         }
+        assert!(!idx.vectors_dropped());
+        assert_eq!(serde_json::to_value(&idx).unwrap()["nodes"]["v7"]["connections"], links_before);
+        assert_eq!(idx.search(&query, 5, 50), expected);
+    }
 
-        // into_sorted_vec() returns ascending order = nearest first
-        nearest
-            .into_sorted_vec()
-            .into_iter()
-            .map(|h| (h.id, h.distance))
-            .collect()
+    // ── Seed cache ─────────────────────────────────────────────────
+
+    #[test]
+    fn seed_cache_matches_uncached_results_and_skips_descent() {
+        let data = clustered_dataset();
+        let mut idx = HNSWIndex::new(data[0].len(), 16, 200, DistanceMetric::Euclidean, Some(12));
+        for (i, v) in data.iter().enumerate() {
+            idx.insert(format!("v{}", i), v.clone());
+        }
+        assert!(idx.max_layer > 0);
+
+        // Repeated nearby queries around a few stored points
+        let queries: Vec<Vec<f32>> = (0..40)
+            .map(|i| {
+                let base = &data[(i % 4) * 37];
+                base.iter().map(|x| x + 0.001 * (i / 4) as f32).collect()
+            })
+            .collect();
+        let uncached: Vec<_> = queries.iter().map(|q| idx.search(q, 10, 100)).collect();
+
+        idx.enable_seed_cache(1.0);
+        let cached: Vec<_> = queries.iter().map(|q| idx.search(q, 10, 100)).collect();
+        assert_eq!(cached, uncached);
+
+        let stats = idx.seed_cache_stats().unwrap();
+        assert_eq!(stats.hits + stats.misses, 40);
+        assert!(stats.hits >= 30, "only {} cache hits", stats.hits);
+    }
+
+    #[test]
+    fn seed_cache_is_invalidated_on_mutation() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, Some(1));
+        for i in 0..20 {
+            idx.insert(format!("v{}", i), make_vec(2, i as u64));
+        }
+        idx.enable_seed_cache(0.5);
+        idx.search(&[0.1, 0.1], 3, 50);
+        assert_eq!(idx.seed_cache_stats().unwrap().entries, 1);
+
+        idx.insert("new".into(), vec![0.1, 0.1]);
+        assert_eq!(idx.seed_cache_stats().unwrap().entries, 0);
+        assert_eq!(idx.search(&[0.1, 0.1], 1, 50)[0].0, "new");
+
+        idx.delete("new");
+        assert_eq!(idx.seed_cache_stats().unwrap().entries, 0);
+        assert_ne!(idx.search(&[0.1, 0.1], 1, 50)[0].0, "new");
+
+        idx.disable_seed_cache();
+        assert!(idx.seed_cache_stats().is_none());
     }
 
-    /// Select best neighbors using heuristic
-    fn select_neighbors(&self, _query: &[f32], candidates: Vec<String>, m: usize) -> HashSet<String> {
-        candidates.into_iter().take(m).collect()
+    // ── Int8 quantization ──────────────────────────────────────────
+
+    /// Fraction of full-precision exact top-k found by `idx.search`
+    fn recall_against(idx: &HNSWIndex, truth_idx: &HNSWIndex, queries: &[Vec<f32>], k: usize) -> f32 {
+        let mut found = 0;
+        for q in queries {
+            let truth: HashSet<String> = brute_force_knn(truth_idx, q, k).into_iter().collect();
+            found += idx.search(q, k, 100).iter().filter(|(id, _)| truth.contains(id)).count();
+        }
+        found as f32 / (queries.len() * k) as f32
     }
 
-    /// Prune connections for a node
-    fn prune_connections(&self, node_id: &str, layer: usize, max_conn: usize) -> HashSet<String> {
-        if let Some(node) = self.nodes.get(node_id) {
-            let mut neighbors: Vec<_> = node.connections[layer]
+    #[test]
+    fn int8_recall_stays_within_tolerance_of_full_precision() {
+        const TOLERANCE: f32 = 0.05;
+        let dims = 32;
+        let mut full = HNSWIndex::new(dims, 16, 200, DistanceMetric::Euclidean, Some(21));
+        let mut int8 = HNSWIndex::new(dims, 16, 200, DistanceMetric::Euclidean, Some(21));
+        int8.set_quantization(Quantization::Int8).unwrap();
+        for i in 0..600 {
+            let v = make_vec(dims, i as u64 * 101 + 7);
+            full.insert(format!("v{}", i), v.clone());
+            int8.insert(format!("v{}", i), v);
+        }
+        let queries: Vec<Vec<f32>> = (0..40).map(|i| make_vec(dims, 50_000 + i)).collect();
+
+        let full_recall = recall_against(&full, &full, &queries, 10);
+        let int8_recall = recall_against(&int8, &full, &queries, 10);
+        assert!(
+            int8_recall >= full_recall - TOLERANCE,
+            "int8 recall {} vs full {}",
+            int8_recall,
+            full_recall
+        );
+    }
+
+    #[test]
+    fn f16_recall_matches_full_precision_within_tolerance() {
+        const TOLERANCE: f32 = 0.02;
+        // Embedding-like data: unit-normalized 64-d vectors around 20 topics
+        let dims = 64;
+        let centers: Vec<Vec<f32>> = (0..20).map(|c| make_vec(dims, c * 7_919 + 3)).collect();
+        let embed = |seed: u64| {
+            let noise = make_vec(dims, seed);
+            let center = &centers[(seed % 20) as usize];
+            let v: Vec<f32> = center.iter().zip(&noise).map(|(c, n)| c - 0.5 + 0.3 * (n - 0.5)).collect();
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            v.into_iter().map(|x| x / norm).collect::<Vec<f32>>()
+        };
+        let mut full = HNSWIndex::new(dims, 16, 200, DistanceMetric::Cosine, Some(23));
+        let mut half = HNSWIndex::new(dims, 16, 200, DistanceMetric::Cosine, Some(23));
+        half.set_quantization(Quantization::F16).unwrap();
+        for i in 0..800 {
+            let v = embed(i * 31 + 1);
+            full.insert(format!("v{}", i), v.clone());
+            half.insert(format!("v{}", i), v);
+        }
+        let queries: Vec<Vec<f32>> = (0..40).map(|i| embed(70_001 + i * 13)).collect();
+
+        let full_recall = recall_against(&full, &full, &queries, 10);
+        let half_recall = recall_against(&half, &full, &queries, 10);
+        assert!(
+            half_recall >= full_recall - TOLERANCE,
+            "f16 recall {} vs full {}",
+            half_recall,
+            full_recall
+        );
+
+        let stored = half.get_vector("v0").unwrap().into_owned();
+        for (a, b) in stored.iter().zip(full.get_vector("v0").unwrap().iter()) {
+            assert!((a - b).abs() < 1e-3);
+        }
+        let back: HNSWIndex = serde_json::from_str(&serde_json::to_string(&half).unwrap()).unwrap();
+        assert_eq!(back.quantization(), Quantization::F16);
+        assert_eq!(back.get_vector("v0").unwrap(), stored);
+
+        let mut too_big = vec![0.0; dims];
+        too_big[0] = 70_000.0;
+        assert!(half.validate_vector(&too_big).is_err());
+    }
+
+    #[test]
+    fn int8_stores_codes_and_reconstructs_vectors() {
+        let mut idx = HNSWIndex::new(4, 16, 200, DistanceMetric::Cosine, None);
+        idx.set_quantization(Quantization::Int8).unwrap();
+        idx.insert("a".into(), vec![0.1, -0.5, 0.9, 0.3]);
+        assert!(idx.nodes["a"].vector.is_empty());
+        assert!(idx.nodes["a"].quantized.is_some());
+        assert!(idx.set_quantization(Quantization::None).is_err());
+
+        let restored = idx.get_vector("a").unwrap().into_owned();
+        for (a, b) in restored.iter().zip([0.1, -0.5, 0.9, 0.3]) {
+            assert!((a - b).abs() < 0.01);
+        }
+
+        // Quantized nodes survive JSON and the binary vector split
+        let json = serde_json::to_string(&idx).unwrap();
+        let back: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.quantization(), Quantization::Int8);
+        let (mut graph, block) = back.split_vectors();
+        assert!(block.is_empty());
+        graph.restore_vectors(&block).unwrap();
+        assert_eq!(graph.get_vector("a").unwrap(), restored);
+
+        assert_eq!(idx.remove("a").unwrap(), restored);
+    }
+
+    #[test]
+    fn navigation_importance_ranks_entry_point_and_hubs_above_leaves() {
+        let mut idx = HNSWIndex::new(8, 8, 100, DistanceMetric::Euclidean, Some(17));
+        for i in 0..400 {
+            idx.insert(format!("v{}", i), make_vec(8, i as u64 * 13 + 5));
+        }
+        let scores = idx.navigation_importance(100, 20);
+        assert_eq!(scores.len(), 400);
+        assert!(scores.windows(2).all(|w| w[0].score >= w[1].score));
+
+        // Every search starts by expanding the entry point
+        let entry = idx.entry_point.clone().unwrap();
+        let entry_score = scores.iter().find(|s| s.id == entry).unwrap().score;
+        assert_eq!(entry_score, 1.0);
+
+        let mean = |upper: bool| {
+            let group: Vec<f32> = scores
                 .iter()
-                .map(|id| {
-                    let dist = self.distance_between(node_id, id);
-                    (id.clone(), dist)
-                })
+                .filter(|s| (idx.nodes[&s.id].connections.len() > 1) == upper)
+                .map(|s| s.score)
                 .collect();
+            group.iter().sum::<f32>() / group.len() as f32
+        };
+        assert!(mean(true) > mean(false), "hubs {} vs leaves {}", mean(true), mean(false));
+    }
 
-            neighbors.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
-            neighbors.into_iter().take(max_conn).map(|(id, _)| id).collect()
-        } else {
-            HashSet::new()
+    #[test]
+    fn navigation_importance_on_empty_index() {
+        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        assert!(idx.navigation_importance(10, 10).is_empty());
+    }
+
+    // ── Radius search ──────────────────────────────────────────────
+
+    /// 10x10 grid of unit-spaced points named "x,y"
+    fn grid_index() -> HNSWIndex {
+        let mut idx = HNSWIndex::new(2, 8, 100, DistanceMetric::Euclidean, Some(3));
+        for x in 0..10 {
+            for y in 0..10 {
+                idx.insert(format!("{},{}", x, y), vec![x as f32, y as f32]);
+            }
         }
+        idx
     }
 
-    /// Calculate distance using the configured metric
-    fn compute_distance(&self, a: &[f32], b: &[f32]) -> f32 {
-        match self.metric {
-            DistanceMetric::Euclidean => distance::euclidean_distance_squared(a, b),
-            DistanceMetric::Cosine => distance::cosine_distance(a, b),
-            DistanceMetric::DotProduct => {
-                // For dot product, negate so that higher dot product = smaller "distance"
-                -distance::dot_product(a, b)
+    #[test]
+    fn search_radius_returns_exact_membership() {
+        let idx = grid_index();
+        let results = idx.search_radius(&[5.0, 5.0], 1.0, 10);
+        let ids: HashSet<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["5,5", "4,5", "6,5", "5,4", "5,6"].into_iter().collect());
+        assert_eq!(results[0], ("5,5".to_string(), 0.0));
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+
+        // sqrt(2) picks up the diagonals; a 3x3 block in total
+        let results = idx.search_radius(&[5.0, 5.0], 1.5, 10);
+        assert_eq!(results.len(), 9);
+        assert!(results.iter().all(|(_, d)| *d <= 1.5));
+    }
+
+    #[test]
+    fn search_radius_is_not_capped_by_ef() {
+        let idx = grid_index();
+        // Radius 4.5 around the center covers far more than ef = 5 points
+        let expected = brute_force_knn(&idx, &[4.5, 4.5], 100)
+            .into_iter()
+            .filter(|id| {
+                let v = idx.get_vector(id).unwrap();
+                ((v[0] - 4.5).powi(2) + (v[1] - 4.5).powi(2)).sqrt() <= 4.5
+            })
+            .collect::<HashSet<_>>();
+        let found: HashSet<String> = idx.search_radius(&[4.5, 4.5], 4.5, 5).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn search_radius_edge_cases() {
+        let empty = HNSWIndex::new(2, 8, 100, DistanceMetric::Euclidean, None);
+        assert!(empty.search_radius(&[0.0, 0.0], 10.0, 10).is_empty());
+
+        let idx = grid_index();
+        let exact = idx.search_radius(&[3.0, 7.0], 0.0, 10);
+        assert_eq!(exact, vec![("3,7".to_string(), 0.0)]);
+        assert!(idx.search_radius(&[3.5, 7.0], 0.0, 10).is_empty());
+        assert!(idx.search_radius(&[3.0, 7.0], -1.0, 10).is_empty());
+    }
+
+    #[test]
+    fn pair_distance_uses_final_units_and_rejects_unknown_ids() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![0.0, 0.0]);
+        idx.insert("b".into(), vec![3.0, 4.0]);
+        assert_eq!(idx.pair_distance("a", "b"), Some(5.0));
+        assert_eq!(idx.pair_distance("b", "a"), Some(5.0));
+        assert_eq!(idx.pair_distance("a", "a"), Some(0.0));
+        assert_eq!(idx.pair_distance("a", "missing"), None);
+    }
+
+    #[test]
+    fn pair_distance_matches_each_metric() {
+        let cases = [
+            (DistanceMetric::Euclidean, 5.0f32.sqrt()),
+            (DistanceMetric::Cosine, 1.0 - std::f32::consts::FRAC_1_SQRT_2),
+            (DistanceMetric::DotProduct, -5.0),
+            (DistanceMetric::Manhattan, 3.0),
+            (DistanceMetric::Angular, 0.25),
+            (DistanceMetric::Minkowski { p: 3.0 }, 9.0f32.cbrt()),
+            (DistanceMetric::Hamming, 1.0),
+            (DistanceMetric::Jaccard, 1.0 / 3.0),
+        ];
+        for (metric, expected) in cases {
+            let mut idx = HNSWIndex::new(3, 16, 200, metric, None);
+            idx.insert("a".into(), vec![1.0, 2.0, 0.0]);
+            idx.insert("b".into(), vec![3.0, 1.0, 0.0]);
+            idx.insert("c".into(), vec![1.0, 2.0, 5.0]);
+            let actual = if matches!(metric, DistanceMetric::Hamming | DistanceMetric::Jaccard) {
+                idx.pair_distance("a", "c").unwrap()
+            } else {
+                idx.pair_distance("a", "b").unwrap()
+            };
+            assert!((actual - expected).abs() < 1e-5, "{:?}: {} != {}", metric, actual, expected);
+            assert_eq!(idx.pair_distance("a", "missing"), None);
+        }
+    }
+
+    #[test]
+    fn rebuild_after_heavy_deletion_recovers_fresh_build_recall() {
+        let dims = 8;
+        let mut idx = HNSWIndex::new(dims, 8, 100, DistanceMetric::Euclidean, Some(29));
+        for i in 0..2000 {
+            idx.insert(format!("v{}", i), make_vec(dims, i as u64 * 31 + 3));
+        }
+        // Delete a pseudo-random half
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut ids = idx.all_ids();
+        ids.sort();
+        for id in &ids {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state & 1 == 0 {
+                idx.delete(id);
             }
         }
+        let survivors: Vec<(String, Vec<f32>)> = idx
+            .vectors_by_id()
+            .into_iter()
+            .map(|(id, v)| (id.to_string(), v.into_owned()))
+            .collect();
+
+        let mut fresh = HNSWIndex::new(dims, 8, 100, DistanceMetric::Euclidean, Some(29));
+        for (id, v) in &survivors {
+            fresh.insert(id.clone(), v.clone());
+        }
+
+        idx.rebuild().unwrap();
+        assert_eq!(idx.node_count(), survivors.len());
+        for (id, v) in &survivors {
+            assert_eq!(idx.get_vector(id).unwrap().as_ref(), v.as_slice());
+        }
+
+        let queries: Vec<Vec<f32>> = (0..50).map(|i| make_vec(dims, 70_000 + i)).collect();
+        let baseline = fresh.estimate_recall(&queries, 10, 20);
+        let rebuilt = idx.estimate_recall(&queries, 10, 20);
+        assert!(rebuilt >= baseline - 0.03, "rebuilt {} vs fresh {}", rebuilt, baseline);
     }
 
-    /// Calculate distance to a query vector
-    fn distance_to(&self, id: &str, query: &[f32]) -> f32 {
-        self.nodes
-            .get(id)
-            .map(|node| self.compute_distance(&node.vector, query))
-            .unwrap_or(f32::INFINITY)
+    #[test]
+    fn repair_on_delete_keeps_layer0_connected_and_recall_up() {
+        let dims = 8;
+        let build = |repair: bool| {
+            let mut idx = HNSWIndex::new(dims, 6, 60, DistanceMetric::Euclidean, Some(41));
+            idx.set_repair_on_delete(repair);
+            for i in 0..1500 {
+                idx.insert(format!("v{}", i), make_vec(dims, i as u64 * 31 + 3));
+            }
+            // Delete two thirds, leaving the survivors' links thinned out
+            for i in 0..1500 {
+                if i % 3 != 0 {
+                    idx.delete(&format!("v{}", i));
+                }
+            }
+            idx
+        };
+        let (plain, repaired) = (build(false), build(true));
+        assert_eq!(plain.node_count(), 500);
+        assert_eq!(repaired.node_count(), 500);
+        assert_eq!(
+            repaired.running_stats.as_ref().unwrap().layer0_edges,
+            repaired.scan_stats().layer0_edges
+        );
+
+        // Repair never pushes a node over its cap
+        for node in repaired.nodes.values() {
+            for (layer, links) in node.connections.iter().enumerate() {
+                assert!(links.len() <= repaired.layer_caps().at(None, layer));
+            }
+        }
+
+        let reachable = |idx: &HNSWIndex| {
+            let mut seen: HashSet<&str> = HashSet::new();
+            let mut stack: Vec<&str> = idx.entry_point.iter().map(String::as_str).collect();
+            while let Some(id) = stack.pop() {
+                // One-way links to deleted nodes can linger; skip them
+                let Some(node) = idx.nodes.get(id) else { continue };
+                if seen.insert(id) {
+                    stack.extend(node.connections[0].iter().map(String::as_str));
+                }
+            }
+            seen.len()
+        };
+        let (plain_degree, repaired_degree) =
+            (plain.stats().avg_layer0_connections, repaired.stats().avg_layer0_connections);
+        assert!(repaired_degree > plain_degree, "{} vs {}", repaired_degree, plain_degree);
+        assert!(reachable(&repaired) >= reachable(&plain));
+        assert_eq!(reachable(&repaired), 500);
+
+        let queries: Vec<Vec<f32>> = (0..50).map(|i| make_vec(dims, 80_000 + i)).collect();
+        let plain_recall = plain.estimate_recall(&queries, 10, 20);
+        let repaired_recall = repaired.estimate_recall(&queries, 10, 20);
+        assert!(
+            repaired_recall >= plain_recall,
+            "repaired {} vs plain {}",
+            repaired_recall,
+            plain_recall
+        );
     }
 
-    /// Calculate distance between two nodes
-    fn distance_between(&self, id1: &str, id2: &str) -> f32 {
-        match (self.nodes.get(id1), self.nodes.get(id2)) {
-            (Some(n1), Some(n2)) => self.compute_distance(&n1.vector, &n2.vector),
-            _ => f32::INFINITY,
+    #[test]
+    fn exact_threshold_is_opt_in() {
+        let mut idx = HNSWIndex::new(8, 4, 20, DistanceMetric::Euclidean, Some(17));
+        assert_eq!(idx.exact_threshold(), 0);
+        for i in 0..200 {
+            idx.insert(format!("v{}", i), make_vec(8, i as u64));
         }
+        let query = make_vec(8, 50_000);
+
+        idx.set_exact_threshold(1000);
+        assert_eq!(idx.search(&query, 10, 10), idx.search_exact(&query, 10));
+
+        let restored: HNSWIndex = serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        assert_eq!(restored.exact_threshold(), 1000);
     }
 
-    /// Random layer assignment (exponential decay)
-    fn random_layer(&self) -> usize {
-        let mut buf = [0u8; 4];
-        getrandom::getrandom(&mut buf).unwrap_or_default();
-        let random_val = f32::from_bits(u32::from_le_bytes(buf)).abs() / f32::MAX;
-        // Clamp to avoid ln(0) = -inf
-        let clamped = random_val.max(f32::MIN_POSITIVE);
-        let layer = (-clamped.ln() * self.ml) as usize;
-        layer.min(16)
+    #[test]
+    fn normalized_cosine_survives_serialization_with_identical_distances() {
+        let dims = 16;
+        let mut idx = HNSWIndex::new(dims, 8, 100, DistanceMetric::Cosine, Some(41));
+        for i in 0..150 {
+            // Raw vectors of varying length
+            let scale = 1.0 + (i % 7) as f32;
+            let v: Vec<f32> = make_vec(dims, i as u64 * 13 + 5).iter().map(|x| x * scale).collect();
+            idx.insert(format!("v{}", i), v);
+        }
+        let query: Vec<f32> = make_vec(dims, 99_001).iter().map(|x| x * 3.0).collect();
+        let raw = idx.search_exact(&query, 10);
+
+        idx.set_normalized(true).unwrap();
+        let norm = distance::magnitude(&idx.get_vector("v3").unwrap());
+        assert!((norm - 1.0).abs() < 1e-5, "{}", norm);
+        // Normalizing the stored vectors leaves cosine distances in place
+        for ((raw_id, raw_d), (id, d)) in raw.iter().zip(idx.search_exact(&query, 10)) {
+            assert_eq!(raw_id, &id);
+            assert!((raw_d - d).abs() < 1e-5);
+        }
+
+        idx.insert("late".into(), vec![5.0; dims]);
+        assert!((distance::magnitude(&idx.get_vector("late").unwrap()) - 1.0).abs() < 1e-5);
+
+        let restored: HNSWIndex = serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        assert!(restored.normalized());
+        assert_eq!(restored.search(&query, 10, 50), idx.search(&query, 10, 50));
+        assert_eq!(restored.search_exact(&query, 10), idx.search_exact(&query, 10));
+
+        let mut euclidean = HNSWIndex::new(dims, 8, 100, DistanceMetric::Euclidean, Some(41));
+        assert!(euclidean.set_normalized(true).is_err());
+        idx.reindex_with_metric(DistanceMetric::Euclidean).unwrap();
+        assert!(!idx.normalized());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::hnsw::DistanceMetric;
-    use crate::vector::random_vector_seeded;
+    #[test]
+    fn rebuild_keeps_per_node_m_and_handles_empty_index() {
+        let mut empty = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        empty.rebuild().unwrap();
+        assert_eq!(empty.node_count(), 0);
+        assert!(empty.entry_point.is_none());
 
-    /// Helper: create a deterministic vector from a seed
-    fn make_vec(dims: usize, seed: u64) -> Vec<f32> {
-        random_vector_seeded(dims, seed)
+        let mut idx = HNSWIndex::new(2, 4, 200, DistanceMetric::Euclidean, Some(2));
+        idx.insert_with_m("hub".into(), vec![0.0, 0.0], Some(12));
+        idx.insert("a".into(), vec![1.0, 0.0]);
+        idx.rebuild().unwrap();
+        assert_eq!(idx.nodes["hub"].max_m, Some(12));
+        assert_eq!(idx.search(&[0.9, 0.0], 1, 10)[0].0, "a");
     }
 
-    // ── Construction & basics ──────────────────────────────────────
+    #[test]
+    fn auto_metric_picks_dot_product_for_unit_vectors() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        idx.set_auto_metric(true).unwrap();
+        assert!(idx.auto_metric_pending());
+
+        let angles = [0.0f32, 0.3, 1.2, 2.5, 4.0];
+        let vectors: Vec<Vec<f32>> = angles.iter().map(|a| vec![a.cos(), a.sin()]).collect();
+        let sample: Vec<&[f32]> = vectors.iter().map(|v| v.as_slice()).collect();
+        idx.resolve_auto_metric(&sample);
+        assert_eq!(idx.metric, DistanceMetric::DotProduct);
+        assert!(!idx.auto_metric_pending());
+        assert!(idx.set_auto_metric(true).is_ok());
+        idx.set_auto_metric(false).unwrap();
+
+        for (i, v) in vectors.iter().enumerate() {
+            idx.insert(format!("a{}", angles[i]), v.clone());
+        }
+        // Nearest by angle to 0.35 rad: 0.3, then 0.0, then 1.2
+        let q = [0.35f32.cos(), 0.35f32.sin()];
+        let ids: Vec<String> = idx.search(&q, 3, 10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["a0.3", "a0", "a1.2"]);
+    }
 
     #[test]
-    fn new_creates_empty_index() {
-        let idx = HNSWIndex::new(128, 16, 200, DistanceMetric::Euclidean);
-        assert_eq!(idx.dimensions, 128);
-        assert_eq!(idx.m, 16);
-        assert_eq!(idx.ef_construction, 200);
+    fn auto_metric_falls_back_to_euclidean_and_resolves_on_insert() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Cosine, None);
+        idx.set_auto_metric(true).unwrap();
+        idx.insert("a".into(), vec![3.0, 4.0]);
         assert_eq!(idx.metric, DistanceMetric::Euclidean);
-        assert!(idx.entry_point.is_none());
-        assert_eq!(idx.nodes.len(), 0);
+        assert!(!idx.auto_metric_pending());
+        assert!(idx.set_auto_metric(true).is_err());
+
+        let json = serde_json::to_string(&idx).unwrap();
+        let back: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.metric, DistanceMetric::Euclidean);
+        assert!(!back.auto_metric_pending());
+    }
+
+    #[test]
+    fn stats_report_counts_matching_inserts() {
+        let mut idx = HNSWIndex::new(4, 8, 100, DistanceMetric::Cosine, Some(9));
+        for i in 0..200 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 1));
+        }
+        idx.delete("v0");
+
+        let stats = idx.stats();
+        assert_eq!(stats.node_count, 199);
+        assert_eq!(stats.dimensions, 4);
+        assert_eq!(stats.metric, "cosine");
+        assert_eq!((stats.m, stats.ef_construction), (8, 100));
+        assert_eq!(stats.max_layer, idx.max_layer);
+        assert_eq!(stats.nodes_per_layer.len(), idx.max_layer + 1);
+        assert_eq!(stats.nodes_per_layer[0], 199);
+        assert!(stats.nodes_per_layer.windows(2).all(|w| w[0] >= w[1]));
+
+        // Each layer holds every node whose top layer is at or above it
+        let histogram = idx.top_layer_histogram();
+        for (layer, count) in stats.nodes_per_layer.iter().enumerate() {
+            assert_eq!(*count, histogram.iter().skip(layer).sum::<usize>());
+        }
+        assert!(stats.avg_layer0_connections > 1.0);
+        assert!(stats.avg_layer0_connections <= 16.0);
+    }
+
+    #[test]
+    fn stats_on_empty_index() {
+        let stats = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None).stats();
+        assert_eq!(stats.node_count, 0);
+        assert!(stats.nodes_per_layer.is_empty());
+        assert_eq!(stats.avg_layer0_connections, 0.0);
+    }
+
+    /// Drop every link to and from `id`, leaving it unreachable
+    fn strip_links(idx: &mut HNSWIndex, id: &str) {
+        let layers = idx.nodes[id].connections.len();
+        idx.nodes.get_mut(id).unwrap().connections = vec![HashSet::new(); layers];
+        for node in idx.nodes.values_mut() {
+            for layer in node.connections.iter_mut() {
+                layer.remove(id);
+            }
+        }
+    }
+
+    #[test]
+    fn reconnect_restores_a_stripped_node() {
+        let mut idx = HNSWIndex::new(4, 8, 100, DistanceMetric::Euclidean, Some(12));
+        for i in 0..300 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 * 3 + 1));
+        }
+        let target = (0..300)
+            .map(|i| format!("v{}", i))
+            .find(|id| Some(id) != idx.entry_point.as_ref())
+            .unwrap();
+        let query = idx.get_vector(&target).unwrap().into_owned();
+        strip_links(&mut idx, &target);
+        assert!(idx.search(&query, 5, 50).iter().all(|(id, _)| *id != target));
+
+        assert_eq!(idx.reconnect(&[target.as_str(), "missing"]), Ok(1));
+        assert!(!idx.nodes[&target].connections[0].is_empty());
+        // Linked both ways, so searches can reach it again
+        let neighbor = idx.nodes[&target].connections[0].iter().next().unwrap().clone();
+        assert!(idx.nodes[&neighbor].connections[0].contains(&target));
+        assert_eq!(idx.search(&query, 1, 50)[0].0, target);
+    }
+
+    #[test]
+    fn reconnect_entry_point_with_no_links() {
+        let mut idx = HNSWIndex::new(2, 8, 100, DistanceMetric::Euclidean, Some(5));
+        for i in 0..50 {
+            idx.insert(format!("v{}", i), vec![i as f32, (i % 7) as f32]);
+        }
+        let entry = idx.entry_point.clone().unwrap();
+        strip_links(&mut idx, &entry);
+        assert_eq!(idx.reconnect(&[entry.as_str()]), Ok(1));
+        assert!(!idx.nodes[&entry].connections[0].is_empty());
+        assert_eq!(idx.search(&[20.1, 6.0], 1, 50)[0].0, "v20");
+    }
+
+    #[test]
+    fn medoid_is_the_central_vector() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        assert_eq!(idx.medoid(), None);
+        idx.insert("center".into(), vec![0.1, -0.1]);
+        for (i, (x, y)) in [(5.0, 0.0), (-5.0, 0.0), (0.0, 5.0), (0.0, -5.0)].into_iter().enumerate() {
+            idx.insert(format!("edge{}", i), vec![x, y]);
+        }
+        let (id, distance) = idx.medoid().unwrap();
+        assert_eq!(id, "center");
+        // Centroid is (0.02, -0.02); distance measured in final (sqrt) units
+        assert!((distance - (0.08f32 * 0.08 * 2.0).sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn check_dimensions_rejects_hand_edited_vector() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        assert!(idx.check_dimensions().is_ok());
+
+        let json = serde_json::to_string(&idx)
+            .unwrap()
+            .replace("[0.0,1.0,0.0]", "[0.0,1.0]");
+        let corrupt: HNSWIndex = serde_json::from_str(&json).unwrap();
+        let err = corrupt.check_dimensions().unwrap_err();
+        assert!(err.contains("'b' has 2 dimensions"), "{}", err);
+    }
+
+    #[test]
+    fn check_dimensions_reports_corrupt_declared_dimensions() {
+        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean, None);
+        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
+        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
+        let json = serde_json::to_string(&idx)
+            .unwrap()
+            .replace("\"dimensions\":3", "\"dimensions\":4");
+        let corrupt: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            corrupt.check_dimensions().unwrap_err(),
+            "Declared dimensions (4) do not match the stored vectors, which all have 3"
+        );
+    }
+
+    #[test]
+    fn ids_pages_cover_every_id_exactly_once() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        for i in 0..100 {
+            idx.insert(format!("id{:03}", i), vec![i as f32, 0.0]);
+        }
+        let mut seen = Vec::new();
+        for page in 0..10 {
+            let (ids, total) = idx.ids_page(page * 10, 10);
+            assert_eq!(total, 100);
+            assert_eq!(ids.len(), 10);
+            seen.extend(ids);
+        }
+        let expected: Vec<String> = (0..100).map(|i| format!("id{:03}", i)).collect();
+        assert_eq!(seen, expected);
+
+        assert_eq!(idx.ids_page(95, 10).0.len(), 5);
+        assert!(idx.ids_page(100, 10).0.is_empty());
+    }
+
+    #[test]
+    fn running_stats_match_full_scan_through_upserts_and_deletes() {
+        let mut idx = HNSWIndex::new(4, 6, 60, DistanceMetric::Euclidean, Some(33));
+        for i in 0..300 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 * 5 + 2));
+        }
+        for i in (0..300).step_by(3) {
+            idx.delete(&format!("v{}", i));
+        }
+        // Upserts are a delete followed by an insert
+        for i in (1..300).step_by(7) {
+            let id = format!("v{}", i);
+            idx.delete(&id);
+            idx.insert(id, make_vec(4, i as u64 + 10_000));
+        }
+        idx.reconnect(&["v4", "v5"]).unwrap();
+
+        let incremental = idx.running_stats.clone().unwrap();
+        assert_eq!(incremental, idx.scan_stats());
+
+        // A deserialized index seeds its aggregates from a scan
+        let json = serde_json::to_string(&idx).unwrap();
+        let back: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.running_stats.clone().unwrap(), incremental);
+        assert_eq!(back.stats(), idx.stats());
+
+        idx.rebuild().unwrap();
+        assert_eq!(idx.running_stats.clone().unwrap(), idx.scan_stats());
+    }
+
+    #[test]
+    fn sample_ids_is_seeded_and_roughly_uniform() {
+        let mut idx = HNSWIndex::new(2, 8, 50, DistanceMetric::Euclidean, Some(3));
+        for i in 0..50 {
+            idx.insert(format!("v{:02}", i), vec![i as f32, 0.0]);
+        }
+        let before = serde_json::to_string(&idx).unwrap();
+
+        let sample = idx.sample_ids(10, Some(7));
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 10);
+        assert_eq!(idx.sample_ids(10, Some(7)), sample);
+        assert_ne!(idx.sample_ids(10, Some(8)), sample);
+        assert_eq!(serde_json::to_string(&idx).unwrap(), before);
+
+        let mut all = idx.sample_ids(80, Some(1));
+        all.sort();
+        assert_eq!(all, {
+            let mut ids = idx.all_ids();
+            ids.sort();
+            ids
+        });
+        assert!(idx.sample_ids(0, Some(1)).is_empty());
+        assert!(HNSWIndex::new(2, 8, 50, DistanceMetric::Euclidean, None).sample_ids(5, None).is_empty());
+
+        // Every id is picked about n / len of the time
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for seed in 0..2000 {
+            for id in idx.sample_ids(5, Some(seed)) {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+        assert_eq!(counts.len(), 50);
+        // Expected 200 picks each
+        assert!(counts.values().all(|&c| (140..=260).contains(&c)), "{:?}", counts);
     }
 
     #[test]
-    fn first_insert_sets_entry_point() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        assert_eq!(idx.entry_point, Some("a".into()));
-        assert_eq!(idx.nodes.len(), 1);
+    fn loo_recall_is_high_and_leaves_index_untouched() {
+        let mut idx = HNSWIndex::new(8, 16, 200, DistanceMetric::Euclidean, Some(19));
+        for i in 0..400 {
+            idx.insert(format!("v{}", i), make_vec(8, i as u64 * 31 + 7));
+        }
+        let before = serde_json::to_string(&idx).unwrap();
+
+        let recall = idx.loo_recall(10, 100, 60);
+        assert!(recall > 0.9, "leave-one-out recall {}", recall);
+        assert_eq!(idx.loo_recall(10, 100, 60), recall);
+        assert_eq!(serde_json::to_string(&idx).unwrap(), before);
+
+        let mut single = HNSWIndex::new(2, 4, 20, DistanceMetric::Euclidean, Some(1));
+        single.insert("only".into(), vec![1.0, 1.0]);
+        assert_eq!(single.loo_recall(5, 20, 5), 1.0);
     }
 
     #[test]
-    fn size_tracking_after_insertions() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        for i in 0..10 {
-            idx.insert(format!("v{}", i), make_vec(3, i as u64));
+    fn default_ef_falls_back_to_ef_construction_and_persists() {
+        let mut idx = HNSWIndex::new(4, 8, 120, DistanceMetric::Euclidean, Some(2));
+        for i in 0..200 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 1));
         }
-        assert_eq!(idx.nodes.len(), 10);
-    }
+        let query = make_vec(4, 4242);
+        assert_eq!((idx.default_ef(), idx.ef_setting()), (120, None));
+        assert_eq!(idx.search_with_default_ef(&query, 5), idx.search(&query, 5, 120));
 
-    // ── Insert & search correctness ────────────────────────────────
+        assert!(idx.set_ef(0).is_err());
+        idx.set_ef(16).unwrap();
+        assert_eq!((idx.default_ef(), idx.ef_setting()), (16, Some(16)));
+        assert_eq!(idx.search_with_default_ef(&query, 5), idx.search(&query, 5, 16));
 
-    #[test]
-    fn insert_one_search_finds_it() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        let v = vec![1.0, 0.0, 0.0];
-        idx.insert("a".into(), v.clone());
-        let results = idx.search(&v, 1, 50);
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0, "a");
-        assert!(results[0].1 < 1e-6); // distance ~0
+        let back: HNSWIndex = serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        assert_eq!(back.default_ef(), 16);
     }
 
     #[test]
-    fn insert_two_search_returns_correct_order() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        let close = vec![1.0, 0.0, 0.0];
-        let far = vec![10.0, 10.0, 10.0];
-        idx.insert("close".into(), close.clone());
-        idx.insert("far".into(), far);
+    fn clear_empties_the_graph_but_keeps_configuration() {
+        let mut idx = HNSWIndex::new(3, 8, 64, DistanceMetric::Cosine, Some(6));
+        idx.set_ef(32).unwrap();
+        for i in 0..50 {
+            idx.insert(format!("v{}", i), make_vec(3, i as u64 + 1));
+        }
+        idx.clear();
+        assert_eq!(idx.node_count(), 0);
+        assert!(idx.search(&[1.0, 0.0, 0.0], 5, 50).is_empty());
+        assert_eq!(idx.stats().nodes_per_layer, Vec::<usize>::new());
+        assert_eq!((idx.dimensions, idx.metric, idx.default_ef()), (3, DistanceMetric::Cosine, 32));
 
-        let results = idx.search(&close, 2, 50);
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].0, "close");
-        assert!(results[0].1 < results[1].1);
+        idx.insert("again".into(), vec![1.0, 0.0, 0.0]);
+        assert_eq!(idx.search(&[1.0, 0.1, 0.0], 1, 50)[0].0, "again");
+        assert_eq!(idx.stats().max_layer, idx.max_layer);
     }
 
     #[test]
-    fn search_returns_k_sorted_by_distance() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        for i in 0..15 {
-            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 7 + 42));
+    fn search_falls_back_when_churn_strands_nodes() {
+        let mut idx = HNSWIndex::new(2, 4, 16, DistanceMetric::Euclidean, Some(21));
+        for i in 0..120 {
+            idx.insert(format!("v{}", i), vec![(i % 12) as f32, (i / 12) as f32]);
         }
-        let query = make_vec(3, 999);
-        let results = idx.search(&query, 5, 50);
-        assert_eq!(results.len(), 5);
-        // Verify sorted by distance
-        for w in results.windows(2) {
-            assert!(w[0].1 <= w[1].1);
+        for i in (0..120).filter(|i| i % 3 != 0) {
+            idx.delete(&format!("v{}", i));
+        }
+        // Churn damage: a few survivors lose every link
+        let entry = idx.entry_point.clone().unwrap();
+        let mut survivors = idx.all_ids();
+        survivors.sort();
+        let stranded: Vec<String> = survivors.into_iter().filter(|id| *id != entry).take(5).collect();
+        for id in &stranded {
+            strip_links(&mut idx, id);
         }
-    }
 
-    #[test]
-    fn search_k_greater_than_size_returns_all() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
-        let results = idx.search(&[0.5, 0.5, 0.0], 100, 200);
-        assert_eq!(results.len(), 2);
+        let n = idx.node_count();
+        let query = [5.0, 5.0];
+        // Plain traversal can no longer reach the stranded nodes
+        assert!(idx.search_filtered(&query, n, n, |_| true).len() < n);
+
+        let results = idx.search(&query, n, 10);
+        assert_eq!(results.len(), n);
+        assert!(stranded.iter().all(|id| results.iter().any(|(r, _)| r == id)));
+        let exact: Vec<String> = idx.exact_knn(&query, 5).into_iter().map(|(id, _)| id).collect();
+        let top: Vec<String> = results.iter().take(5).map(|(id, _)| id.clone()).collect();
+        assert_eq!(top, exact);
     }
 
     #[test]
-    fn search_empty_index_returns_empty() {
-        let idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        let results = idx.search(&[1.0, 0.0, 0.0], 5, 50);
-        assert!(results.is_empty());
-    }
+    fn validate_reports_each_kind_of_corruption() {
+        let mut idx = HNSWIndex::new(3, 4, 32, DistanceMetric::Euclidean, Some(9));
+        for i in 0..40 {
+            idx.insert(format!("v{:02}", i), make_vec(3, i as u64 * 11 + 5));
+        }
+        assert_eq!(idx.validate(), Ok(()));
+        let json = serde_json::to_string(&idx).unwrap();
+        let load = || serde_json::from_str::<HNSWIndex>(&json).unwrap();
 
-    // ── Nearest-neighbor quality ───────────────────────────────────
+        let mut dangling = load();
+        dangling.nodes.get_mut("v03").unwrap().connections[0].insert("ghost".into());
+        assert_eq!(
+            dangling.validate(),
+            Err(vec!["Node 'v03' links to missing node 'ghost' at layer 0".to_string()])
+        );
 
-    #[test]
-    fn search_finds_true_nearest_neighbor() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        let target = vec![5.0, 5.0, 5.0];
-        let nearest = vec![5.1, 5.1, 5.1]; // very close to target
-        let far1 = vec![100.0, 0.0, 0.0];
-        let far2 = vec![0.0, 100.0, 0.0];
+        let mut orphan = load();
+        let entry = orphan.entry_point.clone().unwrap();
+        let target = if entry == "v07" { "v08" } else { "v07" };
+        strip_links(&mut orphan, target);
+        assert_eq!(
+            orphan.validate(),
+            Err(vec![format!("Node '{}' has no layer-0 connections", target)])
+        );
 
-        idx.insert("nearest".into(), nearest);
-        idx.insert("far1".into(), far1);
-        idx.insert("far2".into(), far2);
+        let mut bad_entry = load();
+        bad_entry.entry_point = Some("nowhere".into());
+        assert_eq!(
+            bad_entry.validate(),
+            Err(vec!["Entry point 'nowhere' is not in the index".to_string()])
+        );
+        bad_entry.entry_point = None;
+        assert_eq!(bad_entry.validate(), Err(vec!["Index has nodes but no entry point".to_string()]));
 
-        let results = idx.search(&target, 1, 50);
-        assert_eq!(results[0].0, "nearest");
+        assert_eq!(HNSWIndex::new(3, 4, 32, DistanceMetric::Euclidean, None).validate(), Ok(()));
     }
 
     #[test]
-    fn cluster_search_finds_cluster_before_outlier() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        // Cluster around origin
-        idx.insert("c0".into(), vec![0.1, 0.1, 0.1]);
-        idx.insert("c1".into(), vec![0.2, 0.0, 0.1]);
-        idx.insert("c2".into(), vec![0.0, 0.2, 0.1]);
-        // Outlier
-        idx.insert("outlier".into(), vec![50.0, 50.0, 50.0]);
+    fn validate_reciprocal_reports_one_way_links() {
+        // Caps far above the node count, so nothing is ever pruned
+        let mut idx = HNSWIndex::new(3, 16, 32, DistanceMetric::Euclidean, Some(4));
+        for i in 0..12 {
+            idx.insert(format!("v{:02}", i), make_vec(3, i as u64 * 7 + 1));
+        }
+        assert_eq!(idx.validate_reciprocal(), Ok(()));
 
-        let results = idx.search(&[0.0, 0.0, 0.0], 4, 50);
-        // All cluster members should come before outlier
-        let outlier_pos = results.iter().position(|(id, _)| id == "outlier").unwrap();
-        assert_eq!(outlier_pos, 3); // outlier is last
+        let peer = idx.nodes["v03"].connections[0].iter().min().unwrap().clone();
+        idx.nodes.get_mut(&peer).unwrap().connections[0].remove("v03");
+        assert_eq!(idx.validate(), Ok(()));
+        assert_eq!(
+            idx.validate_reciprocal(),
+            Err(vec![format!("Node 'v03' links to '{}' at layer 0, but not the other way", peer)])
+        );
     }
 
-    // ── Dimension validation ───────────────────────────────────────
-
     #[test]
-    fn insert_wrong_dimension_is_ignored() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("good".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("bad".into(), vec![1.0, 0.0]); // wrong dimensions
-        assert_eq!(idx.nodes.len(), 1);
-        assert!(!idx.nodes.contains_key("bad"));
+    fn remove_drops_one_way_links_to_the_deleted_node() {
+        let mut idx = HNSWIndex::new(4, 4, 32, DistanceMetric::Euclidean, Some(21));
+        for i in 0..600 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 9));
+        }
+        for i in (0..600).step_by(2) {
+            idx.remove(&format!("v{}", i));
+        }
+        // Heavy deletion can still strand a node, but never leaves links behind
+        let problems = idx.validate().err().unwrap_or_default();
+        assert!(problems.iter().all(|p| !p.contains("missing node")), "{:?}", problems);
+        assert!(idx.nodes.values().all(|n| n.connections.iter().flatten().all(|id| idx.nodes.contains_key(id))));
+        assert_eq!(
+            idx.running_stats.as_ref().unwrap().layer0_edges,
+            idx.scan_stats().layer0_edges
+        );
     }
 
-    // ── Delete ─────────────────────────────────────────────────────
-
     #[test]
-    fn delete_existing_returns_true() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        assert!(idx.delete("a"));
-        assert_eq!(idx.nodes.len(), 0);
+    fn max_layer_cap_bounds_every_node_and_persists() {
+        // m = 2 gives ml ≈ 1.44, so uncapped layers regularly exceed 2
+        let mut idx = HNSWIndex::new(2, 2, 20, DistanceMetric::Euclidean, Some(13));
+        idx.set_max_layer_cap(2).unwrap();
+        for i in 0..2000 {
+            idx.insert(format!("v{}", i), make_vec(2, i as u64 + 3));
+        }
+        assert!(idx.nodes.values().all(|n| n.connections.len() <= 3));
+        assert_eq!(idx.max_layer, 2);
+        assert!(idx.set_max_layer_cap(5).is_err());
+
+        let back: HNSWIndex = serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        assert_eq!(back.max_layer_cap(), 2);
+
+        let mut uncapped = HNSWIndex::new(2, 2, 20, DistanceMetric::Euclidean, Some(13));
+        assert_eq!(uncapped.max_layer_cap(), DEFAULT_MAX_LAYER_CAP);
+        assert!((0..2000).any(|_| uncapped.random_layer() > 2));
     }
 
     #[test]
-    fn delete_nonexistent_returns_false() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        assert!(!idx.delete("nope"));
+    fn deleting_the_entry_point_moves_it_to_a_top_layer_node() {
+        let mut idx = HNSWIndex::new(4, 4, 50, DistanceMetric::Euclidean, Some(17));
+        assert_eq!((idx.entry_point(), idx.max_layer()), (None, 0));
+        for i in 0..200 {
+            idx.insert(format!("v{}", i), make_vec(4, i as u64 + 5));
+        }
+        let entry = idx.entry_point().unwrap().to_string();
+        assert_eq!(idx.nodes[&entry].connections.len(), idx.max_layer() + 1);
+
+        idx.remove(&entry);
+        let next = idx.entry_point().unwrap();
+        assert_ne!(next, entry);
+        assert_eq!(idx.nodes[next].connections.len(), idx.max_layer() + 1);
     }
 
     #[test]
-    fn delete_entry_point_search_still_works() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
-        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
+    fn custom_m0_caps_layer_zero_degree_and_persists() {
+        let mut idx = HNSWIndex::new(8, 8, 100, DistanceMetric::Euclidean, Some(31));
+        assert_eq!(idx.m0(), 16);
+        assert!(idx.set_m0(1).is_err());
+        idx.set_m0(5).unwrap();
+        for i in 0..400 {
+            idx.insert(format!("v{}", i), make_vec(8, i as u64 + 11));
+        }
+        let layer0_max = idx.nodes.values().map(|n| n.connections[0].len()).max().unwrap();
+        assert_eq!(layer0_max, 5);
+        let upper_max = idx.nodes.values().flat_map(|n| n.connections.iter().skip(1)).map(HashSet::len).max();
+        assert!(upper_max.unwrap_or(0) <= 8);
+        assert!(idx.set_m0(6).is_err());
+        assert_eq!(idx.validate(), Ok(()));
 
-        let entry = idx.entry_point.clone().unwrap();
-        idx.delete(&entry);
+        let json = serde_json::to_string(&idx).unwrap();
+        let back: HNSWIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.m0(), 5);
 
-        // Search still works with remaining nodes
-        let results = idx.search(&[0.5, 0.5, 0.0], 2, 50);
-        assert!(!results.is_empty());
-        // Deleted entry should not appear
-        for (id, _) in &results {
-            assert_ne!(id, &entry);
-        }
+        // Snapshots from before m0 was stored keep the old 2 * m
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("m0");
+        let old: HNSWIndex = serde_json::from_value(value).unwrap();
+        assert_eq!(old.m0(), 16);
     }
 
     #[test]
-    fn delete_all_vectors_empties_index() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
-        idx.delete("a");
-        idx.delete("b");
-        assert_eq!(idx.nodes.len(), 0);
-        let results = idx.search(&[1.0, 0.0, 0.0], 5, 50);
-        assert!(results.is_empty());
+    fn zero_vectors_are_only_rejected_under_directional_metrics() {
+        let metrics = [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Cosine,
+            DistanceMetric::DotProduct,
+            DistanceMetric::Manhattan,
+            DistanceMetric::Angular,
+            DistanceMetric::Minkowski { p: 3.0 },
+            DistanceMetric::Hamming,
+        ];
+        for metric in metrics {
+            let directional = matches!(metric, DistanceMetric::Cosine | DistanceMetric::Angular);
+            let mut idx = HNSWIndex::new(3, 16, 200, metric, Some(1));
+            idx.try_insert("a".into(), vec![1.0, 0.0, 0.0]).unwrap();
+
+            // Inserting a zero vector is allowed unless the flag is set
+            assert!(idx.validate_vector(&[0.0; 3]).is_ok(), "{:?}", metric);
+            idx.set_reject_zero_vectors(true);
+            assert_eq!(idx.validate_vector(&[0.0; 3]).is_err(), directional, "{:?}", metric);
+            assert!(idx.validate_vector(&[0.0, 2.0, 0.0]).is_ok());
+
+            // A zero query is always an error when only direction matters
+            assert_eq!(idx.validate_query(&[0.0; 3]).is_err(), directional, "{:?}", metric);
+            assert!(idx.validate_query(&[0.0, 0.5, 0.0]).is_ok());
+            assert!(idx.validate_query(&[0.0; 2]).is_err());
+        }
     }
 
     #[test]
-    fn insert_delete_reinsert_same_id() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.delete("a");
-        idx.insert("a".into(), vec![0.0, 1.0, 0.0]);
-        assert_eq!(idx.nodes.len(), 1);
-        let results = idx.search(&[0.0, 1.0, 0.0], 1, 50);
-        assert_eq!(results[0].0, "a");
-    }
+    fn bulk_insert_recall_matches_or_beats_sorted_sequential_insert() {
+        // Points along a line, inserted in sorted order: the worst case for
+        // sequential construction
+        let records: Vec<(String, Vec<f32>)> = (0..1500)
+            .map(|i| {
+                let t = i as f32 * 0.01;
+                (format!("p{}", i), vec![t, (t * 7.0).sin(), (t * 3.0).cos()])
+            })
+            .collect();
+        let mut sequential = HNSWIndex::new(3, 4, 16, DistanceMetric::Euclidean, Some(8));
+        for (id, v) in records.clone() {
+            sequential.insert(id, v);
+        }
+        let mut bulk = HNSWIndex::new(3, 4, 16, DistanceMetric::Euclidean, Some(8));
+        bulk.bulk_insert(records);
+        assert_eq!(bulk.node_count(), 1500);
 
-    // ── Serialization round-trip ───────────────────────────────────
+        let queries: Vec<Vec<f32>> = (0..100)
+            .map(|i| {
+                let t = i as f32 * 0.149 + 0.003;
+                vec![t, (t * 7.0).sin(), (t * 3.0).cos()]
+            })
+            .collect();
+        let recall = |idx: &HNSWIndex| {
+            let mut found = 0;
+            for q in &queries {
+                let truth: HashSet<String> = brute_force_knn(idx, q, 10).into_iter().collect();
+                found += idx.search(q, 10, 10).iter().filter(|(id, _)| truth.contains(id)).count();
+            }
+            found as f32 / (queries.len() * 10) as f32
+        };
+        let (sequential_recall, bulk_recall) = (recall(&sequential), recall(&bulk));
+        assert!(bulk_recall >= sequential_recall, "bulk {} < sequential {}", bulk_recall, sequential_recall);
+    }
 
     #[test]
-    fn serialize_deserialize_preserves_search_results() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
-        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
+    fn medoid_entry_point_is_central_and_keeps_recall() {
+        let dims = 8;
+        let clustered = |i: u64| -> Vec<f32> {
+            let offset = (i % 6) as f32 * 10.0;
+            make_vec(dims, i * 7 + 1).iter().map(|x| x + offset).collect()
+        };
+        let build = |medoid: bool| {
+            let mut idx = HNSWIndex::new(dims, 12, 100, DistanceMetric::Euclidean, Some(4));
+            if medoid {
+                idx.set_medoid_entry_point(Some(usize::MAX));
+            }
+            for i in 0..2000u64 {
+                idx.insert(format!("v{}", i), clustered(i));
+            }
+            let entry = idx.entry_point.clone().unwrap();
+            idx.delete(&entry);
+            idx
+        };
+        let (tallest, medoid) = (build(false), build(true));
+        let spread = |idx: &HNSWIndex, id: &str| -> f32 {
+            let v = idx.nodes[id].vector();
+            idx.nodes.values().map(|n| idx.compute_distance(&n.vector(), &v)).sum()
+        };
 
-        let query = vec![0.9, 0.1, 0.0];
-        let results_before = idx.search(&query, 3, 50);
+        // Same graph either way; only the entry point differs
+        let entry = medoid.entry_point.clone().unwrap();
+        assert_eq!(medoid.max_layer, tallest.max_layer);
+        assert_eq!(medoid.nodes[&entry].connections.len(), medoid.max_layer + 1);
+        let top_layer: Vec<&HNSWNode> =
+            medoid.nodes.values().filter(|n| n.connections.len() == medoid.max_layer + 1).collect();
+        assert!(top_layer.len() > 1);
+        for node in top_layer {
+            assert!(spread(&medoid, &entry) <= spread(&medoid, &node.id));
+        }
 
-        let json = serde_json::to_string(&idx).unwrap();
-        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
-        let results_after = idx2.search(&query, 3, 50);
+        let queries: Vec<Vec<f32>> = (0..200).map(|q| clustered(q * 13 + 99_999)).collect();
+        let recall = |idx: &HNSWIndex| {
+            let mut found = 0;
+            for q in &queries {
+                let truth: HashSet<String> = brute_force_knn(idx, q, 10).into_iter().collect();
+                found += idx.search(q, 10, 10).iter().filter(|(id, _)| truth.contains(id)).count();
+            }
+            found as f32 / (queries.len() * 10) as f32
+        };
+        let (tallest_recall, medoid_recall) = (recall(&tallest), recall(&medoid));
+        assert!(medoid_recall + 0.01 >= tallest_recall, "{} vs {}", medoid_recall, tallest_recall);
 
-        assert_eq!(results_before.len(), results_after.len());
-        for (a, b) in results_before.iter().zip(results_after.iter()) {
-            assert_eq!(a.0, b.0);
-            assert!((a.1 - b.1).abs() < 1e-6);
-        }
+        let mut empty = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+        assert_eq!(empty.recompute_entry_point(10), None);
+        empty.insert("only".into(), vec![1.0, 2.0]);
+        assert_eq!(empty.recompute_entry_point(10), Some("only".into()));
     }
 
     #[test]
-    fn serialize_empty_index() {
-        let idx = HNSWIndex::new(128, 16, 200, DistanceMetric::Euclidean);
-        let json = serde_json::to_string(&idx).unwrap();
-        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
-        assert!(idx2.entry_point.is_none());
-        assert_eq!(idx2.nodes.len(), 0);
-        assert_eq!(idx2.dimensions, 128);
-    }
+    fn dot_product_distances_are_negated_similarities() {
+        let mut idx = HNSWIndex::new(2, 16, 200, DistanceMetric::DotProduct, Some(4));
+        idx.insert("small".into(), vec![1.0, 0.0]);
+        idx.insert("large".into(), vec![3.0, 1.0]);
+        idx.insert("opposite".into(), vec![-2.0, 0.0]);
 
-    #[test]
-    fn serialize_after_deletions() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        idx.insert("b".into(), vec![0.0, 1.0, 0.0]);
-        idx.insert("c".into(), vec![0.0, 0.0, 1.0]);
-        idx.delete("b");
+        let results = idx.search(&[1.0, 1.0], 3, 10);
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["large", "small", "opposite"]);
+        let distances: Vec<f32> = results.iter().map(|(_, d)| *d).collect();
+        assert_eq!(distances, [-4.0, -1.0, 2.0]);
+        let similarities: Vec<f32> = distances.iter().map(|&d| idx.metric.similarity(d).unwrap()).collect();
+        assert_eq!(similarities, [4.0, 1.0, -2.0]);
+        assert_eq!(DistanceMetric::Euclidean.similarity(1.0), None);
 
-        let json = serde_json::to_string(&idx).unwrap();
-        let idx2: HNSWIndex = serde_json::from_str(&json).unwrap();
-        assert_eq!(idx2.nodes.len(), 2);
-        assert!(!idx2.nodes.contains_key("b"));
-        // Search still works
-        let results = idx2.search(&[1.0, 0.0, 0.0], 2, 50);
-        assert_eq!(results.len(), 2);
+        // An overflowing dot product ranks last rather than poisoning the order
+        let huge = f32::MAX;
+        assert_eq!(idx.compute_distance(&[huge, -huge], &[huge, huge]), f32::INFINITY);
     }
 
-    // ── Connection integrity ───────────────────────────────────────
-
     #[test]
-    fn connections_are_bidirectional_within_shared_layers() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        for i in 0..10 {
-            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 13 + 1));
+    fn reprune_thins_links_and_keeps_search_exact() {
+        let dims = 8;
+        let mut idx = HNSWIndex::new(dims, 12, 100, DistanceMetric::Euclidean, Some(41));
+        idx.set_neighbor_heuristic(false);
+        for i in 0..1500u64 {
+            idx.insert(format!("v{}", i), make_vec(dims, i * 3 + 5));
         }
+        let before = idx.stats().avg_layer0_connections;
 
-        // Connections should be bidirectional when both nodes exist on the same layer.
-        // A high-layer node may connect to a low-layer node unidirectionally
-        // (the low-layer node doesn't have connections at that layer).
-        for (id, node) in &idx.nodes {
-            for (layer, neighbors) in node.connections.iter().enumerate() {
-                for neighbor_id in neighbors {
-                    let neighbor = idx.nodes.get(neighbor_id).unwrap();
-                    if layer < neighbor.connections.len() {
-                        assert!(
-                            neighbor.connections[layer].contains(id),
-                            "Missing reverse connection: {} -> {} at layer {}",
-                            neighbor_id, id, layer
-                        );
-                    }
-                    // If neighbor doesn't have this layer, unidirectional is expected
+        idx.set_neighbor_heuristic(true);
+        idx.reprune().unwrap();
+        let after = idx.stats().avg_layer0_connections;
+        assert!(after < before, "layer-0 degree {} -> {}", before, after);
+        assert_eq!(idx.validate(), Ok(()));
+
+        // Kept links are reciprocal unless the other end is full
+        for node in idx.nodes.values() {
+            for (layer, links) in node.connections.iter().enumerate() {
+                for neighbor in links {
+                    let back = &idx.nodes[neighbor].connections[layer];
+                    let cap = idx.layer_caps().at(None, layer);
+                    assert!(back.contains(&node.id) || back.len() == cap);
                 }
             }
         }
+
+        let queries: Vec<Vec<f32>> = (0..100).map(|q| make_vec(dims, q * 11 + 70_001)).collect();
+        let mut found = 0;
+        for q in &queries {
+            let truth: HashSet<String> = brute_force_knn(&idx, q, 10).into_iter().collect();
+            found += idx.search(q, 10, 50).iter().filter(|(id, _)| truth.contains(id)).count();
+        }
+        let recall = found as f32 / 1000.0;
+        assert!(recall >= 0.95, "recall after reprune = {}", recall);
     }
 
     #[test]
-    fn no_dangling_references_after_delete() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        for i in 0..10 {
-            idx.insert(format!("v{}", i), make_vec(3, i as u64 * 7 + 3));
+    fn equal_distances_are_ordered_by_id() {
+        // Twelve integer points exactly 5 from the query, inserted out of order
+        let points = [
+            ("k", [5.0, 0.0]),
+            ("d", [4.0, 3.0]),
+            ("h", [3.0, 4.0]),
+            ("a", [0.0, 5.0]),
+            ("l", [-3.0, 4.0]),
+            ("c", [-4.0, 3.0]),
+            ("j", [-5.0, 0.0]),
+            ("e", [-4.0, -3.0]),
+            ("b", [-3.0, -4.0]),
+            ("i", [0.0, -5.0]),
+            ("f", [3.0, -4.0]),
+            ("g", [4.0, -3.0]),
+        ];
+        let mut idx = HNSWIndex::new(2, 4, 50, DistanceMetric::Euclidean, None);
+        for (id, v) in points {
+            idx.insert(id.to_string(), v.to_vec());
         }
-        idx.delete("v5");
+        idx.insert("center".into(), vec![0.0, 0.0]);
+        idx.insert("far".into(), vec![50.0, 50.0]);
 
-        for (_id, node) in &idx.nodes {
-            for neighbors in &node.connections {
-                assert!(!neighbors.contains("v5"), "Dangling reference to deleted node v5");
-            }
+        let first = idx.search(&[0.0, 0.0], 13, 50);
+        let order: Vec<&str> = first.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(order, ["center", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l"]);
+        for _ in 0..20 {
+            assert_eq!(idx.search(&[0.0, 0.0], 13, 50), first);
         }
+
+        // With k cutting through the ties, the smallest ids are kept
+        let top: Vec<String> = idx.search(&[0.0, 0.0], 4, 50).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(top, ["center", "a", "b", "c"]);
     }
 
-    // ── Edge cases ─────────────────────────────────────────────────
+    #[test]
+    fn try_new_rejects_m_below_two() {
+        for m in [0, 1] {
+            let err = HNSWIndex::try_new(4, m, 50, DistanceMetric::Euclidean, None).err().unwrap();
+            assert_eq!(err, format!("m must be at least 2, got {}", m));
+        }
+        let idx = HNSWIndex::try_new(4, MIN_M, 50, DistanceMetric::Euclidean, None).unwrap();
+        assert!(idx.ml.is_finite() && idx.ml > 0.0);
+    }
 
     #[test]
-    fn large_ef_does_not_panic() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        let results = idx.search(&[1.0, 0.0, 0.0], 1, 10000);
-        assert_eq!(results.len(), 1);
+    #[should_panic(expected = "m must be at least 2")]
+    fn new_panics_on_m_of_one() {
+        HNSWIndex::new(4, 1, 50, DistanceMetric::Euclidean, None);
     }
 
     #[test]
-    fn k_zero_returns_empty() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Euclidean);
-        idx.insert("a".into(), vec![1.0, 0.0, 0.0]);
-        let results = idx.search(&[1.0, 0.0, 0.0], 0, 50);
-        assert!(results.is_empty());
+    fn search_recent_ranks_newer_equidistant_records_first() {
+        let mut idx = HNSWIndex::new(2, 8, 50, DistanceMetric::Euclidean, Some(5));
+        let points = [("a", [3.0, 4.0]), ("b", [-3.0, 4.0]), ("c", [4.0, -3.0]), ("d", [-4.0, -3.0])];
+        for (id, v) in points {
+            idx.insert(id.to_string(), v.to_vec());
+        }
+        idx.insert("far".into(), vec![9.0, 9.0]);
+        // All four ties are 5 from the origin; "b" is newest, "a" oldest,
+        // "d" has no timestamp
+        let now = 10_000;
+        assert!(idx.set_timestamp("a", now - 3000));
+        assert!(idx.set_timestamp("b", now - 10));
+        assert!(idx.set_timestamp("c", now - 600));
+        assert!(idx.set_timestamp("far", now));
+        assert!(!idx.set_timestamp("missing", now));
+        assert_eq!(idx.timestamp("c"), Some(now - 600));
+
+        let order = |half_life: f64| -> Vec<String> {
+            idx.search_recent(&[0.0, 0.0], 4, 50, half_life, now).into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(order(600.0), ["b", "c", "a", "d"]);
+        // Distances are reported unweighted
+        assert!(idx.search_recent(&[0.0, 0.0], 4, 50, 600.0, now).iter().all(|(_, d)| (d - 5.0).abs() < 1e-6));
+        // Recency doesn't lift a brand-new record past much nearer ones
+        assert!(!order(600.0).contains(&"far".to_string()));
+        // With an endless half-life, timestamped ties fall back to id order
+        assert_eq!(order(f64::INFINITY), ["a", "b", "c", "d"]);
+
+        // Deleting forgets the timestamp
+        idx.delete("b");
+        assert_eq!(idx.timestamp("b"), None);
     }
 
-    // ── Distance metric tests ──────────────────────────────────────
+    #[test]
+    fn search_recent_keeps_dot_product_order_among_untimestamped_records() {
+        let mut idx = HNSWIndex::new(2, 8, 50, DistanceMetric::DotProduct, Some(5));
+        idx.insert("strong".into(), vec![3.0, 0.0]);
+        idx.insert("medium".into(), vec![2.0, 0.0]);
+        idx.insert("weak".into(), vec![1.0, 0.0]);
+        let order = |idx: &HNSWIndex, now: u64| -> Vec<String> {
+            idx.search_recent(&[1.0, 0.0], 3, 50, 600.0, now).into_iter().map(|(id, _)| id).collect()
+        };
+        // No timestamps at all: similarity still decides the order
+        assert_eq!(order(&idx, 10_000), ["strong", "medium", "weak"]);
+
+        // A fresh record overtakes a slightly stronger stale one
+        let now = 10_000;
+        assert!(idx.set_timestamp("medium", now));
+        assert_eq!(order(&idx, now), ["medium", "strong", "weak"]);
+    }
 
     #[test]
-    fn cosine_metric_returns_correct_order() {
-        let mut idx = HNSWIndex::new(3, 16, 200, DistanceMetric::Cosine);
-        // Same direction as query (cosine distance ~ 0)
-        idx.insert("same_dir".into(), vec![2.0, 0.0, 0.0]);
-        // Orthogonal (cosine distance ~ 1)
-        idx.insert("ortho".into(), vec![0.0, 1.0, 0.0]);
-        // Opposite (cosine distance ~ 2)
-        idx.insert("opposite".into(), vec![-1.0, 0.0, 0.0]);
+    fn reindex_with_metric_rebuilds_for_the_new_ordering() {
+        let mut idx = HNSWIndex::new(2, 8, 100, DistanceMetric::Euclidean, Some(12));
+        idx.insert("near_small".into(), vec![1.0, 0.1]);
+        idx.insert("far_aligned".into(), vec![10.0, 0.0]);
+        for i in 0..50 {
+            idx.insert(format!("v{}", i), vec![-(i as f32) - 1.0, i as f32]);
+        }
+        assert_eq!(idx.search(&[1.0, 0.0], 1, 50)[0].0, "near_small");
 
-        let results = idx.search(&[1.0, 0.0, 0.0], 3, 50);
-        assert_eq!(results[0].0, "same_dir");
-        assert_eq!(results[2].0, "opposite");
+        idx.reindex_with_metric(DistanceMetric::Cosine).unwrap();
+        assert_eq!(idx.metric, DistanceMetric::Cosine);
+        assert_eq!(idx.node_count(), 52);
+        assert_eq!(idx.validate(), Ok(()));
+        let results = idx.search(&[1.0, 0.0], 2, 50);
+        assert_eq!(results[0].0, "far_aligned");
+        assert!(results[0].1.abs() < 1e-6);
+        assert_eq!(results[1].0, "near_small");
+    }
+
+    #[test]
+    fn tune_ef_returns_the_smallest_sufficient_ef() {
+        let dims = 16;
+        let mut idx = HNSWIndex::new(dims, 4, 20, DistanceMetric::Euclidean, Some(33));
+        for i in 0..2000u64 {
+            idx.insert(format!("v{}", i), make_vec(dims, i * 5 + 2));
+        }
+        let queries: Vec<Vec<f32>> = (0..50).map(|q| make_vec(dims, q * 17 + 90_001)).collect();
+
+        let ef = idx.tune_ef(&queries, 10, 0.95);
+        assert!(idx.estimate_recall(&queries, 10, ef) >= 0.95);
+        if ef > 10 {
+            assert!(idx.estimate_recall(&queries, 10, ef / 2) < 0.95);
+        }
+
+        assert_eq!(idx.tune_ef(&queries, 10, 0.0), 10);
+        assert!(idx.tune_ef(&queries, 10, 1.0) <= TUNE_EF_MAX);
+        let empty = HNSWIndex::new(dims, 4, 20, DistanceMetric::Euclidean, None);
+        assert_eq!(empty.tune_ef(&queries, 10, 0.95), 10);
     }
 }
@@ -0,0 +1,211 @@
+//! Lloyd's k-means clustering over stored vectors
+//!
+//! Centroids are seeded with deterministic farthest-point initialization
+//! (the first vector, then repeatedly the vector farthest from every chosen
+//! centroid), so results are reproducible for a given input order.
+
+use crate::distance;
+use serde::{Deserialize, Serialize};
+
+/// Result of a single k-means run
+#[derive(Clone, Debug)]
+#[allow(dead_code)]
+pub struct KMeansResult {
+    pub centroids: Vec<Vec<f32>>,
+    /// Cluster index assigned to each input vector
+    pub assignments: Vec<usize>,
+    /// Within-cluster sum of squared Euclidean distances
+    pub wcss: f32,
+}
+
+/// One point on the elbow curve
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ElbowPoint {
+    pub k: usize,
+    pub wcss: f32,
+}
+
+/// Cluster `vectors` into (at most) `k` groups
+pub fn kmeans(vectors: &[&[f32]], k: usize, max_iters: usize) -> KMeansResult {
+    let k = k.min(vectors.len());
+    if k == 0 {
+        return KMeansResult {
+            centroids: vec![],
+            assignments: vec![0; vectors.len()],
+            wcss: 0.0,
+        };
+    }
+
+    let mut centroids = farthest_point_init(vectors, k);
+    let mut assignments = vec![0; vectors.len()];
+
+    for iter in 0..max_iters.max(1) {
+        let mut changed = false;
+        for (i, v) in vectors.iter().enumerate() {
+            let nearest = nearest_centroid(v, &centroids);
+            if iter == 0 || nearest != assignments[i] {
+                changed = true;
+                assignments[i] = nearest;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        // Recompute centroids as the mean of their members
+        let dims = vectors[0].len();
+        let mut sums = vec![vec![0.0f32; dims]; k];
+        let mut counts = vec![0usize; k];
+        for (v, &c) in vectors.iter().zip(assignments.iter()) {
+            for (s, x) in sums[c].iter_mut().zip(v.iter()) {
+                *s += x;
+            }
+            counts[c] += 1;
+        }
+        for c in 0..k {
+            // Empty clusters keep their previous centroid
+            if counts[c] > 0 {
+                centroids[c] = sums[c].iter().map(|s| s / counts[c] as f32).collect();
+            }
+        }
+    }
+
+    let wcss = vectors
+        .iter()
+        .zip(assignments.iter())
+        .map(|(v, &c)| distance::euclidean_distance_squared(v, &centroids[c]))
+        .sum();
+
+    KMeansResult {
+        centroids,
+        assignments,
+        wcss,
+    }
+}
+
+/// Run k-means for each k and report the within-cluster sum of squares
+pub fn elbow(vectors: &[&[f32]], k_values: &[usize], max_iters: usize) -> Vec<ElbowPoint> {
+    k_values
+        .iter()
+        .map(|&k| ElbowPoint {
+            k,
+            wcss: kmeans(vectors, k, max_iters).wcss,
+        })
+        .collect()
+}
+
+/// Component-wise mean of `vectors` (empty input yields an empty vector)
+pub fn centroid(vectors: &[&[f32]]) -> Vec<f32> {
+    let Some(first) = vectors.first() else {
+        return vec![];
+    };
+    let mut sum = vec![0.0f32; first.len()];
+    for v in vectors {
+        for (s, x) in sum.iter_mut().zip(v.iter()) {
+            *s += x;
+        }
+    }
+    sum.iter().map(|s| s / vectors.len() as f32).collect()
+}
+
+fn farthest_point_init(vectors: &[&[f32]], k: usize) -> Vec<Vec<f32>> {
+    let mut centroids = vec![vectors[0].to_vec()];
+    let mut closest: Vec<f32> = vectors
+        .iter()
+        .map(|v| distance::euclidean_distance_squared(v, vectors[0]))
+        .collect();
+
+    while centroids.len() < k {
+        let (next, _) = closest
+            .iter()
+            .enumerate()
+            .fold((0, f32::NEG_INFINITY), |best, (i, &d)| if d > best.1 { (i, d) } else { best });
+        centroids.push(vectors[next].to_vec());
+        for (d, v) in closest.iter_mut().zip(vectors.iter()) {
+            *d = d.min(distance::euclidean_distance_squared(v, vectors[next]));
+        }
+    }
+    centroids
+}
+
+fn nearest_centroid(v: &[f32], centroids: &[Vec<f32>]) -> usize {
+    let mut best = 0;
+    let mut best_dist = f32::INFINITY;
+    for (c, centroid) in centroids.iter().enumerate() {
+        let d = distance::euclidean_distance_squared(v, centroid);
+        if d < best_dist {
+            best = c;
+            best_dist = d;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::random_vector_seeded;
+
+    /// Three tight, well-separated blobs of 20 points each
+    fn three_blobs() -> Vec<Vec<f32>> {
+        let centers = [[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]];
+        let mut out = Vec::new();
+        for (c, center) in centers.iter().enumerate() {
+            for i in 0..20 {
+                let jitter = random_vector_seeded(2, (c * 100 + i) as u64 + 1);
+                out.push(vec![center[0] + jitter[0] * 0.5, center[1] + jitter[1] * 0.5]);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn kmeans_separates_clear_clusters() {
+        let data = three_blobs();
+        let refs: Vec<&[f32]> = data.iter().map(|v| v.as_slice()).collect();
+        let result = kmeans(&refs, 3, 50);
+        assert_eq!(result.centroids.len(), 3);
+        for blob in 0..3 {
+            let first = result.assignments[blob * 20];
+            assert!(result.assignments[blob * 20..(blob + 1) * 20].iter().all(|&a| a == first));
+        }
+    }
+
+    #[test]
+    fn elbow_flattens_after_true_cluster_count() {
+        let data = three_blobs();
+        let refs: Vec<&[f32]> = data.iter().map(|v| v.as_slice()).collect();
+        let curve = elbow(&refs, &[1, 2, 3, 4, 5], 50);
+        let w: Vec<f32> = curve.iter().map(|p| p.wcss).collect();
+        assert_eq!(curve[2].k, 3);
+
+        // Big drops up to k = 3, then only marginal gains
+        assert!(w[0] - w[1] > 10.0 * (w[2] - w[3]));
+        assert!(w[1] - w[2] > 10.0 * (w[2] - w[3]));
+        assert!(w[3] <= w[2] + 1e-3);
+    }
+
+    #[test]
+    fn kmeans_k_larger_than_input_is_clamped() {
+        let data = [vec![0.0, 0.0], vec![1.0, 1.0]];
+        let refs: Vec<&[f32]> = data.iter().map(|v| v.as_slice()).collect();
+        let result = kmeans(&refs, 5, 10);
+        assert_eq!(result.centroids.len(), 2);
+        assert!(result.wcss < 1e-6);
+    }
+
+    #[test]
+    fn kmeans_empty_input() {
+        let result = kmeans(&[], 3, 10);
+        assert!(result.centroids.is_empty());
+        assert_eq!(result.wcss, 0.0);
+    }
+
+    #[test]
+    fn centroid_is_component_mean() {
+        let data = [vec![0.0, 2.0], vec![2.0, 4.0], vec![4.0, 0.0]];
+        let refs: Vec<&[f32]> = data.iter().map(|v| v.as_slice()).collect();
+        assert_eq!(centroid(&refs), vec![2.0, 2.0]);
+        assert!(centroid(&[]).is_empty());
+    }
+}
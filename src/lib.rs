@@ -1,17 +1,36 @@
+mod binary;
+mod checksum;
+mod chunks;
 mod distance;
+mod error;
+mod filter;
 mod hnsw;
+mod kmeans;
+mod lru;
+mod metadata;
+mod presort;
+mod projection;
+mod quantize;
+mod rng;
+pub mod native;
 mod vector;
 
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use error::{DbError, ErrorCode};
+use filter::MetadataFilter;
+pub use metadata::{Metadata, MetadataValue};
 
 /// Vector search result
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchResult {
     pub id: String,
     pub distance: f32,
-    pub metadata: Option<HashMap<String, String>>,
+    pub metadata: Option<Metadata>,
 }
 
 /// Vector record for storage
@@ -19,137 +38,951 @@ pub struct SearchResult {
 pub struct VectorRecord {
     pub id: String,
     pub vector: Vec<f32>,
-    pub metadata: Option<HashMap<String, String>>,
+    pub metadata: Option<Metadata>,
+}
+
+/// A stored vector's distance from some reference point
+#[derive(Serialize)]
+struct Neighbor {
+    id: String,
+    distance: f32,
+}
+
+/// Audit record for one search, see `VectorDB::search_with_provenance`
+#[derive(Serialize)]
+struct SearchProvenance {
+    /// FNV-1a hash of the query's f32 bit patterns, as 16 hex digits
+    query_hash: String,
+    k: usize,
+    ef: usize,
+    /// Candidate list size actually used at layer 0
+    effective_ef: usize,
+    metric: &'static str,
+    /// Caller-supplied time of the search (e.g. `Date.now()`)
+    timestamp: f64,
+    results: Vec<Neighbor>,
+}
+
+/// The members of one metadata group nearest to the group's centroid
+#[derive(Serialize)]
+struct GroupRepresentatives {
+    group: String,
+    size: usize,
+    representatives: Vec<Neighbor>,
+}
+
+/// Error for distance-based calls after `optimize_storage("drop-vectors")`
+const VECTORS_DROPPED: &str =
+    "Vectors were dropped by optimize_storage(\"drop-vectors\"); re-insert every record to search again";
+
+/// `k` used by `search_default` when no defaults are stored
+const DEFAULT_SEARCH_K: usize = 10;
+
+/// Query parameters stored with a database, see `VectorDB::set_search_defaults`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+struct SearchDefaults {
+    k: usize,
+    ef: usize,
+}
+
+/// Version of the JSON state `serialize` writes
+///
+/// Reserved numbers: a snapshot without a `version` field is the legacy
+/// pre-version format (`{ vectors, metadata, hnsw_state }`), 1 is `DBState`,
+/// and 2 and up are reserved for future formats, which older builds reject
+/// by number instead of misreading.
+const STATE_VERSION: u32 = 1;
+
+/// Only the top-level `version` of a JSON snapshot, read first so
+/// `deserialize` can pick the matching parser
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: Option<u32>,
+    #[serde(default)]
+    checksum: Option<String>,
+}
+
+/// Versioned on-disk state written by `serialize`
+#[derive(Serialize)]
+struct DBState<'a> {
+    version: u32,
+    hnsw_index: &'a hnsw::HNSWIndex,
+    metadata: &'a HashMap<String, Metadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    search_defaults: Option<SearchDefaults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    projection: Option<&'a projection::RandomProjection>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    originals: &'a HashMap<String, Vec<f32>>,
+}
+
+/// Owned counterpart of `DBState` for `deserialize`
+#[derive(Deserialize)]
+struct DBStateV1 {
+    version: u32,
+    hnsw_index: hnsw::HNSWIndex,
+    metadata: HashMap<String, Metadata>,
+    #[serde(default)]
+    search_defaults: Option<SearchDefaults>,
+    #[serde(default)]
+    projection: Option<projection::RandomProjection>,
+    #[serde(default)]
+    originals: HashMap<String, Vec<f32>>,
+}
+
+/// Records changed since the last `serialize`, `serialize_binary` or
+/// `serialize_delta`
+#[derive(Default)]
+struct ChangeLog {
+    /// Inserted, updated or deleted ids
+    ids: BTreeSet<String>,
+    /// Set by changes a delta can't express: `reproject`, `reindex_with_metric`,
+    /// `set_normalized` and dropping vectors
+    needs_snapshot: bool,
+}
+
+/// Patch written by `serialize_delta`: current records for changed ids that
+/// still exist, and tombstones for the ones that were deleted
+#[derive(Serialize, Deserialize)]
+struct DeltaState {
+    upserts: Vec<VectorRecord>,
+    deletes: Vec<String>,
+}
+
+/// Check a legacy snapshot's index against its top-level `vectors` and
+/// `metadata` maps
+///
+/// The index must have non-zero, consistent dimensions and hold every id in
+/// `vectors` with the same length. Metadata for ids the index doesn't hold is
+/// dropped; the returned warnings list each one.
+fn validate_legacy(
+    index: &hnsw::HNSWIndex,
+    vectors: &HashMap<String, Vec<f32>>,
+    mut metadata: MetadataMap,
+) -> Result<(MetadataMap, Vec<String>), String> {
+    if index.dimensions == 0 {
+        return Err("Legacy database declares zero dimensions".to_string());
+    }
+    index.check_dimensions()?;
+
+    let mut ids: Vec<&String> = vectors.keys().collect();
+    ids.sort();
+    for id in ids {
+        if !index.contains(id) {
+            return Err(format!("Legacy database lists vector '{}' that is missing from its index", id));
+        }
+        if vectors[id].len() != index.dimensions {
+            return Err(format!(
+                "Legacy database vector '{}' has {} dimensions but the index declares {}",
+                id,
+                vectors[id].len(),
+                index.dimensions
+            ));
+        }
+    }
+
+    let mut orphans: Vec<String> = metadata.keys().filter(|id| !index.contains(id)).cloned().collect();
+    orphans.sort();
+    let warnings = orphans
+        .into_iter()
+        .map(|id| {
+            metadata.remove(&id);
+            format!("Dropped metadata for '{}', which is not in the index", id)
+        })
+        .collect();
+    Ok((metadata, warnings))
 }
 
+/// Check a snapshot's projection against its index and stored originals
+///
+/// The projection must map onto the index's dimensions, and every original
+/// must belong to an indexed id and have the projection's input dimensions.
+fn check_projection(
+    index: &hnsw::HNSWIndex,
+    projection: Option<&projection::RandomProjection>,
+    originals: &HashMap<String, Vec<f32>>,
+) -> Result<(), String> {
+    let Some(projection) = projection else {
+        if originals.is_empty() {
+            return Ok(());
+        }
+        return Err("Database stores original vectors but no projection".to_string());
+    };
+    if projection.output_dims() != index.dimensions {
+        return Err(format!(
+            "Projection outputs {} dimensions but the index declares {}",
+            projection.output_dims(),
+            index.dimensions
+        ));
+    }
+    for (id, vector) in originals {
+        if !index.contains(id) {
+            return Err(format!("Original vector '{}' is missing from the index", id));
+        }
+        if vector.len() != projection.input_dims() {
+            return Err(format!(
+                "Original vector '{}' has {} dimensions but the projection expects {}",
+                id,
+                vector.len(),
+                projection.input_dims()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Metadata of every stored id
+type MetadataMap = HashMap<String, Metadata>;
+
+/// A record's validated vector and metadata, ready to upsert
+type ParsedRecord = (Vec<f32>, Option<Metadata>);
+
 /// Main VectorDB class - exposed to JavaScript
-#[wasm_bindgen]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct VectorDB {
     hnsw_index: hnsw::HNSWIndex,
-    metadata: HashMap<String, HashMap<String, String>>,
+    metadata: HashMap<String, Metadata>,
+    /// Set by `load_readonly`; insert/delete fail until `to_mutable` is called
+    read_only: bool,
+    /// Stored `k`/`ef` for `search_default`; persisted with the database
+    search_defaults: Option<SearchDefaults>,
+    /// Problems repaired while loading, see `load_warnings`
+    load_warnings: Vec<String>,
+    /// Changes since the last save, for `serialize_delta`; behind a RefCell
+    /// so the `&self` snapshot methods can reset it
+    changes: RefCell<ChangeLog>,
+    /// Set by `with_random_projection`; applied to every inserted and
+    /// queried vector before it reaches the index
+    projection: Option<projection::RandomProjection>,
+    /// Unprojected vectors of a projecting database, returned by `get`
+    originals: HashMap<String, Vec<f32>>,
+    /// Access recency and record limit, set by `set_capacity_limit`; behind
+    /// a RefCell so `&self` reads can record their accesses
+    access: RefCell<Option<lru::AccessOrder>>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl VectorDB {
     /// Create a new VectorDB instance
-    #[wasm_bindgen(constructor)]
-    pub fn new(dimensions: usize, m: usize, ef_construction: usize, metric: Option<String>) -> VectorDB {
-        let distance_metric = match metric.as_deref() {
-            Some("cosine") => hnsw::DistanceMetric::Cosine,
-            Some("dotproduct") | Some("dot_product") => hnsw::DistanceMetric::DotProduct,
-            _ => hnsw::DistanceMetric::Euclidean,
+    ///
+    /// Pass a `seed` to make graph construction deterministic.
+    ///
+    /// `metric: "auto"` picks dot product if the first inserted vectors are
+    /// unit-normalized and Euclidean otherwise; `metric()` reports the choice.
+    ///
+    /// `quantization: "int8"` stores each vector as one byte per dimension
+    /// (about 4x less memory) at the cost of approximate distances and a
+    /// small recall loss; `get` then returns a reconstructed vector.
+    /// `quantization: "f16"` stores half-precision floats (2x less memory)
+    /// with rounding error around 0.05% per component.
+    ///
+    /// `metric: "minkowski"` uses `(Σ|x_i - y_i|^p)^(1/p)` with the given `p`
+    /// (default 2). `p = 1` equals Manhattan, `p = 2` Euclidean, and large `p`
+    /// approaches the largest coordinate difference. A `p` that isn't a
+    /// positive finite number falls back to 2. A `p` below 1 is allowed but
+    /// isn't a true metric, which can cost the graph search recall.
+    ///
+    /// Fails if `m` is below 2 or `quantization` is anything else.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: Option<String>,
+        seed: Option<u64>,
+        quantization: Option<String>,
+        p: Option<f32>,
+    ) -> Result<VectorDB, JsValue> {
+        let distance_metric = metric
+            .as_deref()
+            .and_then(|name| parse_metric(name, p))
+            .unwrap_or(hnsw::DistanceMetric::Euclidean);
+        let mut hnsw_index = hnsw::HNSWIndex::try_new(dimensions, m, ef_construction, distance_metric, seed)
+            .map_err(DbError::invalid)?;
+        if metric.as_deref() == Some("auto") {
+            hnsw_index
+                .set_auto_metric(true)
+                .expect("an empty index accepts automatic metric selection");
+        }
+        let storage = match quantization.as_deref() {
+            None => quantize::Quantization::None,
+            Some("int8") => quantize::Quantization::Int8,
+            Some("f16") => quantize::Quantization::F16,
+            Some(other) => {
+                return Err(DbError::invalid(format!(
+                    "Unknown quantization: {} (expected \"int8\" or \"f16\")",
+                    other
+                ))
+                .into())
+            }
         };
-        VectorDB {
-            hnsw_index: hnsw::HNSWIndex::new(dimensions, m, ef_construction, distance_metric),
+        hnsw_index
+            .set_quantization(storage)
+            .expect("an empty index accepts any quantization");
+        Ok(VectorDB {
+            hnsw_index,
             metadata: HashMap::new(),
+            read_only: false,
+            search_defaults: None,
+            load_warnings: Vec::new(),
+            changes: RefCell::default(),
+            access: RefCell::default(),
+            projection: None,
+            originals: HashMap::new(),
+        })
+    }
+
+    /// `new` holding at most `max_nodes` records: inserting past the limit
+    /// deletes the least recently accessed record, see `set_capacity_limit`
+    pub fn new_with_capacity_limit(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: Option<String>,
+        seed: Option<u64>,
+        max_nodes: usize,
+    ) -> Result<VectorDB, JsValue> {
+        let mut db = VectorDB::new(dimensions, m, ef_construction, metric, seed, None, None)?;
+        db.set_capacity_limit(Some(max_nodes))?;
+        Ok(db)
+    }
+
+    /// `new` with room reserved for `capacity` records in the index and the
+    /// metadata map, for loads of known size
+    pub fn with_capacity(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: Option<String>,
+        seed: Option<u64>,
+        capacity: usize,
+    ) -> Result<VectorDB, JsValue> {
+        let mut db = VectorDB::new(dimensions, m, ef_construction, metric, seed, None, None)?;
+        db.hnsw_index.reserve(capacity);
+        db.metadata.reserve(capacity);
+        Ok(db)
+    }
+
+    /// Build a database from a full dataset of `{id, vector, metadata}` records
+    ///
+    /// Every record is validated first and the whole load fails on the first
+    /// bad one. The records are then inserted in a shuffled order (see
+    /// `HNSWIndex::bulk_insert`), which gives a better-connected graph than
+    /// inserting sorted or clustered data as-is. When an id repeats, the last
+    /// occurrence wins.
+    pub fn from_records(
+        dimensions: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: Option<String>,
+        records: JsValue,
+        seed: Option<u64>,
+    ) -> Result<VectorDB, JsValue> {
+        if !js_sys::Array::is_array(&records) {
+            return Err(DbError::invalid("Records must be an array").into());
+        }
+        let mut db = VectorDB::new(dimensions, m, ef_construction, metric, seed, None, None)?;
+        let mut parsed = Vec::new();
+        for (i, record) in js_sys::Array::from(&records).iter().enumerate() {
+            let id = js_sys::Reflect::get(&record, &"id".into())
+                .ok()
+                .and_then(|v| v.as_string())
+                .ok_or_else(|| DbError::invalid(format!("Record {} is missing a string id", i)))?;
+            let (vector, metadata) = db
+                .parse_js_record(&id, &record)
+                .map_err(|e| DbError::new(e.code, format!("Record {} ({}): {}", i, id, e)))?;
+            parsed.push((id, vector, metadata));
+        }
+
+        let sample: Vec<&[f32]> = parsed
+            .iter()
+            .take(hnsw::AUTO_METRIC_SAMPLE)
+            .map(|(_, v, _)| v.as_slice())
+            .collect();
+        db.hnsw_index.resolve_auto_metric(&sample);
+        let mut vectors = Vec::with_capacity(parsed.len());
+        for (id, vector, metadata) in parsed {
+            db.record_change(&id);
+            match metadata {
+                Some(meta) => db.metadata.insert(id.clone(), meta),
+                None => db.metadata.remove(&id),
+            };
+            vectors.push((id, vector));
+        }
+        db.hnsw_index.bulk_insert(vectors);
+        Ok(db)
+    }
+
+    /// Create a database that indexes a seeded random projection of each
+    /// `dimensions`-long vector down to `target_dims`
+    ///
+    /// Inserts, queries and deltas keep using `dimensions`-long vectors; the
+    /// projection is applied before they reach the index, and `get` (like
+    /// every other read) returns the original vector, which is stored
+    /// alongside. Graph construction and search then cost roughly
+    /// `target_dims / dimensions` of the full-dimensional work, but distances
+    /// are only approximately preserved, so recall against the exact
+    /// full-dimensional neighbors drops, more so for smaller `target_dims`.
+    /// Reported distances are in the projected space. The projection suits
+    /// Euclidean, dot product and cosine metrics; "auto" is not supported
+    /// and falls back to Euclidean like any unknown name.
+    pub fn with_random_projection(
+        dimensions: usize,
+        target_dims: usize,
+        m: usize,
+        ef_construction: usize,
+        metric: Option<String>,
+        seed: Option<u64>,
+    ) -> Result<VectorDB, JsValue> {
+        if target_dims == 0 || target_dims > dimensions {
+            return Err(DbError::invalid(format!(
+                "target_dims must be between 1 and {}, got {}",
+                dimensions, target_dims
+            ))
+            .into());
+        }
+        let metric = metric.filter(|name| name != "auto");
+        let mut db = VectorDB::new(target_dims, m, ef_construction, metric, seed, None, None)?;
+        let projection_seed = seed.unwrap_or_else(|| {
+            let mut buf = [0u8; 8];
+            getrandom::getrandom(&mut buf).unwrap_or_default();
+            u64::from_le_bytes(buf)
+        });
+        db.projection = Some(projection::RandomProjection::new(dimensions, target_dims, projection_seed));
+        Ok(db)
+    }
+
+    /// The distance metric in use: "euclidean", "cosine", "dotproduct",
+    /// "manhattan", "angular", "minkowski", "hamming" or "jaccard", or "auto"
+    /// while an automatic metric awaits its first insert
+    pub fn metric(&self) -> String {
+        if self.hnsw_index.auto_metric_pending() {
+            return "auto".to_string();
         }
+        self.hnsw_index.metric.name().to_string()
     }
 
     /// Insert a vector into the database
     pub fn insert(&mut self, id: String, vector: Vec<f32>, metadata: JsValue) -> Result<(), JsValue> {
-        if vector.len() != self.hnsw_index.dimensions {
-            return Err(JsValue::from_str(&format!(
-                "Vector dimension mismatch: expected {}, got {}",
-                self.hnsw_index.dimensions,
-                vector.len()
-            )));
+        self.ensure_writable()?;
+        self.validate_input(&id, &vector)?;
+        let meta = parse_metadata(metadata)?;
+        self.upsert(id, vector, meta, None, None);
+        Ok(())
+    }
+
+    /// `insert` taking the vector as a `Float32Array` (including a `subarray`
+    /// view into a larger buffer), read with a single `copy_to` into wasm memory
+    pub fn insert_view(&mut self, id: String, vector: &js_sys::Float32Array, metadata: JsValue) -> Result<(), JsValue> {
+        self.insert(id, vector.to_vec(), metadata)
+    }
+
+    /// `insert` that reports what happened: `{ status: "created" }` for a new
+    /// id, `{ status: "replaced" }` when an existing record was overwritten
+    pub fn insert_or_replace(&mut self, id: String, vector: Vec<f32>, metadata: JsValue) -> Result<JsValue, JsValue> {
+        self.ensure_writable()?;
+        self.validate_input(&id, &vector)?;
+        let meta = parse_metadata(metadata)?;
+        let status = if self.upsert(id, vector, meta, None, None) { "replaced" } else { "created" };
+        let out = js_sys::Object::new();
+        js_sys::Reflect::set(&out, &"status".into(), &status.into())?;
+        Ok(out.into())
+    }
+
+    /// `insert` that also records the insertion time, in seconds on the
+    /// caller's clock (e.g. `Date.now() / 1000`), for `search_recent`
+    pub fn insert_with_timestamp(
+        &mut self,
+        id: String,
+        vector: Vec<f32>,
+        metadata: JsValue,
+        timestamp: u64,
+    ) -> Result<(), JsValue> {
+        self.insert(id.clone(), vector, metadata)?;
+        self.hnsw_index.set_timestamp(&id, timestamp);
+        Ok(())
+    }
+
+    /// Insertion time given to `insert_with_timestamp`, or undefined for
+    /// records inserted without one
+    pub fn inserted_at(&self, id: String) -> Option<u64> {
+        self.hnsw_index.timestamp(&id)
+    }
+
+    /// Insert a vector whose connection limit uses `m` instead of the global value.
+    /// Use a larger `m` for important "hub" records to improve their retrievability.
+    pub fn insert_with_m(&mut self, id: String, vector: Vec<f32>, metadata: JsValue, m: usize) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.validate_input(&id, &vector)?;
+        self.ensure_not_dropped(&id, "m")?;
+        if m == 0 {
+            return Err(DbError::invalid("m must be at least 1").into());
         }
+        let meta = parse_metadata(metadata)?;
+        self.upsert(id, vector, meta, Some(m), None);
+        Ok(())
+    }
 
-        // Validate vector values
-        if vector.iter().any(|x| !x.is_finite()) {
-            return Err(JsValue::from_str("Vector contains NaN or Infinity values"));
+    /// Insert a vector searching `ef_construction` candidates for its graph
+    /// neighbors instead of the database-wide value, for this insert only
+    pub fn insert_with_ef(
+        &mut self,
+        id: String,
+        vector: Vec<f32>,
+        metadata: JsValue,
+        ef_construction: usize,
+    ) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.validate_input(&id, &vector)?;
+        self.ensure_not_dropped(&id, "ef_construction")?;
+        if ef_construction == 0 {
+            return Err(DbError::invalid("ef_construction must be at least 1").into());
         }
+        let meta = parse_metadata(metadata)?;
+        self.upsert(id, vector, meta, None, Some(ef_construction));
+        Ok(())
+    }
 
-        // Parse metadata if provided
-        let meta: Option<HashMap<String, String>> = if metadata.is_null() || metadata.is_undefined() {
-            None
-        } else {
-            serde_wasm_bindgen::from_value(metadata).ok()
-        };
+    /// Insert many `{id, vector, metadata}` records in one call
+    ///
+    /// Each record is validated and upserted independently. Returns an array of
+    /// `{id, ok, error}` objects in input order so partial failures can be
+    /// reported without aborting the rest of the batch.
+    ///
+    /// With `presort` set, valid records are inserted in Morton (Z-curve) order
+    /// over a low-dimensional projection of their vectors instead of input
+    /// order, so spatially close vectors are inserted together. This speeds up
+    /// large bulk loads and builds a graph of comparable quality. When an id
+    /// repeats within the batch, the last occurrence still wins.
+    pub fn insert_batch(&mut self, records: JsValue, presort: Option<bool>) -> Result<JsValue, JsValue> {
+        self.ensure_writable()?;
+        if !js_sys::Array::is_array(&records) {
+            return Err(DbError::invalid("Records must be an array").into());
+        }
 
-        // Handle upsert: delete old entry if it exists
-        if self.hnsw_index.contains(&id) {
-            self.hnsw_index.delete(&id);
+        // Parse everything first so a presorted batch can be reordered
+        let mut ids = Vec::new();
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for record in js_sys::Array::from(&records).iter() {
+            let id = js_sys::Reflect::get(&record, &"id".into())
+                .ok()
+                .and_then(|v| v.as_string());
+            let outcome = match &id {
+                Some(id) => self.parse_js_record(id, &record).map_err(String::from),
+                None => Err("Record is missing a string id".to_string()),
+            };
+            ids.push(id);
+            match outcome {
+                Ok(record) => {
+                    parsed.push(Some(record));
+                    errors.push(None);
+                }
+                Err(e) => {
+                    parsed.push(None);
+                    errors.push(Some(e));
+                }
+            }
         }
 
-        // Add to HNSW index
-        self.hnsw_index.insert(id.clone(), vector);
+        let mut order: Vec<usize> = (0..parsed.len()).filter(|&i| parsed[i].is_some()).collect();
+        if presort.unwrap_or(false) {
+            let last: HashMap<&str, usize> = order
+                .iter()
+                .filter_map(|&i| ids[i].as_deref().map(|id| (id, i)))
+                .collect();
+            order.retain(|&i| ids[i].as_deref().and_then(|id| last.get(id)) == Some(&i));
+            let vectors: Vec<&[f32]> = order
+                .iter()
+                .filter_map(|&i| parsed[i].as_ref().map(|(v, _)| v.as_slice()))
+                .collect();
+            order = presort::morton_order(&vectors).into_iter().map(|j| order[j]).collect();
+        }
+        let sample: Vec<&[f32]> = order
+            .iter()
+            .take(hnsw::AUTO_METRIC_SAMPLE)
+            .filter_map(|&i| parsed[i].as_ref().map(|(v, _)| v.as_slice()))
+            .collect();
+        self.hnsw_index.resolve_auto_metric(&sample);
+        for i in order {
+            if let (Some(id), Some((vector, metadata))) = (&ids[i], parsed[i].take()) {
+                self.upsert(id.clone(), vector, metadata, None, None);
+            }
+        }
 
-        // Store metadata (replace or remove)
-        match meta {
-            Some(m) => { self.metadata.insert(id.clone(), m); }
-            None => { self.metadata.remove(&id); }
+        let results = js_sys::Array::new();
+        for (id, error) in ids.into_iter().zip(errors) {
+            let result_obj = js_sys::Object::new();
+            let id_value = id.map(JsValue::from).unwrap_or(JsValue::NULL);
+            js_sys::Reflect::set(&result_obj, &"id".into(), &id_value)?;
+            js_sys::Reflect::set(&result_obj, &"ok".into(), &error.is_none().into())?;
+            let error = error.map(JsValue::from).unwrap_or(JsValue::NULL);
+            js_sys::Reflect::set(&result_obj, &"error".into(), &error)?;
+            results.push(&result_obj);
         }
 
-        Ok(())
+        Ok(results.into())
     }
 
     /// Search for nearest neighbors
-    pub fn search(&self, query: Vec<f32>, k: usize, ef: usize) -> Result<JsValue, JsValue> {
-        if query.len() != self.hnsw_index.dimensions {
-            return Err(JsValue::from_str(&format!(
-                "Query dimension mismatch: expected {}, got {}",
-                self.hnsw_index.dimensions,
-                query.len()
-            )));
+    ///
+    /// With `include_vectors` set, each result also carries its stored vector
+    /// as a `Float32Array` `vector` field (approximate in a quantized database).
+    /// With `max_distance` set, results farther than it are dropped after the
+    /// top k are found, so fewer than k may come back. The cutoff is in the
+    /// units results report (true Euclidean distance, not its square).
+    /// With `normalize_query` set, a copy of the query is scaled to unit
+    /// length first, for corpora that mix normalized and raw vectors. It is a
+    /// no-op for an already-normalized query and changes nothing useful
+    /// under Euclidean, where a query's length is part of its position.
+    pub fn search(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        ef: usize,
+        include_vectors: Option<bool>,
+        max_distance: Option<f32>,
+        normalize_query: Option<bool>,
+    ) -> Result<JsValue, JsValue> {
+        let mut query = self.prepare_query(query)?;
+        if normalize_query.unwrap_or(false) {
+            distance::normalize(&mut query);
         }
+        self.check_query(&query)?;
+        check_max_distance(max_distance)?;
 
-        let results = self.hnsw_index.search(&query, k, ef);
+        let mut results = self.hnsw_index.search(&query, k, ef);
+        if let Some(max_distance) = max_distance {
+            results.retain(|(_, distance)| *distance <= max_distance);
+        }
+        self.results_to_js(results, include_vectors.unwrap_or(false))
+    }
 
-        // Manually create JS array to avoid serde_wasm_bindgen HashMap issues
-        let js_results = js_sys::Array::new();
+    /// `search` taking the query as a `Float32Array`, read with a single
+    /// `copy_to` into wasm memory
+    pub fn search_view(
+        &self,
+        query: &js_sys::Float32Array,
+        k: usize,
+        ef: usize,
+        include_vectors: Option<bool>,
+        max_distance: Option<f32>,
+        normalize_query: Option<bool>,
+    ) -> Result<JsValue, JsValue> {
+        self.search(query.to_vec(), k, ef, include_vectors, max_distance, normalize_query)
+    }
 
-        for (id, distance) in results {
-            let meta = self.metadata.get(&id);
+    /// `search` favoring recently inserted records
+    ///
+    /// The `ef` nearest candidates are re-ranked by distance scaled with a
+    /// recency weight that halves every `half_life_secs` of age (`now` minus
+    /// the `insert_with_timestamp` time), so a record one half-life old ranks
+    /// as if 1.5x as far (under dot product, 1.5x less similar), and records
+    /// without a timestamp as if 2x. Records of the same age keep their
+    /// distance order. Reported distances are unweighted. An infinite
+    /// half-life ranks the timestamped records by distance alone.
+    pub fn search_recent(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        ef: usize,
+        half_life_secs: f64,
+        now: u64,
+    ) -> Result<JsValue, JsValue> {
+        if half_life_secs.is_nan() || half_life_secs <= 0.0 {
+            return Err(DbError::invalid("half_life_secs must be positive").into());
+        }
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        self.results_to_js(self.hnsw_index.search_recent(&query, k, ef, half_life_secs, now), false)
+    }
 
-            let result_obj = js_sys::Object::new();
+    /// Exact nearest neighbors by comparing the query with every record
+    ///
+    /// Always returns the true top k, at one distance computation per record.
+    /// `search` does this by itself below `exact_threshold` records, if set.
+    pub fn search_exact(&self, query: Vec<f32>, k: usize) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        self.results_to_js(self.hnsw_index.search_exact(&query, k), false)
+    }
 
-            // Set id and distance
-            js_sys::Reflect::set(&result_obj, &"id".into(), &id.into())?;
-            js_sys::Reflect::set(&result_obj, &"distance".into(), &distance.into())?;
+    /// Rank a known shortlist of ids by distance to `query`, nearest first,
+    /// keeping at most k; ids not in the database are skipped, and a repeated
+    /// id is ranked once
+    ///
+    /// Costs one distance computation per listed id and no graph search, for
+    /// re-ranking candidates found some other way (e.g. a keyword filter).
+    pub fn rank_subset(&self, query: Vec<f32>, ids: Vec<String>, k: usize) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.results_to_js(self.hnsw_index.rank_ids(&query, &ids, k), false)
+    }
+
+    /// Make `search` scan every record, like `search_exact`, while the
+    /// database holds fewer than `threshold` records. The default, 0, always
+    /// uses the graph. Saved with the database.
+    pub fn set_exact_threshold(&mut self, threshold: usize) {
+        self.hnsw_index.set_exact_threshold(threshold);
+    }
+
+    /// Record count below which `search` scans instead of using the graph
+    pub fn exact_threshold(&self) -> usize {
+        self.hnsw_index.exact_threshold()
+    }
+
+    /// Set the maximum number of links per record at layer 0 (default
+    /// `2 * m`); only allowed while the database is empty. Saved with the
+    /// database.
+    pub fn set_m0(&mut self, m0: usize) -> Result<(), JsValue> {
+        self.hnsw_index
+            .set_m0(m0)
+            .map_err(|e| DbError::new(ErrorCode::InvalidArgument, e).into())
+    }
+
+    /// Maximum number of links per record at layer 0
+    pub fn m0(&self) -> usize {
+        self.hnsw_index.m0()
+    }
+
+    /// Set the highest HNSW layer a record can be assigned (default 16); only
+    /// allowed while the database is empty. Saved with the database.
+    pub fn set_max_layer_cap(&mut self, cap: usize) -> Result<(), JsValue> {
+        self.hnsw_index
+            .set_max_layer_cap(cap)
+            .map_err(|e| DbError::new(ErrorCode::InvalidArgument, e).into())
+    }
+
+    /// Highest HNSW layer a record can be assigned
+    pub fn max_layer_cap(&self) -> usize {
+        self.hnsw_index.max_layer_cap()
+    }
 
-            // Manually convert metadata HashMap to JS object
-            if let Some(meta_map) = meta {
-                let meta_obj = js_sys::Object::new();
-                for (key, value) in meta_map {
-                    js_sys::Reflect::set(&meta_obj, &key.as_str().into(), &value.as_str().into())?;
+    /// Run `search` for each query in an array (arrays or Float32Arrays)
+    ///
+    /// Returns one entry per query in input order: the query's results array,
+    /// or an `{ error, code }` object if that query is malformed or has the
+    /// wrong dimension, so one bad query doesn't fail the batch.
+    pub fn search_batch(&self, queries: JsValue, k: usize, ef: usize) -> Result<JsValue, JsValue> {
+        if !js_sys::Array::is_array(&queries) {
+            return Err(DbError::invalid("Queries must be an array of vectors").into());
+        }
+        let out = js_sys::Array::new();
+        for (i, item) in js_sys::Array::from(&queries).iter().enumerate() {
+            match self.parse_query(i, item) {
+                Ok(query) => out.push(&self.results_to_js(self.hnsw_index.search(&query, k, ef), false)?),
+                Err(e) => {
+                    let error_obj = js_sys::Object::new();
+                    js_sys::Reflect::set(&error_obj, &"error".into(), &e.message.into())?;
+                    js_sys::Reflect::set(&error_obj, &"code".into(), &e.code.as_str().into())?;
+                    out.push(&error_obj)
                 }
-                js_sys::Reflect::set(&result_obj, &"metadata".into(), &meta_obj)?;
-            } else {
-                js_sys::Reflect::set(&result_obj, &"metadata".into(), &JsValue::NULL)?;
-            }
+            };
+        }
+        Ok(out.into())
+    }
 
-            js_results.push(&result_obj);
+    /// `search` plus an audit record of how the results were produced
+    ///
+    /// Returns `{ results, provenance }`, where `results` is what `search`
+    /// returns and `provenance` is `{ query_hash, k, ef, effective_ef, metric,
+    /// timestamp, results: [{ id, distance }] }`. The timestamp is supplied by
+    /// the caller so the record matches the application's clock.
+    pub fn search_with_provenance(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        ef: usize,
+        timestamp: f64,
+    ) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+
+        let results = self.hnsw_index.search(&query, k, ef);
+        let provenance = SearchProvenance {
+            query_hash: query_hash(&query),
+            k,
+            ef,
+            effective_ef: self.hnsw_index.effective_ef(k, ef),
+            metric: self.hnsw_index.metric.name(),
+            timestamp,
+            results: results
+                .iter()
+                .map(|(id, distance)| Neighbor { id: id.clone(), distance: *distance })
+                .collect(),
+        };
+
+        let out = js_sys::Object::new();
+        js_sys::Reflect::set(&out, &"results".into(), &self.results_to_js(results, false)?)?;
+        js_sys::Reflect::set(&out, &"provenance".into(), &to_js(&provenance)?)?;
+        Ok(out.into())
+    }
+
+    /// Store default `k` and `ef` for `search_default`
+    ///
+    /// The defaults are saved by `serialize`/`serialize_binary`, so a shipped
+    /// database carries its tuned query parameters. This `ef` wins over the
+    /// one from `set_ef`.
+    pub fn set_search_defaults(&mut self, k: usize, ef: usize) -> Result<(), JsValue> {
+        if k == 0 || ef == 0 {
+            return Err(DbError::invalid("k and ef must be at least 1").into());
         }
+        self.search_defaults = Some(SearchDefaults { k, ef });
+        Ok(())
+    }
 
-        Ok(js_results.into())
+    /// The stored `{ k, ef }` defaults, or null when none are set
+    pub fn search_defaults(&self) -> Result<JsValue, JsValue> {
+        match &self.search_defaults {
+            Some(defaults) => to_js(defaults),
+            None => Ok(JsValue::NULL),
+        }
     }
 
-    /// Get a vector and its metadata by ID
-    pub fn get(&self, id: String) -> Result<JsValue, JsValue> {
-        match self.hnsw_index.get_vector(&id) {
-            Some(vector) => {
-                let result_obj = js_sys::Object::new();
-                js_sys::Reflect::set(&result_obj, &"id".into(), &id.clone().into())?;
+    /// `search` with the stored defaults, or k = 10 and the index's
+    /// `default_ef` when none are set
+    pub fn search_default(&self, query: Vec<f32>) -> Result<JsValue, JsValue> {
+        let SearchDefaults { k, ef } = self.search_defaults.unwrap_or(SearchDefaults {
+            k: DEFAULT_SEARCH_K,
+            ef: self.hnsw_index.default_ef(),
+        });
+        self.search(query, k, ef, None, None, None)
+    }
 
-                let js_vec = js_sys::Float32Array::new_with_length(vector.len() as u32);
-                js_vec.copy_from(vector);
-                js_sys::Reflect::set(&result_obj, &"vector".into(), &js_vec.into())?;
+    /// Set the index's default `ef`, used when a search doesn't give one;
+    /// saved with the database
+    pub fn set_ef(&mut self, ef: usize) -> Result<(), JsValue> {
+        self.hnsw_index.set_ef(ef).map_err(|e| DbError::invalid(e).into())
+    }
 
-                if let Some(meta_map) = self.metadata.get(&id) {
-                    let meta_obj = js_sys::Object::new();
-                    for (key, value) in meta_map {
-                        js_sys::Reflect::set(&meta_obj, &key.as_str().into(), &value.as_str().into())?;
-                    }
-                    js_sys::Reflect::set(&result_obj, &"metadata".into(), &meta_obj)?;
-                } else {
-                    js_sys::Reflect::set(&result_obj, &"metadata".into(), &JsValue::NULL)?;
-                }
+    /// The index's default `ef`: the value from `set_ef`, or `ef_construction`
+    pub fn default_ef(&self) -> usize {
+        self.hnsw_index.default_ef()
+    }
+
+    /// The `ef` from `set_ef`, or undefined if it was never called
+    pub fn ef_setting(&self) -> Option<usize> {
+        self.hnsw_index.ef_setting()
+    }
+
+    /// All records within `radius` of the query, nearest first
+    ///
+    /// Distances use the same units as `search` (e.g. true Euclidean distance,
+    /// `1 - cos` for cosine). `ef` bounds exploration outside the radius; every
+    /// in-radius node reached is returned regardless of `ef`.
+    pub fn search_radius(&self, query: Vec<f32>, radius: f32, ef: usize) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        self.results_to_js(self.hnsw_index.search_radius(&query, radius, ef), false)
+    }
+
+    /// Search results ranked `offset..offset + limit`, for infinite scroll
+    ///
+    /// Each call searches for the top `offset + limit`, so deeper pages cost
+    /// more. Use the same `ef` (at least the deepest `offset + limit`) for
+    /// every page so pages come from one ranking and never overlap.
+    pub fn search_page(&self, query: Vec<f32>, offset: usize, limit: usize, ef: usize) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        self.results_to_js(self.hnsw_index.search_page(&query, offset, limit, ef), false)
+    }
+
+    /// Search for nearest neighbors whose metadata matches every `key -> value`
+    /// pair in `filter` (e.g. `{ tenant: "acme" }`)
+    ///
+    /// A value may instead be a numeric range such as `{ gte: 10, lt: 50 }`
+    /// (bounds `gte`, `gt`, `lte`, `lt` and `eq`, all of which must hold).
+    /// Records missing a ranged field, or holding a string or boolean there,
+    /// are excluded.
+    ///
+    /// The filter is applied while traversing the graph, so up to k matching
+    /// neighbors are returned even when most records are filtered out. The
+    /// cost is extra exploration: the search keeps expanding until it has
+    /// collected `ef` matches, so very selective filters visit many more nodes
+    /// than an unfiltered search. `include_vectors` and `max_distance` work as
+    /// in `search`.
+    pub fn search_filtered(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        ef: usize,
+        filter: JsValue,
+        include_vectors: Option<bool>,
+        max_distance: Option<f32>,
+    ) -> Result<JsValue, JsValue> {
+        let query = self.prepare_query(query)?;
+        self.check_query(&query)?;
+        check_max_distance(max_distance)?;
+        let filter = parse_filter(filter)?;
 
-                Ok(result_obj.into())
+        let mut results = self
+            .hnsw_index
+            .search_filtered(&query, k, ef, |id| filter.matches(self.metadata.get(id)));
+        if let Some(max_distance) = max_distance {
+            results.retain(|(_, distance)| *distance <= max_distance);
+        }
+        self.results_to_js(results, include_vectors.unwrap_or(false))
+    }
+
+    /// Fetch several records at once: an array in input order holding
+    /// `{ id, vector, metadata }` for each stored id and null for missing ones
+    ///
+    /// Unlike `get`, this doesn't count as an access under a capacity limit.
+    pub fn get_many(&self, ids: Vec<String>) -> Result<JsValue, JsValue> {
+        let records = js_sys::Array::new();
+        for id in ids {
+            records.push(&self.record_js(&id)?);
+        }
+        Ok(records.into())
+    }
+
+    /// Distance between two stored records, in the same units as `search`
+    /// results (true Euclidean distance rather than its square)
+    pub fn distance_between(&self, id_a: String, id_b: String) -> Result<f32, JsValue> {
+        self.ensure_vectors()?;
+        for id in [&id_a, &id_b] {
+            if !self.hnsw_index.contains(id) {
+                return Err(DbError::new(ErrorCode::NotFound, format!("Unknown id: {}", id)).into());
             }
-            None => Ok(JsValue::NULL),
         }
+        self.hnsw_index
+            .pair_distance(&id_a, &id_b)
+            .ok_or_else(|| DbError::new(ErrorCode::NotFound, "Unknown id").into())
+    }
+
+    /// Get a vector and its metadata by ID
+    ///
+    /// After `optimize_storage("drop-vectors")` the record's `vector` is null
+    /// until it is re-inserted.
+    pub fn get(&self, id: String) -> Result<JsValue, JsValue> {
+        if self.hnsw_index.contains(&id) {
+            self.touch(&id);
+        }
+        self.record_js(&id)
+    }
+
+    /// Just the metadata object of a record, or null if it has none or the id
+    /// is unknown; cheaper than `get` since the vector isn't copied
+    pub fn get_metadata(&self, id: String) -> Result<JsValue, JsValue> {
+        metadata_to_js(self.metadata.get(&id))
+    }
+
+    /// Replace the metadata of an existing record without touching the graph.
+    /// Null/undefined metadata removes it. Returns false if the id is unknown.
+    pub fn update_metadata(&mut self, id: String, metadata: JsValue) -> Result<bool, JsValue> {
+        self.ensure_writable()?;
+        if !self.hnsw_index.contains(&id) {
+            return Ok(false);
+        }
+        let metadata = parse_metadata(metadata)?;
+        self.record_change(&id);
+        match metadata {
+            Some(m) => { self.metadata.insert(id, m); }
+            None => { self.metadata.remove(&id); }
+        }
+        Ok(true)
     }
 
     /// Check if a vector exists by ID
@@ -167,113 +1000,1497 @@ impl VectorDB {
         Ok(js_arr.into())
     }
 
-    /// Delete a vector by ID
-    pub fn delete(&mut self, id: String) -> bool {
-        self.metadata.remove(&id);
-        self.hnsw_index.delete(&id)
+    /// One page of ids in sorted order: `{ ids, total }`
+    ///
+    /// Lets large databases be listed in bounded chunks instead of one array.
+    /// The ids are sorted on each call, so pages are stable while the
+    /// database is not modified.
+    pub fn list_ids_page(&self, offset: usize, limit: usize) -> Result<JsValue, JsValue> {
+        let (ids, total) = self.hnsw_index.ids_page(offset, limit);
+        let js_ids = js_sys::Array::new();
+        for id in ids {
+            js_ids.push(&id.into());
+        }
+        let page = js_sys::Object::new();
+        js_sys::Reflect::set(&page, &"ids".into(), &js_ids)?;
+        js_sys::Reflect::set(&page, &"total".into(), &(total as f64).into())?;
+        Ok(page.into())
     }
 
-    /// Delete multiple vectors by ID, returns number of deletions
-    pub fn delete_batch(&mut self, ids: Vec<String>) -> usize {
-        let mut count = 0;
+    /// One page of full `{ id, vector, metadata }` records in sorted-id order
+    ///
+    /// Like `list_ids_page` but with each record's vector and metadata, for
+    /// exporting or migrating the database in batches; a page shorter than
+    /// `limit` is the last one.
+    pub fn entries(&self, offset: usize, limit: usize) -> Result<JsValue, JsValue> {
+        let (ids, _) = self.hnsw_index.ids_page(offset, limit);
+        let records = js_sys::Array::new();
         for id in ids {
-            self.metadata.remove(&id);
-            if self.hnsw_index.delete(&id) {
-                count += 1;
-            }
+            records.push(&self.record_js(&id)?);
         }
-        count
+        Ok(records.into())
     }
 
-    /// Get total number of vectors
-    pub fn size(&self) -> usize {
-        self.hnsw_index.node_count()
+    /// Up to `n` randomly chosen `{ id, vector, metadata }` records (every
+    /// record when `n` is at least `size()`), e.g. for previews or sampled
+    /// evaluation; the same `seed` picks the same records from the same data
+    pub fn sample(&self, n: usize, seed: Option<u64>) -> Result<JsValue, JsValue> {
+        let records = js_sys::Array::new();
+        for id in self.hnsw_index.sample_ids(n, seed) {
+            records.push(&self.record_js(&id)?);
+        }
+        Ok(records.into())
     }
 
-    /// Serialize the entire database to JSON
-    pub fn serialize(&self) -> Result<String, JsValue> {
-        #[derive(Serialize)]
-        struct DBState<'a> {
-            version: u32,
-            hnsw_index: &'a hnsw::HNSWIndex,
-            metadata: &'a HashMap<String, HashMap<String, String>>,
-        }
+    /// Delete a vector by ID
+    pub fn delete(&mut self, id: String) -> Result<bool, JsValue> {
+        self.ensure_writable()?;
+        Ok(self.delete_record(&id))
+    }
 
-        let state = DBState {
-            version: 1,
-            hnsw_index: &self.hnsw_index,
-            metadata: &self.metadata,
+    /// Cap the database at `max_nodes` records, evicting the least recently
+    /// accessed one whenever an insert would exceed it; undefined removes the
+    /// cap. Returns how many records were evicted to fit a lower cap at once.
+    ///
+    /// Inserts, `get` and every record a search returns count as accesses;
+    /// bulk reads (`get_many`, `entries`, `sample`) don't.
+    /// When a cap is first set, the records already stored start out in id
+    /// order, as if accessed alphabetically; changing the cap keeps the
+    /// order. Evictions go through `delete`, so they appear in
+    /// `serialize_delta`. An evicted record is gone for good, and searches
+    /// near it lose recall. The cap and access order aren't saved with the
+    /// database.
+    pub fn set_capacity_limit(&mut self, max_nodes: Option<usize>) -> Result<usize, JsValue> {
+        let Some(max_nodes) = max_nodes else {
+            *self.access.get_mut() = None;
+            return Ok(0);
         };
+        if max_nodes == 0 {
+            return Err(DbError::invalid("max_nodes must be at least 1").into());
+        }
+        if self.hnsw_index.node_count() > max_nodes {
+            self.ensure_writable()?;
+        }
+        match self.access.get_mut() {
+            Some(access) => access.set_max_nodes(max_nodes),
+            None => {
+                let mut ids = self.hnsw_index.all_ids();
+                ids.sort();
+                *self.access.get_mut() = Some(lru::AccessOrder::new(max_nodes, ids));
+            }
+        }
+        let mut evicted = 0;
+        while self.hnsw_index.node_count() > max_nodes && self.evict_least_recent() {
+            evicted += 1;
+        }
+        Ok(evicted)
+    }
 
-        serde_json::to_string(&state)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    /// The record cap set by `set_capacity_limit`, if any
+    pub fn capacity_limit(&self) -> Option<usize> {
+        self.access.borrow().as_ref().map(lru::AccessOrder::max_nodes)
     }
 
-    /// Deserialize and restore database from JSON
-    pub fn deserialize(json: String) -> Result<VectorDB, JsValue> {
-        // Try v1 format first
-        #[derive(Deserialize)]
-        struct DBStateV1 {
-            version: u32,
-            hnsw_index: hnsw::HNSWIndex,
-            metadata: HashMap<String, HashMap<String, String>>,
-        }
+    /// Ids in eviction order, least recently accessed first; empty without
+    /// a capacity limit
+    pub fn access_order(&self) -> Vec<String> {
+        self.access.borrow().as_ref().map(lru::AccessOrder::ids).unwrap_or_default()
+    }
 
-        // Legacy format (pre-version)
-        #[derive(Deserialize)]
-        #[allow(dead_code)]
-        struct DBStateLegacy {
-            vectors: HashMap<String, Vec<f32>>,
-            metadata: HashMap<String, HashMap<String, String>>,
-            hnsw_state: String,
-        }
+    /// Rebuild the HNSW graph from the stored vectors, restoring connectivity
+    /// (and recall) after many deletions. Ids, vectors and metadata are
+    /// unchanged; only the graph links are recomputed.
+    pub fn compact(&mut self) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        self.hnsw_index.rebuild().map_err(|e| DbError::new(ErrorCode::VectorsDropped, e))?;
+        Ok(())
+    }
+
+    /// Recompute the graph links of just the given records, a cheaper
+    /// targeted repair than `compact`. Unknown ids are skipped; returns how
+    /// many records were reconnected.
+    pub fn reconnect(&mut self, ids: Vec<String>) -> Result<usize, JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        Ok(self.hnsw_index.reconnect(&ids).map_err(|e| DbError::new(ErrorCode::VectorsDropped, e))?)
+    }
+
+    /// Re-run neighbor selection over every node's existing links, thinning
+    /// over-connected clusters without the cost of `compact`
+    ///
+    /// Kept links are then made two-way where there is room, so some records
+    /// can gain links even as most lose them.
+    pub fn reprune(&mut self) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        self.hnsw_index.reprune().map_err(|e| DbError::new(ErrorCode::VectorsDropped, e))?;
+        Ok(())
+    }
+
+    /// Move the entry point to the top-layer node most central to a random
+    /// sample of `sample_size` records; returns the new entry point's id
+    pub fn recompute_entry_point(&mut self, sample_size: usize) -> Result<Option<String>, JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        Ok(self.hnsw_index.recompute_entry_point(sample_size))
+    }
+
+    /// When the entry point is deleted, replace it with `recompute_entry_point`
+    /// over `sample_size` records instead of any top-layer node (undefined restores that default)
+    pub fn set_medoid_entry_point(&mut self, sample_size: Option<usize>) {
+        self.hnsw_index.set_medoid_entry_point(sample_size);
+    }
+
+    /// Relink a deleted record's former graph neighbors to each other (off by
+    /// default); slows deletes down but keeps recall up under heavy deletion
+    pub fn set_repair_on_delete(&mut self, enabled: bool) {
+        self.hnsw_index.set_repair_on_delete(enabled);
+    }
+
+    /// Whether deletes relink the deleted record's former neighbors
+    pub fn repair_on_delete(&self) -> bool {
+        self.hnsw_index.repair_on_delete()
+    }
+
+    /// Store vectors at unit length under cosine, so each distance skips the
+    /// stored vector's magnitude (off by default)
+    ///
+    /// Enabling normalizes the records already stored, and `get` then returns
+    /// unit vectors. The setting is saved with the database. Fails under any
+    /// other metric.
+    pub fn set_normalized(&mut self, enabled: bool) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.hnsw_index.set_normalized(enabled).map_err(DbError::invalid)?;
+        self.changes.get_mut().needs_snapshot = true;
+        Ok(())
+    }
 
-        if let Ok(state) = serde_json::from_str::<DBStateV1>(&json) {
-            if state.version != 1 {
-                return Err(JsValue::from_str(&format!(
-                    "Unsupported database version: {}",
-                    state.version
-                )));
+    /// Whether vectors are stored at unit length
+    pub fn normalized(&self) -> bool {
+        self.hnsw_index.normalized()
+    }
+
+    /// Delete a vector by ID and return the removed `{ id, vector, metadata }`
+    /// record (or null if absent), e.g. for undo buffers
+    pub fn remove(&mut self, id: String) -> Result<JsValue, JsValue> {
+        self.ensure_writable()?;
+        self.record_change(&id);
+        if let Some(access) = self.access.get_mut() {
+            access.forget(&id);
+        }
+        let metadata = self.metadata.remove(&id);
+        let original = self.originals.remove(&id);
+        let dropped = self.hnsw_index.is_dropped(&id);
+        match self.hnsw_index.remove(&id) {
+            Some(vector) => {
+                let vector = original.as_deref().unwrap_or(&vector);
+                record_to_js(&id, (!dropped).then_some(vector), metadata.as_ref())
             }
-            return Ok(VectorDB {
-                hnsw_index: state.hnsw_index,
-                metadata: state.metadata,
-            });
+            None => Ok(JsValue::NULL),
         }
+    }
 
-        // Fall back to legacy format
-        let state: DBStateLegacy = serde_json::from_str(&json)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        let hnsw_index: hnsw::HNSWIndex = serde_json::from_str(&state.hnsw_state)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        Ok(VectorDB {
-            hnsw_index,
-            metadata: state.metadata,
-        })
+    /// Delete multiple vectors by ID, returns number of deletions
+    pub fn delete_batch(&mut self, ids: Vec<String>) -> Result<usize, JsValue> {
+        self.ensure_writable()?;
+        Ok(ids.iter().filter(|id| self.delete_record(id)).count())
     }
-}
 
-/// Standalone distance functions exposed to JS
-#[wasm_bindgen]
-pub fn cosine_similarity(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
-    if a.len() != b.len() {
-        return Err(JsValue::from_str("Vectors must have same dimensions"));
+    /// Graph integrity problems found by walking the index (missing entry
+    /// point, links to missing nodes, nodes without layer-0 links); empty when
+    /// the graph is sound. Useful as a self-check after `deserialize`.
+    pub fn validate(&self) -> Vec<String> {
+        self.hnsw_index.validate().err().unwrap_or_default()
     }
-    Ok(distance::cosine_similarity(&a, &b))
-}
 
-#[wasm_bindgen]
-pub fn euclidean_distance(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
-    if a.len() != b.len() {
-        return Err(JsValue::from_str("Vectors must have same dimensions"));
+    /// `validate`, also listing every graph link whose target doesn't link
+    /// back. Pruning leaves such one-way links in a healthy graph, so expect
+    /// some on any database past a few dozen records.
+    pub fn validate_reciprocal(&self) -> Vec<String> {
+        self.hnsw_index.validate_reciprocal().err().unwrap_or_default()
     }
-    Ok(distance::euclidean_distance(&a, &b))
-}
 
-#[wasm_bindgen]
-pub fn dot_product(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
-    if a.len() != b.len() {
-        return Err(JsValue::from_str("Vectors must have same dimensions"));
+    /// Remove every vector and its metadata, keeping the dimensions, metric,
+    /// index parameters and search defaults
+    pub fn clear(&mut self) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.changes.get_mut().ids.extend(self.hnsw_index.all_ids());
+        self.hnsw_index.clear();
+        self.metadata.clear();
+        self.originals.clear();
+        if let Some(access) = self.access.get_mut() {
+            access.clear();
+        }
+        Ok(())
     }
-    Ok(distance::dot_product(&a, &b))
+
+    /// Release memory, returning the approximate bytes freed
+    ///
+    /// `"dedup-metadata"` drops empty metadata objects and trims the spare
+    /// capacity of every metadata map and string. Equal values in different
+    /// records stay separate strings; only slack is reclaimed.
+    ///
+    /// `"drop-vectors"` discards every vector payload (and a projecting
+    /// database's originals) but keeps ids, metadata and graph links, for
+    /// workloads that only look records up by id. Afterwards `get` returns
+    /// `vector: null`, exports skip the dropped records, and searches and
+    /// other distance-based calls fail. Only dropped ids can be written, and
+    /// re-inserting one refills its vector in place; once every record has
+    /// been re-inserted, search works again on the original graph. The graph
+    /// links were chosen for the dropped vectors, so a record must come back
+    /// with the same vector (anything else fails) and without a per-insert
+    /// `m` or `ef_construction`.
+    pub fn optimize_storage(&mut self, mode: String) -> Result<usize, JsValue> {
+        self.ensure_writable()?;
+        match mode.as_str() {
+            "dedup-metadata" => {
+                let before = self.metadata.len();
+                self.metadata.retain(|_, meta| !meta.is_empty());
+                let mut freed = (before - self.metadata.len()) * std::mem::size_of::<Metadata>();
+                for meta in self.metadata.values_mut() {
+                    let entries: Vec<(String, MetadataValue)> = meta.drain().collect();
+                    let mut fitted = HashMap::with_capacity(entries.len());
+                    for (mut key, mut value) in entries {
+                        freed += key.capacity() - key.len();
+                        key.shrink_to_fit();
+                        if let MetadataValue::String(value) = &mut value {
+                            freed += value.capacity() - value.len();
+                            value.shrink_to_fit();
+                        }
+                        fitted.insert(key, value);
+                    }
+                    *meta = fitted;
+                }
+                self.metadata.shrink_to_fit();
+                Ok(freed)
+            }
+            "drop-vectors" => {
+                let originals = std::mem::take(&mut self.originals);
+                let freed = originals.values().map(|v| v.len() * std::mem::size_of::<f32>()).sum::<usize>()
+                    + self.hnsw_index.drop_vectors();
+                self.changes.get_mut().needs_snapshot = true;
+                Ok(freed)
+            }
+            _ => Err(DbError::invalid(format!(
+                "Unknown storage mode: {} (expected \"dedup-metadata\" or \"drop-vectors\")",
+                mode
+            ))
+            .into()),
+        }
+    }
+
+    /// Switch to another metric ("euclidean", "cosine", "dotproduct",
+    /// "manhattan", "angular", "minkowski" with optional `p`, "hamming" or
+    /// "jaccard")
+    /// and rebuild the graph under it
+    ///
+    /// Graph links are chosen by distance, so swapping the metric alone would
+    /// leave a graph built for the old one. Ids, vectors and metadata are kept.
+    pub fn reindex_with_metric(&mut self, metric: String, p: Option<f32>) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        let metric = parse_metric(&metric, p).ok_or_else(|| DbError::invalid(format!("Unknown metric: {}", metric)))?;
+        self.hnsw_index
+            .reindex_with_metric(metric)
+            .map_err(|e| DbError::new(ErrorCode::VectorsDropped, e))?;
+        self.changes.get_mut().needs_snapshot = true;
+        Ok(())
+    }
+
+    /// Migrate to a larger dimensionality by zero-padding every stored vector.
+    ///
+    /// Existing records keep their relative distances, and subsequent inserts
+    /// and searches must use the new dimension. Shrinking is not supported.
+    pub fn reproject(&mut self, new_dimensions: usize) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        self.ensure_vectors()?;
+        if self.projection.is_some() {
+            return Err(DbError::invalid("Cannot pad the dimensions of a randomly projected database").into());
+        }
+        self.hnsw_index
+            .pad_dimensions(new_dimensions)
+            .map_err(DbError::invalid)?;
+        self.changes.get_mut().needs_snapshot = true;
+        Ok(())
+    }
+
+    /// Get total number of vectors
+    pub fn size(&self) -> usize {
+        self.hnsw_index.node_count()
+    }
+
+    /// Scale search ef with k: effective ef becomes max(ef, k * factor).
+    /// The factor defaults to 2 when omitted.
+    pub fn enable_auto_ef(&mut self, factor: Option<usize>) {
+        self.hnsw_index
+            .set_auto_ef_factor(Some(factor.unwrap_or(hnsw::DEFAULT_AUTO_EF_FACTOR)));
+    }
+
+    /// Disable automatic ef scaling (effective ef is max(ef, k))
+    pub fn disable_auto_ef(&mut self) {
+        self.hnsw_index.set_auto_ef_factor(None);
+    }
+
+    /// Toggle the diversity heuristic for neighbor selection (on by default)
+    pub fn set_neighbor_heuristic(&mut self, enabled: bool) {
+        self.hnsw_index.set_neighbor_heuristic(enabled);
+    }
+
+    /// Reject zero vectors on insert under the cosine and angular metrics (off
+    /// by default). Zero queries are always rejected under those metrics.
+    pub fn set_reject_zero_vectors(&mut self, enabled: bool) {
+        self.hnsw_index.set_reject_zero_vectors(enabled);
+    }
+
+    /// Toggle cached-norm Euclidean distances for high-dimensional vectors (on by default)
+    pub fn set_cached_norms(&mut self, enabled: bool) {
+        self.hnsw_index.set_cached_norms(enabled);
+    }
+
+    /// Reuse upper-layer descents for queries falling in the same
+    /// `cell_size`-wide grid cell; cleared on every insert or delete
+    pub fn enable_seed_cache(&mut self, cell_size: f32) -> Result<(), JsValue> {
+        if !(cell_size.is_finite() && cell_size > 0.0) {
+            return Err(DbError::invalid("cell_size must be a positive finite number").into());
+        }
+        self.hnsw_index.enable_seed_cache(cell_size);
+        Ok(())
+    }
+
+    /// Turn off the query seed cache
+    pub fn disable_seed_cache(&mut self) {
+        self.hnsw_index.disable_seed_cache();
+    }
+
+    /// `{ cell_size, entries, hits, misses }` for the seed cache, or null when disabled
+    pub fn seed_cache_stats(&self) -> Result<JsValue, JsValue> {
+        match self.hnsw_index.seed_cache_stats() {
+            Some(stats) => to_js(&stats),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    /// Backfill heuristic neighbor selections up to m with discarded candidates
+    pub fn set_keep_pruned_connections(&mut self, enabled: bool) {
+        self.hnsw_index.set_keep_pruned_connections(enabled);
+    }
+
+    /// Current automatic ef scaling factor, or undefined when disabled
+    pub fn auto_ef_factor(&self) -> Option<usize> {
+        self.hnsw_index.auto_ef_factor()
+    }
+
+    /// The layer-0 candidate list size that `search(query, k, ef)` will use
+    pub fn effective_ef(&self, k: usize, ef: usize) -> usize {
+        self.hnsw_index.effective_ef(k, ef)
+    }
+
+    /// Average fraction of the exact top-k (brute force) that `search(query, k, ef)`
+    /// finds over the given queries, for tuning m / ef_construction / ef
+    pub fn estimate_recall(&self, queries: JsValue, k: usize, ef: usize) -> Result<f32, JsValue> {
+        let queries = self.parse_queries(queries)?;
+        Ok(self.hnsw_index.estimate_recall(&queries, k, ef))
+    }
+
+    /// Smallest ef among k, 2k, 4k, ... (at most 4096) whose `estimate_recall`
+    /// over `queries` reaches `target_recall`, or the largest ef tried if none does
+    pub fn tune_ef(&self, queries: JsValue, k: usize, target_recall: f32) -> Result<usize, JsValue> {
+        if !(target_recall > 0.0 && target_recall <= 1.0) {
+            return Err(DbError::invalid("target_recall must be in (0, 1]").into());
+        }
+        let queries = self.parse_queries(queries)?;
+        Ok(self.hnsw_index.tune_ef(&queries, k, target_recall))
+    }
+
+    /// Leave-one-out recall over `samples` stored vectors: each is taken out,
+    /// searched for, and scored against the exact top-k of the rest
+    ///
+    /// Measures recall on the database's own data without separate queries.
+    /// The database itself is not modified. Returns 0 while vectors are
+    /// dropped by `optimize_storage`.
+    pub fn loo_recall(&self, k: usize, ef: usize, samples: usize) -> f32 {
+        if self.hnsw_index.vectors_dropped() {
+            return 0.0;
+        }
+        self.hnsw_index.loo_recall(k, ef, samples)
+    }
+
+    /// Average top-k id overlap between this database and a serialized one
+    /// over the same queries, e.g. to validate a rebuild or migration
+    pub fn compare_against(&self, other_json: String, queries: JsValue, k: usize, ef: usize) -> Result<f32, JsValue> {
+        let other = VectorDB::deserialize(other_json)?;
+        let queries = self.parse_queries(queries)?;
+        if other.hnsw_index.dimensions != self.hnsw_index.dimensions {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!(
+                    "Dimension mismatch: this database has {}, other has {}",
+                    self.hnsw_index.dimensions, other.hnsw_index.dimensions
+                ),
+            )
+            .into());
+        }
+        Ok(self.hnsw_index.result_overlap(&other.hnsw_index, &queries, k, ef))
+    }
+
+    /// Pairs `{ a, b, distance }` where record `a` here and record `b` in the
+    /// serialized database are each other's nearest neighbor, e.g. for
+    /// deduplicating across sources
+    pub fn mutual_nn(&self, other_json: String, ef: usize) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        let other = VectorDB::deserialize(other_json)?;
+        if other.hnsw_index.dimensions != self.hnsw_index.dimensions {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!(
+                    "Dimension mismatch: this database has {}, other has {}",
+                    self.hnsw_index.dimensions, other.hnsw_index.dimensions
+                ),
+            )
+            .into());
+        }
+        if other.hnsw_index.metric != self.hnsw_index.metric {
+            return Err(DbError::invalid(format!(
+                "Metric mismatch: this database uses {:?}, other uses {:?}",
+                self.hnsw_index.metric, other.hnsw_index.metric
+            ))
+            .into());
+        }
+        to_js(&self.hnsw_index.mutual_nearest_neighbors(&other.hnsw_index, ef))
+    }
+
+    /// Within-cluster sum of squares from k-means for each k, to locate the elbow
+    pub fn cluster_elbow(&self, k_values: Vec<usize>, max_iters: usize) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        let records = self.hnsw_index.vectors_by_id();
+        let vectors: Vec<&[f32]> = records.iter().map(|(_, v)| v.as_ref()).collect();
+        to_js(&kmeans::elbow(&vectors, &k_values, max_iters))
+    }
+
+    /// For each distinct value of metadata `group_key`, the `k_per_group`
+    /// members nearest to that group's centroid (records without the key are
+    /// ignored). Returns `[{ group, size, representatives: [{ id, distance }] }]`
+    /// sorted by group value.
+    pub fn group_representatives(&self, group_key: String, k_per_group: usize) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        let mut groups: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for (id, vector) in self.hnsw_index.vectors_by_id() {
+            if let Some(group) = self.metadata.get(id).and_then(|m| m.get(&group_key)) {
+                groups.entry(group.to_string()).or_default().push((id, vector));
+            }
+        }
+
+        let summaries: Vec<GroupRepresentatives> = groups
+            .into_iter()
+            .map(|(group, members)| {
+                let vectors: Vec<&[f32]> = members.iter().map(|(_, v)| v.as_ref()).collect();
+                let ids: Vec<&str> = members.iter().map(|(id, _)| *id).collect();
+                let center = kmeans::centroid(&vectors);
+                GroupRepresentatives {
+                    group: group.to_string(),
+                    size: members.len(),
+                    representatives: self
+                        .hnsw_index
+                        .rank_ids(&center, &ids, k_per_group)
+                        .into_iter()
+                        .map(|(id, distance)| Neighbor { id, distance })
+                        .collect(),
+                }
+            })
+            .collect();
+        to_js(&summaries)
+    }
+
+    /// Index shape for tuning and debugging: `{ node_count, max_layer,
+    /// dimensions, metric, m, ef_construction, avg_layer0_connections,
+    /// nodes_per_layer }`
+    pub fn stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.hnsw_index.stats())
+    }
+
+    /// Where searches start: `{ entry_point, max_layer }`, with
+    /// `entry_point` null when the database is empty
+    ///
+    /// Deleting the entry point hands the role to the node with the most
+    /// layers, which lowers `max_layer` when no other node reached it.
+    pub fn entry_info(&self) -> Result<JsValue, JsValue> {
+        let out = js_sys::Object::new();
+        let entry_point = self.hnsw_index.entry_point().map_or(JsValue::NULL, JsValue::from_str);
+        js_sys::Reflect::set(&out, &"entry_point".into(), &entry_point)?;
+        js_sys::Reflect::set(&out, &"max_layer".into(), &(self.hnsw_index.max_layer() as f64).into())?;
+        Ok(out.into())
+    }
+
+    /// The most typical record: `{ id, distance }` of the stored vector nearest
+    /// to the mean of all vectors under the configured metric, or null when
+    /// the database is empty
+    pub fn medoid(&self) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        match self.hnsw_index.medoid() {
+            Some((id, distance)) => to_js(&Neighbor { id, distance }),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    /// Observed layer distribution versus the expected geometric decay, with anomalies
+    pub fn layer_health(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.hnsw_index.layer_health())
+    }
+
+    /// `{ layer, nodes, edges }` for one graph layer, with each directed link
+    /// as a `[from, to]` pair, e.g. for a force-directed layout
+    pub fn to_adjacency(&self, layer: usize) -> Result<JsValue, JsValue> {
+        to_js(&self.hnsw_index.to_adjacency(layer))
+    }
+
+    /// One graph layer as a Graphviz DOT digraph
+    pub fn to_dot(&self, layer: usize) -> String {
+        self.hnsw_index.to_dot(layer)
+    }
+
+    /// Per-node navigational importance from `samples` sample searches
+    ///
+    /// Returns `[{ id, score }]`, highest score first, where `score` is the
+    /// fraction of sample searches that routed through the node. High-score
+    /// nodes hold the graph together and are poor deletion candidates.
+    pub fn navigation_importance(&self, samples: usize, ef: usize) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        to_js(&self.hnsw_index.navigation_importance(samples, ef))
+    }
+
+    /// Distribution of sampled nodes' distances to their k-th nearest neighbor
+    pub fn density_profile(&self, k: usize, ef: usize, samples: usize) -> Result<JsValue, JsValue> {
+        self.ensure_vectors()?;
+        to_js(&self.hnsw_index.density_profile(k, ef, samples))
+    }
+
+    /// Number of records whose metadata matches every `{ key: value }` pair
+    /// in `filter`, without returning them
+    ///
+    /// One pass over the metadata. An empty, null or undefined filter counts
+    /// every record, including those without metadata.
+    pub fn count_where(&self, filter: JsValue) -> Result<usize, JsValue> {
+        let filter = parse_filter(filter)?;
+        if filter.is_empty() {
+            return Ok(self.hnsw_index.node_count());
+        }
+        Ok(self.metadata.values().filter(|meta| filter.matches(Some(meta))).count())
+    }
+
+    /// Export records whose metadata matches `filter` as NDJSON chunks
+    ///
+    /// Returns an array of strings, each holding up to `chunk_size` newline-
+    /// terminated JSON records (`{ id, vector, metadata }`) in id order, so a
+    /// large subset can be written out incrementally instead of as one string.
+    /// A null/undefined filter exports every record.
+    pub fn export_filtered_ndjson_chunks(
+        &self,
+        filter: JsValue,
+        chunk_size: usize,
+    ) -> Result<JsValue, JsValue> {
+        if chunk_size == 0 {
+            return Err(DbError::invalid("chunk_size must be at least 1").into());
+        }
+        let filter = parse_filter(filter)?;
+
+        let chunks = js_sys::Array::new();
+        let mut chunk = String::new();
+        let mut in_chunk = 0;
+        for (id, vector) in self.hnsw_index.vectors_by_id() {
+            let metadata = self.metadata.get(id);
+            if !filter.matches(metadata) {
+                continue;
+            }
+            let record = VectorRecord {
+                id: id.to_string(),
+                vector: self.originals.get(id).cloned().unwrap_or_else(|| vector.to_vec()),
+                metadata: metadata.cloned(),
+            };
+            let line = serde_json::to_string(&record)
+                .map_err(|e| DbError::parse(format!("Export error: {}", e)))?;
+            chunk.push_str(&line);
+            chunk.push('\n');
+            in_chunk += 1;
+            if in_chunk == chunk_size {
+                chunks.push(&JsValue::from_str(&chunk));
+                chunk.clear();
+                in_chunk = 0;
+            }
+        }
+        if in_chunk > 0 {
+            chunks.push(&JsValue::from_str(&chunk));
+        }
+        Ok(chunks.into())
+    }
+
+    /// Import a JSON array of rows in an external shape, e.g.
+    /// `[{"text": "...", "embedding": [...]}]`
+    ///
+    /// `id_field` and `vector_field` name the fields holding each row's id
+    /// (a string or number) and vector; `metadata_fields` is an optional array
+    /// of field names copied into metadata, with non-string values stored as
+    /// their JSON text. Rows are validated and upserted one by one. Returns
+    /// how many were inserted; rows that fail are skipped, and `load_warnings`
+    /// then lists one "Row i skipped: ..." entry for each.
+    pub fn import_json(
+        &mut self,
+        json: String,
+        id_field: String,
+        vector_field: String,
+        metadata_fields: JsValue,
+    ) -> Result<usize, JsValue> {
+        self.ensure_writable()?;
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_str(&json).map_err(|e| DbError::parse(format!("Import error: {}", e)))?;
+        let metadata_fields: Vec<String> = if metadata_fields.is_null() || metadata_fields.is_undefined() {
+            Vec::new()
+        } else {
+            serde_wasm_bindgen::from_value(metadata_fields)
+                .map_err(|e| DbError::invalid(format!("metadata_fields must be an array of strings: {}", e)))?
+        };
+
+        let mut parsed = Vec::new();
+        self.load_warnings.clear();
+        for (i, row) in rows.iter().enumerate() {
+            match self.map_json_row(row, &id_field, &vector_field, &metadata_fields) {
+                Ok(record) => parsed.push(record),
+                Err(e) => self.load_warnings.push(format!("Row {} skipped: {}", i, e)),
+            }
+        }
+
+        let sample: Vec<&[f32]> = parsed
+            .iter()
+            .take(hnsw::AUTO_METRIC_SAMPLE)
+            .map(|(_, (v, _))| v.as_slice())
+            .collect();
+        self.hnsw_index.resolve_auto_metric(&sample);
+        let inserted = parsed.len();
+        for (id, (vector, metadata)) in parsed {
+            self.upsert(id, vector, metadata, None, None);
+        }
+        Ok(inserted)
+    }
+
+    /// The database as a plain JS object, skipping the `serialize` +
+    /// `JSON.parse` round trip: `{ version, params: { dimensions, m,
+    /// ef_construction, metric, ef }, records: [{ id, vector, metadata }] }`
+    ///
+    /// Records are in id order and shaped like `get` results. Graph links
+    /// aren't included, so this is for inspection; save with `serialize`.
+    pub fn to_object(&self) -> Result<JsValue, JsValue> {
+        let dimensions = self
+            .projection
+            .as_ref()
+            .map_or(self.hnsw_index.dimensions, |p| p.input_dims());
+        let params = js_sys::Object::new();
+        js_sys::Reflect::set(&params, &"dimensions".into(), &(dimensions as f64).into())?;
+        js_sys::Reflect::set(&params, &"m".into(), &(self.hnsw_index.m() as f64).into())?;
+        js_sys::Reflect::set(&params, &"m0".into(), &(self.hnsw_index.m0() as f64).into())?;
+        js_sys::Reflect::set(
+            &params,
+            &"ef_construction".into(),
+            &(self.hnsw_index.ef_construction() as f64).into(),
+        )?;
+        js_sys::Reflect::set(&params, &"metric".into(), &self.metric().into())?;
+        js_sys::Reflect::set(&params, &"ef".into(), &(self.hnsw_index.default_ef() as f64).into())?;
+
+        let out = js_sys::Object::new();
+        js_sys::Reflect::set(&out, &"version".into(), &STATE_VERSION.into())?;
+        js_sys::Reflect::set(&out, &"params".into(), &params)?;
+        js_sys::Reflect::set(&out, &"records".into(), &self.entries(0, usize::MAX)?)?;
+        Ok(out.into())
+    }
+
+    /// Serialize the entire database to JSON
+    pub fn serialize(&self) -> Result<String, JsValue> {
+        let state = DBState {
+            version: STATE_VERSION,
+            hnsw_index: &self.hnsw_index,
+            metadata: &self.metadata,
+            search_defaults: self.search_defaults,
+            projection: self.projection.as_ref(),
+            originals: &self.originals,
+        };
+
+        let json = serde_json::to_string(&state).map_err(DbError::parse)?;
+        self.changes.take();
+        Ok(checksum::seal(&json))
+    }
+
+    /// Serialize the database as an array of JSON strings, none much over
+    /// `chunk_bytes`, for stores that cap the size of one value (IndexedDB
+    /// in some browsers)
+    ///
+    /// The first string is a manifest holding the index settings and the
+    /// chunk and record counts; each following string is a numbered chunk of
+    /// whole records, each with its own metadata, timestamp and other per-id
+    /// entries. The manifest isn't held to `chunk_bytes`: it keeps every
+    /// setting, including the random projection's shape and seed if there is
+    /// one. Pass every string, in any order, to `deserialize_chunks`. A
+    /// record larger than `chunk_bytes` gets a chunk to itself. Each string is
+    /// sealed with a checksum, like `serialize` output.
+    pub fn serialize_chunks(&self, chunk_bytes: usize) -> Result<JsValue, JsValue> {
+        if chunk_bytes == 0 {
+            return Err(DbError::invalid("chunk_bytes must be at least 1").into());
+        }
+        let state = DBState {
+            version: STATE_VERSION,
+            hnsw_index: &self.hnsw_index,
+            metadata: &self.metadata,
+            search_defaults: self.search_defaults,
+            projection: self.projection.as_ref(),
+            originals: &self.originals,
+        };
+        let value = serde_json::to_value(&state).map_err(DbError::parse)?;
+        let pieces = chunks::split(value, chunk_bytes).map_err(DbError::parse)?;
+        self.changes.take();
+        Ok(pieces.iter().map(|piece| JsValue::from_str(piece)).collect::<js_sys::Array>().into())
+    }
+
+    /// Serialize only the records changed since the last save (`serialize`,
+    /// `serialize_binary` or `serialize_delta`) as
+    /// `{ upserts: [{ id, vector, metadata }], deletes: [id] }`
+    ///
+    /// Appending each delta after a full snapshot and replaying them in order
+    /// with `apply_delta` reconstructs the database. Deltas carry records
+    /// only: configuration changes such as `set_search_defaults` and insertion
+    /// timestamps still need a full `serialize`. After a change to the
+    /// dimensions (`reproject`), metric (`reindex_with_metric`) or
+    /// normalization (`set_normalized`) this returns an error until one is
+    /// taken.
+    pub fn serialize_delta(&mut self) -> Result<String, JsValue> {
+        self.ensure_vectors()?;
+        let changes = self.changes.get_mut();
+        if changes.needs_snapshot {
+            return Err(
+                DbError::invalid("The database changed in a way a delta can't express; call serialize() instead").into(),
+            );
+        }
+        let mut delta = DeltaState { upserts: Vec::new(), deletes: Vec::new() };
+        for id in &changes.ids {
+            let original = self.originals.get(id).map(|v| Cow::Borrowed(v.as_slice()));
+            match original.or_else(|| self.hnsw_index.get_vector(id)) {
+                Some(vector) => delta.upserts.push(VectorRecord {
+                    id: id.clone(),
+                    vector: vector.into_owned(),
+                    metadata: self.metadata.get(id).cloned(),
+                }),
+                None => delta.deletes.push(id.clone()),
+            }
+        }
+        let json = serde_json::to_string(&delta).map_err(DbError::parse)?;
+        self.changes.get_mut().ids.clear();
+        Ok(json)
+    }
+
+    /// Merge a patch written by `serialize_delta`: tombstoned ids are deleted,
+    /// then each upsert inserts or replaces its record
+    ///
+    /// Every upserted vector is validated before anything changes, so a bad
+    /// delta leaves the database untouched.
+    pub fn apply_delta(&mut self, json: String) -> Result<(), JsValue> {
+        self.ensure_writable()?;
+        let delta: DeltaState = serde_json::from_str(&json).map_err(DbError::parse)?;
+        for record in &delta.upserts {
+            self
+                .validate_input(&record.id, &record.vector)
+                .map_err(|e| DbError::new(e.code, format!("Record '{}': {}", record.id, e)))?;
+        }
+        for id in delta.deletes {
+            self.delete_record(&id);
+        }
+        for record in delta.upserts {
+            self.upsert(record.id, record.vector, record.metadata, None, None);
+        }
+        Ok(())
+    }
+
+    /// Serialize the entire database to a compact binary form
+    ///
+    /// Vectors are stored as raw little-endian f32s after a versioned header;
+    /// the graph and metadata stay JSON-encoded. Typically several times
+    /// smaller than `serialize` for high-dimensional vectors.
+    pub fn serialize_binary(&self) -> Result<Vec<u8>, JsValue> {
+        let (graph, vectors) = self.hnsw_index.split_vectors();
+        let state = DBState {
+            version: STATE_VERSION,
+            hnsw_index: &graph,
+            metadata: &self.metadata,
+            search_defaults: self.search_defaults,
+            projection: self.projection.as_ref(),
+            originals: &self.originals,
+        };
+        let json = serde_json::to_vec(&state).map_err(DbError::parse)?;
+        let bytes = binary::encode(&json, &vectors).map_err(DbError::parse)?;
+        self.changes.take();
+        Ok(bytes)
+    }
+
+    /// Deserialize a database written by `serialize_binary`
+    pub fn deserialize_binary(bytes: Vec<u8>) -> Result<VectorDB, JsValue> {
+        binary::verify_checksum(&bytes).map_err(|e| DbError::new(ErrorCode::ChecksumMismatch, e))?;
+        let (json, vectors) = binary::decode(&bytes).map_err(DbError::parse)?;
+        let state: DBStateV1 = serde_json::from_slice(json).map_err(DbError::parse)?;
+        if state.version != STATE_VERSION {
+            return Err(unsupported_version(state.version));
+        }
+        let mut hnsw_index = state.hnsw_index;
+        hnsw_index.restore_vectors(&vectors).map_err(DbError::parse)?;
+        hnsw_index.check_dimensions().map_err(DbError::parse)?;
+        check_projection(&hnsw_index, state.projection.as_ref(), &state.originals).map_err(DbError::parse)?;
+        Ok(VectorDB {
+            hnsw_index,
+            metadata: state.metadata,
+            read_only: false,
+            search_defaults: state.search_defaults,
+            load_warnings: Vec::new(),
+            changes: RefCell::default(),
+            access: RefCell::default(),
+            projection: state.projection,
+            originals: state.originals,
+        })
+    }
+
+    /// Deserialize and restore database from JSON
+    ///
+    /// Reads the top-level `version` first and hands the snapshot to that
+    /// version's parser; a snapshot without one is read as the legacy format.
+    /// Unknown versions fail with an "Unsupported database version" error.
+    ///
+    /// A snapshot with a `checksum` field (everything `serialize` writes) must
+    /// match it byte for byte, or loading fails with `CHECKSUM_MISMATCH`;
+    /// snapshots from before checksums were added load unchecked. Those can't
+    /// be told apart from a snapshot whose `checksum` key itself was damaged
+    /// or removed, so such a snapshot also loads unchecked: the checksum
+    /// catches accidental corruption of the contents, not tampering.
+    ///
+    /// Fails if any stored vector's length differs from the declared
+    /// dimensionality, rather than letting searches truncate distances.
+    pub fn deserialize(json: String) -> Result<VectorDB, JsValue> {
+        let probe: VersionProbe = serde_json::from_str(&json).map_err(DbError::parse)?;
+        if probe.checksum.is_some() && matches!(probe.version, Some(STATE_VERSION)) {
+            checksum::verify(&json).map_err(|e| DbError::new(ErrorCode::ChecksumMismatch, e))?;
+        }
+        match probe.version {
+            Some(STATE_VERSION) => VectorDB::deserialize_v1(&json),
+            Some(version) => Err(unsupported_version(version)),
+            None => VectorDB::deserialize_legacy(&json),
+        }
+    }
+
+    /// Restore a database from the strings `serialize_chunks` returned, in
+    /// any order
+    ///
+    /// Every string carries its own checksum and must match it, or loading
+    /// fails with `CHECKSUM_MISMATCH`, as does a mix of strings with and
+    /// without one. Chunks from before checksums were added load unchecked.
+    pub fn deserialize_chunks(chunks: JsValue) -> Result<VectorDB, JsValue> {
+        let pieces: Vec<String> = serde_wasm_bindgen::from_value(chunks).map_err(DbError::parse)?;
+        chunks::verify(&pieces).map_err(|e| DbError::new(ErrorCode::ChecksumMismatch, e))?;
+        let json = chunks::join(&pieces).map_err(DbError::parse)?;
+        VectorDB::deserialize(json)
+    }
+
+    /// Restore a snapshot for reads only: insert and delete return an error
+    pub fn load_readonly(json: String) -> Result<VectorDB, JsValue> {
+        let mut db = VectorDB::deserialize(json)?;
+        db.read_only = true;
+        Ok(db)
+    }
+
+    /// Allow mutations on a database loaded with `load_readonly`
+    pub fn to_mutable(&mut self) {
+        self.read_only = false;
+    }
+
+    /// Whether mutations are currently rejected
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Problems `deserialize` repaired instead of rejecting, such as metadata
+    /// for ids missing from a legacy snapshot's index; empty for a clean load
+    pub fn load_warnings(&self) -> Vec<String> {
+        self.load_warnings.clone()
+    }
+}
+
+impl VectorDB {
+    /// `search` for Rust callers, returning typed results with metadata
+    /// attached instead of JS objects
+    pub fn search_results(&self, query: &[f32], k: usize, ef: usize) -> Result<Vec<SearchResult>, String> {
+        let query = self.prepare_query(query.to_vec())?;
+        self.hnsw_index.validate_query(&query)?;
+        Ok(native::search_results(&self.hnsw_index, &self.metadata, &query, k, ef))
+    }
+
+    /// Restore a version 1 (`DBState`) snapshot
+    fn deserialize_v1(json: &str) -> Result<VectorDB, JsValue> {
+        let state: DBStateV1 = serde_json::from_str(json).map_err(DbError::parse)?;
+        state.hnsw_index.check_dimensions().map_err(DbError::parse)?;
+        check_projection(&state.hnsw_index, state.projection.as_ref(), &state.originals)
+            .map_err(DbError::parse)?;
+        Ok(VectorDB {
+            hnsw_index: state.hnsw_index,
+            metadata: state.metadata,
+            read_only: false,
+            search_defaults: state.search_defaults,
+            load_warnings: Vec::new(),
+            changes: RefCell::default(),
+            access: RefCell::default(),
+            projection: state.projection,
+            originals: state.originals,
+        })
+    }
+
+    /// Restore a pre-version snapshot, whose index is a nested JSON string
+    fn deserialize_legacy(json: &str) -> Result<VectorDB, JsValue> {
+        #[derive(Deserialize)]
+        struct DBStateLegacy {
+            vectors: HashMap<String, Vec<f32>>,
+            metadata: HashMap<String, Metadata>,
+            hnsw_state: String,
+        }
+
+        let state: DBStateLegacy = serde_json::from_str(json)
+            .map_err(DbError::parse)?;
+        let hnsw_index: hnsw::HNSWIndex = serde_json::from_str(&state.hnsw_state)
+            .map_err(DbError::parse)?;
+        let (metadata, load_warnings) =
+            validate_legacy(&hnsw_index, &state.vectors, state.metadata).map_err(DbError::parse)?;
+        Ok(VectorDB {
+            hnsw_index,
+            metadata,
+            read_only: false,
+            search_defaults: None,
+            load_warnings,
+            changes: RefCell::default(),
+            access: RefCell::default(),
+            projection: None,
+            originals: HashMap::new(),
+        })
+    }
+
+    /// Parse an array of query vectors (arrays or Float32Arrays), checking dimensions
+    fn parse_queries(&self, queries: JsValue) -> Result<Vec<Vec<f32>>, JsValue> {
+        if !js_sys::Array::is_array(&queries) {
+            return Err(DbError::invalid("Queries must be an array of vectors").into());
+        }
+        js_sys::Array::from(&queries)
+            .iter()
+            .enumerate()
+            .map(|(i, item)| self.parse_query(i, item).map_err(JsValue::from))
+            .collect()
+    }
+
+    /// Parse query `i` of a batch (array or Float32Array), checking its dimension
+    fn parse_query(&self, i: usize, item: JsValue) -> Result<Vec<f32>, DbError> {
+        let query: Vec<f32> = if item.is_instance_of::<js_sys::Float32Array>() {
+            js_sys::Float32Array::from(item).to_vec()
+        } else {
+            serde_wasm_bindgen::from_value(item).map_err(|e| DbError::invalid(format!("Query {}: {}", i, e)))?
+        };
+        let query = self
+            .prepare_query(query)
+            .map_err(|e| DbError::new(e.code, format!("Query {}: {}", i, e)))?;
+        if query.len() != self.hnsw_index.dimensions {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!(
+                    "Query {} dimension mismatch: expected {}, got {}",
+                    i,
+                    self.hnsw_index.dimensions,
+                    query.len()
+                ),
+            ));
+        }
+        self.hnsw_index
+            .validate_query(&query)
+            .map_err(|e| DbError::invalid(format!("Query {}: {}", i, e)))?;
+        Ok(query)
+    }
+
+    /// Insert or replace a validated vector and its metadata; true if an
+    /// existing record was replaced
+    fn upsert(
+        &mut self,
+        id: String,
+        vector: Vec<f32>,
+        meta: Option<Metadata>,
+        m: Option<usize>,
+        ef_construction: Option<usize>,
+    ) -> bool {
+        if !self.hnsw_index.contains(&id) {
+            let max_nodes = self.access.get_mut().as_ref().map(lru::AccessOrder::max_nodes);
+            if let Some(max_nodes) = max_nodes {
+                while self.hnsw_index.node_count() >= max_nodes && self.evict_least_recent() {}
+            }
+        }
+        self.record_change(&id);
+
+        let vector = match &self.projection {
+            Some(projection) => {
+                let projected = projection.project(&vector);
+                self.originals.insert(id.clone(), vector);
+                projected
+            }
+            None => vector,
+        };
+
+        let replaced = self.hnsw_index.contains(&id);
+        if self.hnsw_index.is_dropped(&id) {
+            // Vector dropped by `optimize_storage`: refill it in place,
+            // keeping the node's graph links
+            self.hnsw_index
+                .restore_vector(&id, vector)
+                .expect("validate_input checked the vector against the dropped one");
+        } else {
+            // Handle upsert: delete old entry if it exists
+            if replaced {
+                self.hnsw_index.delete(&id);
+            }
+
+            // Add to HNSW index
+            match ef_construction {
+                Some(ef) => self.hnsw_index.insert_with_ef(id.clone(), vector, ef),
+                None => self.hnsw_index.insert_with_m(id.clone(), vector, m),
+            }
+        }
+
+        self.touch(&id);
+        // Store metadata (replace or remove)
+        match meta {
+            Some(m) => { self.metadata.insert(id, m); }
+            None => { self.metadata.remove(&id); }
+        }
+        replaced
+    }
+
+    /// Delete a record and everything stored with it; true if it existed
+    fn delete_record(&mut self, id: &str) -> bool {
+        self.record_change(id);
+        self.metadata.remove(id);
+        self.originals.remove(id);
+        if let Some(access) = self.access.get_mut() {
+            access.forget(id);
+        }
+        self.hnsw_index.delete(id)
+    }
+
+    /// Delete the least recently accessed record; false if none is tracked
+    fn evict_least_recent(&mut self) -> bool {
+        let victim = self.access.get_mut().as_ref().and_then(|access| access.least_recent().map(str::to_string));
+        match victim {
+            Some(id) => {
+                self.delete_record(&id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `{ id, vector, metadata }` for a stored id, or null, without counting
+    /// as an access
+    fn record_js(&self, id: &str) -> Result<JsValue, JsValue> {
+        if !self.hnsw_index.contains(id) {
+            return Ok(JsValue::NULL);
+        }
+        record_to_js(id, self.stored_vector(id).as_deref(), self.metadata.get(id))
+    }
+
+    /// Note an access to `id` for the capacity limit's eviction order
+    fn touch(&self, id: &str) {
+        if let Some(access) = self.access.borrow_mut().as_mut() {
+            access.touch(id);
+        }
+    }
+
+    /// Note `id` as changed for the next `serialize_delta`
+    fn record_change(&mut self, id: &str) {
+        self.changes.get_mut().ids.insert(id.to_string());
+    }
+
+    /// Check a caller's vector for `id`: its input dimension, then the
+    /// index's checks on its projection when the database projects
+    ///
+    /// While vectors are dropped, only the dropped ids may be written.
+    fn validate_input(&self, id: &str, vector: &[f32]) -> Result<(), DbError> {
+        if self.hnsw_index.vectors_dropped() && !self.hnsw_index.is_dropped(id) {
+            return Err(DbError::new(
+                ErrorCode::VectorsDropped,
+                format!("Cannot write '{}' while vectors are dropped; re-insert every dropped record first", id),
+            ));
+        }
+        if vector.len() != self.input_dimensions() {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!("Vector dimension mismatch: expected {}, got {}", self.input_dimensions(), vector.len()),
+            ));
+        }
+        if vector.iter().any(|x| !x.is_finite()) {
+            return Err(DbError::new(ErrorCode::NonFinite, "Vector contains NaN or Infinity values"));
+        }
+        let projected = self.projection.as_ref().map(|projection| projection.project(vector));
+        let stored = projected.as_deref().unwrap_or(vector);
+        self.hnsw_index
+            .validate_vector(stored)
+            .map_err(|e| DbError::new(ErrorCode::InvalidArgument, e))?;
+        if !self.hnsw_index.matches_dropped(id, stored) {
+            return Err(DbError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "'{}' must be re-inserted with the vector that was dropped; its graph links only fit that one",
+                    id
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail for a record whose vector was dropped: re-inserting it keeps its
+    /// graph links, so a per-insert graph `setting` can't apply
+    fn ensure_not_dropped(&self, id: &str, setting: &str) -> Result<(), DbError> {
+        if self.hnsw_index.is_dropped(id) {
+            return Err(DbError::new(
+                ErrorCode::InvalidArgument,
+                format!("'{}' keeps its graph links when re-inserted after drop-vectors, so {} doesn't apply", id, setting),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Length of the vectors callers pass: the projection's input when the
+    /// database projects, the index dimensions otherwise
+    fn input_dimensions(&self) -> usize {
+        self.projection
+            .as_ref()
+            .map_or(self.hnsw_index.dimensions, |projection| projection.input_dims())
+    }
+
+    /// Fail with `DIMENSION_MISMATCH` unless a prepared query matches the index
+    fn check_query_dimensions(&self, query: &[f32]) -> Result<(), DbError> {
+        if query.len() != self.hnsw_index.dimensions {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!("Query dimension mismatch: expected {}, got {}", self.hnsw_index.dimensions, query.len()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `check_query_dimensions`, then the index's other query checks
+    fn check_query(&self, query: &[f32]) -> Result<(), DbError> {
+        self.check_query_dimensions(query)?;
+        self.hnsw_index
+            .validate_query(query)
+            .map_err(|e| DbError::new(ErrorCode::InvalidArgument, e))
+    }
+
+    /// Map a caller's query into index space (unchanged without a
+    /// projection); fails while vectors are dropped, as there is nothing to
+    /// search
+    fn prepare_query(&self, query: Vec<f32>) -> Result<Vec<f32>, DbError> {
+        if self.hnsw_index.vectors_dropped() {
+            return Err(DbError::new(ErrorCode::VectorsDropped, VECTORS_DROPPED));
+        }
+        let Some(projection) = &self.projection else {
+            return Ok(query);
+        };
+        if query.len() != projection.input_dims() {
+            return Err(DbError::new(
+                ErrorCode::DimensionMismatch,
+                format!("Query dimension mismatch: expected {}, got {}", projection.input_dims(), query.len()),
+            ));
+        }
+        Ok(projection.project(&query))
+    }
+
+    /// The vector a caller inserted: the original when the database
+    /// projects, the indexed vector otherwise
+    fn stored_vector(&self, id: &str) -> Option<Cow<'_, [f32]>> {
+        match self.originals.get(id) {
+            Some(original) => Some(Cow::Borrowed(original)),
+            None => self.hnsw_index.get_vector(id),
+        }
+    }
+
+    /// Validated vector and metadata of a `{vector, metadata}` record
+    fn parse_js_record(&self, id: &str, record: &JsValue) -> Result<ParsedRecord, DbError> {
+        let vector_value = js_sys::Reflect::get(record, &"vector".into())
+            .map_err(|_| DbError::invalid("Record is missing a vector"))?;
+        let vector: Vec<f32> = if vector_value.is_instance_of::<js_sys::Float32Array>() {
+            js_sys::Float32Array::from(vector_value).to_vec()
+        } else {
+            serde_wasm_bindgen::from_value(vector_value)
+                .map_err(|e| DbError::invalid(format!("Invalid vector: {}", e)))?
+        };
+        self.validate_input(id, &vector)?;
+
+        let metadata = js_sys::Reflect::get(record, &"metadata".into()).unwrap_or(JsValue::UNDEFINED);
+        Ok((vector, parse_metadata(metadata)?))
+    }
+
+    /// Map one `import_json` row onto an id, a validated vector and metadata
+    fn map_json_row(
+        &self,
+        row: &serde_json::Value,
+        id_field: &str,
+        vector_field: &str,
+        metadata_fields: &[String],
+    ) -> Result<(String, ParsedRecord), String> {
+        let row = row.as_object().ok_or("not an object")?;
+        let id = match row.get(id_field) {
+            Some(serde_json::Value::String(id)) => id.clone(),
+            Some(serde_json::Value::Number(id)) => id.to_string(),
+            _ => return Err(format!("missing a string or number '{}'", id_field)),
+        };
+        let vector: Vec<f32> = row
+            .get(vector_field)
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| format!("missing an array '{}'", vector_field))?
+            .iter()
+            .map(|x| x.as_f64().map(|x| x as f32))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("'{}' must contain only numbers", vector_field))?;
+        self.validate_input(&id, &vector)?;
+
+        let metadata: Metadata = metadata_fields
+            .iter()
+            .filter_map(|field| {
+                let value = match row.get(field)? {
+                    serde_json::Value::Null => return None,
+                    serde_json::Value::String(s) => MetadataValue::String(s.clone()),
+                    serde_json::Value::Bool(b) => MetadataValue::Bool(*b),
+                    serde_json::Value::Number(n) => MetadataValue::Number(n.as_f64()?),
+                    other => MetadataValue::String(other.to_string()),
+                };
+                Some((field.clone(), value))
+            })
+            .collect();
+        Ok((id, (vector, (!metadata.is_empty()).then_some(metadata))))
+    }
+
+    /// Convert `(id, distance)` pairs into JS `{ id, distance, metadata }` objects
+    fn results_to_js(&self, results: Vec<(String, f32)>, include_vectors: bool) -> Result<JsValue, JsValue> {
+        // Manually create JS array to avoid serde_wasm_bindgen HashMap issues
+        let js_results = js_sys::Array::new();
+
+        for (id, distance) in results {
+            self.touch(&id);
+            let meta = self.metadata.get(&id);
+
+            let result_obj = js_sys::Object::new();
+
+            // Set id and distance
+            js_sys::Reflect::set(&result_obj, &"id".into(), &id.as_str().into())?;
+            js_sys::Reflect::set(&result_obj, &"distance".into(), &distance.into())?;
+            if let Some(similarity) = self.hnsw_index.metric.similarity(distance) {
+                js_sys::Reflect::set(&result_obj, &"similarity".into(), &similarity.into())?;
+            }
+
+            if include_vectors {
+                if let Some(vector) = self.stored_vector(&id) {
+                    let js_vec = js_sys::Float32Array::new_with_length(vector.len() as u32);
+                    js_vec.copy_from(&vector);
+                    js_sys::Reflect::set(&result_obj, &"vector".into(), &js_vec.into())?;
+                }
+            }
+
+            js_sys::Reflect::set(&result_obj, &"metadata".into(), &metadata_to_js(meta)?)?;
+
+            js_results.push(&result_obj);
+        }
+
+        Ok(js_results.into())
+    }
+
+    /// Fail with `VECTORS_DROPPED` while any vector is dropped
+    fn ensure_vectors(&self) -> Result<(), JsValue> {
+        if self.hnsw_index.vectors_dropped() {
+            return Err(DbError::new(ErrorCode::VectorsDropped, VECTORS_DROPPED).into());
+        }
+        Ok(())
+    }
+
+    fn ensure_writable(&self) -> Result<(), JsValue> {
+        if self.read_only {
+            return Err(DbError::new(
+                ErrorCode::ReadOnly,
+                "Database is read-only; call to_mutable() before modifying it",
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Error for a snapshot whose format version this build can't read
+fn unsupported_version(version: u32) -> JsValue {
+    DbError::new(
+        ErrorCode::UnsupportedVersion,
+        format!("Unsupported database version: {} (this build reads up to {})", version, STATE_VERSION),
+    )
+    .into()
+}
+
+/// Parse an optional `{key: string | number | boolean}` metadata object;
+/// null/undefined means none.
+/// Errors if any value is a non-finite number.
+fn parse_metadata(metadata: JsValue) -> Result<Option<Metadata>, DbError> {
+    if metadata.is_null() || metadata.is_undefined() {
+        return Ok(None);
+    }
+    // Like vectors, numeric values must be finite so they can't poison
+    // sorting or scoring later
+    if metadata.is_object() {
+        for entry in js_sys::Object::entries(metadata.unchecked_ref()).iter() {
+            let entry = js_sys::Array::from(&entry);
+            if let Some(n) = entry.get(1).as_f64() {
+                if !n.is_finite() {
+                    return Err(DbError::new(
+                        ErrorCode::NonFinite,
+                        format!(
+                            "Metadata value for '{}' is NaN or Infinity",
+                            entry.get(0).as_string().unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(serde_wasm_bindgen::from_value(metadata).ok())
+}
+
+/// Metric for a name accepted by `VectorDB::new` (other than "auto"), with
+/// `p` defaulting to 2 for Minkowski unless positive and finite
+fn parse_metric(name: &str, p: Option<f32>) -> Option<hnsw::DistanceMetric> {
+    Some(match name {
+        "euclidean" | "l2" => hnsw::DistanceMetric::Euclidean,
+        "cosine" => hnsw::DistanceMetric::Cosine,
+        "dotproduct" | "dot_product" => hnsw::DistanceMetric::DotProduct,
+        "manhattan" | "l1" => hnsw::DistanceMetric::Manhattan,
+        "angular" => hnsw::DistanceMetric::Angular,
+        "hamming" => hnsw::DistanceMetric::Hamming,
+        "jaccard" => hnsw::DistanceMetric::Jaccard,
+        "minkowski" => hnsw::DistanceMetric::Minkowski {
+            p: p.filter(|p| p.is_finite() && *p > 0.0).unwrap_or(2.0),
+        },
+        _ => return None,
+    })
+}
+
+/// FNV-1a over the little-endian bytes of each component, as 16 hex digits
+fn query_hash(query: &[f32]) -> String {
+    let bytes: Vec<u8> = query.iter().flat_map(|x| x.to_le_bytes()).collect();
+    format!("{:016x}", checksum::fnv1a64(&bytes))
+}
+
+/// Reject a NaN `max_distance`, which no distance could satisfy
+fn check_max_distance(max_distance: Option<f32>) -> Result<(), DbError> {
+    if max_distance.is_some_and(f32::is_nan) {
+        return Err(DbError::invalid("max_distance must not be NaN"));
+    }
+    Ok(())
+}
+
+/// Parse a `{ key: value | { gte, gt, lte, lt, eq } }` JS object into a
+/// metadata filter (null/undefined matches all)
+fn parse_filter(filter: JsValue) -> Result<MetadataFilter, JsValue> {
+    if filter.is_null() || filter.is_undefined() {
+        return Ok(MetadataFilter::default());
+    }
+    let conditions: HashMap<String, filter::Condition> = serde_wasm_bindgen::from_value(filter)
+        .map_err(|e| DbError::invalid(format!("Invalid filter: {}", e)))?;
+    MetadataFilter::from_conditions(conditions).map_err(|e| DbError::invalid(format!("Invalid filter: {}", e)).into())
+}
+
+/// Build a JS `{ id, vector: Float32Array, metadata }` record object, with
+/// a null vector for a record whose vector was dropped
+fn record_to_js(
+    id: &str,
+    vector: Option<&[f32]>,
+    metadata: Option<&Metadata>,
+) -> Result<JsValue, JsValue> {
+    let result_obj = js_sys::Object::new();
+    js_sys::Reflect::set(&result_obj, &"id".into(), &id.into())?;
+
+    let js_vec = match vector {
+        Some(vector) => {
+            let js_vec = js_sys::Float32Array::new_with_length(vector.len() as u32);
+            js_vec.copy_from(vector);
+            js_vec.into()
+        }
+        None => JsValue::NULL,
+    };
+    js_sys::Reflect::set(&result_obj, &"vector".into(), &js_vec)?;
+    js_sys::Reflect::set(&result_obj, &"metadata".into(), &metadata_to_js(metadata)?)?;
+
+    Ok(result_obj.into())
+}
+
+/// Build a JS object from a metadata map, or null when there is none
+fn metadata_to_js(metadata: Option<&Metadata>) -> Result<JsValue, JsValue> {
+    let Some(meta_map) = metadata else {
+        return Ok(JsValue::NULL);
+    };
+    let meta_obj = js_sys::Object::new();
+    for (key, value) in meta_map {
+        js_sys::Reflect::set(&meta_obj, &key.as_str().into(), &value.to_js())?;
+    }
+    Ok(meta_obj.into())
+}
+
+/// Convert a plain serializable value (no HashMaps) to a JS value
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value).map_err(|e| DbError::parse(e).into())
+}
+
+/// Fail with `DIMENSION_MISMATCH` unless two vectors have the same length
+fn check_same_dimensions(a: &[f32], b: &[f32]) -> Result<(), DbError> {
+    if a.len() != b.len() {
+        return Err(DbError::new(ErrorCode::DimensionMismatch, "Vectors must have same dimensions"));
+    }
+    Ok(())
+}
+
+/// Standalone distance functions exposed to JS
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn cosine_similarity(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::cosine_similarity(&a, &b))
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn euclidean_distance(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::euclidean_distance(&a, &b))
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn dot_product(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::dot_product(&a, &b))
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn manhattan_distance(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::manhattan_distance(&a, &b))
+}
+
+/// Number of positions where exactly one vector has a nonzero (set) entry
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn hamming_distance(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::hamming_distance(&a, &b))
+}
+
+/// Jaccard distance between the sets of nonzero positions of two vectors
+/// (0 when both are all zero)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn jaccard_distance(a: Vec<f32>, b: Vec<f32>) -> Result<f32, JsValue> {
+    check_same_dimensions(&a, &b)?;
+    Ok(distance::jaccard_distance(&a, &b))
 }
@@ -0,0 +1,104 @@
+//! Access recency for `VectorDB`'s bounded-memory mode
+//!
+//! Each access stamps an id with the next value of a counter; the id with
+//! the smallest stamp is the least recently used and is evicted first.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Record ids ordered by last access, with the record limit they enforce
+#[derive(Debug, Clone)]
+pub struct AccessOrder {
+    max_nodes: usize,
+    tick: u64,
+    last_access: HashMap<String, u64>,
+    by_tick: BTreeMap<u64, String>,
+}
+
+impl AccessOrder {
+    /// Track up to `max_nodes` records, starting with `ids` from least to
+    /// most recently used
+    pub fn new(max_nodes: usize, ids: impl IntoIterator<Item = String>) -> Self {
+        let mut order = AccessOrder {
+            max_nodes,
+            tick: 0,
+            last_access: HashMap::new(),
+            by_tick: BTreeMap::new(),
+        };
+        for id in ids {
+            order.touch(&id);
+        }
+        order
+    }
+
+    pub fn max_nodes(&self) -> usize {
+        self.max_nodes
+    }
+
+    pub fn set_max_nodes(&mut self, max_nodes: usize) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Mark `id` as the most recently used
+    pub fn touch(&mut self, id: &str) {
+        self.tick += 1;
+        match self.last_access.get_mut(id) {
+            Some(stamp) => {
+                self.by_tick.remove(stamp);
+                *stamp = self.tick;
+            }
+            None => {
+                self.last_access.insert(id.to_string(), self.tick);
+            }
+        }
+        self.by_tick.insert(self.tick, id.to_string());
+    }
+
+    /// Stop tracking a deleted id
+    pub fn forget(&mut self, id: &str) {
+        if let Some(stamp) = self.last_access.remove(id) {
+            self.by_tick.remove(&stamp);
+        }
+    }
+
+    /// Stop tracking every id, keeping the limit
+    pub fn clear(&mut self) {
+        self.last_access.clear();
+        self.by_tick.clear();
+    }
+
+    /// The id to evict next
+    pub fn least_recent(&self) -> Option<&str> {
+        self.by_tick.values().next().map(String::as_str)
+    }
+
+    /// Every tracked id, least recently used first
+    pub fn ids(&self) -> Vec<String> {
+        self.by_tick.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touching_moves_an_id_to_the_back() {
+        let mut order = AccessOrder::new(3, ["a", "b", "c"].map(String::from));
+        assert_eq!(order.least_recent(), Some("a"));
+        order.touch("a");
+        assert_eq!(order.ids(), vec!["b", "c", "a"]);
+        order.touch("d");
+        assert_eq!(order.ids(), vec!["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn forgotten_ids_are_never_evicted() {
+        let mut order = AccessOrder::new(2, ["a", "b"].map(String::from));
+        order.forget("a");
+        order.forget("missing");
+        assert_eq!(order.least_recent(), Some("b"));
+        order.clear();
+        assert_eq!(order.least_recent(), None);
+        assert_eq!(order.max_nodes(), 2);
+    }
+}
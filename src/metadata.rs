@@ -0,0 +1,143 @@
+//! Typed metadata values
+//!
+//! Metadata values are strings, numbers or booleans. They serialize as plain
+//! JSON values (`"en"`, `3.5`, `true`), so snapshots written before numbers
+//! and booleans were supported, where every value is a string, still load
+//! with every value as a string.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+/// One record's metadata
+pub type Metadata = HashMap<String, MetadataValue>;
+
+/// A single metadata value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MetadataValue {
+    String(String),
+    /// Always finite; `VectorDB` rejects NaN and infinite values
+    Number(f64),
+    Bool(bool),
+}
+
+impl MetadataValue {
+    /// The value when it is a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The value when it is a number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MetadataValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value when it is a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            MetadataValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The matching JS string, number or boolean
+    pub fn to_js(&self) -> JsValue {
+        match self {
+            MetadataValue::String(s) => JsValue::from_str(s),
+            MetadataValue::Number(n) => JsValue::from_f64(*n),
+            MetadataValue::Bool(b) => JsValue::from_bool(*b),
+        }
+    }
+}
+
+/// Strings as-is, numbers and booleans as JavaScript prints them
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataValue::String(s) => f.write_str(s),
+            MetadataValue::Number(n) => write!(f, "{}", n),
+            MetadataValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::String(value)
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::String(value.to_string())
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(value: f64) -> Self {
+        MetadataValue::Number(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+impl PartialEq<str> for MetadataValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for MetadataValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_as_plain_json() {
+        let meta: Metadata = [
+            ("lang".to_string(), MetadataValue::from("en")),
+            ("year".to_string(), MetadataValue::from(2024.0)),
+            ("draft".to_string(), MetadataValue::from(false)),
+        ]
+        .into_iter()
+        .collect();
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json, serde_json::json!({ "lang": "en", "year": 2024.0, "draft": false }));
+        let back: Metadata = serde_json::from_value(json).unwrap();
+        assert_eq!(back, meta);
+    }
+
+    #[test]
+    fn numeric_strings_stay_strings() {
+        // Legacy metadata stored every value as a string
+        let legacy: Metadata = serde_json::from_str(r#"{"year":"2024","draft":"false"}"#).unwrap();
+        assert_eq!(legacy["year"], "2024");
+        assert_eq!(legacy["draft"], "false");
+        assert_eq!(legacy["year"].as_f64(), None);
+    }
+
+    #[test]
+    fn display_matches_javascript() {
+        assert_eq!(MetadataValue::from(3.0).to_string(), "3");
+        assert_eq!(MetadataValue::from(0.5).to_string(), "0.5");
+        assert_eq!(MetadataValue::from(true).to_string(), "true");
+        assert_eq!(MetadataValue::from("x").to_string(), "x");
+    }
+}
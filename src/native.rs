@@ -0,0 +1,50 @@
+//! Plain Rust API for using the index as a native dependency
+//!
+//! `VectorDB` speaks `JsValue` and is meant for JavaScript callers; these
+//! types work on any target without a JS host:
+//!
+//! ```
+//! use idbvec::native::{DistanceMetric, HNSWIndex};
+//!
+//! let mut index = HNSWIndex::new(3, 16, 200, DistanceMetric::Cosine, Some(42));
+//! index.try_insert("a".into(), vec![1.0, 0.0, 0.0]).unwrap();
+//! index.try_insert("b".into(), vec![0.0, 1.0, 0.0]).unwrap();
+//!
+//! let results = index.search(&[0.9, 0.1, 0.0], 1, 50);
+//! assert_eq!(results[0].0, "a");
+//! assert!(index.delete("a"));
+//! ```
+//!
+//! `search_results` joins hits with a metadata map into `SearchResult`s,
+//! the same shape `VectorDB::search` returns to JavaScript.
+
+use std::collections::HashMap;
+
+pub use crate::hnsw::{
+    DensityProfile, DistanceMetric, HNSWIndex, IndexStats, LayerAdjacency, LayerHealth, MutualPair,
+    NodeImportance, SeedCacheStats, AUTO_METRIC_SAMPLE, DEFAULT_AUTO_EF_FACTOR,
+    DEFAULT_EXACT_THRESHOLD, DEFAULT_MAX_LAYER_CAP, MIN_M, TUNE_EF_MAX,
+};
+pub use crate::quantize::Quantization;
+pub use crate::{Metadata, MetadataValue, SearchResult, VectorRecord};
+
+/// Search `index` and attach each hit's entry from `metadata`
+///
+/// Ids without metadata get `metadata: None`.
+pub fn search_results(
+    index: &HNSWIndex,
+    metadata: &HashMap<String, Metadata>,
+    query: &[f32],
+    k: usize,
+    ef: usize,
+) -> Vec<SearchResult> {
+    index
+        .search(query, k, ef)
+        .into_iter()
+        .map(|(id, distance)| SearchResult {
+            metadata: metadata.get(&id).cloned(),
+            id,
+            distance,
+        })
+        .collect()
+}
@@ -0,0 +1,159 @@
+//! Seeded random projection to fewer dimensions
+//!
+//! Each output coordinate is the input's dot product with a random ±1
+//! direction, scaled by `1 / sqrt(output_dims)` (Achlioptas' sign matrix).
+//! Pairwise Euclidean distances and dot products are preserved in
+//! expectation, with a spread that shrinks as `output_dims` grows
+//! (Johnson–Lindenstrauss). The matrix is derived from the seed alone, so
+//! only the seed and shape are serialized.
+
+use crate::rng;
+use serde::{Deserialize, Serialize};
+
+/// A fixed `input_dims -> output_dims` linear map
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "ProjectionSpec", into = "ProjectionSpec")]
+pub struct RandomProjection {
+    input_dims: usize,
+    output_dims: usize,
+    seed: u64,
+    /// Row-major `output_dims × input_dims` entries of ±1 / sqrt(output_dims)
+    matrix: Vec<f32>,
+}
+
+/// The serialized form: everything needed to regenerate the matrix
+#[derive(Clone, Serialize, Deserialize)]
+struct ProjectionSpec {
+    input_dims: usize,
+    output_dims: usize,
+    seed: u64,
+}
+
+impl From<ProjectionSpec> for RandomProjection {
+    fn from(spec: ProjectionSpec) -> Self {
+        RandomProjection::new(spec.input_dims, spec.output_dims, spec.seed)
+    }
+}
+
+impl From<RandomProjection> for ProjectionSpec {
+    fn from(projection: RandomProjection) -> Self {
+        ProjectionSpec {
+            input_dims: projection.input_dims,
+            output_dims: projection.output_dims,
+            seed: projection.seed,
+        }
+    }
+}
+
+impl RandomProjection {
+    pub fn new(input_dims: usize, output_dims: usize, seed: u64) -> Self {
+        let scale = 1.0 / (output_dims as f32).sqrt();
+        let mut state = seed;
+        let mut bits = 0u64;
+        let mut matrix = Vec::with_capacity(input_dims * output_dims);
+        for i in 0..input_dims * output_dims {
+            if i % 64 == 0 {
+                bits = rng::splitmix64(&mut state);
+            }
+            matrix.push(if bits & 1 == 1 { scale } else { -scale });
+            bits >>= 1;
+        }
+        RandomProjection { input_dims, output_dims, seed, matrix }
+    }
+
+    pub fn input_dims(&self) -> usize {
+        self.input_dims
+    }
+
+    pub fn output_dims(&self) -> usize {
+        self.output_dims
+    }
+
+    /// Project a vector of `input_dims` components
+    pub fn project(&self, vector: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(vector.len(), self.input_dims);
+        self.matrix
+            .chunks_exact(self.input_dims.max(1))
+            .take(self.output_dims)
+            .map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hnsw::{DistanceMetric, HNSWIndex};
+    use crate::vector::random_vector_seeded;
+
+    #[test]
+    fn projection_is_determined_by_its_seed() {
+        let v = random_vector_seeded(32, 5);
+        let a = RandomProjection::new(32, 8, 7);
+        let b = RandomProjection::new(32, 8, 7);
+        let c = RandomProjection::new(32, 8, 8);
+        assert_eq!(a.project(&v).len(), 8);
+        assert_eq!(a.project(&v), b.project(&v));
+        assert_ne!(a.project(&v), c.project(&v));
+
+        // Only the shape and seed are serialized; the matrix is regenerated
+        let json = serde_json::to_string(&a).unwrap();
+        assert!(!json.contains("matrix"));
+        let restored: RandomProjection = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.project(&v), a.project(&v));
+    }
+
+    #[test]
+    fn projected_search_trades_recall_for_dimensions() {
+        let dims = 128;
+        // Clustered data, like embeddings: 20 centers plus small noise
+        let centers: Vec<Vec<f32>> = (0..20).map(|c| random_vector_seeded(dims, 500 + c)).collect();
+        let embed = |seed: u64| -> Vec<f32> {
+            let center = &centers[(seed % 20) as usize];
+            let noise = random_vector_seeded(dims, seed);
+            center.iter().zip(&noise).map(|(c, n)| c + 0.3 * n).collect()
+        };
+        let data: Vec<Vec<f32>> = (0..600).map(|i| embed(i * 31 + 3)).collect();
+        let queries: Vec<Vec<f32>> = (0..40).map(|i| embed(70_000 + i)).collect();
+        let k = 10;
+
+        // Exact top-k in the original space
+        let mut full = HNSWIndex::new(dims, 16, 200, DistanceMetric::Euclidean, Some(1));
+        for (i, v) in data.iter().enumerate() {
+            full.insert(format!("v{}", i), v.clone());
+        }
+        let ids = full.all_ids();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let truth: Vec<Vec<String>> = queries
+            .iter()
+            .map(|q| full.rank_ids(q, &ids, k).into_iter().map(|(id, _)| id).collect())
+            .collect();
+
+        let recall = |target_dims: usize| {
+            let projection = RandomProjection::new(dims, target_dims, 9);
+            let mut idx = HNSWIndex::new(target_dims, 16, 200, DistanceMetric::Euclidean, Some(1));
+            for (i, v) in data.iter().enumerate() {
+                idx.insert(format!("v{}", i), projection.project(v));
+            }
+            let hits: usize = queries
+                .iter()
+                .zip(&truth)
+                .map(|(q, truth)| {
+                    idx.search(&projection.project(q), k, 100)
+                        .iter()
+                        .filter(|(id, _)| truth.contains(id))
+                        .count()
+                })
+                .sum();
+            hits as f32 / (queries.len() * k) as f32
+        };
+
+        let full_recall = full.estimate_recall(&queries, k, 100);
+        let (r64, r16) = (recall(64), recall(16));
+        assert!(full_recall > 0.95, "full-dimensional recall {}", full_recall);
+        assert!(r64 > r16, "64 dims {} vs 16 dims {}", r64, r16);
+        assert!(r64 < full_recall, "64 dims {} vs full {}", r64, full_recall);
+        // Projection loses fine-grained neighbor order, more so at 16 dims
+        assert!(r64 > 0.5, "64 dims {}", r64);
+    }
+}
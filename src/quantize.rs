@@ -0,0 +1,221 @@
+//! Scalar quantization of stored vectors
+//!
+//! `Int8Vector` maps each vector's [min, max] range linearly onto the 256
+//! `i8` codes, cutting storage to a quarter of `f32`. Every reconstructed
+//! component is within `scale / 2` of the original, where
+//! `scale = (max - min) / 255`, so vectors with a wide value range (or a few
+//! outlier components) lose the most precision.
+//!
+//! `F16Vector` rounds each component to IEEE half precision, halving
+//! storage. The relative rounding error is at most 2^-11 (about 0.05%) for
+//! components of magnitude 6.1e-5 to 65504; smaller ones lose relative
+//! precision and larger ones saturate to infinity.
+
+use half::f16;
+use serde::{Deserialize, Serialize};
+
+/// Storage precision for vectors in an index
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+pub enum Quantization {
+    /// Full-precision f32 vectors
+    #[default]
+    None,
+    /// Per-vector affine int8 codes
+    Int8,
+    /// IEEE half-precision components
+    F16,
+}
+
+/// A stored vector in reduced precision
+///
+/// Untagged so int8 nodes saved before f16 existed still load: the two
+/// variants have disjoint field names.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QuantizedVector {
+    Int8(Int8Vector),
+    F16(F16Vector),
+}
+
+impl QuantizedVector {
+    /// Encode `vector` for `quantization`, or None when it stores full f32s
+    pub fn encode(quantization: Quantization, vector: &[f32]) -> Option<Self> {
+        match quantization {
+            Quantization::None => None,
+            Quantization::Int8 => Some(QuantizedVector::Int8(Int8Vector::encode(vector))),
+            Quantization::F16 => Some(QuantizedVector::F16(F16Vector::encode(vector))),
+        }
+    }
+
+    /// Number of components encoded
+    pub fn dimensions(&self) -> usize {
+        match self {
+            QuantizedVector::Int8(q) => q.dimensions(),
+            QuantizedVector::F16(q) => q.dimensions(),
+        }
+    }
+
+    /// Bytes of encoded payload (codes plus any scale and offset)
+    pub fn byte_size(&self) -> usize {
+        match self {
+            QuantizedVector::Int8(q) => q.codes.len() + 2 * std::mem::size_of::<f32>(),
+            QuantizedVector::F16(q) => q.halves.len() * std::mem::size_of::<u16>(),
+        }
+    }
+
+    /// Reconstruct an approximate f32 vector
+    pub fn decode(&self) -> Vec<f32> {
+        match self {
+            QuantizedVector::Int8(q) => q.decode(),
+            QuantizedVector::F16(q) => q.decode(),
+        }
+    }
+
+    /// `decode` into `out`, replacing its contents but keeping its allocation
+    pub fn decode_into(&self, out: &mut Vec<f32>) {
+        out.clear();
+        match self {
+            QuantizedVector::Int8(q) => out.extend(q.values()),
+            QuantizedVector::F16(q) => out.extend(q.values()),
+        }
+    }
+}
+
+/// A vector stored as half-precision floats
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct F16Vector {
+    /// Raw `f16` bit patterns
+    halves: Vec<u16>,
+}
+
+impl F16Vector {
+    pub fn encode(vector: &[f32]) -> Self {
+        F16Vector {
+            halves: vector.iter().map(|&x| f16::from_f32(x).to_bits()).collect(),
+        }
+    }
+
+    /// Number of components encoded
+    pub fn dimensions(&self) -> usize {
+        self.halves.len()
+    }
+
+    /// Widen back to f32
+    pub fn decode(&self) -> Vec<f32> {
+        self.values().collect()
+    }
+
+    fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        self.halves.iter().map(|&h| f16::from_bits(h).to_f32())
+    }
+}
+
+/// A vector quantized to one signed byte per component
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Int8Vector {
+    codes: Vec<i8>,
+    /// Width of one code step
+    scale: f32,
+    /// Value represented by code -128 (the vector's minimum component)
+    offset: f32,
+}
+
+impl Int8Vector {
+    pub fn encode(vector: &[f32]) -> Self {
+        let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        if vector.is_empty() || max <= min {
+            // Empty or constant vector: every component equals the offset
+            return Int8Vector {
+                codes: vec![-128; vector.len()],
+                scale: 0.0,
+                offset: if vector.is_empty() { 0.0 } else { min },
+            };
+        }
+        let scale = (max - min) / 255.0;
+        let codes = vector
+            .iter()
+            .map(|x| (((x - min) / scale).round().clamp(0.0, 255.0) - 128.0) as i8)
+            .collect();
+        Int8Vector {
+            codes,
+            scale,
+            offset: min,
+        }
+    }
+
+    /// Number of components encoded
+    pub fn dimensions(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Reconstruct an approximate f32 vector
+    pub fn decode(&self) -> Vec<f32> {
+        self.values().collect()
+    }
+
+    fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        self.codes.iter().map(|&c| (c as f32 + 128.0) * self.scale + self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_error_is_within_half_a_step() {
+        let v: Vec<f32> = (0..100).map(|i| ((i as f32) * 0.37).sin() * 3.0).collect();
+        let q = Int8Vector::encode(&v);
+        assert_eq!(q.codes.len(), 100);
+        let decoded = q.decode();
+        for (a, b) in v.iter().zip(decoded.iter()) {
+            assert!((a - b).abs() <= q.scale / 2.0 + 1e-6, "{} vs {}", a, b);
+        }
+        // Extremes are represented exactly
+        let min = v.iter().copied().fold(f32::INFINITY, f32::min);
+        assert!(decoded.iter().any(|x| (x - min).abs() < 1e-6));
+    }
+
+    #[test]
+    fn f16_roundtrip_has_small_relative_error() {
+        let v: Vec<f32> = (0..100).map(|i| ((i as f32) * 0.37).sin() * 3.0 + 0.01).collect();
+        let q = F16Vector::encode(&v);
+        assert_eq!(q.dimensions(), 100);
+        for (a, b) in v.iter().zip(q.decode()) {
+            assert!((a - b).abs() <= a.abs() / 2048.0 + 1e-7, "{} vs {}", a, b);
+        }
+        assert_eq!(F16Vector::encode(&[1.0, -0.5, 0.0]).decode(), vec![1.0, -0.5, 0.0]);
+    }
+
+    #[test]
+    fn quantized_vector_deserializes_both_variants() {
+        let int8 = QuantizedVector::encode(Quantization::Int8, &[0.0, 1.0]).unwrap();
+        let f16 = QuantizedVector::encode(Quantization::F16, &[0.0, 1.0]).unwrap();
+        for q in [int8, f16] {
+            let back: QuantizedVector = serde_json::from_str(&serde_json::to_string(&q).unwrap()).unwrap();
+            assert_eq!(back, q);
+        }
+        assert!(QuantizedVector::encode(Quantization::None, &[1.0]).is_none());
+    }
+
+    #[test]
+    fn decode_into_reuses_the_buffer() {
+        let mut out = vec![9.0; 8];
+        for q in [
+            QuantizedVector::encode(Quantization::Int8, &[0.0, 1.0, 0.5]).unwrap(),
+            QuantizedVector::encode(Quantization::F16, &[1.0, -0.5]).unwrap(),
+        ] {
+            let capacity = out.capacity();
+            q.decode_into(&mut out);
+            assert_eq!(out, q.decode());
+            assert_eq!(out.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn constant_and_empty_vectors() {
+        assert_eq!(Int8Vector::encode(&[2.5, 2.5, 2.5]).decode(), vec![2.5, 2.5, 2.5]);
+        assert!(Int8Vector::encode(&[]).decode().is_empty());
+    }
+}
@@ -3,7 +3,7 @@
 //! These exercise the HNSW index through its public API with larger datasets
 //! and cross-module workflows (insert → search → serialize → deserialize → search).
 
-use idbvec::*;
+use idbvec::native::*;
 use std::collections::HashMap;
 
 /// Helper: create a deterministic vector from a seed using LCG
@@ -37,13 +37,66 @@ fn brute_force_knn(vectors: &[(String, Vec<f32>)], query: &[f32], k: usize) -> V
 
 // ── Recall quality ─────────────────────────────────────────────
 
-/// HNSWIndex is not re-exported, so we use VectorRecord + SearchResult
-/// which *are* public. But VectorDB requires JsValue…
-/// We can still test the pure-Rust types that are pub.
-///
-/// Since HNSWIndex is pub but inside the crate, integration tests can't
-/// access it directly. Instead we test via the public re-exported types
-/// and the standalone distance functions (which are pub).
+#[test]
+fn hnsw_recall_matches_brute_force() {
+    let dims = 16;
+    let mut index = HNSWIndex::new(dims, 16, 200, DistanceMetric::Euclidean, Some(7));
+    let vectors: Vec<(String, Vec<f32>)> = (0..1000)
+        .map(|i| (format!("v{}", i), make_vec(dims, i as u64 * 7919 + 1)))
+        .collect();
+    for (id, v) in &vectors {
+        index.try_insert(id.clone(), v.clone()).unwrap();
+    }
+    assert_eq!(index.node_count(), 1000);
+
+    let k = 10;
+    let mut found = 0;
+    for q in 0..50 {
+        let query = make_vec(dims, 1_000_000 + q);
+        let truth = brute_force_knn(&vectors, &query, k);
+        let results = index.search(&query, k, 100);
+        found += results.iter().filter(|(id, _)| truth.contains(id)).count();
+    }
+    let recall = found as f32 / (50 * k) as f32;
+    assert!(recall >= 0.9, "recall@10 = {}", recall);
+}
+
+#[test]
+fn hnsw_search_distances_are_true_euclidean() {
+    let mut index = HNSWIndex::new(2, 16, 200, DistanceMetric::Euclidean, None);
+    index.try_insert("origin".into(), vec![0.0, 0.0]).unwrap();
+    index.try_insert("p".into(), vec![3.0, 4.0]).unwrap();
+    let results = index.search(&[0.0, 0.0], 2, 50);
+    assert_eq!(results[0].0, "origin");
+    assert!((results[1].1 - 5.0).abs() < 1e-5);
+}
+
+#[test]
+fn try_insert_validates_and_replaces() {
+    let mut index = HNSWIndex::new(3, 16, 200, DistanceMetric::Cosine, None);
+    assert!(index.try_insert("bad".into(), vec![1.0, 0.0]).is_err());
+    assert!(index.try_insert("nan".into(), vec![f32::NAN, 0.0, 0.0]).is_err());
+    index.try_insert("a".into(), vec![1.0, 0.0, 0.0]).unwrap();
+    index.try_insert("a".into(), vec![0.0, 1.0, 0.0]).unwrap();
+    assert_eq!(index.node_count(), 1);
+    assert_eq!(index.get_vector("a").as_deref(), Some(&[0.0, 1.0, 0.0][..]));
+    assert!(index.delete("a"));
+    assert!(index.search(&[0.0, 1.0, 0.0], 1, 50).is_empty());
+}
+
+#[test]
+fn index_serialization_roundtrip() {
+    let mut index = HNSWIndex::new(8, 16, 200, DistanceMetric::Euclidean, Some(1));
+    for i in 0..100 {
+        index.try_insert(format!("v{}", i), make_vec(8, i)).unwrap();
+    }
+    let json = serde_json::to_string(&index).unwrap();
+    let restored: HNSWIndex = serde_json::from_str(&json).unwrap();
+    let query = make_vec(8, 4242);
+    assert_eq!(index.search(&query, 5, 50), restored.search(&query, 5, 50));
+}
+
+// ── Public record types ────────────────────────────────────────
 
 #[test]
 fn public_types_are_constructable() {
@@ -88,6 +141,58 @@ fn search_result_serialization_roundtrip() {
     );
 }
 
+#[test]
+fn search_results_attach_metadata_to_each_hit() {
+    let mut index = HNSWIndex::new(2, 8, 50, DistanceMetric::Euclidean, Some(4));
+    let mut metadata = HashMap::new();
+    for i in 0..20 {
+        let id = format!("p{}", i);
+        index.insert(id.clone(), vec![i as f32, 0.0]);
+        // Leave odd ids without metadata
+        if i % 2 == 0 {
+            let mut meta = HashMap::new();
+            meta.insert("label".to_string(), format!("point {}", i).into());
+            metadata.insert(id, meta);
+        }
+    }
+
+    let results = search_results(&index, &metadata, &[4.2, 0.0], 3, 50);
+    let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(ids, vec!["p4", "p5", "p3"]);
+    for (result, (id, distance)) in results.iter().zip(index.search(&[4.2, 0.0], 3, 50)) {
+        assert_eq!(result.id, id);
+        assert!((result.distance - distance).abs() < 1e-6);
+        assert_eq!(result.metadata.as_ref(), metadata.get(&id));
+    }
+    assert_eq!(results[0].metadata.as_ref().unwrap()["label"], "point 4");
+    assert!(results[1].metadata.is_none());
+
+    // Serializes directly, matching the JS result shape
+    let json = serde_json::to_value(&results[0]).unwrap();
+    assert_eq!(json["metadata"]["label"], "point 4");
+}
+
+#[test]
+fn vector_record_keeps_metadata_types() {
+    let mut meta = HashMap::new();
+    meta.insert("title".to_string(), MetadataValue::from("doc"));
+    meta.insert("year".to_string(), MetadataValue::from(2024.0));
+    meta.insert("draft".to_string(), MetadataValue::from(true));
+    let vr = VectorRecord {
+        id: "v1".into(),
+        vector: vec![0.1, 0.2],
+        metadata: Some(meta),
+    };
+    let json = serde_json::to_string(&vr).unwrap();
+    assert!(json.contains("\"year\":2024.0"));
+    assert!(json.contains("\"draft\":true"));
+    let vr2: VectorRecord = serde_json::from_str(&json).unwrap();
+    let meta = vr2.metadata.unwrap();
+    assert_eq!(meta["title"], "doc");
+    assert_eq!(meta["year"].as_f64(), Some(2024.0));
+    assert_eq!(meta["draft"].as_bool(), Some(true));
+}
+
 #[test]
 fn vector_record_serialization_roundtrip() {
     let vr = VectorRecord {
@@ -102,13 +207,25 @@ fn vector_record_serialization_roundtrip() {
     assert!(vr2.metadata.is_none());
 }
 
-// ── Standalone distance functions (pub wasm_bindgen fns) ───────
+// ── Standalone distance functions ──────────────────────────────
+
+// Note: The standalone distance fns (cosine_similarity, euclidean_distance,
+// dot_product, manhattan_distance, hamming_distance, jaccard_distance)
+// report errors as JsValue, which can't be constructed in native tests, so
+// only their success paths are exercised here. The wasm.rs tests cover the
+// JS-facing API directly.
 
-// Note: The standalone pub fns (cosine_similarity, euclidean_distance,
-// dot_product) require JsValue in their error path, which won't work
-// in native tests. We test the distance module indirectly through
-// the public types and the HNSW behavior tested in unit tests.
-// The wasm.rs tests cover the JS-facing API directly.
+#[test]
+fn standalone_distance_functions() {
+    let a = vec![1.0, 0.0];
+    let b = vec![0.0, 1.0];
+    assert!(idbvec::cosine_similarity(a.clone(), b.clone()).unwrap().abs() < 1e-6);
+    assert!((idbvec::euclidean_distance(a.clone(), b.clone()).unwrap() - 2f32.sqrt()).abs() < 1e-6);
+    assert!(idbvec::dot_product(a.clone(), b.clone()).unwrap().abs() < 1e-6);
+    assert!((idbvec::manhattan_distance(a.clone(), b.clone()).unwrap() - 2.0).abs() < 1e-6);
+    assert_eq!(idbvec::hamming_distance(a.clone(), b.clone()).unwrap(), 2.0);
+    assert_eq!(idbvec::jaccard_distance(a, b).unwrap(), 1.0);
+}
 
 #[test]
 fn make_vec_is_deterministic() {
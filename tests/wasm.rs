@@ -5,32 +5,104 @@
 
 #![cfg(target_arch = "wasm32")]
 
-use wasm_bindgen::JsValue;
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
 use idbvec::*;
 
+/// `code` and `message` of an error thrown by the API
+fn error_parts(err: &JsValue) -> (String, String) {
+    let get = |key: &str| js_sys::Reflect::get(err, &key.into()).unwrap().as_string().unwrap_or_default();
+    (get("code"), get("message"))
+}
+
+/// `db`'s snapshot without its checksum, for tests that edit it by hand
+fn unsealed(db: &VectorDB) -> String {
+    let mut json: serde_json::Value = serde_json::from_str(&db.serialize().unwrap()).unwrap();
+    json.as_object_mut().unwrap().remove("checksum");
+    json.to_string()
+}
+
 // ── VectorDB construction ──────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn new_vectordb_has_size_zero() {
-    let db = VectorDB::new(3, 16, 200, None);
+    let db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     assert_eq!(db.size(), 0);
 }
 
 #[wasm_bindgen_test]
 fn new_vectordb_with_metric() {
-    let db = VectorDB::new(3, 16, 200, Some("cosine".into()));
+    let db = VectorDB::new(3, 16, 200, Some("cosine".into()), None, None, None).unwrap();
     assert_eq!(db.size(), 0);
 }
 
+#[wasm_bindgen_test]
+fn new_vectordb_with_manhattan_metric() {
+    let mut db = VectorDB::new(2, 16, 200, Some("l1".into()), None, None, None).unwrap();
+    db.insert("axis".into(), vec![3.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("diagonal".into(), vec![2.0, 2.0], JsValue::NULL).unwrap();
+    let results = js_sys::Array::from(&db.search(vec![0.0, 0.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "axis");
+}
+
+#[wasm_bindgen_test]
+fn new_vectordb_with_minkowski_metric() {
+    let mut db = VectorDB::new(2, 16, 200, Some("minkowski".into()), None, None, Some(1.0)).unwrap();
+    assert_eq!(db.metric(), "minkowski");
+    db.insert("axis".into(), vec![3.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("diagonal".into(), vec![2.0, 2.0], JsValue::NULL).unwrap();
+    // p = 1 ranks like Manhattan
+    let results = js_sys::Array::from(&db.search(vec![0.0, 0.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "axis");
+
+    let restored = VectorDB::deserialize(db.serialize().unwrap()).unwrap();
+    assert_eq!(restored.distance_between("axis".into(), "diagonal".into()).unwrap(), 3.0);
+}
+
+#[wasm_bindgen_test]
+fn new_vectordb_with_angular_metric() {
+    let mut db = VectorDB::new(2, 16, 200, Some("angular".into()), None, None, None).unwrap();
+    assert_eq!(db.metric(), "angular");
+    db.insert("x".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("y".into(), vec![0.0, 3.0], JsValue::NULL).unwrap();
+    let results = js_sys::Array::from(&db.search(vec![2.0, 0.0], 2, 50, None, None, None).unwrap());
+    let far = js_sys::Reflect::get(&results.get(1), &"distance".into()).unwrap();
+    assert!((far.as_f64().unwrap() - 0.5).abs() < 1e-3);
+}
+
 // ── Insert ─────────────────────────────────────────────────────
 
+#[wasm_bindgen_test]
+fn with_capacity_behaves_like_new() {
+    let mut plain = VectorDB::new(3, 16, 200, None, Some(5), None, None).unwrap();
+    let mut reserved = VectorDB::with_capacity(3, 16, 200, None, Some(5), 100).unwrap();
+    for db in [&mut plain, &mut reserved] {
+        for i in 0..50 {
+            db.insert(format!("v{}", i), vec![i as f32, 1.0, 0.0], JsValue::NULL).unwrap();
+        }
+    }
+    assert_eq!(reserved.size(), 50);
+    assert_eq!(reserved.serialize().unwrap(), plain.serialize().unwrap());
+    assert!(VectorDB::with_capacity(3, 1, 200, None, None, 10).is_err());
+}
+
+#[wasm_bindgen_test]
+fn new_rejects_m_below_two() {
+    for m in [0, 1] {
+        let err = VectorDB::new(3, m, 200, None, None, None, None).err().unwrap();
+        assert_eq!(err.as_string().unwrap(), format!("m must be at least 2, got {}", m));
+    }
+    assert!(VectorDB::new(3, 2, 200, None, None, None, None).is_ok());
+}
+
 #[wasm_bindgen_test]
 fn insert_increases_size() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     assert_eq!(db.size(), 1);
@@ -38,7 +110,7 @@ fn insert_increases_size() {
 
 #[wasm_bindgen_test]
 fn insert_dimension_mismatch_returns_err() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     let result = db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL);
     assert!(result.is_err());
     assert_eq!(db.size(), 0);
@@ -46,7 +118,7 @@ fn insert_dimension_mismatch_returns_err() {
 
 #[wasm_bindgen_test]
 fn insert_nan_returns_err() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     let result = db.insert("a".into(), vec![1.0, f32::NAN, 0.0], JsValue::NULL);
     assert!(result.is_err());
     assert_eq!(db.size(), 0);
@@ -54,7 +126,7 @@ fn insert_nan_returns_err() {
 
 #[wasm_bindgen_test]
 fn insert_infinity_returns_err() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     let result = db.insert("a".into(), vec![1.0, f32::INFINITY, 0.0], JsValue::NULL);
     assert!(result.is_err());
     assert_eq!(db.size(), 0);
@@ -62,7 +134,7 @@ fn insert_infinity_returns_err() {
 
 #[wasm_bindgen_test]
 fn insert_multiple_vectors() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
@@ -74,7 +146,7 @@ fn insert_multiple_vectors() {
 
 #[wasm_bindgen_test]
 fn insert_duplicate_id_upserts() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("a".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
@@ -82,35 +154,126 @@ fn insert_duplicate_id_upserts() {
     assert_eq!(db.size(), 1);
 }
 
+#[wasm_bindgen_test]
+fn insert_or_replace_reports_created_then_replaced() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let status = |out: JsValue| js_sys::Reflect::get(&out, &"status".into()).unwrap().as_string().unwrap();
+    let first = db.insert_or_replace("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(status(first), "created");
+    let second = db.insert_or_replace("a".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(status(second), "replaced");
+    assert_eq!(db.size(), 1);
+}
+
+#[wasm_bindgen_test]
+fn insert_view_matches_insert() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    // A view into the middle of a larger buffer
+    let buffer = js_sys::Float32Array::from(&[9.0f32, 1.0, 2.0, 3.0, 9.0][..]);
+    db.insert_view("a".into(), &buffer.subarray(1, 4), JsValue::NULL).unwrap();
+    assert_eq!(db.size(), 1);
+    let record = db.get("a".into()).unwrap();
+    let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&record, &"vector".into()).unwrap());
+    assert_eq!(vector.to_vec(), vec![1.0, 2.0, 3.0]);
+
+    let short = js_sys::Float32Array::from(&[1.0f32, 0.0][..]);
+    assert!(db.insert_view("b".into(), &short, JsValue::NULL).is_err());
+    let nan = js_sys::Float32Array::from(&[1.0f32, f32::NAN, 0.0][..]);
+    assert!(db.insert_view("c".into(), &nan, JsValue::NULL).is_err());
+    assert_eq!(db.size(), 1);
+}
+
 // ── Search ─────────────────────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn search_returns_results() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
         .unwrap();
 
-    let results = db.search(vec![1.0, 0.0, 0.0], 2, 50).unwrap();
+    let results = db.search(vec![1.0, 0.0, 0.0], 2, 50, None, None, None).unwrap();
     // Results should be a JsValue (array)
     assert!(results.is_object());
 }
 
 #[wasm_bindgen_test]
 fn search_dimension_mismatch_returns_err() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
-    let result = db.search(vec![1.0, 0.0], 1, 50);
+    let result = db.search(vec![1.0, 0.0], 1, 50, None, None, None);
     assert!(result.is_err());
 }
 
+#[wasm_bindgen_test]
+fn rank_subset_orders_listed_ids_and_skips_unknown() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for (id, x) in [("a", 1.0), ("b", 2.0), ("c", 3.0), ("d", 4.0)] {
+        db.insert(id.into(), vec![x, 0.0], JsValue::NULL).unwrap();
+    }
+    let ids = |results: JsValue| -> Vec<String> {
+        js_sys::Array::from(&results)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+    let shortlist: Vec<String> = ["d", "missing", "b", "c"].iter().map(|s| s.to_string()).collect();
+    let ranked = db.rank_subset(vec![0.0, 0.0], shortlist.clone(), 10).unwrap();
+    assert_eq!(ids(ranked), vec!["b", "c", "d"]);
+    let top = db.rank_subset(vec![5.0, 0.0], shortlist, 2).unwrap();
+    assert_eq!(ids(top), vec!["d", "c"]);
+    assert!(db.rank_subset(vec![0.0], vec!["a".into()], 1).is_err());
+}
+
+#[wasm_bindgen_test]
+fn sample_is_sized_and_reproducible() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for i in 0..30 {
+        db.insert(format!("v{}", i), vec![i as f32, 1.0], JsValue::NULL).unwrap();
+    }
+    let ids = |records: JsValue| -> Vec<String> {
+        js_sys::Array::from(&records)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+    let first = ids(db.sample(5, Some(42)).unwrap());
+    assert_eq!(first.len(), 5);
+    assert_eq!(ids(db.sample(5, Some(42)).unwrap()), first);
+    assert_eq!(ids(db.sample(100, None).unwrap()).len(), 30);
+
+    let record = js_sys::Array::from(&db.sample(1, Some(1)).unwrap()).get(0);
+    let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&record, &"vector".into()).unwrap());
+    assert_eq!(vector.length(), 2);
+}
+
+#[wasm_bindgen_test]
+fn search_view_matches_search() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap();
+
+    let query = js_sys::Float32Array::from(&[1.0f32, 0.1, 0.0][..]);
+    let from_view = js_sys::Array::from(&db.search_view(&query, 2, 50, None, None, None).unwrap());
+    let from_vec = js_sys::Array::from(&db.search(vec![1.0, 0.1, 0.0], 2, 50, None, None, None).unwrap());
+    assert_eq!(from_view.length(), 2);
+    for i in 0..2 {
+        let field = |results: &js_sys::Array, name: &str| js_sys::Reflect::get(&results.get(i), &name.into()).unwrap();
+        assert_eq!(field(&from_view, "id").as_string(), field(&from_vec, "id").as_string());
+        assert_eq!(field(&from_view, "distance").as_f64(), field(&from_vec, "distance").as_f64());
+    }
+
+    let short = js_sys::Float32Array::from(&[1.0f32, 0.0][..]);
+    assert!(db.search_view(&short, 1, 50, None, None, None).is_err());
+}
+
 // ── Get ───────────────────────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn get_existing_returns_object() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     let result = db.get("a".into()).unwrap();
@@ -119,16 +282,43 @@ fn get_existing_returns_object() {
 
 #[wasm_bindgen_test]
 fn get_nonexistent_returns_null() {
-    let db = VectorDB::new(3, 16, 200, None);
+    let db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     let result = db.get("nope".into()).unwrap();
     assert!(result.is_null());
 }
 
+// ── Update metadata ───────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn update_metadata_replaces_and_removes() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"status".into(), &"draft".into()).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], meta.into()).unwrap();
+
+    let updated = js_sys::Object::new();
+    js_sys::Reflect::set(&updated, &"status".into(), &"published".into()).unwrap();
+    assert!(db.update_metadata("a".into(), updated.into()).unwrap());
+    assert_eq!(db.size(), 1);
+
+    let record = db.get("a".into()).unwrap();
+    let metadata = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    let status = js_sys::Reflect::get(&metadata, &"status".into()).unwrap();
+    assert_eq!(status.as_string().unwrap(), "published");
+
+    assert!(db.update_metadata("a".into(), JsValue::NULL).unwrap());
+    let record = db.get("a".into()).unwrap();
+    assert!(js_sys::Reflect::get(&record, &"metadata".into()).unwrap().is_null());
+
+    assert!(!db.update_metadata("missing".into(), JsValue::NULL).unwrap());
+    assert_eq!(db.size(), 1);
+}
+
 // ── Has ───────────────────────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn has_existing_returns_true() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     assert!(db.has("a".into()));
@@ -136,7 +326,7 @@ fn has_existing_returns_true() {
 
 #[wasm_bindgen_test]
 fn has_nonexistent_returns_false() {
-    let db = VectorDB::new(3, 16, 200, None);
+    let db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     assert!(!db.has("nope".into()));
 }
 
@@ -144,7 +334,7 @@ fn has_nonexistent_returns_false() {
 
 #[wasm_bindgen_test]
 fn list_ids_returns_array() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
@@ -157,40 +347,82 @@ fn list_ids_returns_array() {
 
 #[wasm_bindgen_test]
 fn delete_existing_returns_true() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
-    assert!(db.delete("a".into()));
+    assert!(db.delete("a".into()).unwrap());
     assert_eq!(db.size(), 0);
 }
 
 #[wasm_bindgen_test]
 fn delete_nonexistent_returns_false() {
-    let mut db = VectorDB::new(3, 16, 200, None);
-    assert!(!db.delete("nope".into()));
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    assert!(!db.delete("nope".into()).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn remove_returns_deleted_record() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"title".into(), &"hello".into()).unwrap();
+    db.insert("a".into(), vec![1.0, 2.0, 3.0], meta.into()).unwrap();
+
+    let removed = db.remove("a".into()).unwrap();
+    let id = js_sys::Reflect::get(&removed, &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "a");
+    let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&removed, &"vector".into()).unwrap());
+    assert_eq!(vector.to_vec(), vec![1.0, 2.0, 3.0]);
+    let metadata = js_sys::Reflect::get(&removed, &"metadata".into()).unwrap();
+    let title = js_sys::Reflect::get(&metadata, &"title".into()).unwrap();
+    assert_eq!(title.as_string().unwrap(), "hello");
+
+    assert!(!db.has("a".into()));
+    assert_eq!(db.size(), 0);
+    assert!(db.remove("a".into()).unwrap().is_null());
 }
 
 // ── Delete Batch ──────────────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn delete_batch_removes_multiple() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("c".into(), vec![0.0, 0.0, 1.0], JsValue::NULL)
         .unwrap();
-    let count = db.delete_batch(vec!["a".into(), "c".into()]);
+    let count = db.delete_batch(vec!["a".into(), "c".into()]).unwrap();
     assert_eq!(count, 2);
     assert_eq!(db.size(), 1);
 }
 
+// ── Clear ──────────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn clear_resets_and_accepts_new_inserts() {
+    let mut db = VectorDB::new(3, 16, 200, Some("cosine".into()), None, None, None).unwrap();
+    for i in 0..5 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &i.to_string().into()).unwrap();
+        db.insert(format!("v{}", i), vec![1.0, i as f32, 0.0], meta.into()).unwrap();
+    }
+    db.clear().unwrap();
+    assert_eq!(db.size(), 0);
+    assert!(db.get("v1".into()).unwrap().is_null());
+    assert_eq!(db.metric(), "cosine");
+
+    db.insert("v1".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(db.size(), 1);
+    let record = db.get("v1".into()).unwrap();
+    assert!(js_sys::Reflect::get(&record, &"metadata".into()).unwrap().is_null());
+}
+
 // ── Serialize / Deserialize ────────────────────────────────────
 
 #[wasm_bindgen_test]
 fn serialize_deserialize_roundtrip() {
-    let mut db = VectorDB::new(3, 16, 200, None);
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
     db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
         .unwrap();
     db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
@@ -201,13 +433,55 @@ fn serialize_deserialize_roundtrip() {
     assert_eq!(db2.size(), 2);
 
     // Search still works after deserialization
-    let results = db2.search(vec![1.0, 0.0, 0.0], 2, 50).unwrap();
+    let results = db2.search(vec![1.0, 0.0, 0.0], 2, 50, None, None, None).unwrap();
     assert!(results.is_object());
 }
 
+#[wasm_bindgen_test]
+fn typed_metadata_survives_serialization() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"title".into(), &"hello".into()).unwrap();
+    js_sys::Reflect::set(&meta, &"year".into(), &JsValue::from_f64(2024.0)).unwrap();
+    js_sys::Reflect::set(&meta, &"score".into(), &JsValue::from_f64(0.25)).unwrap();
+    js_sys::Reflect::set(&meta, &"draft".into(), &JsValue::TRUE).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], meta.into()).unwrap();
+
+    let check = |db: &VectorDB| {
+        let meta = db.get_metadata("a".into()).unwrap();
+        let field = |name: &str| js_sys::Reflect::get(&meta, &name.into()).unwrap();
+        assert_eq!(field("title").as_string().unwrap(), "hello");
+        assert_eq!(field("year").as_f64(), Some(2024.0));
+        assert_eq!(field("score").as_f64(), Some(0.25));
+        assert_eq!(field("draft").as_bool(), Some(true));
+
+        // Filters compare typed values
+        let filter = js_sys::Object::new();
+        js_sys::Reflect::set(&filter, &"year".into(), &JsValue::from_f64(2024.0)).unwrap();
+        assert_eq!(db.count_where(filter.clone().into()).unwrap(), 1);
+        js_sys::Reflect::set(&filter, &"year".into(), &"2024".into()).unwrap();
+        assert_eq!(db.count_where(filter.into()).unwrap(), 0);
+    };
+    check(&db);
+    check(&VectorDB::deserialize(db.serialize().unwrap()).unwrap());
+    check(&VectorDB::deserialize_binary(db.serialize_binary().unwrap()).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn legacy_string_metadata_stays_string() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    // A snapshot written when every metadata value was a string
+    let json = unsealed(&db).replace("\"metadata\":{}", "\"metadata\":{\"a\":{\"year\":\"2024\"}}");
+    let restored = VectorDB::deserialize(json).unwrap();
+    let meta = restored.get_metadata("a".into()).unwrap();
+    let year = js_sys::Reflect::get(&meta, &"year".into()).unwrap();
+    assert_eq!(year.as_string().unwrap(), "2024");
+}
+
 #[wasm_bindgen_test]
 fn serialize_empty_db() {
-    let db = VectorDB::new(5, 16, 200, None);
+    let db = VectorDB::new(5, 16, 200, None, None, None, None).unwrap();
     let json = db.serialize().unwrap();
     let db2 = VectorDB::deserialize(json).unwrap();
     assert_eq!(db2.size(), 0);
@@ -250,3 +524,1831 @@ fn dot_product_dimension_mismatch() {
     let result = dot_product(vec![1.0, 2.0], vec![3.0]);
     assert!(result.is_err());
 }
+
+#[wasm_bindgen_test]
+fn manhattan_distance_basic() {
+    let result = manhattan_distance(vec![0.0, 0.0], vec![3.0, -4.0]).unwrap();
+    assert!((result - 7.0).abs() < 1e-6);
+}
+
+#[wasm_bindgen_test]
+fn manhattan_distance_dimension_mismatch() {
+    let result = manhattan_distance(vec![1.0], vec![1.0, 2.0]);
+    assert!(result.is_err());
+}
+
+#[wasm_bindgen_test]
+fn hamming_distance_counts_differing_bits() {
+    assert_eq!(hamming_distance(vec![1.0, 0.0, 1.0], vec![1.0, 0.0, 1.0]).unwrap(), 0.0);
+    assert_eq!(hamming_distance(vec![1.0, 0.0, 1.0], vec![0.0, 1.0, 0.0]).unwrap(), 3.0);
+    assert_eq!(hamming_distance(vec![1.0, 1.0, 0.0], vec![1.0, 0.0, 0.0]).unwrap(), 1.0);
+    assert!(hamming_distance(vec![1.0], vec![1.0, 0.0]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn jaccard_distance_compares_nonzero_sets() {
+    assert_eq!(jaccard_distance(vec![1.0, 0.0, 3.0], vec![2.0, 0.0, 1.0]).unwrap(), 0.0);
+    assert_eq!(jaccard_distance(vec![1.0, 0.0], vec![0.0, 1.0]).unwrap(), 1.0);
+    assert_eq!(jaccard_distance(vec![1.0, 1.0, 0.0, 1.0], vec![1.0, 0.0, 1.0, 1.0]).unwrap(), 0.5);
+    assert_eq!(jaccard_distance(vec![0.0, 0.0], vec![0.0, 0.0]).unwrap(), 0.0);
+    assert!(jaccard_distance(vec![1.0], vec![1.0, 0.0]).is_err());
+}
+
+// ── Density profile ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn density_profile_returns_object() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for i in 0..10 {
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], JsValue::NULL)
+            .unwrap();
+    }
+    let profile = db.density_profile(1, 50, 5).unwrap();
+    let samples = js_sys::Reflect::get(&profile, &"samples".into()).unwrap();
+    assert_eq!(samples.as_f64(), Some(5.0));
+}
+
+// ── Automatic ef scaling ───────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn auto_ef_scales_effective_ef_with_k() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    assert_eq!(db.effective_ef(100, 10), 100);
+    db.enable_auto_ef(None);
+    assert_eq!(db.auto_ef_factor(), Some(2));
+    assert_eq!(db.effective_ef(100, 10), 200);
+    db.enable_auto_ef(Some(3));
+    assert_eq!(db.effective_ef(100, 10), 300);
+    db.disable_auto_ef();
+    assert_eq!(db.auto_ef_factor(), None);
+    assert_eq!(db.effective_ef(100, 10), 100);
+}
+
+// ── Layer health ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn layer_health_returns_object() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
+        .unwrap();
+    let health = db.layer_health().unwrap();
+    let count = js_sys::Reflect::get(&health, &"node_count".into()).unwrap();
+    assert_eq!(count.as_f64(), Some(1.0));
+    assert!(js_sys::Reflect::has(&health, &"healthy".into()).unwrap());
+}
+
+// ── Reproject ──────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn reproject_pads_and_accepts_new_dimension() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("old".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
+        .unwrap();
+    db.reproject(5).unwrap();
+
+    assert!(db.insert("short".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).is_err());
+    db.insert("new".into(), vec![0.0, 0.0, 0.0, 1.0, 0.0], JsValue::NULL)
+        .unwrap();
+    assert_eq!(db.size(), 2);
+    assert!(db.search(vec![1.0, 0.0, 0.0, 0.0, 0.0], 2, 50, None, None, None).is_ok());
+    assert!(db.reproject(4).is_err());
+}
+
+// ── Cluster elbow ──────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn cluster_elbow_returns_point_per_k() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for i in 0..6 {
+        let x = if i < 3 { 0.0 } else { 10.0 };
+        db.insert(format!("v{}", i), vec![x, i as f32 * 0.1], JsValue::NULL)
+            .unwrap();
+    }
+    let curve = db.cluster_elbow(vec![1, 2, 3], 20).unwrap();
+    let arr = js_sys::Array::from(&curve);
+    assert_eq!(arr.length(), 3);
+    let k = js_sys::Reflect::get(&arr.get(1), &"k".into()).unwrap();
+    assert_eq!(k.as_f64(), Some(2.0));
+}
+
+// ── Neighbor selection ─────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn neighbor_selection_options_keep_search_working() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.set_keep_pruned_connections(true);
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
+        .unwrap();
+    db.set_neighbor_heuristic(false);
+    db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
+        .unwrap();
+    let results = js_sys::Array::from(&db.search(vec![1.0, 0.0, 0.0], 2, 50, None, None, None).unwrap());
+    assert_eq!(results.length(), 2);
+}
+
+// ── Seeded construction ────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn seeded_databases_serialize_identically() {
+    let build = || {
+        let mut db = VectorDB::new(3, 16, 200, None, Some(1234), None, None).unwrap();
+        for i in 0..20 {
+            let x = i as f32;
+            db.insert(format!("v{}", i), vec![x, x * 0.5, 1.0], JsValue::NULL)
+                .unwrap();
+        }
+        db.serialize().unwrap()
+    };
+    assert_eq!(build(), build());
+}
+
+// ── Read-only snapshots ────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn load_readonly_rejects_mutations_but_allows_reads() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
+        .unwrap();
+    let json = db.serialize().unwrap();
+
+    let mut ro = VectorDB::load_readonly(json).unwrap();
+    assert!(ro.is_read_only());
+    assert!(ro.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).is_err());
+    assert!(ro.delete("a".into()).is_err());
+    assert!(ro.delete_batch(vec!["a".into()]).is_err());
+    assert_eq!(ro.size(), 1);
+    assert!(ro.get("a".into()).unwrap().is_object());
+    assert!(ro.search(vec![1.0, 0.0, 0.0], 1, 50, None, None, None).is_ok());
+
+    ro.to_mutable();
+    assert!(!ro.is_read_only());
+    ro.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL)
+        .unwrap();
+    assert_eq!(ro.size(), 2);
+}
+
+// ── Compare against ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn compare_against_self_snapshot_is_full_overlap() {
+    let mut db = VectorDB::new(3, 16, 200, None, Some(1), None, None).unwrap();
+    for i in 0..10 {
+        let x = i as f32;
+        db.insert(format!("v{}", i), vec![x, 1.0, -x], JsValue::NULL)
+            .unwrap();
+    }
+    let json = db.serialize().unwrap();
+    let queries = js_sys::Array::new();
+    queries.push(&js_sys::Float32Array::from(&[1.0f32, 1.0, -1.0][..]));
+    queries.push(&serde_wasm_bindgen::to_value(&vec![5.0f32, 1.0, -5.0]).unwrap());
+
+    let overlap = db.compare_against(json, queries.into(), 3, 50).unwrap();
+    assert!((overlap - 1.0).abs() < 1e-6);
+}
+
+// ── Insert batch ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn insert_batch_reports_per_record_results() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let records = js_sys::Array::new();
+    let make = |id: &str, vector: &[f32]| {
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &"id".into(), &id.into()).unwrap();
+        js_sys::Reflect::set(&obj, &"vector".into(), &js_sys::Float32Array::from(vector)).unwrap();
+        obj
+    };
+    records.push(&make("a", &[1.0, 0.0, 0.0]));
+    records.push(&make("bad", &[1.0, 0.0]));
+    records.push(&make("nan", &[f32::NAN, 0.0, 0.0]));
+    records.push(&make("b", &[0.0, 1.0, 0.0]));
+
+    let results = js_sys::Array::from(&db.insert_batch(records.into(), None).unwrap());
+    assert_eq!(results.length(), 4);
+    let ok = |i: u32| js_sys::Reflect::get(&results.get(i), &"ok".into()).unwrap().as_bool();
+    assert_eq!(ok(0), Some(true));
+    assert_eq!(ok(1), Some(false));
+    assert_eq!(ok(2), Some(false));
+    assert_eq!(ok(3), Some(true));
+    let error = js_sys::Reflect::get(&results.get(1), &"error".into()).unwrap();
+    assert!(error.as_string().unwrap().contains("dimension mismatch"));
+    assert_eq!(db.size(), 2);
+}
+
+// ── Insert with m ──────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn insert_with_m_adds_searchable_record() {
+    let mut db = VectorDB::new(3, 4, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL)
+        .unwrap();
+    db.insert_with_m("hub".into(), vec![0.0, 1.0, 0.0], JsValue::NULL, 32)
+        .unwrap();
+    assert!(db.insert_with_m("bad".into(), vec![0.0, 1.0, 0.0], JsValue::NULL, 0).is_err());
+    assert_eq!(db.size(), 2);
+    let results = js_sys::Array::from(&db.search(vec![0.0, 1.0, 0.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "hub");
+}
+
+#[wasm_bindgen_test]
+fn insert_with_ef_adds_searchable_record() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert_with_ef("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL, 10).unwrap();
+    let err = db.insert_with_ef("bad".into(), vec![0.0, 0.0, 1.0], JsValue::NULL, 0).err().unwrap();
+    assert_eq!(err.as_string().unwrap(), "ef_construction must be at least 1");
+    assert_eq!(db.size(), 2);
+    let results = js_sys::Array::from(&db.search(vec![0.0, 1.0, 0.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "b");
+}
+
+// ── Filtered search ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_filtered_returns_k_matching_records() {
+    let mut db = VectorDB::new(3, 16, 200, None, Some(3), None, None).unwrap();
+    for i in 0..60 {
+        let meta = js_sys::Object::new();
+        let tenant = if i % 10 == 0 { "acme" } else { "other" };
+        js_sys::Reflect::set(&meta, &"tenant".into(), &tenant.into()).unwrap();
+        let x = i as f32;
+        db.insert(format!("v{}", i), vec![x, x * 0.5, 1.0], meta.into())
+            .unwrap();
+    }
+
+    let filter = js_sys::Object::new();
+    js_sys::Reflect::set(&filter, &"tenant".into(), &"acme".into()).unwrap();
+    let results = js_sys::Array::from(
+        &db.search_filtered(vec![0.0, 0.0, 1.0], 3, 10, filter.clone().into(), None, None).unwrap(),
+    );
+    assert_eq!(results.length(), 3);
+    let ids: Vec<String> = (0..3)
+        .map(|i| {
+            js_sys::Reflect::get(&results.get(i), &"id".into())
+                .unwrap()
+                .as_string()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(ids, vec!["v0", "v10", "v20"]);
+
+    // v20 is about 22.4 away, past the cutoff
+    let near = db.search_filtered(vec![0.0, 0.0, 1.0], 3, 10, filter.clone().into(), None, Some(15.0)).unwrap();
+    assert_eq!(js_sys::Array::from(&near).length(), 2);
+    assert!(db.search_filtered(vec![0.0, 0.0, 1.0], 3, 10, filter.into(), None, Some(f32::NAN)).is_err());
+}
+
+#[wasm_bindgen_test]
+fn search_filtered_applies_numeric_ranges() {
+    let mut db = VectorDB::new(3, 16, 200, None, Some(3), None, None).unwrap();
+    for i in 0..400 {
+        let meta = js_sys::Object::new();
+        // Every 7th price is a string and every 11th record has none
+        if i % 11 != 0 {
+            let price: JsValue = if i % 7 == 0 { i.to_string().into() } else { JsValue::from_f64(i as f64) };
+            js_sys::Reflect::set(&meta, &"price".into(), &price).unwrap();
+        }
+        let x = i as f32;
+        db.insert(format!("v{}", i), vec![x, x * 0.5, 1.0], meta.into()).unwrap();
+    }
+
+    let range = js_sys::Object::new();
+    js_sys::Reflect::set(&range, &"gte".into(), &JsValue::from_f64(100.0)).unwrap();
+    js_sys::Reflect::set(&range, &"lt".into(), &JsValue::from_f64(120.0)).unwrap();
+    let filter = js_sys::Object::new();
+    js_sys::Reflect::set(&filter, &"price".into(), &range).unwrap();
+    let results = js_sys::Array::from(
+        &db.search_filtered(vec![0.0, 0.0, 1.0], 50, 100, filter.clone().into(), None, None).unwrap(),
+    );
+    let mut ids: Vec<usize> = results
+        .iter()
+        .map(|r| {
+            let id = js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap();
+            id[1..].parse().unwrap()
+        })
+        .collect();
+    ids.sort();
+    let expected: Vec<usize> = (100..120).filter(|i| i % 7 != 0 && i % 11 != 0).collect();
+    assert_eq!(ids, expected);
+    assert_eq!(db.count_where(filter.into()).unwrap(), expected.len());
+
+    let empty = js_sys::Object::new();
+    js_sys::Reflect::set(&empty, &"price".into(), &js_sys::Object::new()).unwrap();
+    assert!(db.search_filtered(vec![0.0, 0.0, 1.0], 5, 50, empty.into(), None, None).is_err());
+}
+
+// ── Filtered NDJSON export ─────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn count_where_counts_matching_records() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    let tags = [("news", "en"), ("news", "fr"), ("blog", "en"), ("news", "en")];
+    for (i, (kind, lang)) in tags.iter().enumerate() {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"kind".into(), &(*kind).into()).unwrap();
+        js_sys::Reflect::set(&meta, &"lang".into(), &(*lang).into()).unwrap();
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], meta.into()).unwrap();
+    }
+    db.insert("bare".into(), vec![9.0, 9.0], JsValue::NULL).unwrap();
+
+    let filter = |pairs: &[(&str, &str)]| {
+        let obj = js_sys::Object::new();
+        for (k, v) in pairs {
+            js_sys::Reflect::set(&obj, &(*k).into(), &(*v).into()).unwrap();
+        }
+        JsValue::from(obj)
+    };
+    assert_eq!(db.count_where(filter(&[("kind", "news")])).unwrap(), 3);
+    assert_eq!(db.count_where(filter(&[("kind", "news"), ("lang", "en")])).unwrap(), 2);
+    assert_eq!(db.count_where(filter(&[("lang", "de")])).unwrap(), 0);
+    assert_eq!(db.count_where(filter(&[])).unwrap(), 5);
+    assert_eq!(db.count_where(JsValue::NULL).unwrap(), 5);
+}
+
+#[wasm_bindgen_test]
+fn export_filtered_ndjson_chunks_contains_only_matches() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    for i in 0..25 {
+        let meta = js_sys::Object::new();
+        let tenant = if i % 3 == 0 { "acme" } else { "other" };
+        js_sys::Reflect::set(&meta, &"tenant".into(), &tenant.into()).unwrap();
+        db.insert(format!("v{:02}", i), vec![i as f32, 1.0, 0.0], meta.into())
+            .unwrap();
+    }
+
+    let filter = js_sys::Object::new();
+    js_sys::Reflect::set(&filter, &"tenant".into(), &"acme".into()).unwrap();
+    let chunks = js_sys::Array::from(&db.export_filtered_ndjson_chunks(filter.into(), 4).unwrap());
+    // 9 matching records (0, 3, ..., 24) in chunks of 4
+    assert_eq!(chunks.length(), 3);
+
+    let mut records: Vec<VectorRecord> = Vec::new();
+    for chunk in chunks.iter() {
+        let text = chunk.as_string().unwrap();
+        for line in text.lines() {
+            records.push(serde_json::from_str(line).unwrap());
+        }
+    }
+    assert_eq!(records.len(), 9);
+    for record in &records {
+        let meta = record.metadata.as_ref().unwrap();
+        assert_eq!(meta.get("tenant").and_then(MetadataValue::as_str), Some("acme"));
+        assert_eq!(record.vector.len(), 3);
+    }
+    assert_eq!(records[0].id, "v00");
+    assert_eq!(records[8].id, "v24");
+    assert!(db.export_filtered_ndjson_chunks(JsValue::NULL, 0).is_err());
+}
+
+// ── Group representatives ──────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn group_representatives_picks_members_near_each_centroid() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    let insert = |db: &mut VectorDB, id: &str, v: Vec<f32>, label: &str| {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"label".into(), &label.into()).unwrap();
+        db.insert(id.into(), v, meta.into()).unwrap();
+    };
+    // Group "a" centered on (0, 0), group "b" on (10, 10)
+    insert(&mut db, "a_center", vec![0.1, 0.0], "a");
+    insert(&mut db, "a_left", vec![-2.0, 0.0], "a");
+    insert(&mut db, "a_right", vec![2.0, 0.0], "a");
+    insert(&mut db, "b_center", vec![10.0, 10.1], "b");
+    insert(&mut db, "b_up", vec![10.0, 13.0], "b");
+    insert(&mut db, "b_down", vec![10.0, 7.0], "b");
+    db.insert("unlabeled".into(), vec![5.0, 5.0], JsValue::NULL).unwrap();
+
+    let groups = js_sys::Array::from(&db.group_representatives("label".into(), 1).unwrap());
+    assert_eq!(groups.length(), 2);
+    for (i, (label, expected)) in [("a", "a_center"), ("b", "b_center")].iter().enumerate() {
+        let group = groups.get(i as u32);
+        let name = js_sys::Reflect::get(&group, &"group".into()).unwrap();
+        assert_eq!(name.as_string().unwrap(), *label);
+        let size = js_sys::Reflect::get(&group, &"size".into()).unwrap();
+        assert_eq!(size.as_f64(), Some(3.0));
+        let reps = js_sys::Array::from(&js_sys::Reflect::get(&group, &"representatives".into()).unwrap());
+        assert_eq!(reps.length(), 1);
+        let id = js_sys::Reflect::get(&reps.get(0), &"id".into()).unwrap();
+        assert_eq!(id.as_string().unwrap(), *expected);
+    }
+}
+
+// ── Mutual nearest neighbors ───────────────────────────────────
+
+#[wasm_bindgen_test]
+fn mutual_nn_reports_near_identical_pair() {
+    let mut left = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    left.insert("l_dup".into(), vec![5.0, 5.0], JsValue::NULL).unwrap();
+    left.insert("l_other".into(), vec![0.0, 0.0], JsValue::NULL).unwrap();
+    let mut right = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    right.insert("r_dup".into(), vec![5.0, 5.01], JsValue::NULL).unwrap();
+    right.insert("r_other".into(), vec![-30.0, 0.0], JsValue::NULL).unwrap();
+
+    let pairs = js_sys::Array::from(&left.mutual_nn(right.serialize().unwrap(), 50).unwrap());
+    let found = pairs.iter().any(|pair| {
+        let a = js_sys::Reflect::get(&pair, &"a".into()).unwrap().as_string();
+        let b = js_sys::Reflect::get(&pair, &"b".into()).unwrap().as_string();
+        a.as_deref() == Some("l_dup") && b.as_deref() == Some("r_dup")
+    });
+    assert!(found);
+}
+
+// ── Zero vectors ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn zero_vectors_under_cosine() {
+    let mut db = VectorDB::new(2, 16, 200, Some("cosine".to_string()), Some(3), None, None).unwrap();
+    db.insert("a".to_string(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("zero".to_string(), vec![0.0, 0.0], JsValue::NULL).unwrap();
+
+    db.set_reject_zero_vectors(true);
+    assert!(db.insert("zero2".to_string(), vec![0.0, 0.0], JsValue::NULL).is_err());
+    assert!(db.search(vec![0.0, 0.0], 2, 50, None, None, None).is_err());
+    assert!(db.search(vec![1.0, 0.0], 2, 50, None, None, None).is_ok());
+
+    // Every search entry point rejects a zero query, not just `search`
+    let err = db.search_radius(vec![0.0, 0.0], 1.0, 50).unwrap_err();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    assert!(db.search_page(vec![0.0, 0.0], 0, 2, 50).is_err());
+    assert!(db.search_filtered(vec![0.0, 0.0], 2, 50, JsValue::NULL, None, None).is_err());
+    assert!(db.search_with_provenance(vec![0.0, 0.0], 2, 50, 0.0).is_err());
+
+    let mut euclidean = VectorDB::new(2, 16, 200, None, Some(3), None, None).unwrap();
+    euclidean.set_reject_zero_vectors(true);
+    euclidean.insert("zero".to_string(), vec![0.0, 0.0], JsValue::NULL).unwrap();
+    assert!(euclidean.search(vec![0.0, 0.0], 1, 50, None, None, None).is_ok());
+}
+
+// ── Dot product similarity ─────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn dot_product_results_carry_similarity() {
+    let mut db = VectorDB::new(2, 16, 200, Some("dotproduct".to_string()), Some(2), None, None).unwrap();
+    db.insert("small".to_string(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("large".to_string(), vec![3.0, 1.0], JsValue::NULL).unwrap();
+
+    let results = js_sys::Array::from(&db.search(vec![1.0, 1.0], 2, 10, None, None, None).unwrap());
+    let field = |i: u32, key: &str| js_sys::Reflect::get(&results.get(i), &key.into()).unwrap();
+    assert_eq!(field(0, "id").as_string().unwrap(), "large");
+    assert_eq!(field(0, "distance").as_f64(), Some(-4.0));
+    assert_eq!(field(0, "similarity").as_f64(), Some(4.0));
+    assert_eq!(field(1, "similarity").as_f64(), Some(1.0));
+
+    let mut euclidean = VectorDB::new(2, 16, 200, None, Some(2), None, None).unwrap();
+    euclidean.insert("a".to_string(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    let results = js_sys::Array::from(&euclidean.search(vec![1.0, 1.0], 1, 10, None, None, None).unwrap());
+    assert!(!js_sys::Reflect::has(&results.get(0), &"similarity".into()).unwrap());
+}
+
+// ── Distance cutoff ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn max_distance_cuts_off_in_reported_units() {
+    let ids = |results: JsValue| -> Vec<String> {
+        js_sys::Array::from(&results)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+
+    // Euclidean distances 1, 2 and 3: a cutoff of 2.5 would keep all three
+    // if it were compared against squared distances (1, 4, 9 > 2.5 drops two)
+    let mut euclidean = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    for (id, x) in [("one", 1.0), ("two", 2.0), ("three", 3.0)] {
+        euclidean.insert(id.to_string(), vec![x, 0.0], JsValue::NULL).unwrap();
+    }
+    let results = euclidean.search(vec![0.0, 0.0], 3, 50, None, Some(2.5), None).unwrap();
+    assert_eq!(ids(results), vec!["one", "two"]);
+    let results = euclidean.search(vec![0.0, 0.0], 1, 50, None, Some(2.5), None).unwrap();
+    assert_eq!(ids(results), vec!["one"]);
+    assert!(euclidean.search(vec![0.0, 0.0], 3, 50, None, Some(f32::NAN), None).is_err());
+
+    // Cosine distances 0, 1 - cos(60°) = 0.5 and 1
+    let mut cosine = VectorDB::new(2, 16, 200, Some("cosine".to_string()), Some(1), None, None).unwrap();
+    cosine.insert("same".to_string(), vec![2.0, 0.0], JsValue::NULL).unwrap();
+    cosine.insert("sixty".to_string(), vec![0.5, 0.75f32.sqrt()], JsValue::NULL).unwrap();
+    cosine.insert("orthogonal".to_string(), vec![0.0, 1.0], JsValue::NULL).unwrap();
+    let results = cosine.search(vec![1.0, 0.0], 3, 50, None, Some(0.3), None).unwrap();
+    assert_eq!(ids(results), vec!["same"]);
+    let results = cosine.search(vec![1.0, 0.0], 3, 50, None, Some(0.6), None).unwrap();
+    assert_eq!(ids(results), vec!["same", "sixty"]);
+}
+
+// ── Query normalization ────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn normalize_query_keeps_cosine_ranking() {
+    let mut db = VectorDB::new(3, 16, 200, Some("cosine".to_string()), Some(5), None, None).unwrap();
+    for i in 0..30 {
+        let t = i as f32 * 0.2;
+        // Mix unit-length and raw vectors
+        let scale = if i % 2 == 0 { 1.0 } else { 7.5 };
+        db.insert(format!("v{}", i), vec![t.cos() * scale, t.sin() * scale, 0.3 * scale], JsValue::NULL).unwrap();
+    }
+    let rows = |results: JsValue| -> Vec<(String, f32)> {
+        js_sys::Array::from(&results)
+            .iter()
+            .map(|r| {
+                let get = |key: &str| js_sys::Reflect::get(&r, &key.into()).unwrap();
+                (get("id").as_string().unwrap(), get("distance").as_f64().unwrap() as f32)
+            })
+            .collect()
+    };
+
+    let raw = rows(db.search(vec![12.0, 5.0, 4.0], 5, 50, None, None, None).unwrap());
+    let normalized = rows(db.search(vec![12.0, 5.0, 4.0], 5, 50, None, None, Some(true)).unwrap());
+    assert_eq!(raw.len(), 5);
+    for ((raw_id, raw_distance), (id, distance)) in raw.iter().zip(&normalized) {
+        assert_eq!(raw_id, id);
+        assert!((raw_distance - distance).abs() < 1e-5);
+    }
+
+    // Zero queries are still rejected, normalized or not
+    assert!(db.search(vec![0.0, 0.0, 0.0], 5, 50, None, None, Some(true)).is_err());
+}
+
+// ── Metric reindex ─────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn reindex_with_metric_switches_to_cosine_ordering() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(3), None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"tag".into(), &"kept".into()).unwrap();
+    db.insert("near_small".to_string(), vec![1.0, 0.1], meta.into()).unwrap();
+    db.insert("far_aligned".to_string(), vec![10.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("opposite".to_string(), vec![-1.0, 0.0], JsValue::NULL).unwrap();
+
+    let first_id = |db: &VectorDB| {
+        let results = js_sys::Array::from(&db.search(vec![1.0, 0.0], 3, 50, None, None, None).unwrap());
+        js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap().as_string().unwrap()
+    };
+    assert_eq!(first_id(&db), "near_small");
+
+    assert!(db.reindex_with_metric("chebyshev".to_string(), None).is_err());
+    db.reindex_with_metric("cosine".to_string(), None).unwrap();
+    assert_eq!(db.metric(), "cosine");
+    assert_eq!(first_id(&db), "far_aligned");
+    assert_eq!(db.size(), 3);
+
+    let record = db.get("near_small".to_string()).unwrap();
+    let meta = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    assert_eq!(js_sys::Reflect::get(&meta, &"tag".into()).unwrap().as_string().unwrap(), "kept");
+}
+
+// ── Batch search ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_batch_returns_one_result_array_per_query() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(4), None, None).unwrap();
+    for i in 0..10 {
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], JsValue::NULL).unwrap();
+    }
+    let queries = js_sys::Array::new();
+    queries.push(&js_sys::Float32Array::from(&[0.1f32, 0.0][..]));
+    queries.push(&serde_wasm_bindgen::to_value(&vec![8.8f32, 0.0]).unwrap());
+    queries.push(&js_sys::Float32Array::from(&[1.0f32][..]));
+
+    let out = js_sys::Array::from(&db.search_batch(queries.into(), 2, 50).unwrap());
+    assert_eq!(out.length(), 3);
+    let ids = |entry: JsValue| -> Vec<String> {
+        js_sys::Array::from(&entry)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+    assert_eq!(ids(out.get(0)), vec!["v0", "v1"]);
+    assert_eq!(ids(out.get(1)), vec!["v9", "v8"]);
+    let error = js_sys::Reflect::get(&out.get(2), &"error".into()).unwrap();
+    assert!(error.as_string().unwrap().contains("Query 2 dimension mismatch"));
+    let code = js_sys::Reflect::get(&out.get(2), &"code".into()).unwrap();
+    assert_eq!(code.as_string().unwrap(), "DIMENSION_MISMATCH");
+
+    assert!(db.search_batch(JsValue::from(1), 2, 50).is_err());
+}
+
+// ── Recall estimate ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn tune_ef_meets_the_target_recall() {
+    let mut db = VectorDB::new(4, 4, 16, None, Some(8), None, None).unwrap();
+    for i in 0..300 {
+        let x = i as f32;
+        db.insert(format!("v{}", i), vec![x.sin(), x.cos(), (x * 0.3).sin(), (x * 0.7).cos()], JsValue::NULL)
+            .unwrap();
+    }
+    let queries = js_sys::Array::new();
+    for q in 0..20 {
+        let x = q as f32 * 7.3 + 0.5;
+        queries.push(&js_sys::Float32Array::from(&[x.cos(), x.sin(), (x * 0.5).cos(), (x * 0.2).sin()][..]));
+    }
+
+    let ef = db.tune_ef(queries.clone().into(), 5, 0.9).unwrap();
+    assert!(ef >= 5);
+    assert!(db.estimate_recall(queries.clone().into(), 5, ef).unwrap() >= 0.9);
+    assert!(db.tune_ef(queries.into(), 5, 1.5).is_err());
+}
+
+#[wasm_bindgen_test]
+fn estimate_recall_is_perfect_on_tiny_db() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    for i in 0..6 {
+        db.insert(format!("v{}", i), vec![i as f32, (i * i) as f32], JsValue::NULL)
+            .unwrap();
+    }
+    let queries = js_sys::Array::new();
+    queries.push(&js_sys::Float32Array::from(&[0.5f32, 0.5][..]));
+    queries.push(&js_sys::Float32Array::from(&[3.0f32, 8.0][..]));
+    let recall = db.estimate_recall(queries.into(), 3, 50).unwrap();
+    assert_eq!(recall, 1.0);
+
+    let bad = js_sys::Array::new();
+    bad.push(&js_sys::Float32Array::from(&[1.0f32][..]));
+    assert!(db.estimate_recall(bad.into(), 3, 50).is_err());
+}
+
+#[wasm_bindgen_test]
+fn loo_recall_is_perfect_on_tiny_db_and_keeps_size() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    for i in 0..8 {
+        db.insert(format!("v{}", i), vec![i as f32, (i * i) as f32], JsValue::NULL)
+            .unwrap();
+    }
+    assert_eq!(db.loo_recall(3, 50, 8), 1.0);
+    assert_eq!(db.size(), 8);
+    assert!(db.get("v3".into()).unwrap().is_object());
+}
+
+// ── Paged search ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_page_windows_are_consecutive() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(2), None, None).unwrap();
+    for i in 0..30 {
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], JsValue::NULL)
+            .unwrap();
+    }
+    let ids = |value: JsValue| -> Vec<String> {
+        js_sys::Array::from(&value)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+    let first = ids(db.search_page(vec![0.0, 0.0], 0, 5, 50).unwrap());
+    let second = ids(db.search_page(vec![0.0, 0.0], 5, 5, 50).unwrap());
+    assert_eq!(first, vec!["v0", "v1", "v2", "v3", "v4"]);
+    assert_eq!(second, vec!["v5", "v6", "v7", "v8", "v9"]);
+}
+
+// ── Binary serialization ───────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn binary_roundtrip_is_smaller_and_searches_identically() {
+    let mut db = VectorDB::new(64, 16, 200, None, Some(5), None, None).unwrap();
+    for i in 0..50 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &format!("{}", i).into()).unwrap();
+        let v: Vec<f32> = (0..64).map(|d| ((i * 64 + d) as f32 * 0.618).sin()).collect();
+        db.insert(format!("v{}", i), v, meta.into()).unwrap();
+    }
+
+    let json = db.serialize().unwrap();
+    let bytes = db.serialize_binary().unwrap();
+    assert!(bytes.len() * 2 < json.len(), "binary {} vs json {}", bytes.len(), json.len());
+
+    let restored = VectorDB::deserialize_binary(bytes).unwrap();
+    assert_eq!(restored.size(), 50);
+    let record = restored.get("v7".into()).unwrap();
+    let metadata = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    let n = js_sys::Reflect::get(&metadata, &"n".into()).unwrap();
+    assert_eq!(n.as_string().unwrap(), "7");
+
+    let query: Vec<f32> = (0..64).map(|d| (d as f32 * 0.1).cos()).collect();
+    let before = js_sys::JSON::stringify(&db.search(query.clone(), 5, 50, None, None, None).unwrap()).unwrap();
+    let after = js_sys::JSON::stringify(&restored.search(query, 5, 50, None, None, None).unwrap()).unwrap();
+    assert_eq!(before, after);
+
+    assert!(VectorDB::deserialize_binary(vec![1, 2, 3]).is_err());
+}
+
+// ── Normalized storage ─────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn normalized_flag_is_saved_and_distances_match_after_load() {
+    let mut db = VectorDB::new(3, 16, 200, Some("cosine".to_string()), Some(2), None, None).unwrap();
+    db.insert("a".into(), vec![3.0, 4.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 2.0, 2.0], JsValue::NULL).unwrap();
+    db.set_normalized(true).unwrap();
+    db.insert("c".into(), vec![1.0, 1.0, 1.0], JsValue::NULL).unwrap();
+
+    let restored = VectorDB::deserialize(db.serialize().unwrap()).unwrap();
+    assert!(restored.normalized());
+    let query = vec![2.0, 1.0, 0.5];
+    let before = js_sys::JSON::stringify(&db.search(query.clone(), 3, 50, None, None, None).unwrap()).unwrap();
+    let after = js_sys::JSON::stringify(&restored.search(query, 3, 50, None, None, None).unwrap()).unwrap();
+    assert_eq!(before, after);
+
+    let mut euclidean = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    assert!(euclidean.set_normalized(true).is_err());
+}
+
+// ── Capacity limit ─────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn capacity_limit_evicts_least_recently_accessed() {
+    let mut db = VectorDB::new_with_capacity_limit(2, 16, 200, None, Some(4), 3).unwrap();
+    db.insert("a".into(), vec![0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![10.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("c".into(), vec![20.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(db.access_order(), vec!["a", "b", "c"]);
+
+    // Searching near "a" and reading "b" leaves "c" least recently used
+    db.search(vec![0.1, 0.0], 1, 50, None, None, None).unwrap();
+    db.get("b".into()).unwrap();
+    assert_eq!(db.access_order(), vec!["c", "a", "b"]);
+
+    // Bulk reads leave the order alone
+    db.get_many(vec!["c".into(), "a".into()]).unwrap();
+    db.sample(3, Some(1)).unwrap();
+    db.entries(0, 3).unwrap();
+    assert_eq!(db.access_order(), vec!["c", "a", "b"]);
+
+    db.insert("d".into(), vec![30.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("e".into(), vec![40.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(db.size(), 3);
+    assert!(!db.has("c".into()) && !db.has("a".into()));
+    assert_eq!(db.access_order(), vec!["b", "d", "e"]);
+
+    // Replacing a stored id doesn't evict anything
+    db.insert("b".into(), vec![11.0, 0.0], JsValue::NULL).unwrap();
+    assert_eq!(db.size(), 3);
+    assert_eq!(db.access_order(), vec!["d", "e", "b"]);
+
+    // Lowering the cap evicts down to it at once
+    assert_eq!(db.set_capacity_limit(Some(1)).unwrap(), 2);
+    assert_eq!(db.capacity_limit(), Some(1));
+    assert_eq!(db.access_order(), vec!["b"]);
+    assert!(db.set_capacity_limit(Some(0)).is_err());
+    db.set_capacity_limit(None).unwrap();
+    assert!(db.access_order().is_empty());
+}
+
+// ── Chunked serialization ──────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn chunked_roundtrip_restores_the_same_database() {
+    let mut db = VectorDB::new(32, 16, 200, None, Some(9), None, None).unwrap();
+    for i in 0..300 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &JsValue::from_f64(i as f64)).unwrap();
+        let v: Vec<f32> = (0..32).map(|d| ((i * 32 + d) as f32 * 0.618).sin()).collect();
+        db.insert_with_timestamp(format!("v{}", i), v, meta.into(), 1_700_000_000 + i as u64).unwrap();
+    }
+
+    let chunks = js_sys::Array::from(&db.serialize_chunks(4096).unwrap());
+    assert!(chunks.length() > 10, "only {} chunks", chunks.length());
+    // Timestamps ride along with their records, not in the manifest
+    assert!(!chunks.get(0).as_string().unwrap().contains("timestamps"));
+    for chunk in chunks.iter().skip(1) {
+        // Each chunk is a whole JSON document within the limit
+        let text = chunk.as_string().unwrap();
+        assert!(text.len() <= 4096, "chunk of {} bytes", text.len());
+        assert!(js_sys::JSON::parse(&text).is_ok());
+    }
+
+    // Stored chunks may come back in any order
+    chunks.reverse();
+    let restored = VectorDB::deserialize_chunks(chunks.clone().into()).unwrap();
+    assert_eq!(restored.size(), 300);
+    assert_eq!(restored.serialize().unwrap(), db.serialize().unwrap());
+
+    // An edited chunk fails its checksum
+    let edited = chunks.clone();
+    edited.set(3, edited.get(3).as_string().unwrap().replacen("\"id\":\"", "\"id\":\"x", 1).into());
+    let err = VectorDB::deserialize_chunks(edited.into()).err().unwrap();
+    assert_eq!(error_parts(&err).0, "CHECKSUM_MISMATCH");
+
+    // A lost chunk is an error, not a silently smaller database
+    chunks.shift();
+    let err = VectorDB::deserialize_chunks(chunks.into()).err().unwrap();
+    assert_eq!(error_parts(&err).0, "PARSE_ERROR");
+}
+
+// ── Seed cache ─────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn seed_cache_hits_for_repeated_queries() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(4), None, None).unwrap();
+    for i in 0..50 {
+        db.insert(format!("v{}", i), vec![i as f32, (i % 7) as f32], JsValue::NULL)
+            .unwrap();
+    }
+    assert!(db.seed_cache_stats().unwrap().is_null());
+    assert!(db.enable_seed_cache(0.0).is_err());
+    db.enable_seed_cache(1.0).unwrap();
+
+    let ids = |value: JsValue| -> Vec<String> {
+        js_sys::Array::from(&value)
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect()
+    };
+    let before = ids(db.search(vec![10.2, 3.2], 3, 50, None, None, None).unwrap());
+    let after = ids(db.search(vec![10.3, 3.3], 3, 50, None, None, None).unwrap());
+    assert_eq!(before, after);
+
+    let stats = db.seed_cache_stats().unwrap();
+    let hits = js_sys::Reflect::get(&stats, &"hits".into()).unwrap();
+    assert_eq!(hits.as_f64(), Some(1.0));
+}
+
+// ── Non-finite metadata ────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn non_finite_numeric_metadata_is_rejected() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    let nan_meta = || {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"score".into(), &JsValue::from_f64(f64::NAN)).unwrap();
+        JsValue::from(meta)
+    };
+
+    let err = db.insert("a".into(), vec![1.0, 0.0, 0.0], nan_meta()).unwrap_err();
+    let (code, message) = error_parts(&err);
+    assert_eq!(code, "NON_FINITE");
+    assert!(message.contains("score"));
+    assert_eq!(db.size(), 0);
+
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    assert!(db.update_metadata("a".into(), nan_meta()).is_err());
+
+    let inf_meta = js_sys::Object::new();
+    js_sys::Reflect::set(&inf_meta, &"score".into(), &JsValue::from_f64(f64::INFINITY)).unwrap();
+    let record = js_sys::Object::new();
+    js_sys::Reflect::set(&record, &"id".into(), &"b".into()).unwrap();
+    js_sys::Reflect::set(&record, &"vector".into(), &js_sys::Float32Array::from(&[0.0f32, 1.0, 0.0][..])).unwrap();
+    js_sys::Reflect::set(&record, &"metadata".into(), &inf_meta).unwrap();
+    let records = js_sys::Array::new();
+    records.push(&record);
+    let results = js_sys::Array::from(&db.insert_batch(records.into(), None).unwrap());
+    let ok = js_sys::Reflect::get(&results.get(0), &"ok".into()).unwrap();
+    assert_eq!(ok.as_bool(), Some(false));
+    assert_eq!(db.size(), 1);
+}
+
+// ── Quantization ──────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn int8_database_returns_approximate_vectors() {
+    let mut db = VectorDB::new(3, 16, 200, Some("cosine".into()), None, Some("int8".into()), None).unwrap();
+    db.insert("a".into(), vec![0.2, -0.4, 0.9], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![-0.7, 0.1, 0.3], JsValue::NULL).unwrap();
+
+    let record = db.get("a".into()).unwrap();
+    let vector = js_sys::Reflect::get(&record, &"vector".into()).unwrap();
+    let values = js_sys::Float32Array::from(vector).to_vec();
+    for (got, want) in values.iter().zip([0.2, -0.4, 0.9]) {
+        assert!((got - want).abs() < 0.01);
+    }
+
+    let restored = VectorDB::deserialize(db.serialize().unwrap()).unwrap();
+    let results = js_sys::Array::from(&restored.search(vec![0.2, -0.4, 0.9], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "a");
+}
+
+#[wasm_bindgen_test]
+fn unknown_quantization_is_rejected() {
+    let err = VectorDB::new(3, 16, 200, None, None, Some("int4".into()), None).err().unwrap();
+    let (code, message) = error_parts(&err);
+    assert_eq!(code, "INVALID_ARGUMENT");
+    assert!(message.contains("int4"), "{}", message);
+}
+
+// ── Navigation importance ──────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn navigation_importance_scores_every_node() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(6), None, None).unwrap();
+    for i in 0..20 {
+        db.insert(format!("v{}", i), vec![i as f32, (i % 3) as f32], JsValue::NULL)
+            .unwrap();
+    }
+    let scores = js_sys::Array::from(&db.navigation_importance(10, 20).unwrap());
+    assert_eq!(scores.length(), 20);
+    let top = js_sys::Reflect::get(&scores.get(0), &"score".into()).unwrap();
+    assert_eq!(top.as_f64(), Some(1.0));
+}
+
+// ── Radius search ──────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_radius_returns_points_inside_radius() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    db.insert("origin".into(), vec![0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("near".into(), vec![0.5, 0.0], JsValue::NULL).unwrap();
+    db.insert("far".into(), vec![3.0, 0.0], JsValue::NULL).unwrap();
+
+    let results = js_sys::Array::from(&db.search_radius(vec![0.0, 0.0], 1.0, 10).unwrap());
+    let ids: Vec<String> = results
+        .iter()
+        .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["origin", "near"]);
+    assert!(db.search_radius(vec![0.0], 1.0, 10).is_err());
+}
+
+// ── Presorted bulk insert ──────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn presorted_insert_batch_keeps_input_order_results_and_last_duplicate() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    let records = js_sys::Array::new();
+    for (id, x) in [("a", 9.0f32), ("b", 1.0), ("a", 2.0), ("c", 5.0)] {
+        let record = js_sys::Object::new();
+        js_sys::Reflect::set(&record, &"id".into(), &id.into()).unwrap();
+        js_sys::Reflect::set(&record, &"vector".into(), &js_sys::Float32Array::from(&[x, 0.0][..])).unwrap();
+        records.push(&record);
+    }
+    let results = js_sys::Array::from(&db.insert_batch(records.into(), Some(true)).unwrap());
+    let ids: Vec<String> = results
+        .iter()
+        .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["a", "b", "a", "c"]);
+    assert_eq!(db.size(), 3);
+
+    let record = db.get("a".into()).unwrap();
+    let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&record, &"vector".into()).unwrap());
+    assert_eq!(vector.to_vec(), vec![2.0, 0.0]);
+}
+
+// ── Vectors in search results ──────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_includes_vectors_only_when_requested() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 2.0, 3.0], JsValue::NULL).unwrap();
+
+    let with = js_sys::Array::from(&db.search(vec![1.0, 2.0, 3.0], 1, 50, Some(true), None, None).unwrap());
+    let vector = js_sys::Reflect::get(&with.get(0), &"vector".into()).unwrap();
+    assert_eq!(js_sys::Float32Array::from(vector).to_vec(), vec![1.0, 2.0, 3.0]);
+
+    let without = js_sys::Array::from(&db.search(vec![1.0, 2.0, 3.0], 1, 50, None, None, None).unwrap());
+    assert!(!js_sys::Reflect::has(&without.get(0), &"vector".into()).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn get_metadata_matches_get() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"title".into(), &"hello".into()).unwrap();
+    js_sys::Reflect::set(&meta, &"lang".into(), &"en".into()).unwrap();
+    db.insert("with".into(), vec![1.0, 2.0], meta.into()).unwrap();
+    db.insert("without".into(), vec![3.0, 4.0], JsValue::NULL).unwrap();
+
+    let from_get = js_sys::Reflect::get(&db.get("with".into()).unwrap(), &"metadata".into()).unwrap();
+    let direct = db.get_metadata("with".into()).unwrap();
+    for key in ["title", "lang"] {
+        assert_eq!(
+            js_sys::Reflect::get(&direct, &key.into()).unwrap().as_string(),
+            js_sys::Reflect::get(&from_get, &key.into()).unwrap().as_string()
+        );
+    }
+    assert_eq!(js_sys::Object::keys(&js_sys::Object::from(direct)).length(), 2);
+    assert!(db.get_metadata("without".into()).unwrap().is_null());
+    assert!(db.get_metadata("missing".into()).unwrap().is_null());
+}
+
+// ── Distance between stored ids ────────────────────────────────
+
+#[wasm_bindgen_test]
+fn distance_between_matches_distance_of_fetched_vectors() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 2.0, 3.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![-1.0, 0.5, 2.0], JsValue::NULL).unwrap();
+
+    let fetch = |id: &str| {
+        let record = db.get(id.into()).unwrap();
+        js_sys::Float32Array::from(js_sys::Reflect::get(&record, &"vector".into()).unwrap()).to_vec()
+    };
+    let expected = euclidean_distance(fetch("a"), fetch("b")).unwrap();
+    let actual = db.distance_between("a".into(), "b".into()).unwrap();
+    assert!((actual - expected).abs() < 1e-5);
+
+    assert!(db.distance_between("a".into(), "missing".into()).is_err());
+}
+
+// ── Compaction ─────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn compact_keeps_records_and_metadata() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(8), None, None).unwrap();
+    for i in 0..30 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &i.to_string().into()).unwrap();
+        db.insert(format!("v{}", i), vec![i as f32, 1.0], meta.into()).unwrap();
+    }
+    for i in (0..30).step_by(2) {
+        db.delete(format!("v{}", i)).unwrap();
+    }
+    db.compact().unwrap();
+
+    assert_eq!(db.size(), 15);
+    let record = db.get("v7".into()).unwrap();
+    let meta = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    assert_eq!(js_sys::Reflect::get(&meta, &"n".into()).unwrap().as_string().unwrap(), "7");
+    let results = js_sys::Array::from(&db.search(vec![7.1, 1.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "v7");
+}
+
+// ── Automatic metric ───────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn auto_metric_selects_dot_product_for_normalized_batch() {
+    let mut db = VectorDB::new(2, 16, 200, Some("auto".into()), None, None, None).unwrap();
+    assert_eq!(db.metric(), "auto");
+
+    let records = js_sys::Array::new();
+    for (id, angle) in [("east", 0.0f32), ("north", 1.5708), ("ne", 0.7854)] {
+        let record = js_sys::Object::new();
+        js_sys::Reflect::set(&record, &"id".into(), &id.into()).unwrap();
+        let v = [angle.cos(), angle.sin()];
+        js_sys::Reflect::set(&record, &"vector".into(), &js_sys::Float32Array::from(&v[..])).unwrap();
+        records.push(&record);
+    }
+    db.insert_batch(records.into(), None).unwrap();
+    assert_eq!(db.metric(), "dotproduct");
+
+    let results = js_sys::Array::from(&db.search(vec![0.9, 0.1], 3, 50, None, None, None).unwrap());
+    let ids: Vec<String> = results
+        .iter()
+        .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["east", "ne", "north"]);
+}
+
+#[wasm_bindgen_test]
+fn auto_metric_selects_euclidean_for_unnormalized_insert() {
+    let mut db = VectorDB::new(2, 16, 200, Some("auto".into()), None, None, None).unwrap();
+    db.insert("a".into(), vec![3.0, 4.0], JsValue::NULL).unwrap();
+    assert_eq!(db.metric(), "euclidean");
+}
+
+// ── Index statistics ───────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn stats_reports_inserted_counts() {
+    let mut db = VectorDB::new(3, 12, 150, Some("cosine".into()), Some(4), None, None).unwrap();
+    for i in 0..25 {
+        db.insert(format!("v{}", i), vec![1.0, i as f32, 0.5], JsValue::NULL).unwrap();
+    }
+    let stats = db.stats().unwrap();
+    let get = |key: &str| js_sys::Reflect::get(&stats, &key.into()).unwrap();
+    assert_eq!(get("node_count").as_f64(), Some(25.0));
+    assert_eq!(get("dimensions").as_f64(), Some(3.0));
+    assert_eq!(get("m").as_f64(), Some(12.0));
+    assert_eq!(get("ef_construction").as_f64(), Some(150.0));
+    assert_eq!(get("metric").as_string().unwrap(), "cosine");
+    let per_layer = js_sys::Array::from(&get("nodes_per_layer"));
+    assert_eq!(per_layer.get(0).as_f64(), Some(25.0));
+    assert_eq!(per_layer.length() as f64, get("max_layer").as_f64().unwrap() + 1.0);
+}
+
+// ── Targeted reconnect ─────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn reconnect_counts_known_ids_and_keeps_search_working() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(3), None, None).unwrap();
+    for i in 0..20 {
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], JsValue::NULL).unwrap();
+    }
+    assert_eq!(db.reconnect(vec!["v3".into(), "v9".into(), "nope".into()]).unwrap(), 2);
+    let results = js_sys::Array::from(&db.search(vec![9.1, 0.0], 1, 50, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "v9");
+}
+
+// ── Search defaults ────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_defaults_survive_serialization() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for i in 0..20 {
+        db.insert(format!("v{}", i), vec![i as f32, 0.0], JsValue::NULL).unwrap();
+    }
+    assert!(db.search_defaults().unwrap().is_null());
+    assert_eq!(js_sys::Array::from(&db.search_default(vec![0.0, 0.0]).unwrap()).length(), 10);
+    assert!(db.set_search_defaults(0, 50).is_err());
+    db.set_search_defaults(3, 40).unwrap();
+
+    for restored in [
+        VectorDB::deserialize(db.serialize().unwrap()).unwrap(),
+        VectorDB::deserialize_binary(db.serialize_binary().unwrap()).unwrap(),
+    ] {
+        let defaults = restored.search_defaults().unwrap();
+        assert_eq!(js_sys::Reflect::get(&defaults, &"ef".into()).unwrap().as_f64(), Some(40.0));
+        let results = js_sys::Array::from(&restored.search_default(vec![0.0, 0.0]).unwrap());
+        assert_eq!(results.length(), 3);
+    }
+}
+
+#[wasm_bindgen_test]
+fn set_ef_changes_fallback_ef_and_persists() {
+    let mut db = VectorDB::new(2, 16, 120, None, Some(5), None, None).unwrap();
+    assert_eq!(db.default_ef(), 120);
+    assert!(db.set_ef(0).is_err());
+    db.set_ef(30).unwrap();
+
+    let restored = VectorDB::deserialize(db.serialize().unwrap()).unwrap();
+    assert_eq!(restored.default_ef(), 30);
+}
+
+// ── Medoid ─────────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn medoid_returns_central_record_or_null() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    assert!(db.medoid().unwrap().is_null());
+    db.insert("left".into(), vec![-4.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("middle".into(), vec![0.2, 0.0], JsValue::NULL).unwrap();
+    db.insert("right".into(), vec![4.0, 0.0], JsValue::NULL).unwrap();
+    let medoid = db.medoid().unwrap();
+    let id = js_sys::Reflect::get(&medoid, &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "middle");
+}
+
+// ── Entry point ────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn entry_info_follows_deletion_of_the_entry_point() {
+    let entry_info = |db: &VectorDB| {
+        let info = db.entry_info().unwrap();
+        let entry = js_sys::Reflect::get(&info, &"entry_point".into()).unwrap();
+        let max_layer = js_sys::Reflect::get(&info, &"max_layer".into()).unwrap();
+        (entry.as_string(), max_layer.as_f64().unwrap())
+    };
+    let mut db = VectorDB::new(2, 4, 50, None, Some(9), None, None).unwrap();
+    assert_eq!(entry_info(&db), (None, 0.0));
+    for i in 0..100 {
+        db.insert(format!("v{}", i), vec![i as f32, (i * 7 % 13) as f32], JsValue::NULL).unwrap();
+    }
+
+    let (entry, max_layer) = entry_info(&db);
+    let entry = entry.unwrap();
+    db.delete(entry.clone()).unwrap();
+    let (next, next_max_layer) = entry_info(&db);
+    let next = next.unwrap();
+    assert_ne!(next, entry);
+    assert!(db.get(next).unwrap().is_object());
+    assert!(next_max_layer <= max_layer);
+
+    db.clear().unwrap();
+    assert_eq!(entry_info(&db), (None, 0.0));
+}
+
+#[wasm_bindgen_test]
+fn max_layer_cap_is_set_on_an_empty_database_and_saved() {
+    let mut db = VectorDB::new(2, 2, 20, None, Some(3), None, None).unwrap();
+    assert_eq!(db.max_layer_cap(), 16);
+    db.set_max_layer_cap(1).unwrap();
+    for i in 0..200 {
+        db.insert(format!("v{}", i), vec![i as f32, (i * 7 % 13) as f32], JsValue::NULL).unwrap();
+    }
+    let max_layer = js_sys::Reflect::get(&db.entry_info().unwrap(), &"max_layer".into()).unwrap();
+    assert!(max_layer.as_f64().unwrap() <= 1.0);
+
+    let err = db.set_max_layer_cap(4).unwrap_err();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    assert_eq!(VectorDB::deserialize(db.serialize().unwrap()).unwrap().max_layer_cap(), 1);
+}
+
+// ── Dimension checks on load ───────────────────────────────────
+
+#[wasm_bindgen_test]
+fn deserialize_rejects_wrong_length_vector() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap();
+    let json = unsealed(&db).replace("[0.0,1.0,0.0]", "[0.0,1.0]");
+
+    let err = VectorDB::deserialize(json).err().unwrap();
+    assert!(error_parts(&err).1.contains("'b' has 2 dimensions"));
+}
+
+#[wasm_bindgen_test]
+fn deserialize_dispatches_on_format_version() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+
+    // Version 1
+    let json = db.serialize().unwrap();
+    assert_eq!(VectorDB::deserialize(json.clone()).unwrap().size(), 1);
+
+    // A future version is rejected by number, whatever its shape
+    let mut future: serde_json::Value = serde_json::from_str(&json).unwrap();
+    future["version"] = 2.into();
+    let (_, err) = error_parts(&VectorDB::deserialize(future.to_string()).err().unwrap());
+    assert!(err.contains("Unsupported database version: 2"), "{}", err);
+    let err = VectorDB::deserialize(r#"{"version":7,"chunks":[]}"#.into()).err().unwrap();
+    assert!(error_parts(&err).1.contains("Unsupported database version: 7"));
+
+    // No version field: the legacy format
+    let legacy = legacy_json(&db, serde_json::json!({ "a": [1.0, 0.0] }), serde_json::json!({}));
+    let loaded = VectorDB::deserialize(legacy).unwrap();
+    assert_eq!(loaded.size(), 1);
+    assert!(loaded.load_warnings().is_empty());
+}
+
+/// Legacy (pre-version) snapshot wrapping `db`'s index as a nested string
+fn legacy_json(db: &VectorDB, vectors: serde_json::Value, metadata: serde_json::Value) -> String {
+    let v1: serde_json::Value = serde_json::from_str(&db.serialize().unwrap()).unwrap();
+    serde_json::json!({
+        "vectors": vectors,
+        "metadata": metadata,
+        "hnsw_state": v1["hnsw_index"].to_string(),
+    })
+    .to_string()
+}
+
+#[wasm_bindgen_test]
+fn deserialize_validates_inconsistent_legacy_snapshot() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0], JsValue::NULL).unwrap();
+
+    // Metadata for an id the index lacks is dropped with a warning
+    let json = legacy_json(
+        &db,
+        serde_json::json!({ "a": [1.0, 0.0] }),
+        serde_json::json!({ "a": { "tag": "kept" }, "ghost": { "tag": "gone" } }),
+    );
+    let loaded = VectorDB::deserialize(json).unwrap();
+    assert_eq!(
+        loaded.load_warnings(),
+        vec!["Dropped metadata for 'ghost', which is not in the index".to_string()]
+    );
+    assert_eq!(loaded.size(), 2);
+    assert!(db.load_warnings().is_empty());
+
+    // A vector the index doesn't hold is rejected
+    let json = legacy_json(&db, serde_json::json!({ "zzz": [1.0, 1.0] }), serde_json::json!({}));
+    let err = VectorDB::deserialize(json).err().unwrap();
+    assert!(error_parts(&err).1.contains("'zzz' that is missing from its index"));
+
+    // So is a zero-dimension index
+    let empty = VectorDB::new(0, 16, 200, None, None, None, None).unwrap();
+    let err = VectorDB::deserialize(legacy_json(&empty, serde_json::json!({}), serde_json::json!({})))
+        .err()
+        .unwrap();
+    assert_eq!(error_parts(&err).1, "Legacy database declares zero dimensions");
+}
+
+// ── Delta serialization ────────────────────────────────────────
+
+fn delta_json(db: &mut VectorDB) -> serde_json::Value {
+    serde_json::from_str(&db.serialize_delta().unwrap()).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn serialize_delta_holds_inserts_since_last_save() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    db.insert("old".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.serialize().unwrap();
+
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"tag".into(), &"new".into()).unwrap();
+    db.insert("new".into(), vec![0.0, 1.0], meta.into()).unwrap();
+    let delta = delta_json(&mut db);
+    assert_eq!(delta["upserts"].as_array().unwrap().len(), 1);
+    assert_eq!(delta["upserts"][0]["id"], "new");
+    assert_eq!(delta["upserts"][0]["metadata"]["tag"], "new");
+    assert!(delta["deletes"].as_array().unwrap().is_empty());
+
+    // Nothing changed since that delta
+    let empty = delta_json(&mut db);
+    assert!(empty["upserts"].as_array().unwrap().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn serialize_delta_records_deletes_as_tombstones() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0], JsValue::NULL).unwrap();
+    db.serialize().unwrap();
+
+    db.delete("a".into()).unwrap();
+    // Inserted and deleted within one window: only the tombstone remains
+    db.insert("temp".into(), vec![1.0, 1.0], JsValue::NULL).unwrap();
+    db.delete("temp".into()).unwrap();
+    let delta = delta_json(&mut db);
+    assert!(delta["upserts"].as_array().unwrap().is_empty());
+    assert_eq!(delta["deletes"], serde_json::json!(["a", "temp"]));
+}
+
+#[wasm_bindgen_test]
+fn serialize_delta_requires_a_snapshot_after_a_metric_change() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.serialize().unwrap();
+
+    db.reindex_with_metric("cosine".into(), None).unwrap();
+    let (code, message) = error_parts(&db.serialize_delta().unwrap_err());
+    assert_eq!(code, "INVALID_ARGUMENT");
+    assert!(message.contains("call serialize() instead"));
+
+    db.serialize().unwrap();
+    assert!(delta_json(&mut db)["upserts"].as_array().unwrap().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn applying_deltas_in_order_reconstructs_the_database() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(1), None, None).unwrap();
+    for i in 0..5 {
+        db.insert(format!("v{}", i), vec![i as f32, 1.0], JsValue::NULL).unwrap();
+    }
+    let snapshot = db.serialize().unwrap();
+
+    let mut deltas = Vec::new();
+    db.insert("v5".into(), vec![5.0, 1.0], JsValue::NULL).unwrap();
+    db.delete("v0".into()).unwrap();
+    deltas.push(db.serialize_delta().unwrap());
+    db.insert("v1".into(), vec![9.0, 9.0], JsValue::NULL).unwrap();
+    db.delete_batch(vec!["v2".into(), "v5".into()]).unwrap();
+    deltas.push(db.serialize_delta().unwrap());
+
+    let mut replica = VectorDB::deserialize(snapshot).unwrap();
+    for delta in deltas {
+        replica.apply_delta(delta).unwrap();
+    }
+    let mut ids: Vec<String> = js_sys::Array::from(&replica.list_ids().unwrap())
+        .iter()
+        .map(|id| id.as_string().unwrap())
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec!["v1", "v3", "v4"]);
+    let v1 = replica.get("v1".into()).unwrap();
+    let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&v1, &"vector".into()).unwrap());
+    assert_eq!(vector.to_vec(), vec![9.0, 9.0]);
+
+    let bad = r#"{"upserts":[{"id":"x","vector":[1.0],"metadata":null}],"deletes":["v1"]}"#;
+    assert!(replica.apply_delta(bad.into()).is_err());
+    assert!(replica.has("v1".into()));
+}
+
+// ── Paged id listing ───────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn list_ids_page_returns_every_id_once() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    for i in 0..100 {
+        db.insert(format!("id{:03}", i), vec![i as f32, 1.0], JsValue::NULL).unwrap();
+    }
+    let mut seen = std::collections::HashSet::new();
+    for page in 0..10 {
+        let result = db.list_ids_page(page * 10, 10).unwrap();
+        let total = js_sys::Reflect::get(&result, &"total".into()).unwrap();
+        assert_eq!(total.as_f64(), Some(100.0));
+        let ids = js_sys::Array::from(&js_sys::Reflect::get(&result, &"ids".into()).unwrap());
+        assert_eq!(ids.length(), 10);
+        for id in ids.iter() {
+            assert!(seen.insert(id.as_string().unwrap()));
+        }
+    }
+    assert_eq!(seen.len(), 100);
+}
+
+#[wasm_bindgen_test]
+fn entries_export_rebuilds_an_identical_db() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(5), None, None).unwrap();
+    for i in 0..25 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &format!("{}", i).into()).unwrap();
+        db.insert(format!("id{:02}", i), vec![i as f32, (i % 4) as f32], meta.into()).unwrap();
+    }
+
+    let mut copy = VectorDB::new(2, 16, 200, None, Some(5), None, None).unwrap();
+    let mut offset = 0;
+    loop {
+        let page = js_sys::Array::from(&db.entries(offset, 10).unwrap());
+        for record in page.iter() {
+            let get = |key: &str| js_sys::Reflect::get(&record, &key.into()).unwrap();
+            let vector = js_sys::Float32Array::from(get("vector")).to_vec();
+            copy.insert(get("id").as_string().unwrap(), vector, get("metadata")).unwrap();
+        }
+        offset += page.length() as usize;
+        if page.length() < 10 {
+            break;
+        }
+    }
+
+    assert_eq!(offset, 25);
+    let ids = |db: &VectorDB| -> Vec<String> {
+        js_sys::Array::from(&db.list_ids().unwrap()).iter().map(|id| id.as_string().unwrap()).collect()
+    };
+    assert_eq!(ids(&copy), ids(&db));
+    let record = |db: &VectorDB, id: &str| {
+        let record = db.get(id.to_string()).unwrap();
+        let vector = js_sys::Float32Array::from(js_sys::Reflect::get(&record, &"vector".into()).unwrap()).to_vec();
+        let meta = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+        (vector, js_sys::Reflect::get(&meta, &"n".into()).unwrap().as_string())
+    };
+    for i in 0..25 {
+        let id = format!("id{:02}", i);
+        assert_eq!(record(&copy, &id), record(&db, &id));
+        assert_eq!(record(&copy, &id).1, Some(format!("{}", i)));
+    }
+}
+
+#[wasm_bindgen_test]
+fn from_records_loads_and_validates_every_record() {
+    let record = |id: &str, vector: Vec<f32>| {
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &"id".into(), &id.into()).unwrap();
+        js_sys::Reflect::set(&obj, &"vector".into(), &serde_wasm_bindgen::to_value(&vector).unwrap()).unwrap();
+        obj
+    };
+    let records = js_sys::Array::new();
+    for i in 0..50 {
+        records.push(&record(&format!("v{}", i), vec![i as f32, 1.0]));
+    }
+    records.push(&record("v0", vec![100.0, 1.0]));
+
+    let db = VectorDB::from_records(2, 16, 200, None, records.clone().into(), Some(6)).unwrap();
+    assert_eq!(db.size(), 50);
+    let results = js_sys::Array::from(&db.search(vec![99.0, 1.0], 2, 50, None, None, None).unwrap());
+    let first = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(first.as_string().unwrap(), "v0");
+
+    records.push(&record("bad", vec![1.0]));
+    let err = VectorDB::from_records(2, 16, 200, None, records.into(), Some(6)).err().unwrap();
+    assert!(err.as_string().unwrap().starts_with("Record 51 (bad)"));
+}
+
+#[wasm_bindgen_test]
+fn import_json_maps_custom_fields_and_skips_bad_rows() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(9), None, None).unwrap();
+    let json = r#"[
+        {"slug": "cat", "embedding": [1.0, 0.0], "text": "a cat", "likes": 3},
+        {"slug": 7, "embedding": [0.0, 1.0], "text": "seven"},
+        {"slug": "short", "embedding": [1.0]},
+        {"embedding": [0.5, 0.5]},
+        {"slug": "words", "embedding": ["x", "y"]}
+    ]"#;
+    let fields = serde_wasm_bindgen::to_value(&vec!["text", "likes"]).unwrap();
+    let inserted = db
+        .import_json(json.to_string(), "slug".to_string(), "embedding".to_string(), fields)
+        .unwrap();
+
+    assert_eq!(inserted, 2);
+    assert_eq!(db.size(), 2);
+    let warnings = db.load_warnings();
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].starts_with("Row 2 skipped: Vector dimension mismatch"));
+
+    let record = db.get("cat".to_string()).unwrap();
+    let meta = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    assert_eq!(js_sys::Reflect::get(&meta, &"text".into()).unwrap().as_string().unwrap(), "a cat");
+    assert_eq!(js_sys::Reflect::get(&meta, &"likes".into()).unwrap().as_string().unwrap(), "3");
+    assert!(db.get("7".to_string()).unwrap().is_object());
+
+    assert!(db.import_json("{".to_string(), "id".to_string(), "v".to_string(), JsValue::NULL).is_err());
+}
+
+#[wasm_bindgen_test]
+fn deserialize_rejects_corrupt_declared_dimensions() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    let json = unsealed(&db).replace("\"dimensions\":3", "\"dimensions\":5");
+
+    let err = VectorDB::deserialize(json).err().unwrap();
+    assert_eq!(
+        error_parts(&err).1,
+        "Declared dimensions (5) do not match the stored vectors, which all have 3"
+    );
+}
+
+#[wasm_bindgen_test]
+fn deserialize_rejects_snapshots_that_fail_their_checksum() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap();
+
+    let json = db.serialize().unwrap();
+    assert!(json.starts_with("{\"checksum\":\""));
+    let flipped = json.replacen("[0.0,1.0,0.0]", "[0.0,1.5,0.0]", 1);
+    let (code, message) = error_parts(&VectorDB::deserialize(flipped).err().unwrap());
+    assert_eq!(code, "CHECKSUM_MISMATCH");
+    assert!(message.contains("Checksum mismatch"), "{}", message);
+
+    let mut bytes = db.serialize_binary().unwrap();
+    let last_vector_byte = bytes.len() - 9;
+    bytes[last_vector_byte] ^= 0x01;
+    let (code, _) = error_parts(&VectorDB::deserialize_binary(bytes).err().unwrap());
+    assert_eq!(code, "CHECKSUM_MISMATCH");
+
+    // Snapshots written before checksums load unchecked
+    assert_eq!(VectorDB::deserialize(unsealed(&db)).unwrap().size(), 2);
+}
+
+// ── Search provenance ──────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_with_provenance_matches_results() {
+    let mut db = VectorDB::new(2, 16, 200, Some("cosine".into()), None, None, None).unwrap();
+    for i in 0..10 {
+        db.insert(format!("v{}", i), vec![1.0, i as f32], JsValue::NULL).unwrap();
+    }
+    let out = db.search_with_provenance(vec![1.0, 2.5], 3, 20, 1_700_000_000_000.0).unwrap();
+    let get = |obj: &JsValue, key: &str| js_sys::Reflect::get(obj, &key.into()).unwrap();
+    let provenance = get(&out, "provenance");
+
+    assert_eq!(get(&provenance, "k").as_f64(), Some(3.0));
+    assert_eq!(get(&provenance, "ef").as_f64(), Some(20.0));
+    assert_eq!(get(&provenance, "effective_ef").as_f64(), Some(20.0));
+    assert_eq!(get(&provenance, "metric").as_string().unwrap(), "cosine");
+    assert_eq!(get(&provenance, "timestamp").as_f64(), Some(1_700_000_000_000.0));
+    let hash = get(&provenance, "query_hash").as_string().unwrap();
+    assert_eq!(hash.len(), 16);
+    let again = db.search_with_provenance(vec![1.0, 2.5], 3, 20, 0.0).unwrap();
+    assert_eq!(get(&get(&again, "provenance"), "query_hash").as_string().unwrap(), hash);
+
+    let results = js_sys::Array::from(&get(&out, "results"));
+    let audited = js_sys::Array::from(&get(&provenance, "results"));
+    assert_eq!(results.length(), 3);
+    assert_eq!(audited.length(), 3);
+    for i in 0..3 {
+        assert_eq!(
+            get(&results.get(i), "id").as_string(),
+            get(&audited.get(i), "id").as_string()
+        );
+        assert_eq!(get(&results.get(i), "distance").as_f64(), get(&audited.get(i), "distance").as_f64());
+    }
+}
+
+// ── Batch get ──────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn get_many_preserves_order_and_nulls_missing() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0], JsValue::NULL).unwrap();
+    db.insert("c".into(), vec![1.0, 1.0], JsValue::NULL).unwrap();
+
+    let records = js_sys::Array::from(
+        &db.get_many(vec!["c".into(), "missing".into(), "a".into()]).unwrap(),
+    );
+    assert_eq!(records.length(), 3);
+    let id = |i: u32| js_sys::Reflect::get(&records.get(i), &"id".into()).unwrap().as_string();
+    assert_eq!(id(0).unwrap(), "c");
+    assert!(records.get(1).is_null());
+    assert_eq!(id(2).unwrap(), "a");
+    let vector = js_sys::Reflect::get(&records.get(2), &"vector".into()).unwrap();
+    assert_eq!(js_sys::Float32Array::from(vector).to_vec(), vec![1.0, 0.0]);
+}
+
+// ── Random projection ──────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn random_projection_indexes_fewer_dimensions_but_returns_originals() {
+    let mut db = VectorDB::with_random_projection(8, 3, 16, 200, None, Some(5)).unwrap();
+    let a = vec![1.0, 0.0, 2.0, 0.0, 0.5, 0.0, 0.0, 1.0];
+    db.insert("a".into(), a.clone(), JsValue::NULL).unwrap();
+    db.insert("b".into(), vec![-3.0; 8], JsValue::NULL).unwrap();
+
+    // `get` returns the full-length vector that was inserted
+    let record = db.get("a".into()).unwrap();
+    let vector = js_sys::Reflect::get(&record, &"vector".into()).unwrap();
+    assert_eq!(js_sys::Float32Array::from(vector).to_vec(), a);
+
+    // Inserts and queries use the input dimensions, not the projected ones
+    assert!(db.insert("c".into(), vec![1.0, 2.0, 3.0], JsValue::NULL).is_err());
+    assert!(db.search(vec![1.0, 2.0, 3.0], 1, 10, None, None, None).is_err());
+    let results = js_sys::Array::from(&db.search(a.clone(), 1, 10, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "a");
+
+    // The projection survives a round trip and is regenerated from its seed
+    let restored = VectorDB::deserialize(db.serialize().unwrap()).unwrap();
+    let results = js_sys::Array::from(&restored.search(a, 1, 10, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "a");
+    assert!(db.reproject(16).is_err());
+    assert!(VectorDB::with_random_projection(8, 9, 16, 200, None, None).is_err());
+}
+
+// ── Storage optimization ───────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn optimize_storage_drop_vectors_keeps_ids_and_metadata() {
+    let mut db = VectorDB::new(16, 8, 50, None, Some(2), None, None).unwrap();
+    let vector = |i: u32| (0..16).map(|d| ((i * 7 + d) % 11) as f32).collect::<Vec<f32>>();
+    for i in 0..20 {
+        let meta = js_sys::Object::new();
+        js_sys::Reflect::set(&meta, &"n".into(), &i.to_string().into()).unwrap();
+        db.insert(format!("v{}", i), vector(i), meta.into()).unwrap();
+    }
+    let full_size = db.serialize().unwrap().len();
+
+    assert_eq!(db.optimize_storage("drop-vectors".into()).unwrap(), 20 * 16 * 4);
+    assert!(db.serialize().unwrap().len() < full_size / 2);
+    assert_eq!(db.size(), 20);
+    let record = db.get("v3".into()).unwrap();
+    assert!(js_sys::Reflect::get(&record, &"vector".into()).unwrap().is_null());
+    let meta = js_sys::Reflect::get(&record, &"metadata".into()).unwrap();
+    assert_eq!(js_sys::Reflect::get(&meta, &"n".into()).unwrap().as_string().unwrap(), "3");
+
+    // Nothing to search, and only the dropped records can be written back
+    assert!(db.search(vector(3), 1, 20, None, None, None).is_err());
+    assert!(db.insert("new".into(), vector(99), JsValue::NULL).is_err());
+    assert_eq!(error_parts(&db.compact().err().unwrap()).0, "VECTORS_DROPPED");
+    assert_eq!(error_parts(&db.cluster_elbow(vec![2], 10).err().unwrap()).0, "VECTORS_DROPPED");
+    assert_eq!(error_parts(&db.group_representatives("n".into(), 1).err().unwrap()).0, "VECTORS_DROPPED");
+    assert_eq!(db.size(), 20);
+    // The kept links only fit the original vectors and settings
+    let err = db.insert("v0".into(), vector(50), JsValue::NULL).err().unwrap();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    assert!(db.insert_with_m("v0".into(), vector(0), JsValue::NULL, 16).is_err());
+    for i in 0..19 {
+        db.insert(format!("v{}", i), vector(i), JsValue::NULL).unwrap();
+    }
+    assert!(db.search(vector(3), 1, 20, None, None, None).is_err());
+    db.insert("v19".into(), vector(19), JsValue::NULL).unwrap();
+    let results = js_sys::Array::from(&db.search(vector(3), 1, 20, None, None, None).unwrap());
+    let id = js_sys::Reflect::get(&results.get(0), &"id".into()).unwrap();
+    assert_eq!(id.as_string().unwrap(), "v3");
+    assert!(db.optimize_storage("bogus".into()).is_err());
+}
+
+#[wasm_bindgen_test]
+fn optimize_storage_dedup_metadata_drops_empty_objects() {
+    let mut db = VectorDB::new(2, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], js_sys::Object::new().into()).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"tag".into(), &"kept".into()).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0], meta.into()).unwrap();
+
+    assert!(!db.get_metadata("a".into()).unwrap().is_null());
+    db.optimize_storage("dedup-metadata".into()).unwrap();
+    assert!(db.get_metadata("a".into()).unwrap().is_null());
+    let kept = db.get_metadata("b".into()).unwrap();
+    assert_eq!(js_sys::Reflect::get(&kept, &"tag".into()).unwrap().as_string().unwrap(), "kept");
+    assert_eq!(db.size(), 2);
+}
+
+// ── Recency-weighted search ────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_recent_prefers_newer_equidistant_records() {
+    let mut db = VectorDB::new(2, 16, 200, None, Some(3), None, None).unwrap();
+    db.insert_with_timestamp("old".into(), vec![3.0, 4.0], JsValue::NULL, 1_000).unwrap();
+    db.insert_with_timestamp("new".into(), vec![-3.0, 4.0], JsValue::NULL, 9_000).unwrap();
+    db.insert("untimed".into(), vec![4.0, -3.0], JsValue::NULL).unwrap();
+    assert_eq!(db.inserted_at("new".into()), Some(9_000));
+    assert_eq!(db.inserted_at("untimed".into()), None);
+
+    let results = js_sys::Array::from(&db.search_recent(vec![0.0, 0.0], 3, 20, 3600.0, 10_000).unwrap());
+    let ids: Vec<String> = results
+        .iter()
+        .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+        .collect();
+    assert_eq!(ids, ["new", "old", "untimed"]);
+    assert!(db.search_recent(vec![0.0, 0.0], 3, 20, 0.0, 10_000).is_err());
+}
+
+// ── Structured snapshot ────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn to_object_describes_params_and_every_record() {
+    let mut db = VectorDB::new(2, 12, 100, Some("cosine".into()), None, None, None).unwrap();
+    let meta = js_sys::Object::new();
+    js_sys::Reflect::set(&meta, &"tag".into(), &"x".into()).unwrap();
+    db.insert("b".into(), vec![0.0, 1.0], meta.into()).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap();
+
+    let get = |obj: &JsValue, key: &str| js_sys::Reflect::get(obj, &key.into()).unwrap();
+    let snapshot = db.to_object().unwrap();
+    assert_eq!(get(&snapshot, "version").as_f64(), Some(1.0));
+    let params = get(&snapshot, "params");
+    assert_eq!(get(&params, "dimensions").as_f64(), Some(2.0));
+    assert_eq!(get(&params, "m").as_f64(), Some(12.0));
+    assert_eq!(get(&params, "ef_construction").as_f64(), Some(100.0));
+    assert_eq!(get(&params, "metric").as_string().unwrap(), "cosine");
+
+    let records = js_sys::Array::from(&get(&snapshot, "records"));
+    assert_eq!(records.length(), 2);
+    assert_eq!(get(&records.get(0), "id").as_string().unwrap(), "a");
+    assert_eq!(js_sys::Float32Array::from(get(&records.get(1), "vector")).to_vec(), vec![0.0, 1.0]);
+    assert_eq!(get(&get(&records.get(1), "metadata"), "tag").as_string().unwrap(), "x");
+}
+
+// ── Exact search ───────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn search_exact_matches_brute_force() {
+    let mut db = VectorDB::new(4, 4, 20, None, Some(8), None, None).unwrap();
+    let vector = |i: u32| -> Vec<f32> { (0..4).map(|d| ((i * 4 + d) as f32 * 0.731).sin()).collect() };
+    for i in 0..300 {
+        db.insert(format!("v{}", i), vector(i), JsValue::NULL).unwrap();
+    }
+    assert_eq!(db.exact_threshold(), 0);
+
+    for q in 0..20 {
+        let query = vector(10_000 + q);
+        let mut truth: Vec<(f32, String)> = (0..300)
+            .map(|i| {
+                let d = query.iter().zip(vector(i)).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt();
+                (d, format!("v{}", i))
+            })
+            .collect();
+        truth.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+        let expected: Vec<String> = truth.into_iter().take(10).map(|(_, id)| id).collect();
+
+        let results = js_sys::Array::from(&db.search_exact(query, 10).unwrap());
+        let ids: Vec<String> = results
+            .iter()
+            .map(|r| js_sys::Reflect::get(&r, &"id".into()).unwrap().as_string().unwrap())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+    assert!(db.search_exact(vec![1.0], 3).is_err());
+}
+
+// ── Error codes ────────────────────────────────────────────────
+
+#[wasm_bindgen_test]
+fn insert_errors_carry_codes() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+
+    let err = db.insert("a".into(), vec![1.0, 0.0], JsValue::NULL).unwrap_err();
+    let (code, message) = error_parts(&err);
+    assert_eq!(code, "DIMENSION_MISMATCH");
+    assert_eq!(message, "Vector dimension mismatch: expected 3, got 2");
+
+    let err = db.insert("a".into(), vec![f32::NAN, 0.0, 0.0], JsValue::NULL).unwrap_err();
+    assert_eq!(error_parts(&err).0, "NON_FINITE");
+    assert!(err.is_instance_of::<js_sys::Error>());
+
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    let mut readonly = VectorDB::load_readonly(db.serialize().unwrap()).unwrap();
+    let err = readonly.insert("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL).unwrap_err();
+    assert_eq!(error_parts(&err).0, "READ_ONLY");
+}
+
+#[wasm_bindgen_test]
+fn search_and_distance_errors_carry_codes() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+
+    let err = db.search(vec![1.0, 0.0], 1, 50, None, None, None).unwrap_err();
+    assert_eq!(error_parts(&err).0, "DIMENSION_MISMATCH");
+
+    let err = db.distance_between("a".into(), "missing".into()).unwrap_err();
+    assert_eq!(error_parts(&err), ("NOT_FOUND".to_string(), "Unknown id: missing".to_string()));
+
+    let err = cosine_similarity(vec![1.0, 0.0], vec![1.0]).unwrap_err();
+    assert_eq!(error_parts(&err).0, "DIMENSION_MISMATCH");
+}
+
+#[wasm_bindgen_test]
+fn argument_errors_carry_codes() {
+    let mut db = VectorDB::new(3, 16, 200, None, None, None, None).unwrap();
+    db.insert("a".into(), vec![1.0, 0.0, 0.0], JsValue::NULL).unwrap();
+    let code = |result: Result<JsValue, JsValue>| error_parts(&result.unwrap_err()).0;
+
+    assert_eq!(code(db.search(vec![1.0, 0.0, 0.0], 1, 50, None, Some(f32::NAN), None)), "INVALID_ARGUMENT");
+    assert_eq!(code(db.search_batch(JsValue::NULL, 1, 50)), "INVALID_ARGUMENT");
+    assert_eq!(code(db.serialize_chunks(0)), "INVALID_ARGUMENT");
+
+    let err = db.insert_with_m("b".into(), vec![0.0, 1.0, 0.0], JsValue::NULL, 0).unwrap_err();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    let err = db.set_capacity_limit(Some(0)).unwrap_err();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    let err = db.reindex_with_metric("hamming".into(), None).unwrap_err();
+    assert_eq!(error_parts(&err), ("INVALID_ARGUMENT".to_string(), "Unknown metric: hamming".to_string()));
+
+    let err = VectorDB::from_records(3, 16, 200, None, JsValue::NULL, None).err().unwrap();
+    assert_eq!(error_parts(&err).0, "INVALID_ARGUMENT");
+    let err = db.apply_delta("not json".into()).unwrap_err();
+    assert_eq!(error_parts(&err).0, "PARSE_ERROR");
+
+    // Per-query errors keep the code of the underlying check
+    let queries = js_sys::Array::of1(&js_sys::Float32Array::from(&[1.0f32][..]).into());
+    let err = db.tune_ef(queries.into(), 1, 0.9).unwrap_err();
+    assert_eq!(error_parts(&err).0, "DIMENSION_MISMATCH");
+}
+
+#[wasm_bindgen_test]
+fn deserialize_errors_carry_codes() {
+    let err = VectorDB::deserialize("not json".into()).err().unwrap();
+    assert_eq!(error_parts(&err).0, "PARSE_ERROR");
+
+    let err = VectorDB::deserialize(r#"{"version":7}"#.into()).err().unwrap();
+    assert_eq!(error_parts(&err).0, "UNSUPPORTED_VERSION");
+
+    let err = VectorDB::deserialize_binary(vec![1, 2, 3]).err().unwrap();
+    assert_eq!(error_parts(&err).0, "PARSE_ERROR");
+}
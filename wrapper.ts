@@ -13,37 +13,125 @@ import type {
   VectorDB as WasmVectorDB,
 } from './pkg/bundler/idbvec'
 
+export type MetadataValue = string | number | boolean
+export type Metadata = Record<string, MetadataValue>
+
+// Numeric bounds on a metadata field; every bound given must hold
+export interface NumericRange {
+  gte?: number
+  gt?: number
+  lte?: number
+  lt?: number
+  eq?: number
+}
+
+// Each field must equal a value (of the same type) or hold a number in range
+export type MetadataFilter = Record<string, MetadataValue | NumericRange>
+
+export type ErrorCode =
+  | 'DIMENSION_MISMATCH'
+  | 'NON_FINITE'
+  | 'NOT_FOUND'
+  | 'PARSE_ERROR'
+  | 'UNSUPPORTED_VERSION'
+  | 'CHECKSUM_MISMATCH'
+  | 'READ_ONLY'
+  | 'VECTORS_DROPPED'
+  | 'INVALID_ARGUMENT'
+
+// What insert, search, load and the distance functions throw
+export interface IdbvecError extends Error {
+  code: ErrorCode
+}
+
 export interface SearchResult {
   id: string
-  distance: number
-  metadata?: Record<string, string>
+  distance: number // Lower is nearer; under 'dotproduct' this is the negated dot product
+  similarity?: number // Raw dot product (higher is nearer), present under 'dotproduct'
+  metadata?: Metadata
+  vector?: Float32Array // Present when searched with includeVectors
 }
 
 export interface VectorRecord {
   id: string
   vector: Float32Array
-  metadata?: Record<string, string>
+  metadata?: Metadata
 }
 
 export interface GetResult {
   id: string
-  vector: Float32Array
-  metadata?: Record<string, string>
+  vector: Float32Array | null // null after optimizeStorage('drop-vectors') until re-inserted
+  metadata?: Metadata
+}
+
+export interface SearchProvenance {
+  query_hash: string // FNV-1a of the query's f32 bytes, 16 hex digits
+  k: number
+  ef: number
+  effective_ef: number
+  metric: DistanceMetric
+  timestamp: number
+  results: { id: string; distance: number }[]
 }
 
 export interface SearchOptions {
   k?: number // Number of results (default: 10)
   ef?: number // Search quality parameter (default: 50)
+  filter?: MetadataFilter // Only return records whose metadata matches every condition
+  includeVectors?: boolean // Attach each result's stored vector (default: false)
+  maxDistance?: number // Drop results farther than this, in result distance units
+  normalizeQuery?: boolean // Scale a copy of the query to unit length first (default: false)
 }
 
-export type DistanceMetric = 'euclidean' | 'cosine' | 'dotproduct'
+export interface IndexStats {
+  node_count: number
+  max_layer: number
+  dimensions: number
+  metric: DistanceMetric
+  m: number
+  ef_construction: number
+  avg_layer0_connections: number
+  nodes_per_layer: number[] // Nodes present on each layer, from layer 0 up
+  auto_ef_factor?: number // k multiplier while automatic ef is enabled
+}
+
+export interface EntryInfo {
+  entry_point: string | null // Where every search starts; null when empty
+  max_layer: number
+}
+
+export interface LayerAdjacency {
+  layer: number
+  nodes: string[] // Ids present on the layer, sorted
+  edges: [string, string][] // Directed links as [from, to]
+}
+
+export type DistanceMetric = 'euclidean' | 'cosine' | 'dotproduct' | 'manhattan' | 'angular' | 'minkowski' | 'hamming' | 'jaccard' | 'auto'
 
 export interface VectorDBConfig {
   name: string
   dimensions: number
   m?: number // Max connections per layer (default: 16)
+  m0?: number // Max connections at layer 0 (default: 2 * m)
+  maxLayerCap?: number // Highest HNSW layer a record can reach (default: 16)
   efConstruction?: number // Construction quality (default: 200)
   metric?: DistanceMetric // Distance metric (default: 'euclidean')
+  seed?: number // Seed for deterministic index construction (default: random)
+  quantization?: 'int8' | 'f16' // int8: ~4x less memory, approximate distances; f16: ~2x less, near-exact
+  p?: number // Exponent for the 'minkowski' metric (default: 2)
+  rejectZeroVectors?: boolean // Reject all-zero vectors on insert under 'cosine'/'angular' (default: false)
+  projectedDimensions?: number // Index a seeded random projection to this many dimensions (default: no projection)
+  exactThreshold?: number // search scans every record below this many records; 0 disables (default: 0)
+  repairOnDelete?: boolean // Relink a deleted record's former neighbors to each other (default: false)
+  persistChunkBytes?: number // Save to IndexedDB as chunks of about this many bytes (default: one value)
+  normalized?: boolean // Store vectors at unit length under 'cosine' (default: false)
+  maxNodes?: number // Evict the least recently accessed record past this many (default: unbounded)
+}
+
+// Unit-length copy of a vector (all-zero vectors are returned unchanged)
+function normalized(v: Float32Array): Float32Array {
+  const norm = Math.sqrt(v.reduce((sum, x) => sum + x * x, 0))
+  return norm > 0 ? v.map((x) => x / norm) : v
 }
 
 /**
@@ -52,7 +140,7 @@ export interface VectorDBConfig {
 export class VectorDatabase {
   private wasmDB: WasmVectorDB | null = null
   private idb: IDBDatabase | null = null
-  private config: Required<Pick<VectorDBConfig, 'name' | 'dimensions' | 'm' | 'efConstruction'>> & { metric: DistanceMetric }
+  private config: Required<Pick<VectorDBConfig, 'name' | 'dimensions' | 'm' | 'efConstruction'>> & { m0?: number; maxLayerCap?: number; metric: DistanceMetric; seed?: number; quantization?: 'int8' | 'f16'; p?: number; rejectZeroVectors?: boolean; projectedDimensions?: number; exactThreshold?: number; repairOnDelete?: boolean; persistChunkBytes?: number; normalized?: boolean; maxNodes?: number }
   private saveTimer: ReturnType<typeof setTimeout> | null = null
   private saveDebounceMs: number = 1000
 
@@ -61,8 +149,20 @@ export class VectorDatabase {
       name: config.name,
       dimensions: config.dimensions,
       m: config.m ?? 16,
+      m0: config.m0,
+      maxLayerCap: config.maxLayerCap,
       efConstruction: config.efConstruction ?? 200,
       metric: config.metric ?? 'euclidean',
+      seed: config.seed,
+      quantization: config.quantization,
+      p: config.p,
+      rejectZeroVectors: config.rejectZeroVectors,
+      projectedDimensions: config.projectedDimensions,
+      exactThreshold: config.exactThreshold,
+      repairOnDelete: config.repairOnDelete,
+      persistChunkBytes: config.persistChunkBytes,
+      normalized: config.normalized,
+      maxNodes: config.maxNodes,
     }
   }
 
@@ -78,16 +178,51 @@ export class VectorDatabase {
 
     // Try to restore from IndexedDB
     const saved = await this.loadFromIndexedDB()
-    if (saved) {
+    if (Array.isArray(saved)) {
+      this.wasmDB = wasmModule.VectorDB.deserialize_chunks(saved)
+    } else if (saved) {
       this.wasmDB = wasmModule.VectorDB.deserialize(saved)
+    } else if (this.config.projectedDimensions !== undefined) {
+      this.wasmDB = wasmModule.VectorDB.with_random_projection(
+        this.config.dimensions,
+        this.config.projectedDimensions,
+        this.config.m,
+        this.config.efConstruction,
+        this.config.metric,
+        this.config.seed !== undefined ? BigInt(this.config.seed) : undefined
+      )
     } else {
       this.wasmDB = new wasmModule.VectorDB(
         this.config.dimensions,
         this.config.m,
         this.config.efConstruction,
-        this.config.metric
+        this.config.metric,
+        this.config.seed !== undefined ? BigInt(this.config.seed) : undefined,
+        this.config.quantization,
+        this.config.p
       )
     }
+    if (this.config.m0 !== undefined && this.wasmDB.size() === 0) {
+      this.wasmDB.set_m0(this.config.m0)
+    }
+    if (this.config.maxLayerCap !== undefined && this.wasmDB.size() === 0) {
+      this.wasmDB.set_max_layer_cap(this.config.maxLayerCap)
+    }
+    if (this.config.rejectZeroVectors !== undefined) {
+      this.wasmDB.set_reject_zero_vectors(this.config.rejectZeroVectors)
+    }
+    if (this.config.exactThreshold !== undefined) {
+      this.wasmDB.set_exact_threshold(this.config.exactThreshold)
+    }
+    if (this.config.repairOnDelete !== undefined) {
+      this.wasmDB.set_repair_on_delete(this.config.repairOnDelete)
+    }
+    if (this.config.normalized) {
+      this.wasmDB.set_normalized(true)
+    }
+    if (this.config.maxNodes !== undefined) {
+      this.wasmDB.set_capacity_limit(this.config.maxNodes)
+    }
   }
 
   /**
@@ -96,7 +231,7 @@ export class VectorDatabase {
   async insert(
     id: string,
     vector: Float32Array,
-    metadata?: Record<string, string>
+    metadata?: Metadata
   ): Promise<void> {
     if (!this.wasmDB) throw new Error('Database not initialized')
 
@@ -107,16 +242,66 @@ export class VectorDatabase {
   }
 
   /**
-   * Batch insert multiple vectors
+   * Insert a vector and report whether it created a new record or replaced an existing one
    */
-  async insertBatch(records: VectorRecord[]): Promise<void> {
+  async insertOrReplace(
+    id: string,
+    vector: Float32Array,
+    metadata?: Metadata
+  ): Promise<'created' | 'replaced'> {
     if (!this.wasmDB) throw new Error('Database not initialized')
 
-    for (const record of records) {
-      this.wasmDB.insert(record.id, record.vector, record.metadata ?? null)
-    }
+    const { status } = this.wasmDB.insert_or_replace(id, vector, metadata ?? null)
+    this.debounceSave()
+    return status
+  }
+
+  /**
+   * Insert a vector stamped with its insertion time in seconds (default: now), for searchRecent
+   */
+  async insertWithTimestamp(
+    id: string,
+    vector: Float32Array,
+    metadata?: Metadata,
+    timestamp: number = Date.now() / 1000
+  ): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.insert_with_timestamp(id, vector, metadata ?? null, BigInt(Math.floor(timestamp)))
+    this.debounceSave()
+  }
+
+  /**
+   * Insertion time in seconds given to insertWithTimestamp, or null
+   */
+  insertedAt(id: string): number | null {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    const timestamp = this.wasmDB.inserted_at(id)
+    return timestamp === undefined ? null : Number(timestamp)
+  }
+
+  /**
+   * Batch insert multiple vectors in a single WASM call.
+   * Valid records are stored even if others fail; failures are thrown together.
+   * `presort` inserts in space-filling-curve order, which speeds up large bulk loads.
+   */
+  async insertBatch(records: VectorRecord[], options: { presort?: boolean } = {}): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const results = this.wasmDB.insert_batch(
+      records.map((r) => ({ id: r.id, vector: r.vector, metadata: r.metadata ?? null })),
+      options.presort
+    ) as { id: string | null; ok: boolean; error: string | null }[]
 
     await this.saveToIndexedDB()
+
+    const failures = results.filter((r) => !r.ok)
+    if (failures.length > 0) {
+      throw new Error(
+        `Failed to insert ${failures.length} record(s): ` +
+          failures.map((f) => `${f.id}: ${f.error}`).join('; ')
+      )
+    }
   }
 
   /**
@@ -128,12 +313,116 @@ export class VectorDatabase {
   ): Promise<SearchResult[]> {
     if (!this.wasmDB) throw new Error('Database not initialized')
 
-    const k = options.k ?? 10
-    const ef = options.ef ?? 50
+    const { k, ef } = this.queryParams(options)
 
-    const results = this.wasmDB.search(query, k, ef)
+    if (options.filter) {
+      const q = options.normalizeQuery ? normalized(query) : query
+      return this.wasmDB.search_filtered(
+        q, k, ef, options.filter, options.includeVectors, options.maxDistance
+      ) as SearchResult[]
+    }
+    return this.wasmDB.search(
+      query, k, ef, options.includeVectors, options.maxDistance, options.normalizeQuery
+    ) as SearchResult[]
+  }
 
-    return results as SearchResult[]
+  /**
+   * Exact nearest neighbors by scanning every record
+   */
+  async searchExact(query: Float32Array, k: number = 10): Promise<SearchResult[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.search_exact(query, k) as SearchResult[]
+  }
+
+  /**
+   * Rank a shortlist of ids by distance to the query (unknown ids are skipped)
+   */
+  async rankSubset(query: Float32Array, ids: string[], k: number = ids.length): Promise<SearchResult[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.rank_subset(query, ids, k) as SearchResult[]
+  }
+
+  /**
+   * Search favoring recent records: distances are weighted by a decay that halves
+   * every `halfLifeSecs` since the record's `insertWithTimestamp` time
+   */
+  async searchRecent(
+    query: Float32Array,
+    options: { k?: number; ef?: number; halfLifeSecs: number; now?: number }
+  ): Promise<SearchResult[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const { k, ef } = this.queryParams(options)
+    const now = BigInt(Math.floor(options.now ?? Date.now() / 1000))
+    return this.wasmDB.search_recent(query, k, ef, options.halfLifeSecs, now) as SearchResult[]
+  }
+
+  /**
+   * Search for several queries in one call; each entry is that query's results or `{ error, code }`
+   */
+  async searchBatch(
+    queries: Float32Array[],
+    options: { k?: number; ef?: number } = {}
+  ): Promise<(SearchResult[] | { error: string; code: ErrorCode })[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const { k, ef } = this.queryParams(options)
+    return this.wasmDB.search_batch(queries, k, ef) as (SearchResult[] | { error: string; code: ErrorCode })[]
+  }
+
+  /**
+   * Search and also return an audit record of the query and its results
+   */
+  async searchWithProvenance(
+    query: Float32Array,
+    options: { k?: number; ef?: number; timestamp?: number } = {}
+  ): Promise<{ results: SearchResult[]; provenance: SearchProvenance }> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const { k, ef } = this.queryParams(options)
+    return this.wasmDB.search_with_provenance(query, k, ef, options.timestamp ?? Date.now()) as { results: SearchResult[]; provenance: SearchProvenance }
+  }
+
+  /**
+   * Store default k/ef used by search() when options omit them; persisted with the database
+   */
+  async setSearchDefaults(k: number, ef: number): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.set_search_defaults(k, ef)
+    this.debounceSave()
+  }
+
+  /**
+   * Set the index's default ef, used when neither options nor search defaults give one
+   * (instead of 50)
+   */
+  async setEf(ef: number): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.set_ef(ef)
+    this.debounceSave()
+  }
+
+  /**
+   * Find every vector within `radius` of the query, nearest first
+   */
+  async searchRadius(
+    query: Float32Array,
+    radius: number,
+    ef: number = 50
+  ): Promise<SearchResult[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    return this.wasmDB.search_radius(query, radius, ef) as SearchResult[]
+  }
+
+  /**
+   * Distance between two stored vectors, in the same units as search results
+   */
+  distanceBetween(idA: string, idB: string): number {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.distance_between(idA, idB)
   }
 
   /**
@@ -148,6 +437,39 @@ export class VectorDatabase {
     return result as GetResult
   }
 
+  /**
+   * Get just the metadata of a record, without copying its vector
+   */
+  getMetadata(id: string): Metadata | null {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.get_metadata(id) as Metadata | null
+  }
+
+  /**
+   * Get several vectors by ID in one call; missing IDs yield null, in input order
+   */
+  async getMany(ids: string[]): Promise<(GetResult | null)[]> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const results = this.wasmDB.get_many(ids) as (GetResult | null | undefined)[]
+    return results.map((r) => r ?? null)
+  }
+
+  /**
+   * Replace (or, with null, remove) a record's metadata without re-inserting its vector
+   */
+  async updateMetadata(id: string, metadata: Metadata | null): Promise<boolean> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const updated = this.wasmDB.update_metadata(id, metadata)
+
+    if (updated) {
+      this.debounceSave()
+    }
+
+    return updated
+  }
+
   /**
    * Check if a vector exists by ID
    */
@@ -164,6 +486,42 @@ export class VectorDatabase {
     return this.wasmDB.list_ids() as string[]
   }
 
+  /**
+   * List one page of IDs in sorted order, with the total count
+   */
+  listIdsPage(offset: number, limit: number): { ids: string[]; total: number } {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.list_ids_page(offset, limit) as { ids: string[]; total: number }
+  }
+
+  /**
+   * The whole database as a plain object (params and every record), without a JSON round trip
+   */
+  toObject(): {
+    version: number
+    params: { dimensions: number; m: number; ef_construction: number; metric: string; ef: number }
+    records: GetResult[]
+  } {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.to_object()
+  }
+
+  /**
+   * Up to n random records; the same seed picks the same records
+   */
+  sample(n: number, seed?: number): GetResult[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.sample(n, seed !== undefined ? BigInt(seed) : undefined) as GetResult[]
+  }
+
+  /**
+   * One page of full records in sorted-id order, for bulk export
+   */
+  entries(offset: number, limit: number): GetResult[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.entries(offset, limit) as GetResult[]
+  }
+
   /**
    * Delete a vector by ID
    */
@@ -179,6 +537,19 @@ export class VectorDatabase {
     return deleted
   }
 
+  /**
+   * Delete a vector by ID and return the removed record (null if absent)
+   */
+  async remove(id: string): Promise<GetResult | null> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const removed = this.wasmDB.remove(id)
+    if (removed === null || removed === undefined) return null
+
+    this.debounceSave()
+    return removed as GetResult
+  }
+
   /**
    * Delete multiple vectors by ID
    */
@@ -194,6 +565,140 @@ export class VectorDatabase {
     return count
   }
 
+  /**
+   * Rebuild the index graph to restore search quality after many deletions
+   */
+  async compact(): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.compact()
+    await this.saveToIndexedDB()
+  }
+
+  /**
+   * Switch to another distance metric, rebuilding the graph under it
+   */
+  async reindexWithMetric(metric: Exclude<DistanceMetric, 'auto'>, p?: number): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.reindex_with_metric(metric, p)
+    this.config.metric = metric
+    this.config.p = p
+    await this.saveToIndexedDB()
+  }
+
+  /**
+   * The metric in use; resolves 'auto' to the chosen metric after the first insert
+   */
+  metric(): DistanceMetric {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.metric() as DistanceMetric
+  }
+
+  /**
+   * Index size, configuration and per-layer shape
+   */
+  stats(): IndexStats {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.stats() as IndexStats
+  }
+
+  /**
+   * The search entry point and the highest graph layer
+   */
+  entryInfo(): EntryInfo {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.entry_info() as EntryInfo
+  }
+
+  /**
+   * Ids in eviction order, least recently accessed first (empty without maxNodes)
+   */
+  accessOrder(): string[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.access_order()
+  }
+
+  /**
+   * Nodes and directed links of one graph layer, for visualization
+   */
+  toAdjacency(layer: number = 0): LayerAdjacency {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.to_adjacency(layer) as LayerAdjacency
+  }
+
+  /**
+   * One graph layer as a Graphviz DOT digraph
+   */
+  toDot(layer: number = 0): string {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.to_dot(layer)
+  }
+
+  /**
+   * Graph integrity problems (missing entry point, links to missing nodes, unlinked nodes); empty when sound.
+   * With reciprocal, also lists one-way links, which pruning leaves in healthy graphs too.
+   */
+  validate(reciprocal: boolean = false): string[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return reciprocal ? this.wasmDB.validate_reciprocal() : this.wasmDB.validate()
+  }
+
+  /**
+   * Recompute graph links for specific records; returns how many were reconnected
+   */
+  async reconnect(ids: string[]): Promise<number> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const count = this.wasmDB.reconnect(ids)
+    if (count > 0) {
+      this.debounceSave()
+    }
+    return count
+  }
+
+  /**
+   * Release memory: 'dedup-metadata' trims metadata, 'drop-vectors' discards vector
+   * payloads (search is unavailable until every record is re-inserted).
+   * Returns the approximate bytes freed.
+   */
+  async optimizeStorage(mode: 'dedup-metadata' | 'drop-vectors'): Promise<number> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const freed = this.wasmDB.optimize_storage(mode)
+    await this.saveToIndexedDB()
+    return freed
+  }
+
+  /**
+   * Re-run neighbor selection over existing links (cheaper than compact)
+   */
+  async reprune(): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    this.wasmDB.reprune()
+    this.debounceSave()
+  }
+
+  /**
+   * Move the entry point to the top-layer node most central to a random sample of records
+   */
+  async recomputeEntryPoint(sampleSize: number = 256): Promise<string | null> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const entry = this.wasmDB.recompute_entry_point(sampleSize) ?? null
+    this.debounceSave()
+    return entry
+  }
+
+  /**
+   * The stored vector nearest to the dataset centroid, or null when empty
+   */
+  medoid(): { id: string; distance: number } | null {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return (this.wasmDB.medoid() as { id: string; distance: number } | null) ?? null
+  }
+
   /**
    * Get total number of vectors
    */
@@ -208,15 +713,7 @@ export class VectorDatabase {
   async clear(): Promise<void> {
     if (!this.wasmDB) throw new Error('Database not initialized')
 
-    const wasmModule = await import('./pkg/bundler/idbvec')
-    this.wasmDB.free()
-    this.wasmDB = new wasmModule.VectorDB(
-      this.config.dimensions,
-      this.config.m,
-      this.config.efConstruction,
-      this.config.metric
-    )
-
+    this.wasmDB.clear()
     await this.saveToIndexedDB()
   }
 
@@ -228,6 +725,67 @@ export class VectorDatabase {
     return this.wasmDB.serialize()
   }
 
+  /**
+   * Count records whose metadata matches every pair in the filter; an empty filter counts all
+   */
+  countWhere(filter: MetadataFilter | null): number {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.count_where(filter)
+  }
+
+  /**
+   * Export records matching a metadata filter as NDJSON chunks of up to chunkSize lines
+   */
+  exportFilteredNdjsonChunks(
+    filter: MetadataFilter | null,
+    chunkSize: number = 1000
+  ): string[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.export_filtered_ndjson_chunks(filter, chunkSize) as string[]
+  }
+
+  /**
+   * Export the database state as a manifest followed by JSON chunks of about chunkBytes each
+   */
+  exportChunks(chunkBytes: number): string[] {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.serialize_chunks(chunkBytes) as string[]
+  }
+
+  /**
+   * Import database state from the strings produced by exportChunks, in any order
+   */
+  async importChunks(chunks: string[]): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const wasmModule = await import('./pkg/bundler/idbvec')
+    this.wasmDB.free()
+    this.wasmDB = wasmModule.VectorDB.deserialize_chunks(chunks)
+
+    await this.saveToIndexedDB()
+  }
+
+  /**
+   * Export the database state in the compact binary format
+   */
+  exportBinary(): Uint8Array {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+    return this.wasmDB.serialize_binary()
+  }
+
+  /**
+   * Import database state from the binary format produced by exportBinary
+   */
+  async importBinary(bytes: Uint8Array): Promise<void> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const wasmModule = await import('./pkg/bundler/idbvec')
+    this.wasmDB.free()
+    this.wasmDB = wasmModule.VectorDB.deserialize_binary(bytes)
+
+    await this.saveToIndexedDB()
+  }
+
   /**
    * Import database state from a JSON string
    */
@@ -241,6 +799,23 @@ export class VectorDatabase {
     await this.saveToIndexedDB()
   }
 
+  /**
+   * Import a JSON array of externally produced rows, mapping field names onto
+   * id/vector/metadata; returns how many rows were inserted
+   */
+  async importJson(
+    json: string,
+    fields: { id: string; vector: string; metadata?: string[] }
+  ): Promise<{ inserted: number; skipped: string[] }> {
+    if (!this.wasmDB) throw new Error('Database not initialized')
+
+    const inserted = this.wasmDB.import_json(json, fields.id, fields.vector, fields.metadata ?? null)
+    if (inserted > 0) {
+      this.debounceSave()
+    }
+    return { inserted, skipped: this.wasmDB.load_warnings() }
+  }
+
   /**
    * Flush any pending saves to IndexedDB immediately
    */
@@ -268,6 +843,19 @@ export class VectorDatabase {
   /**
    * Open IndexedDB connection
    */
+  /**
+   * k and ef for a search: from the options, else setSearchDefaults, else
+   * setEf for ef, else 10 and 50
+   */
+  private queryParams(options: { k?: number; ef?: number }): { k: number; ef: number } {
+    const db = this.wasmDB!
+    const defaults = db.search_defaults() as { k: number; ef: number } | null
+    return {
+      k: options.k ?? defaults?.k ?? 10,
+      ef: options.ef ?? defaults?.ef ?? db.ef_setting() ?? 50,
+    }
+  }
+
   private openIndexedDB(): Promise<IDBDatabase> {
     return new Promise((resolve, reject) => {
       const request = indexedDB.open(this.config.name, 1)
@@ -310,26 +898,35 @@ export class VectorDatabase {
 
   /**
    * Save WASM state to IndexedDB
+   *
+   * With persistChunkBytes set, 'state' holds the chunk count and each chunk
+   * is its own 'state:<n>' value; otherwise 'state' holds the whole snapshot.
    */
   private async saveToIndexedDB(): Promise<void> {
     if (!this.idb || !this.wasmDB) return
 
-    const serialized = this.wasmDB.serialize()
+    const chunkBytes = this.config.persistChunkBytes
+    const chunks = chunkBytes !== undefined ? (this.wasmDB.serialize_chunks(chunkBytes) as string[]) : null
+    const serialized = chunks ? chunks.length : this.wasmDB.serialize()
 
     return new Promise((resolve, reject) => {
       const tx = this.idb!.transaction('vectordb', 'readwrite')
       const store = tx.objectStore('vectordb')
-      const request = store.put(serialized, 'state')
+      // Drop chunks left by an earlier, larger save
+      store.delete(IDBKeyRange.bound('state:', 'state:\uffff'))
+      chunks?.forEach((chunk, i) => store.put(chunk, `state:${i}`))
+      store.put(serialized, 'state')
 
-      request.onerror = () => reject(request.error)
-      request.onsuccess = () => resolve()
+      tx.onerror = () => reject(tx.error)
+      tx.oncomplete = () => resolve()
     })
   }
 
   /**
-   * Load WASM state from IndexedDB
+   * Load WASM state from IndexedDB: a snapshot string, or the chunks of a
+   * chunked save
    */
-  private async loadFromIndexedDB(): Promise<string | null> {
+  private async loadFromIndexedDB(): Promise<string | string[] | null> {
     if (!this.idb) return null
 
     return new Promise((resolve, reject) => {
@@ -340,7 +937,20 @@ export class VectorDatabase {
       request.onerror = () => reject(request.error)
       request.onsuccess = () => {
         const result = request.result
-        resolve(result ?? null)
+        if (typeof result !== 'number') {
+          resolve(result ?? null)
+          return
+        }
+        const chunks = store.getAll(IDBKeyRange.bound('state:', 'state:\uffff'))
+        chunks.onerror = () => reject(chunks.error)
+        chunks.onsuccess = () => {
+          const found = chunks.result as string[]
+          if (found.length !== result) {
+            reject(new Error(`Expected ${result} saved chunks, found ${found.length}`))
+          } else {
+            resolve(found)
+          }
+        }
       }
     })
   }
@@ -399,3 +1009,33 @@ export async function dotProduct(
   const wasmModule = await import('./pkg/bundler/idbvec')
   return wasmModule.dot_product(a, b)
 }
+
+export async function manhattanDistance(
+  a: Float32Array,
+  b: Float32Array
+): Promise<number> {
+  const wasmModule = await import('./pkg/bundler/idbvec')
+  return wasmModule.manhattan_distance(a, b)
+}
+
+/**
+ * Count positions where exactly one bit vector is set (any nonzero entry is a set bit)
+ */
+export async function hammingDistance(
+  a: Float32Array,
+  b: Float32Array
+): Promise<number> {
+  const wasmModule = await import('./pkg/bundler/idbvec')
+  return wasmModule.hamming_distance(a, b)
+}
+
+/**
+ * Jaccard distance between the sets of nonzero positions (0 when both vectors are all zero)
+ */
+export async function jaccardDistance(
+  a: Float32Array,
+  b: Float32Array
+): Promise<number> {
+  const wasmModule = await import('./pkg/bundler/idbvec')
+  return wasmModule.jaccard_distance(a, b)
+}